@@ -1,4 +1,4 @@
-use istari::{Istari, Menu};
+use istari::{Istari, Menu, MenuItem};
 use std::io;
 
 /// Advanced application state with multiple fields
@@ -123,20 +123,33 @@ fn main() -> io::Result<()> {
         },
     );
 
-    settings_menu.add_action(
-        'n',
-        "Toggle Notifications",
-        |state: &mut AdvancedAppState, _params: Option<&str>| {
-            state.settings.notifications = !state.settings.notifications;
-            Some(format!(
-                "Notifications are now {}",
+    // Show the live setting in the menu
+    settings_menu.add_item(
+        MenuItem::new_action(
+            'n',
+            "Toggle Notifications".to_string(),
+            |state: &mut AdvancedAppState, _params: Option<&str>| {
+                state.settings.notifications = !state.settings.notifications;
+                Some(format!(
+                    "Notifications are now {}",
+                    if state.settings.notifications {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                ))
+            },
+        )
+        .with_dynamic_description(|state: &AdvancedAppState| {
+            format!(
+                "Notifications: {}",
                 if state.settings.notifications {
-                    "enabled"
+                    "ON"
                 } else {
-                    "disabled"
+                    "OFF"
                 }
-            ))
-        },
+            )
+        }),
     );
 
     settings_menu.add_action(
@@ -1,4 +1,4 @@
-use istari::{Istari, Menu};
+use istari::{Istari, Menu, OutputHandle};
 use std::io;
 use std::time::{Duration, Instant};
 
@@ -43,21 +43,21 @@ impl AnimatedState {
             self.animation_frame = (self.animation_frame + 1) % self.animation_frames.len();
 
             // If timer is active, display remaining time
-            if self.timer_active {
-                if let Some(start) = self.timer_start {
-                    let elapsed = now.duration_since(start);
-                    if elapsed >= self.timer_duration {
-                        // Timer finished
-                        self.timer_active = false;
-                        self.timer_start = None;
-                        return Some("Timer completed!".to_string());
-                    } else {
-                        let remaining = self.timer_duration.as_secs() - elapsed.as_secs();
-                        return Some(format!(
-                            "{} (Timer: {}s remaining)",
-                            self.animation_frames[self.animation_frame], remaining
-                        ));
-                    }
+            if self.timer_active
+                && let Some(start) = self.timer_start
+            {
+                let elapsed = now.duration_since(start);
+                if elapsed >= self.timer_duration {
+                    // Timer finished
+                    self.timer_active = false;
+                    self.timer_start = None;
+                    return Some("Timer completed!".to_string());
+                } else {
+                    let remaining = self.timer_duration.as_secs() - elapsed.as_secs();
+                    return Some(format!(
+                        "{} (Timer: {}s remaining)",
+                        self.animation_frames[self.animation_frame], remaining
+                    ));
                 }
             }
 
@@ -128,15 +128,10 @@ fn main() -> io::Result<()> {
     );
 
     // Define the animation tick handler
-    let tick_handler = |state: &mut AnimatedState, messages: &mut Vec<String>, _delta: f32| {
-        // Update animation and add output message if needed
+    let tick_handler = |state: &mut AnimatedState, output: &OutputHandle, _delta: f32| {
+        // Update the animation line in place to avoid cluttering the display
         if let Some(message) = state.update_animation() {
-            messages.push(message);
-
-            // Keep only the last 10 messages to avoid cluttering the display
-            if messages.len() > 10 {
-                messages.remove(0);
-            }
+            output.replace_last(message);
         }
     };
 
@@ -21,7 +21,7 @@ use std::io;
 ///      * Ctrl+A: Toggle auto-scroll
 ///
 /// The current mode is clearly displayed in the UI title bar.
-
+///
 /// Sample application state
 #[derive(Debug)]
 struct ModeTestState {
@@ -1,4 +1,4 @@
-use istari::{Istari, Menu};
+use istari::{Istari, Menu, MenuItem, ParamSpec, ParamType, Params};
 use std::io;
 
 /// This example demonstrates a simple counter application with multiple menus
@@ -44,17 +44,21 @@ fn main() -> io::Result<()> {
         },
     );
 
-    root_menu.add_action(
-        "dec",
-        "Decrement Counter (optional amount)",
-        |state: &mut AppState, params: Option<&str>| {
-            let amount = params.and_then(|p| p.parse::<i32>().ok()).unwrap_or(1);
-            state.counter -= amount;
-            Some(format!(
-                "Counter decremented by {} to {}",
-                amount, state.counter
-            ))
-        },
+    // Declared parameters are validated and defaulted before the action runs
+    root_menu.add_item(
+        MenuItem::new_action(
+            "dec",
+            "Decrement Counter (optional amount)".to_string(),
+            |state: &mut AppState, params: &Params| {
+                let amount = params.value_as::<i32>("amount")?;
+                state.counter -= amount;
+                Some(format!(
+                    "Counter decremented by {} to {}",
+                    amount, state.counter
+                ))
+            },
+        )
+        .with_params([ParamSpec::optional("amount", ParamType::Integer).with_default(1)]),
     );
 
     // Create a submenu
@@ -116,6 +120,8 @@ fn main() -> io::Result<()> {
 
     // Create and run our application
     let mut app = Istari::new(root_menu, state)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        .with_title_banner("Simple Menu", env!("CARGO_PKG_VERSION"))
+        .with_status_segment(|state: &AppState| format!("Counter: {}", state.counter));
     app.run()
 }
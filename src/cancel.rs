@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+/// Token an async action checks to stop early when the user cancels it.
+///
+/// Cancellation is cooperative: the action decides where it's safe to stop,
+/// e.g. by racing its work against [`CancelToken::cancelled`] in
+/// `tokio::select!`. Tokens are cheap to clone and can be moved into the
+/// action's future.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    /// Whether the action was cancelled
+    cancelled: AtomicBool,
+    /// Wakes futures waiting in [`CancelToken::cancelled`]
+    notify: Notify,
+}
+
+impl CancelToken {
+    /// Create a token that isn't cancelled yet
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the action, waking everything waiting for it
+    pub(crate) fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Check whether the action was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the action is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking, so a cancel in between isn't missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let token = CancelToken::new();
+        let waiting = token.clone();
+        let task = runtime.spawn(async move {
+            waiting.cancelled().await;
+            waiting.is_cancelled()
+        });
+
+        assert!(!token.is_cancelled());
+        token.cancel();
+        assert!(runtime.block_on(task).unwrap());
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{FromRawFd, RawFd};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How long to wait for the rest of the captured output once the streams are
+/// restored, in case a child process still holds them open
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Held while a capture runs, the streams can only be redirected once at a time
+static CAPTURE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Output printed to stdout and stderr while a capture ran
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CapturedOutput {
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+}
+
+/// Redirects stdout and stderr into pipes until it's finished, so what actions
+/// print with `println!` ends up in the output pane instead of over the TUI.
+///
+/// The streams are redirected for the whole process, so anything other threads
+/// print meanwhile is captured too.
+pub(crate) struct StdioCapture {
+    stdout: CapturedStream,
+    stderr: CapturedStream,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl StdioCapture {
+    /// Start redirecting stdout and stderr
+    pub(crate) fn start() -> io::Result<Self> {
+        let lock = CAPTURE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        flush_std();
+        let stdout = CapturedStream::start(libc::STDOUT_FILENO)?;
+        let stderr = match CapturedStream::start(libc::STDERR_FILENO) {
+            Ok(stderr) => stderr,
+            Err(e) => {
+                stdout.finish();
+                return Err(e);
+            }
+        };
+        Ok(Self {
+            stdout,
+            stderr,
+            _lock: lock,
+        })
+    }
+
+    /// Restore stdout and stderr, getting what was printed to them meanwhile
+    pub(crate) fn finish(self) -> CapturedOutput {
+        flush_std();
+        CapturedOutput {
+            stdout: self.stdout.finish(),
+            stderr: self.stderr.finish(),
+        }
+    }
+}
+
+/// Flush what's buffered in the standard streams, so it goes where they point now
+fn flush_std() {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+}
+
+/// A standard stream redirected into a pipe, read on a thread so the pipe never fills up
+struct CapturedStream {
+    fd: RawFd,
+    saved: RawFd,
+    chunks: Receiver<Vec<u8>>,
+}
+
+impl CapturedStream {
+    fn start(fd: RawFd) -> io::Result<Self> {
+        let mut pipe = [0; 2];
+        // SAFETY: `pipe` has room for the two descriptors `pipe(2)` writes
+        if unsafe { libc::pipe(pipe.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let [read_end, write_end] = pipe;
+        // SAFETY: the descriptors are open, and `read_end` is owned by the file from here on
+        let reader = unsafe { File::from_raw_fd(read_end) };
+        // SAFETY: `dup(2)` only reads the descriptor number, an invalid one fails with -1
+        let saved = unsafe { libc::dup(fd) };
+        // SAFETY: `write_end` is open and `fd` is a standard stream, replaced atomically
+        if saved < 0 || unsafe { libc::dup2(write_end, fd) } < 0 {
+            let error = io::Error::last_os_error();
+            // SAFETY: `write_end` and `saved` are open and owned here, nothing else closes them
+            unsafe {
+                libc::close(write_end);
+                if saved >= 0 {
+                    libc::close(saved);
+                }
+            }
+            return Err(error);
+        }
+        // The stream holds the write end now
+        // SAFETY: `write_end` is owned here and `fd` keeps its own duplicate of it
+        unsafe { libc::close(write_end) };
+
+        let (sender, chunks) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = reader;
+            let mut buffer = [0; 4096];
+            while let Ok(read @ 1..) = reader.read(&mut buffer) {
+                if sender.send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { fd, saved, chunks })
+    }
+
+    fn finish(self) -> String {
+        // Restoring the stream closes the pipe unless a child process inherited it
+        // SAFETY: `saved` is a descriptor this stream duplicated and owns
+        unsafe {
+            libc::dup2(self.saved, self.fd);
+            libc::close(self.saved);
+        }
+        let mut bytes = Vec::new();
+        while let Ok(chunk) = self.chunks.recv_timeout(DRAIN_TIMEOUT) {
+            bytes.extend(chunk);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stdio_capture() {
+        let capture = StdioCapture::start().unwrap();
+        // `println!` goes to the test harness, so write to the streams directly
+        writeln!(io::stdout(), "to stdout").unwrap();
+        writeln!(io::stderr(), "to stderr").unwrap();
+        let captured = capture.finish();
+
+        assert!(captured.stdout.contains("to stdout\n"));
+        assert!(captured.stderr.contains("to stderr\n"));
+        assert!(!captured.stdout.contains("to stderr"));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::params::{self, Params};
+
+/// Parse parameter text with the clap command of a menu item.
+///
+/// Returns the parameters carrying the matches, or the text to show instead of
+/// running the action: the generated help for `--help`, or the usage error.
+pub(crate) fn parse_with_clap(
+    command: &clap::Command,
+    key: &str,
+    input: Option<&str>,
+) -> Result<Params, String> {
+    let mut params = Params::parse(input.unwrap_or(""));
+    let args = params::tokenize(input.unwrap_or(""));
+
+    // The key stands in for the binary name in usage and help
+    match command
+        .clone()
+        .try_get_matches_from(std::iter::once(key.to_string()).chain(args))
+    {
+        Ok(matches) => {
+            params.matches = Some(matches);
+            Ok(params)
+        }
+        Err(error) => Err(error.render().to_string().trim_end().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn deploy() -> clap::Command {
+        clap::Command::new("deploy")
+            .about("Deploy the app")
+            .arg(Arg::new("app").required(true))
+            .arg(
+                Arg::new("replicas")
+                    .long("replicas")
+                    .value_parser(clap::value_parser!(u16))
+                    .default_value("1"),
+            )
+            .arg(Arg::new("force").long("force").action(ArgAction::SetTrue))
+    }
+
+    #[test]
+    fn test_parse_with_clap() {
+        let params = parse_with_clap(&deploy(), "d", Some("'my app' --replicas 3")).unwrap();
+        let matches = params.matches().unwrap();
+        assert_eq!(matches.get_one::<String>("app").unwrap(), "my app");
+        assert_eq!(matches.get_one::<u16>("replicas"), Some(&3));
+        assert!(!matches.get_flag("force"));
+        assert_eq!(params.get(0), Some("my app"));
+
+        let help = parse_with_clap(&deploy(), "d", Some("--help")).unwrap_err();
+        assert!(help.starts_with("Deploy the app\n\nUsage: d [OPTIONS] <app>"));
+
+        let error = parse_with_clap(&deploy(), "d", Some("web --replicas x")).unwrap_err();
+        assert!(error.starts_with("error: invalid value 'x' for '--replicas <replicas>'"));
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A date and time of day in UTC, to the second
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct UtcTime {
+    pub year: i64,
+    pub month: i64,
+    pub day: i64,
+    pub hour: u64,
+    pub minute: u64,
+    pub second: u64,
+}
+
+impl UtcTime {
+    /// Get the date and time of seconds since the Unix epoch
+    pub(crate) fn from_unix(timestamp: u64) -> Self {
+        let days = (timestamp / 86_400) as i64;
+        let seconds = timestamp % 86_400;
+
+        // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+        }
+    }
+
+    /// Get the date and time of a system time, the epoch for times before it
+    pub(crate) fn from_system_time(time: SystemTime) -> Self {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        Self::from_unix(seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_unix() {
+        let time = UtcTime::from_unix(1_700_000_000);
+        assert_eq!((time.year, time.month, time.day), (2023, 11, 14));
+        assert_eq!((time.hour, time.minute, time.second), (22, 13, 20));
+
+        // Leap day
+        let time = UtcTime::from_unix(951_782_400);
+        assert_eq!((time.year, time.month, time.day), (2000, 2, 29));
+        assert_eq!(UtcTime::from_system_time(UNIX_EPOCH), UtcTime::from_unix(0));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::cancel::CancelToken;
+use crate::jobs::Job;
+use crate::menu::Menu;
+use crate::output::OutputSink;
+use crate::params::Params;
+use crate::progress::Progress;
+use tokio::runtime::Handle;
+
+/// Everything an action can use while it runs: the application state, its
+/// parameters, the menu it's in and the handles of its job.
+///
+/// Actions taking `&mut ActionContext<T>` get this instead of a bare `&mut T`.
+/// The futures of async actions can't borrow the context, so they clone the
+/// handles they need first.
+pub struct ActionContext<'a, T> {
+    pub(crate) state: &'a mut T,
+    pub(crate) params: &'a Params,
+    pub(crate) menu: &'a Menu<T>,
+    pub(crate) job: &'a Job,
+}
+
+impl<'a, T> ActionContext<'a, T> {
+    /// Create a context for running an action of the given menu
+    pub(crate) fn new(
+        state: &'a mut T,
+        params: &'a Params,
+        menu: &'a Menu<T>,
+        job: &'a Job,
+    ) -> Self {
+        Self {
+            state,
+            params,
+            menu,
+            job,
+        }
+    }
+
+    /// Get the application state
+    pub fn state(&self) -> &T {
+        self.state
+    }
+
+    /// Get mutable access to the application state
+    pub fn state_mut(&mut self) -> &mut T {
+        self.state
+    }
+
+    /// Get the parameters the action was run with
+    pub fn params(&self) -> &Params {
+        self.params
+    }
+
+    /// Get the menu the action belongs to
+    pub fn menu(&self) -> &Menu<T> {
+        self.menu
+    }
+
+    /// Get the sink to write output to while the action runs
+    pub fn output(&self) -> &OutputSink {
+        self.job.output()
+    }
+
+    /// Get the handle to report the action's progress through
+    pub fn progress(&self) -> &Progress {
+        self.job.progress()
+    }
+
+    /// Get the token telling whether the action was cancelled
+    pub fn cancel_token(&self) -> &CancelToken {
+        self.job.cancel_token()
+    }
+
+    /// Get all handles of the action's job
+    pub fn job(&self) -> &Job {
+        self.job
+    }
+
+    /// Get the runtime async actions run on, e.g. to spawn more tasks
+    pub fn runtime(&self) -> &Handle {
+        self.job.runtime()
+    }
+
+    /// Quit the application once the action finished
+    pub fn request_quit(&self) {
+        self.job.request_quit();
+    }
+}
@@ -0,0 +1,169 @@
+/// What a [`Dialog`] asks the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialogKind {
+    /// A message, dismissed with Enter or Esc
+    Message,
+    /// A yes/no question, with whether "Yes" is the selected answer
+    Confirm { yes: bool },
+    /// A single text field, with what was typed into it so far
+    Prompt { value: String },
+}
+
+/// What answering a dialog does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DialogAnswer {
+    /// Nothing besides closing the dialog
+    Nothing,
+    /// Run a command like `deploy/start --force`, relative to the current menu
+    Command(String),
+    /// Answer the confirmation of the action waiting for it
+    Confirmation,
+}
+
+/// A modal dialog shown on top of the TUI until it's answered, see
+/// [`Istari::show_dialog`](crate::Istari::show_dialog).
+///
+/// The Text renderer prints dialogs and takes the next line typed as the answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialog {
+    /// Title of the dialog
+    pub title: String,
+    /// Message, question or label of the field
+    pub text: String,
+    /// What the dialog asks
+    pub kind: DialogKind,
+    /// What answering it does
+    pub(crate) answer: DialogAnswer,
+}
+
+impl Dialog {
+    /// Create a message box
+    pub fn message(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self::new(title, text, DialogKind::Message, DialogAnswer::Nothing)
+    }
+
+    /// Create a yes/no question running `command` if the user answers yes, e.g. `logs/clear --all`
+    pub fn confirm(
+        title: impl Into<String>,
+        question: impl Into<String>,
+        command: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            title,
+            question,
+            DialogKind::Confirm { yes: false },
+            DialogAnswer::Command(command.into()),
+        )
+    }
+
+    /// Create a single-field prompt running `command` with the typed value as its last parameter.
+    ///
+    /// Submitting an empty value cancels it.
+    pub fn prompt(
+        title: impl Into<String>,
+        label: impl Into<String>,
+        command: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            title,
+            label,
+            DialogKind::Prompt {
+                value: String::new(),
+            },
+            DialogAnswer::Command(command.into()),
+        )
+    }
+
+    /// Create the dialog confirming the action waiting for it
+    pub(crate) fn confirmation(description: &str) -> Self {
+        Self::new(
+            "Confirm",
+            format!("Are you sure you want to run '{}'?", description),
+            DialogKind::Confirm { yes: false },
+            DialogAnswer::Confirmation,
+        )
+    }
+
+    fn new(
+        title: impl Into<String>,
+        text: impl Into<String>,
+        kind: DialogKind,
+        answer: DialogAnswer,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            text: text.into(),
+            kind,
+            answer,
+        }
+    }
+
+    /// Fill the field of a prompt with a value to start from
+    pub fn with_value(mut self, initial: impl Into<String>) -> Self {
+        if let DialogKind::Prompt { value } = &mut self.kind {
+            *value = initial.into();
+        }
+        self
+    }
+
+    /// Type a character into the field of a prompt
+    pub fn type_char(&mut self, c: char) {
+        if let DialogKind::Prompt { value } = &mut self.kind {
+            value.push(c);
+        }
+    }
+
+    /// Delete the last character of the field of a prompt
+    pub fn backspace(&mut self) {
+        if let DialogKind::Prompt { value } = &mut self.kind {
+            value.pop();
+        }
+    }
+
+    /// Select the other answer of a question
+    pub fn toggle_choice(&mut self) {
+        if let DialogKind::Confirm { yes } = &mut self.kind {
+            *yes = !*yes;
+        }
+    }
+
+    /// Get a line describing the keys answering the dialog
+    pub fn key_hint(&self) -> &'static str {
+        match self.kind {
+            DialogKind::Message => "Enter/Esc Close",
+            DialogKind::Confirm { .. } => "y/n Answer | Left/Right Select | Enter Confirm | Esc No",
+            DialogKind::Prompt { .. } => "Enter Submit | Esc Cancel",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dialog_input() {
+        let mut prompt = Dialog::prompt("Rename", "New name", "rename").with_value("ab");
+        prompt.type_char('c');
+        prompt.backspace();
+        prompt.type_char('d');
+        prompt.toggle_choice();
+        assert_eq!(
+            prompt.kind,
+            DialogKind::Prompt {
+                value: "abd".to_string()
+            }
+        );
+
+        // Questions default to the safe answer
+        let mut confirm = Dialog::confirm("Clear", "Clear the logs?", "logs/clear");
+        assert_eq!(confirm.kind, DialogKind::Confirm { yes: false });
+        confirm.type_char('x');
+        confirm.toggle_choice();
+        assert_eq!(confirm.kind, DialogKind::Confirm { yes: true });
+        assert_eq!(
+            confirm.answer,
+            DialogAnswer::Command("logs/clear".to_string())
+        );
+    }
+}
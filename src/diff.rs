@@ -0,0 +1,153 @@
+/// Number of unchanged lines kept around each change
+const CONTEXT_LINES: usize = 3;
+
+/// A line of a line-by-line comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    /// Line present in both texts
+    Same(&'a str),
+    /// Line only present in the old text
+    Removed(&'a str),
+    /// Line only present in the new text
+    Added(&'a str),
+}
+
+/// Compare two texts line by line
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+    lines
+}
+
+/// Render a unified diff of two texts, e.g. `unified_diff("current", &current, "desired", &desired)`.
+///
+/// The TUI colors added and removed lines of messages in this format; the
+/// Text renderer prints them with their plain `+`/`-` prefixes.
+pub fn unified_diff(old_label: &str, old: &str, new_label: &str, new: &str) -> String {
+    let lines = diff_lines(old, new);
+    let mut out = format!("--- {}\n+++ {}", old_label, new_label);
+
+    if lines.iter().all(|line| matches!(line, DiffLine::Same(_))) {
+        out.push_str("\n(no differences)");
+        return out;
+    }
+
+    for (start, end) in hunks(&lines) {
+        let hunk = &lines[start..end];
+        // Line numbers of the hunk in the old and new text, counted from 1
+        let old_start = lines[..start]
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Added(_)))
+            .count();
+        let new_start = lines[..start]
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Removed(_)))
+            .count();
+        let old_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Added(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Removed(_)))
+            .count();
+
+        out.push_str(&format!(
+            "\n@@ -{},{} +{},{} @@",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        ));
+        for line in hunk {
+            let (prefix, text) = match line {
+                DiffLine::Same(text) => (' ', text),
+                DiffLine::Removed(text) => ('-', text),
+                DiffLine::Added(text) => ('+', text),
+            };
+            out.push('\n');
+            out.push(prefix);
+            out.push_str(text);
+        }
+    }
+
+    out
+}
+
+/// Check whether a message is a diff rendered by [`unified_diff`]
+pub fn is_unified_diff(message: &str) -> bool {
+    let mut lines = message.lines();
+    lines.next().is_some_and(|line| line.starts_with("--- "))
+        && lines.next().is_some_and(|line| line.starts_with("+++ "))
+}
+
+/// Ranges of lines to show, each change with its surrounding context
+fn hunks(lines: &[DiffLine]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if matches!(line, DiffLine::Same(_)) {
+            continue;
+        }
+        let start = idx.saturating_sub(CONTEXT_LINES);
+        let end = (idx + 1 + CONTEXT_LINES).min(lines.len());
+        match ranges.last_mut() {
+            // Merge changes whose context touches
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk";
+        let diff = unified_diff("current", old, "desired", new);
+        assert!(is_unified_diff(&diff));
+        assert_eq!(
+            diff,
+            "--- current\n+++ desired\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k"
+        );
+
+        let same = unified_diff("current", old, "desired", old);
+        assert!(same.ends_with("(no differences)"));
+        assert!(!is_unified_diff("just output"));
+    }
+}
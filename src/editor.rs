@@ -0,0 +1,122 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Editor used when neither `$VISUAL` nor `$EDITOR` is set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Names tried for the temporary file before giving up
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+/// A temporary file only this process created, removed when dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Create a new file with a random name in the temporary directory.
+    ///
+    /// The file must not exist yet, so another user can't plant a file or symlink
+    /// at the name beforehand, and on Unix only the current user can read it.
+    fn create() -> io::Result<(Self, File)> {
+        let mut last_error = None;
+        for _ in 0..TEMP_FILE_ATTEMPTS {
+            // Each `RandomState` is seeded randomly, so its hashes are hard to guess
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(std::process::id());
+            let path = env::temp_dir().join(format!("istari-{:016x}.txt", hasher.finish()));
+
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok((Self { path }, file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => last_error = Some(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::other("No free temporary file name")))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Get the editor command configured by the user's environment
+pub fn editor_command() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// Open the user's editor on a temporary file containing `initial` and return the saved contents.
+///
+/// The terminal must already be suspended, since the editor takes it over until it exits.
+pub fn edit_text(initial: &str) -> io::Result<String> {
+    // Removed when it goes out of scope, whichever way this returns
+    let (temp_file, mut file) = TempFile::create()?;
+    file.write_all(initial.as_bytes())?;
+    drop(file);
+    let path = &temp_file.path;
+
+    // Support editor commands with arguments, e.g. "code --wait"
+    let editor = editor_command();
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or(DEFAULT_EDITOR);
+
+    let status = Command::new(program).args(parts).arg(path).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "Editor '{}' exited with {}",
+            editor, status
+        )));
+    }
+    fs::read_to_string(path)
+}
+
+/// Flatten edited text into a single line suitable for the command input
+pub fn to_single_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_single_line() {
+        let text = "{\n  \"name\": \"istari\",\n\n  \"tags\": [1, 2]\n}\n";
+        assert_eq!(
+            to_single_line(text),
+            "{ \"name\": \"istari\", \"tags\": [1, 2] }"
+        );
+        assert_eq!(to_single_line("\n\n"), "");
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let (first, _) = TempFile::create().unwrap();
+        let (second, _) = TempFile::create().unwrap();
+        assert_ne!(first.path, second.path);
+        assert!(first.path.starts_with(env::temp_dir()));
+
+        let path = first.path.clone();
+        assert!(path.exists());
+        drop(first);
+        assert!(!path.exists());
+    }
+}
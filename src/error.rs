@@ -8,7 +8,62 @@ pub enum IstariError {
 
     #[error("Reserved command key '{0}' in menu '{1}'")]
     ReservedCommand(String, String),
+
+    #[error("Unreachable command key '{0}' in menu '{1}': {2}")]
+    UnreachableCommand(String, String, String),
+
+    #[error("Invalid parameters for command key '{0}' in menu '{1}': {2}")]
+    InvalidParamSpec(String, String, String),
+
+    #[error("Submenu '{0}' at '{1}' has no items")]
+    EmptyMenu(String, String),
+
+    #[error("Submenu at '{0}' leads back to its ancestor '{1}'")]
+    CyclicMenu(String, String),
+
+    #[error("Refresh action '{0}' in menu '{1}' is not an action of the menu")]
+    InvalidRefresh(String, String),
+
+    #[error("Invalid key map: {0}")]
+    InvalidKeyMap(String),
+
+    #[error("Found {} problems in the menu structure:\n{}", .0.len(), format_problems(.0))]
+    ValidationReport(Vec<IstariError>),
+}
+
+/// Format a list of problems as one indented line per problem
+fn format_problems(problems: &[IstariError]) -> String {
+    problems
+        .iter()
+        .map(|problem| format!("  - {}", problem))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 /// Reserved command keys that cannot be used in menus
-pub const RESERVED_KEYS: [&str; 2] = ["q", "b"];
+pub const RESERVED_KEYS: &[&str] = &[
+    "q",
+    "b",
+    "pin",
+    "unpin",
+    "grep",
+    "category",
+    "quit!",
+    "keys",
+    "tagged",
+    "<",
+    ">",
+    "export-history",
+    "cancel",
+    "jobs",
+    "kill",
+    "undo",
+    "redo",
+    "queue",
+    "stats",
+    "theme",
+    "pane",
+    "tab",
+    "loglevel",
+    "filter",
+];
@@ -0,0 +1,68 @@
+/// Score how well `query` matches `candidate`, `None` if its characters don't all
+/// appear in it in order.
+///
+/// Matching ignores case. Characters matched one after the other and at the start
+/// of words score higher, so `dp` ranks `deploy-prod` above `dump`.
+pub(crate) fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let bonus = |index: usize| {
+        let word_start =
+            index == 0 || matches!(candidate[index - 1], ' ' | '-' | '_' | '/' | '.' | ':');
+        if word_start { 9 } else { 1 }
+    };
+
+    // best[j] is the best score of the query so far with its last character at j
+    let mut best: Vec<Option<i64>> = Vec::new();
+    let mut first = true;
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        let mut next = vec![None; candidate.len()];
+        // Best score of an earlier character matched before j - 1
+        let mut earlier: Option<i64> = None;
+        for (j, &c) in candidate.iter().enumerate() {
+            if c == wanted {
+                let previous = if first {
+                    Some(0)
+                } else {
+                    let consecutive = j
+                        .checked_sub(1)
+                        .and_then(|k| best[k])
+                        .map(|score| score + 5);
+                    earlier.max(consecutive)
+                };
+                next[j] = previous.map(|score| score + bonus(j));
+            }
+            if !first && j >= 1 {
+                earlier = earlier.max(best[j - 1]);
+            }
+        }
+        best = next;
+        first = false;
+    }
+    let score = if first {
+        0
+    } else {
+        best.into_iter().flatten().max()?
+    };
+    // Shorter candidates win ties, they're closer to what was typed
+    Some(score * 100 - candidate.len() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("dp", "deploy-prod").is_some());
+        assert!(fuzzy_score("DEP", "deploy").is_some());
+        assert_eq!(fuzzy_score("pd", "deploy"), None);
+        assert_eq!(fuzzy_score("x", ""), None);
+        assert!(fuzzy_score("", "anything").is_some());
+
+        // Word starts and runs of characters rank higher
+        assert!(fuzzy_score("dp", "deploy-prod") > fuzzy_score("dp", "dump"));
+        assert!(fuzzy_score("rel", "reload") > fuzzy_score("rel", "remove old"));
+        // Shorter candidates win ties
+        assert!(fuzzy_score("log", "logs") > fuzzy_score("log", "logsxxxx"));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::theme::StyledLine;
+use ratatui::style::{Color, Modifier, Style};
+use std::sync::LazyLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Highlight code in the given language, e.g. `json` or `rust`, with a syntect theme.
+///
+/// Returns `None` if the language or the theme is unknown. Only the foreground
+/// and font style are applied, the terminal background stays.
+pub(crate) fn highlight_code(code: &str, language: &str, theme: &str) -> Option<Vec<StyledLine>> {
+    let syntax = SYNTAXES
+        .find_syntax_by_token(language)
+        .or_else(|| SYNTAXES.find_syntax_by_extension(language))?;
+    let theme = THEMES.themes.get(theme)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    LinesWithEndings::from(code)
+        .map(|line| {
+            let regions = highlighter.highlight_line(line, &SYNTAXES).ok()?;
+            Some(
+                regions
+                    .into_iter()
+                    .map(|(style, text)| {
+                        (
+                            text.trim_end_matches(['\n', '\r']).to_string(),
+                            convert_style(style),
+                        )
+                    })
+                    .filter(|(text, _)| !text.is_empty())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Turn a syntect style into a ratatui one
+fn convert_style(style: syntect::highlighting::Style) -> Style {
+    let color = style.foreground;
+    let mut converted = Style::default().fg(Color::Rgb(color.r, color.g, color.b));
+    for (font, modifier) in [
+        (FontStyle::BOLD, Modifier::BOLD),
+        (FontStyle::ITALIC, Modifier::ITALIC),
+        (FontStyle::UNDERLINE, Modifier::UNDERLINED),
+    ] {
+        if style.font_style.contains(font) {
+            converted = converted.add_modifier(modifier);
+        }
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_code() {
+        let lines = highlight_code("{\n  \"a\": 1\n}", "json", "base16-ocean.dark").unwrap();
+        assert_eq!(lines.len(), 3);
+        let text: String = lines[1].iter().map(|(part, _)| part.as_str()).collect();
+        assert_eq!(text, "  \"a\": 1");
+        // The key and the number get different colors
+        assert!(lines[1].len() > 1);
+        assert_ne!(lines[1].first().unwrap().1, lines[1].last().unwrap().1);
+
+        // Extensions work as well as names
+        assert!(highlight_code("fn main() {}", "rs", "base16-ocean.dark").is_some());
+        assert!(highlight_code("x", "no-such-language", "base16-ocean.dark").is_none());
+        assert!(highlight_code("{}", "json", "no-such-theme").is_none());
+    }
+}
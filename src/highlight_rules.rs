@@ -0,0 +1,179 @@
+use crate::theme::StyledLine;
+use ratatui::style::{Color, Modifier, Style};
+use regex::Regex;
+
+/// A rule styling the output matching a regex, added with
+/// [`Istari::with_highlight_rule`](crate::Istari::with_highlight_rule).
+///
+/// The TUI applies rules in the order they were added, on top of the style
+/// of the output, so later rules win where matches overlap.
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    /// What the rule matches
+    pub pattern: Regex,
+    /// Style patched onto the matches
+    pub style: Style,
+    /// Style the whole line holding a match instead of only the match
+    pub whole_line: bool,
+}
+
+impl HighlightRule {
+    /// Create a rule styling the matches of a regex
+    pub fn new(pattern: &str, style: Style) -> Result<Self, String> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| format!("Invalid highlight pattern '{}': {}", pattern, e))?;
+        Ok(Self {
+            pattern,
+            style,
+            whole_line: false,
+        })
+    }
+
+    /// Parse a rule like `ERROR -> red bold` or `^\[db\] -> cyan on black`.
+    ///
+    /// The style is a foreground color, `on` and a background color, and any of
+    /// `bold`, `dim`, `italic`, `underlined` and `reversed`. Colors are names
+    /// like `lightred`, `#rrggbb` values or 256-color indices.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (pattern, style) = spec
+            .rsplit_once("->")
+            .ok_or_else(|| format!("Highlight rule '{}' has no '->' before its style", spec))?;
+        let style = parse_style(style.trim())
+            .map_err(|e| format!("Invalid style in highlight rule '{}': {}", spec, e))?;
+        Self::new(pattern.trim(), style)
+    }
+
+    /// Style the whole line holding a match instead of only the match
+    pub fn whole_line(mut self) -> Self {
+        self.whole_line = true;
+        self
+    }
+}
+
+/// Parse the style of a highlight rule, see [`HighlightRule::parse`]
+fn parse_style(spec: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        let modifier = match word.to_lowercase().as_str() {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "on" => {
+                let color = words.next().ok_or("'on' needs a background color")?;
+                style = style.bg(parse_color(color)?);
+                continue;
+            }
+            _ => {
+                style = style.fg(parse_color(word)?);
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+fn parse_color(name: &str) -> Result<Color, String> {
+    name.parse()
+        .map_err(|_| format!("unknown color or modifier '{}'", name))
+}
+
+/// Apply highlight rules to a line of output
+pub(crate) fn apply_highlight_rules(line: &StyledLine, rules: &[HighlightRule]) -> StyledLine {
+    if rules.is_empty() {
+        return line.clone();
+    }
+
+    let text: String = line.iter().map(|(part, _)| part.as_str()).collect();
+    // Byte offset and style of every character, so regex matches map back to them
+    let mut chars: Vec<(usize, char, Style)> = Vec::with_capacity(text.len());
+    let mut offset = 0;
+    for (part, style) in line {
+        for c in part.chars() {
+            chars.push((offset, c, *style));
+            offset += c.len_utf8();
+        }
+    }
+
+    for rule in rules {
+        for found in rule.pattern.find_iter(&text) {
+            let range = if rule.whole_line {
+                0..text.len()
+            } else {
+                found.range()
+            };
+            for (start, _, style) in &mut chars {
+                if range.contains(start) {
+                    *style = style.patch(rule.style);
+                }
+            }
+            if rule.whole_line {
+                break;
+            }
+        }
+    }
+
+    let mut parts: StyledLine = Vec::new();
+    for (_, c, style) in chars {
+        match parts.last_mut() {
+            Some((part, last)) if *last == style => part.push(c),
+            _ => parts.push((c.to_string(), style)),
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_highlight_rule() {
+        let rule = HighlightRule::parse("ERROR -> red bold").unwrap();
+        assert_eq!(rule.pattern.as_str(), "ERROR");
+        assert_eq!(
+            rule.style,
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+        );
+
+        let rule = HighlightRule::parse(r"^\[db\] -> lightcyan on #202020").unwrap();
+        assert_eq!(
+            rule.style,
+            Style::default()
+                .fg(Color::LightCyan)
+                .bg(Color::Rgb(0x20, 0x20, 0x20))
+        );
+
+        assert!(HighlightRule::parse("ERROR").is_err());
+        assert!(HighlightRule::parse("ERROR -> redd").is_err());
+        assert!(HighlightRule::parse("ERROR( -> red").is_err());
+    }
+
+    #[test]
+    fn test_apply_highlight_rules() {
+        let red = Style::default().fg(Color::Red);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let line = vec![("WARN: é ERROR".to_string(), Style::default())];
+        let rules = [
+            HighlightRule::new("ERROR", red).unwrap(),
+            HighlightRule::new("WARN", bold).unwrap(),
+        ];
+        assert_eq!(
+            apply_highlight_rules(&line, &rules),
+            [
+                ("WARN".to_string(), bold),
+                (": é ".to_string(), Style::default()),
+                ("ERROR".to_string(), red),
+            ]
+        );
+
+        let rules = [HighlightRule::new("é", red).unwrap().whole_line()];
+        assert_eq!(
+            apply_highlight_rules(&line, &rules),
+            [("WARN: é ERROR".to_string(), red)]
+        );
+    }
+}
@@ -1,10 +1,82 @@
+use crate::cancel::CancelToken;
+use crate::dialog::{Dialog, DialogAnswer, DialogKind};
 use crate::error::IstariError;
-use crate::menu::Menu;
-use crate::menu_manager::MenuManager;
-use crate::types::{IntoTickFn, Mode, TickFn};
+use crate::fuzzy::fuzzy_score;
+use crate::highlight_rules::HighlightRule;
+use crate::jobs::{self, Job, JobInfo, JobStatus};
+use crate::keymap::{self, KeyBinding, KeyMap};
+use crate::layout::{LayoutConfig, Panes};
+use crate::menu::{Menu, MenuItem, SharedMenu};
+use crate::menu_manager::{Execution, MenuManager};
+use crate::metrics::{Metric, MetricValue};
+use crate::output::{
+    ActionOutput, OutputCommand, OutputFormat, OutputHandle, OutputLevel, OutputRecord, OutputSink,
+};
+use crate::params::{ParamSpec, Params};
+use crate::plugin::{MenuPlugin, PluginEvent};
+use crate::progress::{JobProgress, Progress};
+use crate::rendering::UIController;
+use crate::session::{ExportOptions, OutputLogFile, SessionLog};
+use crate::stats::ExecutionStats;
+use crate::theme::{ColorSupport, THEME_PRESETS, Theme};
+use crate::tree::MenuTree;
+use crate::types::{
+    ActionResult, IntoActionFn, IntoTickFn, MaybeSendSync, Mode, Navigation, OutputPolicy,
+    QuitBehavior, StatusFn, TickFn,
+};
+use crate::undo::{UndoEntry, UndoStack};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 use tokio;
+use unicode_width::UnicodeWidthStr;
+
+/// Source of the messages written by the tick handler
+const TICK_SOURCE: &str = "<tick>";
+
+/// Source of the banner messages shown on startup
+const BANNER_SOURCE: &str = "<banner>";
+
+/// Name of the output tab holding everything not sent to a named tab
+pub const MAIN_OUTPUT_TAB: &str = "Main";
+
+/// Render a framed block with an application name and version
+pub fn title_banner(name: &str, version: &str) -> String {
+    let lines = [
+        format!("{} v{}", name, version),
+        "Type a key to run a command, Tab to scroll output".to_string(),
+    ];
+    let width = lines.iter().map(|line| line.width()).max().unwrap_or(0);
+
+    let mut banner = format!("╭{}╮\n", "─".repeat(width + 2));
+    for line in &lines {
+        banner.push_str(&format!(
+            "│ {}{} │\n",
+            line,
+            " ".repeat(width - line.width())
+        ));
+    }
+    banner.push_str(&format!("╰{}╯", "─".repeat(width + 2)));
+    banner
+}
+
+/// How often the application ticks and the TUI checks for a new frame, unless set
+const DEFAULT_TICK_RATE: Duration = Duration::from_millis(100);
+
+/// How many times its own run time a tick handler over budget is skipped for
+const TICK_THROTTLE_FACTOR: u32 = 4;
+
+/// Number of messages an output buffer keeps unless set
+pub const OUTPUT_BUFFER_SIZE: usize = 10_000;
+
+/// Number of finished background jobs kept for the jobs list
+const FINISHED_JOBS_SIZE: usize = 20;
+
+/// Number of commands suggested for the input at most
+const MAX_SUGGESTIONS: usize = 8;
 
 /// Defines the user interface mode used by the application
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -13,6 +85,8 @@ pub enum UIMode {
     TUI,
     /// Simple text-based interface
     Text,
+    /// A renderer supplied by the application, see [`Istari::with_renderer`]
+    Custom,
 }
 
 /// Manages command history with navigation capabilities
@@ -98,35 +172,452 @@ impl CommandHistory {
     pub fn exit_browsing(&mut self) {
         self.position = None;
     }
+
+    /// Check if an entry of the history is being browsed
+    pub fn is_browsing(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Change the maximum number of commands to keep, dropping the oldest ones if needed
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        let excess = self.entries.len().saturating_sub(max_size);
+        self.entries.drain(..excess);
+    }
+
+    /// Load history entries from a file with one command per line
+    pub fn load(&mut self, path: &Path) -> std::io::Result<()> {
+        let contents = std::fs::read_to_string(path)?;
+        for line in contents.lines() {
+            self.add(line.to_string());
+        }
+        Ok(())
+    }
+
+    /// Save history entries to a file with one command per line
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut contents = self.entries.join("\n");
+        contents.push('\n');
+        std::fs::write(path, contents)
+    }
+}
+
+/// A reference to an action anywhere in the menu tree, with the parameters to run it with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandRef {
+    /// Keys of the submenus leading to the item
+    pub path: Vec<String>,
+    /// Key of the item
+    pub key: String,
+    /// Parameters to run the item with
+    pub params: Option<String>,
+    /// Description of the item
+    pub description: String,
+}
+
+impl CommandRef {
+    /// Get the key path of the item, e.g. `settings/r`
+    pub fn item_path(&self) -> String {
+        let mut segments = self.path.clone();
+        segments.push(self.key.clone());
+        segments.join("/")
+    }
+
+    /// Get a short label for the command, e.g. `settings/r 5`
+    pub fn label(&self) -> String {
+        match &self.params {
+            Some(params) => format!("{} {}", self.item_path(), params),
+            None => self.item_path(),
+        }
+    }
+}
+
+/// Tracks recently run actions, most recent first
+#[derive(Debug, Clone)]
+pub struct RecentCommands {
+    /// Recent commands, most recent first
+    entries: VecDeque<CommandRef>,
+    /// Maximum number of commands to keep
+    max_size: usize,
+    /// Whether the "Recent" section is rendered at the top of the root menu
+    visible: bool,
+}
+
+impl RecentCommands {
+    /// Create a new recent command tracker
+    pub fn new(max_size: usize, visible: bool) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_size,
+            visible,
+        }
+    }
+
+    /// Record a command, moving it to the front if it was already present
+    pub fn record(&mut self, command: CommandRef) {
+        self.entries.retain(|entry| entry != &command);
+        self.entries.push_front(command);
+        self.entries.truncate(self.max_size);
+    }
+
+    /// Get a command by its 1-based number, 1 being the most recent
+    pub fn get(&self, number: usize) -> Option<&CommandRef> {
+        number.checked_sub(1).and_then(|idx| self.entries.get(idx))
+    }
+
+    /// Iterate over the recent commands, most recent first
+    pub fn iter(&self) -> impl Iterator<Item = &CommandRef> {
+        self.entries.iter()
+    }
+
+    /// Check if the "Recent" section should be rendered
+    pub fn is_visible(&self) -> bool {
+        self.visible && !self.entries.is_empty()
+    }
 }
 
-/// Manages output messages with notification capabilities
+/// Actions pinned to the "Favorites" area of the root menu
 #[derive(Debug, Clone, Default)]
+pub struct Favorites {
+    /// Pinned actions, in the order they were pinned
+    entries: Vec<CommandRef>,
+}
+
+impl Favorites {
+    /// Create an empty favorites list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin an action, returning false if it was already pinned
+    pub fn pin(&mut self, command: CommandRef) -> bool {
+        if self.entries.iter().any(|entry| entry == &command) {
+            return false;
+        }
+        self.entries.push(command);
+        true
+    }
+
+    /// Unpin an action by its 1-based number
+    pub fn unpin(&mut self, number: usize) -> Option<CommandRef> {
+        let idx = number
+            .checked_sub(1)
+            .filter(|&idx| idx < self.entries.len())?;
+        Some(self.entries.remove(idx))
+    }
+
+    /// Get a pinned action by its 1-based number
+    pub fn get(&self, number: usize) -> Option<&CommandRef> {
+        number.checked_sub(1).and_then(|idx| self.entries.get(idx))
+    }
+
+    /// Iterate over the pinned actions
+    pub fn iter(&self) -> impl Iterator<Item = &CommandRef> {
+        self.entries.iter()
+    }
+
+    /// Check if no action is pinned
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Save the pinned actions to a file, one `path<TAB>description` per line
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|entry| format!("{}\t{}\n", entry.item_path(), entry.description))
+            .collect();
+        std::fs::write(path, contents)
+    }
+
+    /// Load pinned actions from a file written by [`Favorites::save`]
+    pub fn load(&mut self, path: &Path) -> std::io::Result<()> {
+        let contents = std::fs::read_to_string(path)?;
+        for line in contents.lines() {
+            let (item_path, description) = line.split_once('\t').unwrap_or((line, ""));
+            let mut path: Vec<String> = item_path.split('/').map(str::to_string).collect();
+            let Some(key) = path.pop().filter(|key| !key.is_empty()) else {
+                continue;
+            };
+            self.pin(CommandRef {
+                path,
+                key,
+                params: None,
+                description: description.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Text of the messages output buffers add or replace while a command runs, in order
+type OutputCapture = Arc<Mutex<Vec<String>>>;
+
+/// Manages output messages with notification capabilities.
+///
+/// The buffer keeps at most `max_size` messages, dropping the oldest first, so
+/// long-running applications don't grow without bound.
+#[derive(Debug, Clone)]
 pub struct OutputBuffer {
-    /// Output messages
-    messages: Vec<String>,
+    /// Output messages, oldest first
+    records: VecDeque<OutputRecord>,
+    /// Whether a message repeating the last one counts it up instead of being added
+    collapse_duplicates: bool,
+    /// Path of the menu new messages are added in
+    menu: String,
+    /// File every message is appended to as it's added, if any
+    log_file: Option<OutputLogFile>,
+    /// Where the text of every message added or replaced is collected too, if anywhere,
+    /// e.g. to log what a command produced
+    capture: Option<OutputCapture>,
+    /// Maximum number of messages kept
+    max_size: usize,
+    /// Number of messages ever added, including the ones dropped since
+    total: usize,
+    /// Number of messages dropped from the front, pushed out or cleared
+    dropped: usize,
     /// Flag indicating if new messages were added
     new_output: bool,
 }
 
+impl Default for OutputBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OutputBuffer {
-    /// Create a new output buffer
+    /// Create a new output buffer keeping the last [`OUTPUT_BUFFER_SIZE`] messages
     pub fn new() -> Self {
+        Self::with_max_size(OUTPUT_BUFFER_SIZE)
+    }
+
+    /// Create a new output buffer keeping the last `max_size` messages
+    pub fn with_max_size(max_size: usize) -> Self {
         Self {
-            messages: Vec::new(),
+            records: VecDeque::new(),
+            collapse_duplicates: false,
+            menu: String::new(),
+            log_file: None,
+            capture: None,
+            max_size,
+            total: 0,
+            dropped: 0,
             new_output: false,
         }
     }
 
+    /// Change the maximum number of messages to keep, dropping the oldest ones if needed
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        let excess = self.records.len().saturating_sub(max_size);
+        self.records.drain(..excess);
+        self.dropped += excess;
+    }
+
+    /// Collapse a message repeating the last one, with the same source and level,
+    /// into it with a `×N` counter instead of adding it, e.g. for retry loops
+    pub fn set_collapse_duplicates(&mut self, collapse: bool) {
+        self.collapse_duplicates = collapse;
+    }
+
+    /// Check whether repeated messages collapse into one
+    pub fn collapses_duplicates(&self) -> bool {
+        self.collapse_duplicates
+    }
+
+    /// Set the path of the menu new messages are added in, its keys joined by `/`
+    pub fn set_menu(&mut self, path: impl Into<String>) {
+        self.menu = path.into();
+    }
+
+    /// Append every message added from now on to a file
+    pub(crate) fn set_log_file(&mut self, log_file: Option<OutputLogFile>) {
+        self.log_file = log_file;
+    }
+
+    /// Collect the text of every message added or replaced from now on
+    fn set_capture(&mut self, capture: Option<OutputCapture>) {
+        self.capture = capture;
+    }
+
+    /// Write a message added or replaced to the log file and capture, if any
+    fn produced(&self, level: OutputLevel, source: Option<&str>, message: &str) {
+        if let Some(log_file) = &self.log_file {
+            log_file.write(level, source, message);
+        }
+        if let Some(capture) = &self.capture
+            && let Ok(mut capture) = capture.lock()
+        {
+            capture.push(message.to_string());
+        }
+    }
+
+    /// Get the maximum number of messages kept
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Get the number of messages ever added, including the ones dropped since.
+    /// Unlike the length of [`messages`](Self::messages), it keeps growing once
+    /// the buffer is full.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Get the number of messages dropped from the front of the buffer so far
+    ///
+    /// Added to an index into [`records`](Self::records), it tells messages apart
+    /// as the oldest ones go.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
     /// Add an output message
     pub fn add(&mut self, message: String) {
-        self.messages.push(message);
+        self.push(None, OutputLevel::Info, message);
+    }
+
+    /// Add an output message produced by the given source
+    pub fn add_from(&mut self, source: impl Into<String>, message: String) {
+        self.push(Some(source.into()), OutputLevel::Info, message);
+    }
+
+    /// Add an output message with its source and level
+    pub fn push(&mut self, source: Option<String>, level: OutputLevel, message: String) {
+        self.push_formatted(source, level, OutputFormat::Plain, message);
+    }
+
+    /// Add an output message with its source, level and format
+    pub fn push_formatted(
+        &mut self,
+        source: Option<String>,
+        level: OutputLevel,
+        format: OutputFormat,
+        message: String,
+    ) {
+        self.total += 1;
         self.new_output = true;
+        self.produced(level, source.as_deref(), &message);
+
+        if self.collapse_duplicates
+            && let Some(last) = self.records.back_mut().filter(|last| {
+                last.text == message
+                    && last.source == source
+                    && last.level == level
+                    && last.format == format
+                    && last.menu == self.menu
+            })
+        {
+            last.repeats += 1;
+            last.time = SystemTime::now();
+            return;
+        }
+
+        if self.records.len() >= self.max_size && self.records.pop_front().is_some() {
+            self.dropped += 1;
+        }
+        if self.max_size > 0 {
+            self.records.push_back(OutputRecord {
+                text: message,
+                source,
+                level,
+                format,
+                time: SystemTime::now(),
+                repeats: 1,
+                menu: self.menu.clone(),
+            });
+        }
+    }
+
+    /// Replace the most recent message produced by the given source,
+    /// or add it if the source has no message yet
+    pub fn replace_from(&mut self, source: impl Into<String>, message: String) {
+        self.replace_formatted(source, OutputFormat::Plain, message);
+    }
+
+    /// Replace the most recent message produced by the given source with a formatted one,
+    /// or add it if the source has no message yet
+    pub fn replace_formatted(
+        &mut self,
+        source: impl Into<String>,
+        format: OutputFormat,
+        message: String,
+    ) {
+        let source = source.into();
+        let previous = self
+            .records
+            .iter()
+            .rposition(|record| record.source.as_deref() == Some(source.as_str()));
+
+        match previous {
+            Some(idx) => {
+                self.produced(self.records[idx].level, Some(&source), &message);
+                let record = &mut self.records[idx];
+                record.text = message;
+                record.format = format;
+                record.time = SystemTime::now();
+                record.repeats = 1;
+                self.new_output = true;
+            }
+            None => self.push_formatted(Some(source), OutputLevel::Info, format, message),
+        }
+    }
+
+    /// Get all messages with their source, level and format, oldest first
+    pub fn records(&self) -> &VecDeque<OutputRecord> {
+        &self.records
+    }
+
+    /// Get the text of all messages, oldest first
+    pub fn messages(&self) -> Vec<&str> {
+        self.records
+            .iter()
+            .map(|record| record.text.as_str())
+            .collect()
+    }
+
+    /// Get the level of each message, oldest first
+    pub fn levels(&self) -> Vec<OutputLevel> {
+        self.records.iter().map(|record| record.level).collect()
     }
 
-    /// Get all messages
-    pub fn messages(&self) -> &[String] {
-        &self.messages
+    /// Apply a change sent through an output handle
+    pub(crate) fn apply(&mut self, command: OutputCommand) {
+        match command {
+            OutputCommand::Push {
+                source,
+                level,
+                message,
+            } => self.push(source, level, message),
+            OutputCommand::ReplaceLast {
+                source: Some(source),
+                message,
+            } => self.replace_from(source, message),
+            OutputCommand::ReplaceLast {
+                source: None,
+                message,
+            } => match self.records.back() {
+                Some(last) => {
+                    self.produced(last.level, last.source.as_deref(), &message);
+                    let last = self.records.back_mut().unwrap();
+                    last.text = message;
+                    last.format = OutputFormat::Plain;
+                    last.time = SystemTime::now();
+                    last.repeats = 1;
+                    self.new_output = true;
+                }
+                None => self.add(message),
+            },
+            OutputCommand::Clear => {
+                self.clear();
+                // Still redraw so the cleared buffer shows up
+                self.new_output = true;
+            }
+            // Progress, tabs and quitting are handled by the application, not the buffer
+            OutputCommand::Progress { .. } | OutputCommand::ToTab { .. } | OutputCommand::Quit => {}
+        }
     }
 
     /// Check if there's new output and reset the flag
@@ -136,29 +627,164 @@ impl OutputBuffer {
         has_new
     }
 
+    /// Check if there's new output without resetting the flag
+    pub fn has_unseen_output(&self) -> bool {
+        self.new_output
+    }
+
     /// Clear all messages
     pub fn clear(&mut self) {
-        self.messages.clear();
+        self.dropped += self.records.len();
+        self.records.clear();
         self.new_output = false;
     }
 }
 
+/// Result of an async action that finished in the background
+struct FinishedAction {
+    /// Id of the job the action ran as
+    job: u64,
+    /// The action that ran
+    command: CommandRef,
+    /// Where its output goes
+    policy: OutputPolicy,
+    /// What it returned
+    result: ActionResult,
+}
+
+/// An async action running in the background
+struct RunningJob {
+    /// Last progress the action reported
+    progress: JobProgress,
+    /// Token the action checks to stop early
+    cancel: CancelToken,
+}
+
+impl RunningJob {
+    /// Describe the job for the jobs list
+    fn info(&self) -> JobInfo {
+        let status = if self.cancel.is_cancelled() {
+            JobStatus::Cancelling
+        } else {
+            JobStatus::Running
+        };
+        JobInfo {
+            id: self.progress.id,
+            command: self.progress.command.clone(),
+            status,
+            started: self.progress.started,
+            finished: None,
+            details: Some(self.progress.message.clone()).filter(|message| !message.is_empty()),
+        }
+    }
+}
+
+/// Action output shown in a popup viewer instead of the output buffer
+#[derive(Debug, Clone)]
+pub struct OutputPopup {
+    /// Title of the popup, usually the description of the item that produced it
+    pub title: String,
+    /// Content of the popup
+    pub content: String,
+    /// Current scroll position (0 = top)
+    pub scroll: usize,
+}
+
+impl OutputPopup {
+    /// Create a new popup scrolled to the top
+    pub fn new(title: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            content: content.into(),
+            scroll: 0,
+        }
+    }
+
+    /// Scroll the popup content by the given number of lines
+    pub fn scroll_by(&mut self, delta: isize) {
+        let max_scroll = self.content.lines().count().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(max_scroll);
+    }
+}
+
+/// Timing statistics of the tick handler
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TickStats {
+    /// Number of times the tick handler ran
+    pub runs: u64,
+    /// Time taken by the last run
+    pub last: Duration,
+    /// Longest time taken by a run
+    pub max: Duration,
+    /// Total time taken by all runs
+    pub total: Duration,
+    /// Number of runs that went over the tick budget
+    pub slow_ticks: u64,
+}
+
+impl TickStats {
+    /// Record the time taken by a run of the tick handler
+    fn record(&mut self, elapsed: Duration) {
+        self.runs += 1;
+        self.last = elapsed;
+        self.max = self.max.max(elapsed);
+        self.total += elapsed;
+    }
+
+    /// Get the average time taken by a run
+    pub fn average(&self) -> Duration {
+        if self.runs == 0 {
+            Duration::ZERO
+        } else {
+            self.total.div_f64(self.runs as f64)
+        }
+    }
+}
+
 /// Main application that handles rendering and events
 pub struct Istari<T> {
     /// Menu navigation and management
     menu_manager: MenuManager<T>,
     /// Application state shared with menu actions
     state: T,
-    /// Output management
+    /// Output management, of the main output tab
     output: OutputBuffer,
+    /// Named output tabs, in the order they were opened
+    output_tabs: Vec<(String, OutputBuffer)>,
+    /// Output tab shown, 0 for the main one and then the named tabs in order
+    active_tab: usize,
     /// Last tick update time, for animations or time-based updates
     last_tick_time: Instant,
     /// Optional tick function that's called on each frame update
     tick_handler: Option<TickFn<T>>,
+    /// Segments of the status bar, left to right
+    status_segments: Vec<StatusFn<T>>,
+    /// Labeled expressions of the state shown in the watch panel
+    watches: Vec<(String, StatusFn<T>)>,
+    /// Sparklines and gauges of the stats pane
+    metrics: Vec<Metric<T>>,
+    /// Time the tick handler may take per tick before it is throttled, if limited
+    tick_budget: Option<Duration>,
+    /// Time between ticks
+    tick_rate: Duration,
+    /// Time between frames of the TUI
+    render_rate: Duration,
+    /// Timing statistics of the tick handler
+    tick_stats: TickStats,
+    /// Whether the tick handler went over its budget on its last run
+    tick_throttled: bool,
+    /// Time before which the tick handler is skipped
+    tick_throttled_until: Option<Instant>,
     /// Current application mode
     current_mode: Mode,
     /// Command input buffer
     input_buffer: String,
+    /// Values offered by the last Tab completion that matched more than one
+    completions: Vec<String>,
+    /// Whether commands matching the input are suggested as it's typed
+    command_suggestions: bool,
+    /// Index of the suggestion picked with the arrow keys, if any
+    selected_suggestion: Option<usize>,
     /// Command history management
     command_history: CommandHistory,
     /// Whether the command input should be displayed
@@ -167,23 +793,189 @@ pub struct Istari<T> {
     runtime: tokio::runtime::Runtime,
     /// User interface mode (TUI or Text)
     ui_mode: UIMode,
+    /// Keys of the interactive renderers
+    keymap: KeyMap,
+    /// Renderer used in [`UIMode::Custom`], taken out while it runs
+    renderer: Option<Box<dyn UIController<T>>>,
+    /// Popup viewer currently shown, if any
+    popup: Option<OutputPopup>,
+    /// Dialogs waiting to be answered, the first one is shown
+    dialogs: VecDeque<Dialog>,
+    /// Recently run actions
+    recent_commands: RecentCommands,
+    /// Actions pinned to the root menu
+    favorites: Favorites,
+    /// File that command history and favorites are persisted to, if any
+    history_file: Option<PathBuf>,
+    /// Category the current menu's items are filtered by, if any
+    category_filter: Option<String>,
+    /// What the quit key does
+    quit_behavior: QuitBehavior,
+    /// Emphasis of menu items tagged with a category, e.g. a warning color for "danger"
+    tag_styles: HashMap<String, OutputLevel>,
+    /// Action waiting for the user to confirm it
+    pending_confirmation: Option<CommandRef>,
+    /// Number of actions that failed with an error
+    error_count: usize,
+    /// Whether the TUI scrolls to new errors and flashes the output border
+    error_focus: bool,
+    /// Lowest level of the output shown, lower levels are hidden but kept
+    log_level: OutputLevel,
+    /// Pattern the output lines shown match, the others are hidden but kept
+    output_filter: Option<Regex>,
+    /// Output written through handles while the output is paused, applied on resume
+    paused_output: Option<Vec<OutputCommand>>,
+    /// Whether what sync actions print to stdout and stderr goes to the output
+    capture_stdio: bool,
+    /// Whether each menu shows only the output added in it
+    menu_output: bool,
+    /// Whether the output of every menu shows anyway, with `menu_output` on
+    all_menus_output: bool,
+    /// Path of the menu seen last, to notice entering another menu
+    last_menu_path: Option<Vec<String>>,
+    /// When the current menu's refresh action last ran
+    last_refresh: Instant,
+    /// Commands entered during this session, for `export-history`
+    session_log: SessionLog,
+    /// Whether to prompt for missing required parameters instead of failing
+    prompt_missing_params: bool,
+    /// Action waiting for a missing parameter, with the parameter's spec
+    pending_param: Option<(CommandRef, ParamSpec)>,
+    /// Plugins contributing menus and background behavior
+    plugins: Vec<Box<dyn MenuPlugin<T>>>,
+    /// When plugins last ticked
+    last_plugin_tick: Instant,
+    /// Progress of the async actions running in the background, by job id
+    running_jobs: BTreeMap<u64, RunningJob>,
+    /// Id given to the next action that's started
+    next_job_id: u64,
+    /// Background jobs that finished recently, oldest first
+    finished_jobs: VecDeque<JobInfo>,
+    /// Whether the TUI shows the jobs panel
+    show_jobs: bool,
+    /// Whether an action asked to quit the application
+    quit_requested: bool,
+    /// Actions that can be undone and redone
+    undo_stack: UndoStack,
+    /// Commands waiting to run one after another, see [`Istari::enqueue`]
+    action_queue: VecDeque<CommandRef>,
+    /// Queued async action still running, with its job id
+    queue_running: Option<(CommandRef, u64)>,
+    /// How often and how long each action ran
+    action_stats: ExecutionStats,
+    /// Styles the renderers draw with
+    theme: Theme,
+    /// Colors the terminal shows, the theme is degraded to them
+    color_support: ColorSupport,
+    /// Title of the application shown in the TUI header before the mode
+    title: String,
+    /// Name and version of the application shown in the header, if set
+    app_info: Option<(String, String)>,
+    /// Splash shown on startup, until dismissed
+    splash: Option<String>,
+    /// How long the splash stays up without a key press, until a key if unset
+    splash_timeout: Option<Duration>,
+    /// Footer help lines replacing the built-in ones, per mode
+    help_lines: Vec<(Mode, String)>,
+    /// Rules styling the output matching a regex, applied in order
+    highlight_rules: Vec<HighlightRule>,
+    /// Where the TUI places the menu and output panels
+    layout: LayoutConfig,
+    /// Which of the menu and output panes the TUI shows
+    panes: Panes,
+    /// Number of async actions that finished in the background
+    completed_actions: usize,
+    /// Sending side of the channel async actions deliver their results through
+    action_sender: Sender<FinishedAction>,
+    /// Receiving side of the channel async actions deliver their results through
+    action_receiver: Receiver<FinishedAction>,
+    /// Sending side of the channel behind output handles
+    output_sender: Sender<OutputCommand>,
+    /// Receiving side of the channel behind output handles, drained on every tick
+    output_receiver: Receiver<OutputCommand>,
 }
 
 impl<T: std::fmt::Debug> Istari<T> {
     /// Create a new Istari application with the given root menu and state
     pub fn new(root_menu: Menu<T>, state: T) -> Result<Self, IstariError> {
+        let (output_sender, output_receiver) = mpsc::channel();
+        let (action_sender, action_receiver) = mpsc::channel();
         Ok(Self {
             menu_manager: MenuManager::new(root_menu)?,
             state,
             output: OutputBuffer::new(),
+            output_tabs: Vec::new(),
+            active_tab: 0,
             last_tick_time: Instant::now(),
             tick_handler: None,
+            status_segments: Vec::new(),
+            watches: Vec::new(),
+            metrics: Vec::new(),
+            tick_budget: None,
+            tick_rate: DEFAULT_TICK_RATE,
+            render_rate: DEFAULT_TICK_RATE,
+            tick_stats: TickStats::default(),
+            tick_throttled: false,
+            tick_throttled_until: None,
             current_mode: Mode::Command, // Default to command mode
             input_buffer: String::new(),
+            completions: Vec::new(),
+            command_suggestions: true,
+            selected_suggestion: None,
             command_history: CommandHistory::new(100),
             show_input: false,
             runtime: tokio::runtime::Runtime::new().unwrap(),
             ui_mode: UIMode::TUI, // Default to TUI mode
+            keymap: KeyMap::default(),
+            renderer: None,
+            popup: None,
+            dialogs: VecDeque::new(),
+            recent_commands: RecentCommands::new(10, false),
+            favorites: Favorites::new(),
+            history_file: None,
+            category_filter: None,
+            quit_behavior: QuitBehavior::default(),
+            tag_styles: HashMap::new(),
+            pending_confirmation: None,
+            error_count: 0,
+            error_focus: false,
+            log_level: OutputLevel::Debug,
+            output_filter: None,
+            paused_output: None,
+            capture_stdio: false,
+            menu_output: false,
+            all_menus_output: false,
+            last_menu_path: None,
+            last_refresh: Instant::now(),
+            session_log: SessionLog::new(),
+            prompt_missing_params: true,
+            pending_param: None,
+            plugins: Vec::new(),
+            last_plugin_tick: Instant::now(),
+            running_jobs: BTreeMap::new(),
+            next_job_id: 1,
+            finished_jobs: VecDeque::new(),
+            show_jobs: false,
+            quit_requested: false,
+            undo_stack: UndoStack::default(),
+            action_queue: VecDeque::new(),
+            queue_running: None,
+            action_stats: ExecutionStats::new(),
+            theme: Theme::default(),
+            color_support: ColorSupport::detect(),
+            title: "Istari".to_string(),
+            app_info: None,
+            splash: None,
+            splash_timeout: None,
+            help_lines: Vec::new(),
+            highlight_rules: Vec::new(),
+            layout: LayoutConfig::default(),
+            panes: Panes::Both,
+            completed_actions: 0,
+            action_sender,
+            action_receiver,
+            output_sender,
+            output_receiver,
         })
     }
 
@@ -196,138 +988,2377 @@ impl<T: std::fmt::Debug> Istari<T> {
         self
     }
 
-    /// Set the user interface mode
-    pub fn with_ui_mode(mut self, mode: UIMode) -> Self {
-        self.ui_mode = mode;
+    /// Add a segment to the status bar, computed from the state every time it's drawn,
+    /// e.g. the connection status. Segments are shown left to right in the order they're added.
+    pub fn with_status_segment<F>(mut self, segment: F) -> Self
+    where
+        F: Fn(&T) -> String + MaybeSendSync + 'static,
+    {
+        self.status_segments.push(Box::new(segment));
         self
     }
 
-    /// Set the maximum number of commands to keep in history
-    pub fn with_max_history_size(mut self, size: usize) -> Self {
-        self.command_history = CommandHistory::new(size);
+    /// Get the text of the status bar, `None` if there are no segments.
+    ///
+    /// Empty segments are left out.
+    pub fn status_line(&self) -> Option<String> {
+        if self.status_segments.is_empty() {
+            return None;
+        }
+        let segments: Vec<String> = self
+            .status_segments
+            .iter()
+            .map(|segment| segment(&self.state))
+            .filter(|text| !text.is_empty())
+            .collect();
+        Some(segments.join(" | "))
+    }
+
+    /// Watch an expression of the state, e.g. a counter or the depth of a queue,
+    /// shown next to its label in a panel below the menu and kept up to date
+    pub fn with_watch<F>(mut self, label: impl Into<String>, watch: F) -> Self
+    where
+        F: Fn(&T) -> String + MaybeSendSync + 'static,
+    {
+        self.watches.push((label.into(), Box::new(watch)));
         self
     }
 
-    /// Get the current UI mode
-    pub fn ui_mode(&self) -> UIMode {
-        self.ui_mode
+    /// Evaluate the watched expressions, with their labels in the order they were added
+    pub fn watches(&self) -> Vec<(&str, String)> {
+        self.watches
+            .iter()
+            .map(|(label, watch)| (label.as_str(), watch(&self.state)))
+            .collect()
     }
 
-    /// Get a reference to the current menu
-    pub fn current_menu(&self) -> Arc<Mutex<Menu<T>>> {
-        self.menu_manager.current_menu()
+    /// Chart a number of the state in the stats pane, sampled every tick, e.g.
+    /// requests per second. The sparkline shows the last samples.
+    pub fn with_sparkline<F>(mut self, label: impl Into<String>, read: F) -> Self
+    where
+        F: Fn(&T) -> f64 + MaybeSendSync + 'static,
+    {
+        self.metrics
+            .push(Metric::sparkline(label.into(), Box::new(read)));
+        self
     }
 
-    /// Get a reference to the output messages
-    pub fn output_messages(&self) -> &[String] {
-        self.output.messages()
+    /// Show a ratio of the state between 0 and 1 as a gauge in the stats pane,
+    /// e.g. disk usage
+    pub fn with_gauge<F>(mut self, label: impl Into<String>, read: F) -> Self
+    where
+        F: Fn(&T) -> f64 + MaybeSendSync + 'static,
+    {
+        self.metrics
+            .push(Metric::gauge(label.into(), Box::new(read)));
+        self
     }
 
-    /// Add an output message
-    pub fn add_output(&mut self, message: String) {
-        self.output.add(message);
+    /// Get the sparklines and gauges of the stats pane, in the order they were added
+    pub fn metrics(&self) -> Vec<MetricValue<'_>> {
+        self.metrics
+            .iter()
+            .map(|metric| metric.value(&self.state))
+            .collect()
     }
 
-    /// Check if there's new output and reset the flag
-    pub fn has_new_output(&mut self) -> bool {
-        self.output.has_new_output()
+    /// Let a plugin contribute its submenus and actions to the root menu and hook into
+    /// ticks and events, validating the resulting menu tree
+    pub fn with_plugin(
+        mut self,
+        plugin: impl MenuPlugin<T> + 'static,
+    ) -> Result<Self, IstariError> {
+        plugin.contribute(&mut self.menu_manager.root_menu().lock().unwrap());
+        self.menu_manager.validate()?;
+        self.plugins.push(Box::new(plugin));
+        Ok(self)
     }
 
-    /// Clear all output messages
-    pub fn clear_output_messages(&mut self) {
-        self.output.clear();
+    /// Register an action that can be run from every menu, e.g. "status" or "clear"
+    pub fn with_global_action<F, Marker>(
+        self,
+        key: impl Into<String>,
+        description: impl Into<String>,
+        action: F,
+    ) -> Result<Self, IstariError>
+    where
+        F: IntoActionFn<T, Marker>,
+    {
+        self.with_global_item(MenuItem::new_action(key, description.into(), action))
     }
 
-    /// Handle a tick update
-    /// This is called regularly to update any time-based state
-    pub fn tick(&mut self) {
-        let now = Instant::now();
-        let delta_time = now.duration_since(self.last_tick_time).as_secs_f32();
-        self.last_tick_time = now;
+    /// Register a menu item that can be run from every menu.
+    ///
+    /// Global items are only matched when the current menu has no item with the same key,
+    /// and can't open submenus.
+    pub fn with_global_item(mut self, item: MenuItem<T>) -> Result<Self, IstariError> {
+        self.menu_manager.add_global(item)?;
+        Ok(self)
+    }
 
-        // Call custom tick handler if one is set
-        if let Some(handler) = &self.tick_handler {
-            // Save the current message count to detect new messages
-            let prev_msg_count = self.output.messages().len();
-            let mut output_messages = self.output.messages.clone();
+    /// Get the menu holding the actions available from every menu
+    pub fn global_menu(&self) -> Arc<Mutex<Menu<T>>> {
+        self.menu_manager.globals()
+    }
 
-            handler(&mut self.state, &mut output_messages, delta_time);
+    /// Limit the time the tick handler may take per tick.
+    ///
+    /// A tick handler going over budget, e.g. by doing blocking I/O, triggers a
+    /// warning and is skipped for a while so the UI stays responsive.
+    pub fn with_tick_budget(mut self, budget: Duration) -> Self {
+        self.tick_budget = Some(budget);
+        self
+    }
 
-            // Check if tick handler added messages
-            if output_messages.len() > prev_msg_count {
-                // Update with new messages
-                self.output.messages = output_messages;
-                self.output.new_output = true;
-            }
-        }
+    /// Set how often the application ticks, running the tick handler and plugins
+    /// and picking up finished async actions, 100ms by default
+    pub fn with_tick_rate(mut self, rate: Duration) -> Self {
+        self.tick_rate = rate;
+        self
     }
 
-    /// Process a single character key command, potentially with parameters
-    pub fn handle_key_with_params(
-        &mut self,
-        key: impl Into<String>,
-        params: Option<String>,
-    ) -> bool {
-        let key_string = key.into();
+    /// Set how often the TUI draws a new frame when something changed, 100ms by
+    /// default, e.g. `Duration::from_millis(33)` for 30 fps animations with a
+    /// tick rate of a second
+    pub fn with_render_rate(mut self, rate: Duration) -> Self {
+        self.render_rate = rate;
+        self
+    }
 
-        // Check for special keys first
-        if key_string == "q" {
-            // Only quit from root menu
-            if self.menu_manager.is_at_root() {
-                return false; // Signal to exit the app
-            } else {
-                self.add_output(
-                    "Use 'b' to return to previous menu, or navigate to root menu to quit"
-                        .to_string(),
-                );
-                return true;
-            }
-        } else if key_string == "b" {
-            // Back navigation
-            if !self.menu_manager.navigate_back() {
-                self.add_output("Already at root menu".to_string());
-            }
-            return true;
-        }
+    /// Get how often the application ticks
+    pub fn tick_rate(&self) -> Duration {
+        self.tick_rate
+    }
 
-        // Check if the key corresponds to a menu item with a submenu
-        if self.menu_manager.has_submenu(&key_string) {
-            self.menu_manager.navigate_to_submenu(&key_string);
-            return true;
-        }
+    /// Get how often the TUI draws a new frame when something changed
+    pub fn render_rate(&self) -> Duration {
+        self.render_rate
+    }
 
-        // Check if the key corresponds to a menu item with an action
-        if self.menu_manager.has_action(&key_string) {
-            let params_ref = params.as_deref();
-            if let Some(result) = self.menu_manager.execute_action(
-                &key_string,
-                &mut self.state,
-                params_ref,
-                &self.runtime,
-            ) {
-                self.add_output(result);
-            }
-            return true;
-        }
+    /// Set the user interface mode
+    pub fn with_ui_mode(mut self, mode: UIMode) -> Self {
+        self.ui_mode = mode;
+        self
+    }
 
-        // If we get here, the key wasn't recognized
-        self.add_output(format!("Unknown command: {}", key_string));
-        true
+    /// Remap the keys of the interactive renderers, e.g. to free Tab for completion
+    pub fn with_keymap(mut self, keymap: KeyMap) -> Self {
+        self.keymap = keymap;
+        self
     }
 
-    /// Original handle_key method that delegates to handle_key_with_params
-    pub fn handle_key(&mut self, key: impl Into<String>) -> bool {
-        self.handle_key_with_params(key, None)
+    /// Remap the keys of the interactive renderers from a key map file, see [`KeyMap::parse`]
+    pub fn with_keymap_file(self, path: impl AsRef<Path>) -> Result<Self, IstariError> {
+        let keymap = KeyMap::load(path.as_ref())?;
+        Ok(self.with_keymap(keymap))
     }
 
-    /// Run the application
-    pub fn run(&mut self) -> std::io::Result<()> {
-        crate::rendering::run(self)
+    /// Run the application with a renderer of its own instead of the TUI or Text one,
+    /// switching to [`UIMode::Custom`]
+    pub fn with_renderer(mut self, renderer: impl UIController<T> + 'static) -> Self {
+        self.renderer = Some(Box::new(renderer));
+        self.ui_mode = UIMode::Custom;
+        self
     }
 
-    /// Get the current mode
-    pub fn mode(&self) -> Mode {
-        self.current_mode
+    /// Take the custom renderer out to run it
+    pub(crate) fn take_renderer(&mut self) -> Option<Box<dyn UIController<T>>> {
+        self.renderer.take()
+    }
+
+    /// Put the custom renderer back once it stopped
+    pub(crate) fn set_renderer(&mut self, renderer: Box<dyn UIController<T>>) {
+        self.renderer = Some(renderer);
+    }
+
+    /// Show the last `count` commands in a "Recent" section at the top of the root menu,
+    /// re-executable with `!1`, `!2`, ...
+    pub fn with_recent_commands(mut self, count: usize) -> Self {
+        self.recent_commands = RecentCommands::new(count, true);
+        self
+    }
+
+    /// Set the styles the renderers draw with
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Get the styles the renderers draw with
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Override the colors the terminal shows, detected from `NO_COLOR`, `TERM`
+    /// and `COLORTERM` by default
+    pub fn with_color_support(mut self, color_support: ColorSupport) -> Self {
+        self.color_support = color_support;
+        self
+    }
+
+    /// Get the colors the renderers degrade the theme to
+    pub fn color_support(&self) -> ColorSupport {
+        self.color_support
+    }
+
+    /// Set the title shown in the header in place of "Istari"
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Get the title shown in the header
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Show the application name and version in the header,
+    /// e.g. `with_app_info("Deploy Tool", env!("CARGO_PKG_VERSION"))`
+    pub fn with_app_info(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.app_info = Some((name.into(), version.into()));
+        self
+    }
+
+    /// Get the application name and version shown in the header, like "Deploy Tool v1.2.0"
+    pub fn app_info(&self) -> Option<String> {
+        self.app_info
+            .as_ref()
+            .map(|(name, version)| format!("{} v{}", name, version))
+    }
+
+    /// Show a splash screen on startup, e.g. ASCII art of the application's name.
+    /// The TUI draws it centered with the application name and version and the
+    /// main key bindings until a key is pressed, text mode prints it once before
+    /// the first menu.
+    pub fn with_splash(mut self, splash: impl Into<String>) -> Self {
+        self.splash = Some(splash.into());
+        self
+    }
+
+    /// Take the splash screen down after `timeout` even if no key was pressed
+    pub fn with_splash_timeout(mut self, timeout: Duration) -> Self {
+        self.splash_timeout = Some(timeout);
+        self
+    }
+
+    /// Get the splash screen, until it is dismissed
+    pub fn splash(&self) -> Option<&str> {
+        self.splash.as_deref()
+    }
+
+    /// Get how long the splash screen stays up without a key press, if limited
+    pub fn splash_timeout(&self) -> Option<Duration> {
+        self.splash_timeout
+    }
+
+    /// Take the splash screen down for good
+    pub fn dismiss_splash(&mut self) {
+        self.splash = None;
+    }
+
+    /// Replace the help line shown in the TUI footer in the given mode
+    pub fn with_help_line(mut self, mode: Mode, line: impl Into<String>) -> Self {
+        self.help_lines.retain(|(other, _)| *other != mode);
+        self.help_lines.push((mode, line.into()));
+        self
+    }
+
+    /// Get the help line set for the given mode, if it replaces the built-in one
+    pub fn help_line(&self, mode: Mode) -> Option<&str> {
+        self.help_lines
+            .iter()
+            .find(|(other, _)| *other == mode)
+            .map(|(_, line)| line.as_str())
+    }
+
+    /// Style the output matching a rule in the TUI, e.g.
+    /// `with_highlight_rule(HighlightRule::parse("ERROR -> red bold")?)`
+    pub fn with_highlight_rule(mut self, rule: HighlightRule) -> Self {
+        self.highlight_rules.push(rule);
+        self
+    }
+
+    /// Get the rules styling the output, in the order they're applied
+    pub fn highlight_rules(&self) -> &[HighlightRule] {
+        &self.highlight_rules
+    }
+
+    /// Set where the TUI places the menu and output panels
+    pub fn with_layout(mut self, layout: LayoutConfig) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Get where the TUI places the menu and output panels
+    pub fn layout(&self) -> &LayoutConfig {
+        &self.layout
+    }
+
+    /// Get which of the menu and output panes the TUI shows
+    pub fn panes(&self) -> Panes {
+        self.panes
+    }
+
+    /// Show only the given panes, kept until they're changed again
+    pub fn set_panes(&mut self, panes: Panes) {
+        self.panes = panes;
+    }
+
+    /// Toggle hiding the menu, giving the output the whole screen
+    pub fn toggle_full_output(&mut self) {
+        self.panes = match self.panes {
+            Panes::OutputOnly => Panes::Both,
+            _ => Panes::OutputOnly,
+        };
+    }
+
+    /// Toggle collapsing the output, giving the menu the whole screen
+    pub fn toggle_output_pane(&mut self) {
+        self.panes = match self.panes {
+            Panes::MenuOnly => Panes::Both,
+            _ => Panes::MenuOnly,
+        };
+    }
+
+    /// Handle the `pane` command, showing only some panes or which are shown
+    fn pane_command(&mut self, param: Option<&str>) {
+        match param.map(|name| (name, Panes::from_name(name))) {
+            None => self.add_output(format!("Panes: {}", self.panes.name())),
+            Some((_, Some(panes))) => {
+                self.set_panes(panes);
+                self.add_output(format!("Panes: {}", panes.name()));
+            }
+            Some((name, None)) => self.add_output(format!(
+                "Unknown pane '{}', use one of: both, output, menu",
+                name
+            )),
+        }
+    }
+
+    /// Switch to other styles, redrawn right away
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Show only output of `level` and above, e.g. `OutputLevel::Warn` to hide debug
+    /// and regular messages. Hidden messages are kept and come back when the level
+    /// is lowered again.
+    pub fn with_log_level(mut self, level: OutputLevel) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Capture what actions print with `println!` and `eprintln!` while they run,
+    /// which would otherwise draw over the TUI, and add it to their output.
+    /// Stderr lines are added as warnings.
+    ///
+    /// Stdout and stderr are redirected for the whole process while a sync action
+    /// runs, or an async one until it returns its future, so prints of async
+    /// actions once they run in the background aren't captured. Only supported
+    /// on Unix, elsewhere this does nothing.
+    pub fn with_stdio_capture(mut self, capture: bool) -> Self {
+        self.capture_stdio = capture;
+        self
+    }
+
+    /// Show only the output added in the current menu, so submenus wrapping
+    /// different tools each get their own. The output of every menu is kept and
+    /// comes back with [`toggle_menu_output`](Self::toggle_menu_output).
+    pub fn with_menu_output(mut self, scoped: bool) -> Self {
+        self.menu_output = scoped;
+        self
+    }
+
+    /// Change the lowest level of the output shown
+    pub fn set_log_level(&mut self, level: OutputLevel) {
+        self.log_level = level;
+    }
+
+    /// Get the lowest level of the output shown
+    pub fn log_level(&self) -> OutputLevel {
+        self.log_level
+    }
+
+    /// Handle the `loglevel` command, filtering the output by level or showing the filter
+    fn loglevel_command(&mut self, param: Option<&str>) {
+        let available = OutputLevel::ALL.map(OutputLevel::name).join(", ");
+        match param.map(|name| (name, OutputLevel::from_name(name))) {
+            None => self.add_output(format!(
+                "Log level: {} (available: {})",
+                self.log_level.name(),
+                available
+            )),
+            Some((_, Some(level))) => {
+                self.set_log_level(level);
+                self.add_output(format!("Log level: {}", level.name()));
+            }
+            Some((name, None)) => self.add_output(format!(
+                "Unknown log level '{}', use one of: {}",
+                name, available
+            )),
+        }
+    }
+
+    /// Show only output lines matching a regex, or every line again for `None`.
+    /// Hidden lines are kept and come back once the filter is cleared.
+    pub fn set_output_filter(&mut self, pattern: Option<&str>) -> Result<(), String> {
+        self.output_filter = match pattern {
+            Some(pattern) => Some(
+                Regex::new(pattern)
+                    .map_err(|e| format!("Invalid filter pattern '{}': {}", pattern, e))?,
+            ),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Get the pattern the output lines shown match, if filtered
+    pub fn output_filter(&self) -> Option<&Regex> {
+        self.output_filter.as_ref()
+    }
+
+    /// Check whether an output line passes the filter
+    pub fn is_line_shown(&self, line: &str) -> bool {
+        self.output_filter
+            .as_ref()
+            .is_none_or(|filter| filter.is_match(line))
+    }
+
+    /// Handle the `filter` command, filtering the output lines by a regex or clearing the filter
+    fn filter_command(&mut self, param: Option<&str>) {
+        match param {
+            None => match &self.output_filter {
+                Some(filter) => self.add_output(format!("Filter: {}", filter.as_str())),
+                None => self.add_output("Usage: filter <regex> | filter off".to_string()),
+            },
+            Some("off") => {
+                self.output_filter = None;
+                self.add_output("Filter off".to_string());
+            }
+            Some(pattern) => match self.set_output_filter(Some(pattern)) {
+                Ok(()) => self.add_output(format!("Filter: {}", pattern)),
+                Err(e) => self.add_output(e),
+            },
+        }
+    }
+
+    /// Handle the `theme` command, switching to a built-in theme or listing them
+    fn theme_command(&mut self, param: Option<&str>) {
+        match param {
+            None => {
+                let current = self.theme.preset_name().unwrap_or("custom");
+                self.add_output(format!(
+                    "Theme: {} (available: {})",
+                    current,
+                    THEME_PRESETS.join(", ")
+                ));
+            }
+            Some(name) => match Theme::preset(name) {
+                Some(theme) => {
+                    self.set_theme(theme);
+                    self.add_output(format!("Switched to the {} theme", name));
+                }
+                None => self.add_output(format!(
+                    "Unknown theme '{}', available: {}",
+                    name,
+                    THEME_PRESETS.join(", ")
+                )),
+            },
+        }
+    }
+
+    /// Set how many actions can be undone, 0 to disable undo
+    pub fn with_undo_limit(mut self, limit: usize) -> Self {
+        self.undo_stack = UndoStack::new(limit);
+        self
+    }
+
+    /// Set the maximum number of messages each output tab keeps, the oldest are
+    /// dropped first. Defaults to [`OUTPUT_BUFFER_SIZE`].
+    pub fn with_max_output_size(mut self, size: usize) -> Self {
+        self.output.set_max_size(size);
+        for (_, buffer) in &mut self.output_tabs {
+            buffer.set_max_size(size);
+        }
+        self
+    }
+
+    /// Collapse output messages repeating the one before into it, shown with a
+    /// `×N` counter, e.g. for animation frames or retry loops
+    pub fn with_collapsed_duplicates(mut self, collapse: bool) -> Self {
+        self.output.set_collapse_duplicates(collapse);
+        for (_, buffer) in &mut self.output_tabs {
+            buffer.set_collapse_duplicates(collapse);
+        }
+        self
+    }
+
+    /// Suggest the commands fuzzy-matching the input as it's typed, on by default.
+    ///
+    /// The TUI lists them under the input box, picked with the arrow keys and
+    /// accepted with Enter.
+    pub fn with_command_suggestions(mut self, enabled: bool) -> Self {
+        self.command_suggestions = enabled;
+        self
+    }
+
+    /// Set the maximum number of commands to keep in history
+    pub fn with_max_history_size(mut self, size: usize) -> Self {
+        self.command_history.set_max_size(size);
+        self
+    }
+
+    /// Persist command history to the given file between runs.
+    ///
+    /// Favorites are persisted alongside it, in the same path with a `.favorites` extension.
+    pub fn with_history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        // Missing files are expected on the first run
+        let _ = self.command_history.load(&path);
+        let _ = self.favorites.load(&Self::favorites_file(&path));
+
+        // Drop favorites that no longer point at an action
+        let stale: Vec<usize> = self
+            .favorites
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !self.is_action_at(&entry.path, &entry.key))
+            .map(|(idx, _)| idx + 1)
+            .collect();
+        for number in stale.into_iter().rev() {
+            self.favorites.unpin(number);
+        }
+
+        self.history_file = Some(path);
+        self
+    }
+
+    /// Append every output message, of every tab, to the given file as it's added or replaced,
+    /// with its time, level and source, so sessions leave a trail that survives
+    /// crashes. The file is created if needed and never truncated.
+    pub fn with_log_file(mut self, path: impl AsRef<Path>) -> Self {
+        match OutputLogFile::open(path.as_ref()) {
+            Ok(log_file) => {
+                self.output.set_log_file(Some(log_file.clone()));
+                for (_, buffer) in &mut self.output_tabs {
+                    buffer.set_log_file(Some(log_file.clone()));
+                }
+            }
+            Err(e) => self.add_output(format!("Failed to open log file: {}", e)),
+        }
+        self
+    }
+
+    /// Get the file favorites are persisted to for a given history file
+    fn favorites_file(history_file: &Path) -> PathBuf {
+        history_file.with_extension("favorites")
+    }
+
+    /// Show a banner in the output pane on startup, one message per line
+    pub fn with_banner(mut self, banner: impl AsRef<str>) -> Self {
+        for line in banner.as_ref().lines() {
+            self.output.add_from(BANNER_SOURCE, line.to_string());
+        }
+        self
+    }
+
+    /// Show a framed banner with the application name and version on startup,
+    /// e.g. `with_title_banner("Deploy Tool", env!("CARGO_PKG_VERSION"))`
+    pub fn with_title_banner(self, name: &str, version: &str) -> Self {
+        let banner = title_banner(name, version);
+        self.with_banner(banner)
+    }
+
+    /// Set what the quit key, Ctrl+Q by default, does, force quitting by default
+    pub fn with_quit_behavior(mut self, behavior: QuitBehavior) -> Self {
+        self.quit_behavior = behavior;
+        self
+    }
+
+    /// Scroll the output to new error messages and flash its border in the TUI,
+    /// so failures of background jobs aren't missed while navigating menus
+    pub fn with_error_focus(mut self, enabled: bool) -> Self {
+        self.error_focus = enabled;
+        self
+    }
+
+    /// Check if new errors are brought into view
+    pub fn error_focus(&self) -> bool {
+        self.error_focus
+    }
+
+    /// Prompt for missing required parameters instead of showing a usage error, on by default
+    pub fn with_missing_param_prompts(mut self, enabled: bool) -> Self {
+        self.prompt_missing_params = enabled;
+        self
+    }
+
+    /// Handle the quit key, Ctrl+Q by default, returning false if the application should exit
+    pub fn handle_quit(&mut self) -> bool {
+        match self.quit_behavior {
+            QuitBehavior::ForceQuit => false,
+            QuitBehavior::Quit => self.handle_key("q"),
+        }
+    }
+
+    /// Describe the whole menu tree, e.g. to generate documentation or shell completions
+    pub fn menu_tree(&self) -> MenuTree {
+        MenuTree::from_menu(&self.menu_manager.root_menu().lock().unwrap())
+    }
+
+    /// Get the key bindings active in the current renderer
+    pub fn key_bindings(&self) -> Vec<KeyBinding> {
+        self.keymap.bindings(self.ui_mode)
+    }
+
+    /// Get the keys of the interactive renderers
+    pub fn keymap(&self) -> &KeyMap {
+        &self.keymap
+    }
+
+    /// Get the application state
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    /// Get the current UI mode
+    pub fn ui_mode(&self) -> UIMode {
+        self.ui_mode
+    }
+
+    /// Get a reference to the current menu
+    pub fn current_menu(&self) -> Arc<Mutex<Menu<T>>> {
+        self.menu_manager.current_menu()
+    }
+
+    /// Get the recently run commands
+    pub fn recent_commands(&self) -> &RecentCommands {
+        &self.recent_commands
+    }
+
+    /// Get the actions pinned to the root menu
+    pub fn favorites(&self) -> &Favorites {
+        &self.favorites
+    }
+
+    /// Pin the action with the given key in the menu at the given path to the root menu
+    pub fn pin(&mut self, path: &[String], key: &str) -> Result<(), String> {
+        let (menu, path) = self
+            .menu_manager
+            .resolve_action(path, key)
+            .ok_or_else(|| format!("No action with key '{}' to pin", key))?;
+        let command = {
+            let menu = menu.lock().unwrap();
+            let item = menu
+                .find_item(key)
+                .expect("resolved actions exist in their menu");
+            CommandRef {
+                path,
+                key: item.key.clone(),
+                params: None,
+                description: item.description.clone(),
+            }
+        };
+
+        if !self.favorites.pin(command) {
+            return Err(format!("'{}' is already pinned", key));
+        }
+        self.save_favorites();
+        Ok(())
+    }
+
+    /// Unpin a favorite by its 1-based number
+    pub fn unpin(&mut self, number: usize) -> Option<CommandRef> {
+        let removed = self.favorites.unpin(number);
+        if removed.is_some() {
+            self.save_favorites();
+        }
+        removed
+    }
+
+    /// Persist favorites if history persistence is enabled
+    fn save_favorites(&mut self) {
+        if let Some(path) = &self.history_file
+            && let Err(e) = self.favorites.save(&Self::favorites_file(path))
+        {
+            self.add_output(format!("Failed to save favorites: {}", e));
+        }
+    }
+
+    /// Check if there is an action with the given key in the menu at the given path
+    fn is_action_at(&self, path: &[String], key: &str) -> bool {
+        self.menu_manager.resolve_action(path, key).is_some()
+    }
+
+    /// Get the category the current menu's items are filtered by, if any
+    pub fn category_filter(&self) -> Option<&str> {
+        self.category_filter.as_deref()
+    }
+
+    /// Render items tagged with a category with the color of an output level,
+    /// e.g. `with_tag_style("danger", OutputLevel::Warn)`
+    pub fn with_tag_style(mut self, category: impl Into<String>, level: OutputLevel) -> Self {
+        self.tag_styles
+            .insert(category.into().to_lowercase(), level);
+        self
+    }
+
+    /// Get the emphasis of a menu item, the most severe one among its categories
+    pub fn item_style(&self, item: &MenuItem<T>) -> OutputLevel {
+        item.categories
+            .iter()
+            .filter_map(|c| self.tag_styles.get(&c.to_lowercase()))
+            .copied()
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// List the items tagged with a category anywhere in the menu tree
+    fn list_tagged(&mut self, category: &str) {
+        let tree = self.menu_tree();
+        let tagged = tree.tagged(category);
+        if tagged.is_empty() {
+            self.add_output(format!("No items in category '{}'", category));
+            return;
+        }
+
+        let width = tagged.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+        for (path, item) in tagged {
+            self.add_output(format!("{:<width$}  {}", path, item.description));
+        }
+    }
+
+    /// Check if an item of the current menu passes the category filter
+    pub fn is_item_visible(&self, item: &MenuItem<T>) -> bool {
+        self.category_filter
+            .as_deref()
+            .is_none_or(|category| item.has_category(category))
+    }
+
+    /// Filter the current menu by a category, or list the categories if none is given
+    fn filter_by_category(&mut self, category: Option<&str>) {
+        let categories = self.current_menu().lock().unwrap().categories();
+
+        match category {
+            Some(category) if categories.contains(&category.to_lowercase()) => {
+                self.category_filter = Some(category.to_lowercase());
+            }
+            Some(category) => {
+                self.add_output(format!("No items in category '{}'", category));
+            }
+            None => {
+                self.category_filter = None;
+                if categories.is_empty() {
+                    self.add_output("This menu has no categories".to_string());
+                } else {
+                    self.add_output(format!("Categories: {}", categories.join(", ")));
+                }
+            }
+        }
+    }
+
+    /// Jump to the menu at the given path of submenu keys, returning false if there is none
+    pub fn go_to_menu(&mut self, path: &[String]) -> bool {
+        let navigated = self.menu_manager.navigate_to_path(path);
+        if navigated {
+            self.category_filter = None;
+        }
+        navigated
+    }
+
+    /// Check if the current menu is the root menu
+    pub fn is_at_root(&self) -> bool {
+        self.menu_manager.is_at_root()
+    }
+
+    /// Get the output messages of the tab shown
+    pub fn output_messages(&self) -> Vec<&str> {
+        self.shown_output().messages()
+    }
+
+    /// Get the number of messages ever added to the tab shown, including the ones
+    /// dropped once it was full
+    pub fn output_total(&self) -> usize {
+        self.shown_output().total()
+    }
+
+    /// Get the number of messages dropped from the front of the tab shown, see
+    /// [`OutputBuffer::dropped`]
+    pub fn output_dropped(&self) -> usize {
+        self.shown_output().dropped()
+    }
+
+    /// Add an output message to the tab shown
+    pub fn add_output(&mut self, message: String) {
+        self.shown_output_mut().add(message);
+    }
+
+    /// Get the output of the tab shown
+    fn shown_output(&self) -> &OutputBuffer {
+        match self.active_tab {
+            0 => &self.output,
+            tab => &self.output_tabs[tab - 1].1,
+        }
+    }
+
+    /// Get the output of the tab shown, mutably
+    fn shown_output_mut(&mut self) -> &mut OutputBuffer {
+        match self.active_tab {
+            0 => &mut self.output,
+            tab => &mut self.output_tabs[tab - 1].1,
+        }
+    }
+
+    /// Get the output of the named tab, or of the main tab for `None`
+    fn tab_output_mut(&mut self, tab: Option<&str>) -> &mut OutputBuffer {
+        match tab {
+            Some(name) => match self.open_output_tab(name) {
+                0 => &mut self.output,
+                index => &mut self.output_tabs[index - 1].1,
+            },
+            None => &mut self.output,
+        }
+    }
+
+    /// Open an output tab unless it's already open, returning its index
+    pub fn open_output_tab(&mut self, name: &str) -> usize {
+        if name == MAIN_OUTPUT_TAB {
+            return 0;
+        }
+        match self.output_tabs.iter().position(|(tab, _)| tab == name) {
+            Some(index) => index + 1,
+            None => {
+                let mut buffer = OutputBuffer::with_max_size(self.output.max_size());
+                buffer.set_collapse_duplicates(self.output.collapses_duplicates());
+                buffer.set_menu(self.output.menu.clone());
+                buffer.set_log_file(self.output.log_file.clone());
+                buffer.set_capture(self.output.capture.clone());
+                self.output_tabs.push((name.to_string(), buffer));
+                self.output_tabs.len()
+            }
+        }
+    }
+
+    /// Collect the text of every message any tab adds or replaces from now on
+    fn set_output_capture(&mut self, capture: Option<OutputCapture>) {
+        self.output.set_capture(capture.clone());
+        for (_, buffer) in &mut self.output_tabs {
+            buffer.set_capture(capture.clone());
+        }
+    }
+
+    /// Get the name of each output tab, the main one first, with whether it has output not shown yet
+    pub fn output_tabs(&self) -> Vec<(&str, bool)> {
+        std::iter::once((MAIN_OUTPUT_TAB, &self.output))
+            .chain(
+                self.output_tabs
+                    .iter()
+                    .map(|(name, buffer)| (name.as_str(), buffer)),
+            )
+            .enumerate()
+            .map(|(index, (name, buffer))| {
+                (name, index != self.active_tab && buffer.has_unseen_output())
+            })
+            .collect()
+    }
+
+    /// Get the index of the output tab shown, 0 being the main one
+    pub fn active_output_tab(&self) -> usize {
+        self.active_tab
+    }
+
+    /// Show the output tab with the given index, returning false if there's none
+    pub fn select_output_tab(&mut self, index: usize) -> bool {
+        if index > self.output_tabs.len() {
+            return false;
+        }
+        self.active_tab = index;
+        // Renderers redraw the tab, following it to its bottom with auto-scroll
+        self.shown_output_mut().new_output = true;
+        true
+    }
+
+    /// Handle the `tab` command, showing an output tab by number or name, or listing them
+    fn tab_command(&mut self, param: Option<&str>) {
+        let Some(param) = param else {
+            let tabs: Vec<String> = self
+                .output_tabs()
+                .into_iter()
+                .enumerate()
+                .map(|(index, (name, unseen))| {
+                    let marker = if index == self.active_tab { ">" } else { "" };
+                    let unseen = if unseen { "*" } else { "" };
+                    format!("{}{} {}{}", marker, index + 1, name, unseen)
+                })
+                .collect();
+            self.add_output(format!("Output tabs: {}", tabs.join(", ")));
+            return;
+        };
+
+        let index = match param.parse::<usize>() {
+            Ok(number) => number.checked_sub(1),
+            Err(_) => self
+                .output_tabs()
+                .iter()
+                .position(|(name, _)| *name == param),
+        };
+        if !index.is_some_and(|index| self.select_output_tab(index)) {
+            self.add_output(format!("No output tab '{}'", param));
+        }
+    }
+
+    /// Get the number of actions that failed with an error this session
+    pub fn error_count(&self) -> usize {
+        self.error_count
+    }
+
+    /// Get the level of each output message of the tab shown
+    pub fn output_levels(&self) -> Vec<OutputLevel> {
+        self.shown_output().levels()
+    }
+
+    /// Get the output messages of the tab shown with their source, level, format,
+    /// time and menu
+    pub fn output_records(&self) -> &VecDeque<OutputRecord> {
+        self.shown_output().records()
+    }
+
+    /// Export every message of the named output tab, or of the main tab for `None`,
+    /// as serializable records, including the ones the log level or filter hide.
+    /// E.g. to log the session as JSON with `serde_json::to_string(&app.export_output(None))`
+    pub fn export_output(&self, tab: Option<&str>) -> Vec<OutputRecord> {
+        let output = match tab {
+            Some(name) if name != MAIN_OUTPUT_TAB => self
+                .output_tabs
+                .iter()
+                .find(|(tab, _)| tab == name)
+                .map(|(_, output)| output),
+            _ => Some(&self.output),
+        };
+        output
+            .map(|output| output.records().iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get a handle for writing output from elsewhere, e.g. a background thread
+    pub fn output_handle(&self) -> OutputHandle {
+        OutputHandle::new(self.output_sender.clone(), None)
+    }
+
+    /// Get a sink for writing lines from elsewhere, e.g. a thread or a spawned
+    /// task. Sinks are cheap to clone, and the TUI shows their lines on its next frame.
+    pub fn output_sink(&self) -> OutputSink {
+        OutputSink::new(self.output_handle())
+    }
+
+    /// Apply the output written through handles and sinks right away instead of
+    /// on the next tick, e.g. once per frame of a custom renderer
+    pub fn drain_output(&mut self) {
+        self.drain_output_handles();
+    }
+
+    /// Freeze the output written through handles, e.g. by the tick handler or
+    /// background jobs, to read it without chasing new lines. New lines are held
+    /// until [`resume_output`](Self::resume_output).
+    pub fn pause_output(&mut self) {
+        self.drain_output_handles();
+        self.paused_output.get_or_insert_with(Vec::new);
+    }
+
+    /// Catch up on the output held while paused and show new output again
+    pub fn resume_output(&mut self) {
+        for command in self.paused_output.take().unwrap_or_default() {
+            self.apply_output(command);
+        }
+        self.drain_output_handles();
+    }
+
+    /// Pause or resume the output written through handles
+    pub fn toggle_output_paused(&mut self) {
+        if self.is_output_paused() {
+            self.resume_output();
+        } else {
+            self.pause_output();
+        }
+    }
+
+    /// Switch between the output of the current menu and the output of every menu,
+    /// with [`with_menu_output`](Self::with_menu_output) on
+    pub fn toggle_menu_output(&mut self) {
+        self.all_menus_output = !self.all_menus_output;
+        self.shown_output_mut().new_output = true;
+    }
+
+    /// Get the path of the menu whose output shows, its keys joined by `/`,
+    /// or `None` when the output of every menu shows
+    pub fn output_menu(&self) -> Option<String> {
+        (self.menu_output && !self.all_menus_output)
+            .then(|| self.menu_manager.current_path().join("/"))
+    }
+
+    /// Check whether the output is paused
+    pub fn is_output_paused(&self) -> bool {
+        self.paused_output.is_some()
+    }
+
+    /// Get the number of changes to the output held while paused
+    pub fn paused_output_count(&self) -> usize {
+        self.paused_output.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Apply output written through output handles since the last call
+    fn drain_output_handles(&mut self) {
+        while let Ok(command) = self.output_receiver.try_recv() {
+            match command {
+                OutputCommand::Progress {
+                    job,
+                    percent,
+                    message,
+                } => {
+                    // Updates of sync actions or actions that already finished aren't shown
+                    if let Some(running) = self.running_jobs.get_mut(&job) {
+                        running.progress.percent = percent;
+                        running.progress.message = message;
+                    }
+                }
+                OutputCommand::Quit => self.quit_requested = true,
+                command => match &mut self.paused_output {
+                    Some(held) => held.push(command),
+                    None => self.apply_output(command),
+                },
+            }
+        }
+    }
+
+    /// Apply a change to the output of the main tab or a named one
+    fn apply_output(&mut self, command: OutputCommand) {
+        match command {
+            OutputCommand::ToTab { tab, command } => {
+                self.tab_output_mut(Some(&tab)).apply(*command)
+            }
+            command => self.output.apply(command),
+        }
+    }
+
+    /// Check if the tab shown has new output and reset the flag
+    pub fn has_new_output(&mut self) -> bool {
+        self.shown_output_mut().has_new_output()
+    }
+
+    /// Clear all output messages of the tab shown
+    pub fn clear_output_messages(&mut self) {
+        self.shown_output_mut().clear();
+    }
+
+    /// Get the popup viewer currently shown, if any
+    pub fn popup(&self) -> Option<&OutputPopup> {
+        self.popup.as_ref()
+    }
+
+    /// Get a mutable reference to the popup viewer currently shown, if any
+    pub fn popup_mut(&mut self) -> Option<&mut OutputPopup> {
+        self.popup.as_mut()
+    }
+
+    /// Show content in the popup viewer, replacing the one shown if any
+    pub fn show_popup(&mut self, popup: OutputPopup) {
+        self.popup = Some(popup);
+    }
+
+    /// Close the popup viewer
+    pub fn close_popup(&mut self) {
+        self.popup = None;
+    }
+
+    /// Show a modal dialog, after the ones already waiting to be answered
+    pub fn show_dialog(&mut self, dialog: Dialog) {
+        self.dialogs.push_back(dialog);
+    }
+
+    /// Get the dialog currently shown, if any
+    pub fn dialog(&self) -> Option<&Dialog> {
+        self.dialogs.front()
+    }
+
+    /// Get a mutable reference to the dialog currently shown, e.g. to type into its field
+    pub fn dialog_mut(&mut self) -> Option<&mut Dialog> {
+        self.dialogs.front_mut()
+    }
+
+    /// Answer the dialog shown with its selected answer or typed value
+    pub fn submit_dialog(&mut self) {
+        let Some(dialog) = self.dialogs.pop_front() else {
+            return;
+        };
+
+        match (dialog.kind, dialog.answer) {
+            (DialogKind::Confirm { yes }, DialogAnswer::Confirmation) => self.confirm(yes),
+            (DialogKind::Confirm { yes: true }, DialogAnswer::Command(command)) => {
+                self.run_command(&command)
+            }
+            (DialogKind::Prompt { value }, DialogAnswer::Command(command)) => {
+                let value = value.trim();
+                if value.is_empty() {
+                    self.add_output(format!("Cancelled '{}'", dialog.title));
+                    return;
+                }
+                let (key, params) = command.split_once(' ').unwrap_or((&command, ""));
+                let mut params = Params::parse(params);
+                params.push_positional(value);
+                self.run_command(&format!("{} {}", key, params));
+            }
+            _ => {}
+        }
+    }
+
+    /// Answer the question of the dialog shown, or close a message
+    pub fn answer_dialog(&mut self, yes: bool) {
+        if let Some(DialogKind::Confirm { yes: selected }) =
+            self.dialogs.front_mut().map(|dialog| &mut dialog.kind)
+        {
+            *selected = yes;
+        }
+        self.submit_dialog();
+    }
+
+    /// Close the dialog shown without running anything, answering "no" to a confirmation
+    pub fn cancel_dialog(&mut self) {
+        if let Some(dialog) = self.dialogs.pop_front()
+            && dialog.answer == DialogAnswer::Confirmation
+        {
+            self.confirm(false);
+        }
+    }
+
+    /// Run a command a dialog was answered with, like it was typed
+    fn run_command(&mut self, command: &str) {
+        match self.resolve_command(command) {
+            Ok(command) => self.run_action_with(&command.path, &command.key, command.params, false),
+            Err(error) => self.add_output(error),
+        }
+    }
+
+    /// Run the action of the item with the given key in the menu at the given path
+    fn run_action(&mut self, path: &[String], key: &str, params: Option<String>) {
+        self.run_action_with(path, key, params, false);
+    }
+
+    /// Run an action, asking for confirmation first if the item requires it and it wasn't given yet
+    fn run_action_with(
+        &mut self,
+        path: &[String],
+        key: &str,
+        params: Option<String>,
+        confirmed: bool,
+    ) {
+        let Some((menu, path)) = self.menu_manager.resolve_action(path, key) else {
+            self.add_output(format!("Unknown command: {}", key));
+            return;
+        };
+        #[cfg(feature = "clap")]
+        let clap_command = menu
+            .lock()
+            .unwrap()
+            .find_item(key)
+            .and_then(|item| item.clap_command.clone());
+        let Some((item_key, description, policy, specs, confirm, undoable)) =
+            menu.lock().unwrap().find_item(key).map(|item| {
+                (
+                    item.key.clone(),
+                    item.description.clone(),
+                    item.output_policy,
+                    item.params.clone(),
+                    item.confirm,
+                    item.undo.is_some(),
+                )
+            })
+        else {
+            self.add_output(format!("Unknown command: {}", key));
+            return;
+        };
+
+        let params = params
+            .map(|params| params.trim().to_string())
+            .filter(|params| !params.is_empty());
+        let command = CommandRef {
+            path,
+            key: item_key,
+            params,
+            description,
+        };
+
+        // Check the parameters against the item's specs before running it
+        #[cfg(feature = "clap")]
+        if let Some(clap_command) = clap_command {
+            match crate::cli::parse_with_clap(
+                &clap_command,
+                &command.key,
+                command.params.as_deref(),
+            ) {
+                Ok(parsed) => self.run_parsed_action(
+                    &menu, command, policy, parsed, confirm, confirmed, undoable,
+                ),
+                Err(help_or_error) => self.add_output(help_or_error),
+            }
+            return;
+        }
+        let parsed = if specs.is_empty() {
+            Params::parse(command.params.as_deref().unwrap_or(""))
+        } else {
+            match crate::params::validate_params(&command.key, &specs, command.params.as_deref()) {
+                Ok(parsed) => parsed,
+                Err(usage_error) => {
+                    let missing = crate::params::missing_param(&specs, command.params.as_deref());
+                    match missing {
+                        Some(spec) if self.prompt_missing_params => {
+                            self.add_output(format!(
+                                "Enter {} for '{}' (empty input cancels)",
+                                spec, command.description
+                            ));
+                            self.pending_param = Some((command, spec.clone()));
+                        }
+                        _ => self.add_output(usage_error),
+                    }
+                    return;
+                }
+            }
+        };
+
+        self.run_parsed_action(&menu, command, policy, parsed, confirm, confirmed, undoable);
+    }
+
+    /// Run an action whose parameters were checked, once it's confirmed if it has to be
+    #[allow(clippy::too_many_arguments)]
+    fn run_parsed_action(
+        &mut self,
+        menu: &SharedMenu<T>,
+        command: CommandRef,
+        policy: OutputPolicy,
+        parsed: Params,
+        confirm: bool,
+        confirmed: bool,
+        undoable: bool,
+    ) {
+        // Hold destructive actions until the user answers the prompt
+        if confirm && !confirmed {
+            self.add_output(format!(
+                "Are you sure you want to run '{}'? (y/n)",
+                command.description
+            ));
+            self.show_dialog(Dialog::confirmation(&command.description));
+            self.pending_confirmation = Some(command);
+            return;
+        }
+
+        let started = self.start_action(menu, &command, policy, &parsed);
+        if started && undoable {
+            self.undo_stack.record(UndoEntry {
+                command: command.clone(),
+                params: parsed,
+            });
+        }
+        self.recent_commands.record(command);
+    }
+
+    /// Queue a command from the current menu, e.g. `inc 5` or `db/migrate 42`, to run
+    /// once the commands queued before it finished, including async ones.
+    ///
+    /// The queue stops when a command fails or is cancelled.
+    pub fn enqueue(&mut self, command: &str) -> Result<(), String> {
+        let command = self.resolve_command(command)?;
+        self.action_queue.push_back(command);
+        Ok(())
+    }
+
+    /// Resolve a command like `sub/key params` relative to the current menu
+    fn resolve_command(&self, command: &str) -> Result<CommandRef, String> {
+        let (key, params) = match command.trim().split_once(char::is_whitespace) {
+            Some((key, params)) => (key, Some(params.trim().to_string())),
+            None => (command.trim(), None),
+        };
+        let mut path = self.menu_manager.current_path().to_vec();
+        let key = match key.rsplit_once('/') {
+            Some((submenus, key)) => {
+                path.extend(submenus.split('/').map(str::to_string));
+                key
+            }
+            None => key,
+        };
+        let (menu, path) = self
+            .menu_manager
+            .resolve_action(&path, key)
+            .ok_or_else(|| format!("Unknown command: {}", key))?;
+        let (key, description) = menu
+            .lock()
+            .unwrap()
+            .find_item(key)
+            .map(|item| (item.key.clone(), item.description.clone()))
+            .ok_or_else(|| format!("Unknown command: {}", key))?;
+
+        Ok(CommandRef {
+            path,
+            key,
+            params: params.filter(|params| !params.is_empty()),
+            description,
+        })
+    }
+
+    /// Get the commands waiting in the queue, next first
+    pub fn queued_commands(&self) -> impl Iterator<Item = &CommandRef> {
+        self.action_queue.iter()
+    }
+
+    /// Get the queued command that's running in the background, if any
+    pub fn queue_running(&self) -> Option<&CommandRef> {
+        self.queue_running.as_ref().map(|(command, _)| command)
+    }
+
+    /// Handle the `queue` command: queue `;`-separated commands, list the queue, or clear it
+    fn queue_command(&mut self, param: Option<&str>) {
+        match param {
+            None => {
+                if self.queue_running.is_none() && self.action_queue.is_empty() {
+                    self.add_output("The queue is empty".to_string());
+                    return;
+                }
+                let mut lines = vec!["Queue:".to_string()];
+                if let Some(command) = self.queue_running() {
+                    lines.push(format!("  > {} (running)", command.label()));
+                }
+                for (idx, command) in self.action_queue.iter().enumerate() {
+                    lines.push(format!("  {}. {}", idx + 1, command.label()));
+                }
+                self.add_output(lines.join("\n"));
+            }
+            Some("--clear") => {
+                let skipped = self.action_queue.len();
+                self.action_queue.clear();
+                self.add_output(format!("Cleared {} queued commands", skipped));
+            }
+            Some(commands) => {
+                // Queue all commands or none of them
+                let queued = self.action_queue.len();
+                for command in commands.split(';').filter(|c| !c.trim().is_empty()) {
+                    if let Err(e) = self.enqueue(command) {
+                        self.action_queue.truncate(queued);
+                        self.add_output(e);
+                        return;
+                    }
+                }
+                self.add_output(format!(
+                    "Queued {} commands",
+                    self.action_queue.len() - queued
+                ));
+            }
+        }
+    }
+
+    /// Run queued commands until one is still running in the background or waiting for a prompt
+    fn advance_queue(&mut self) {
+        loop {
+            if self.pending_param.is_some()
+                || self.pending_confirmation.is_some()
+                || !self.dialogs.is_empty()
+            {
+                return;
+            }
+
+            if let Some((command, job)) = &self.queue_running {
+                if self.running_jobs.contains_key(job) {
+                    return;
+                }
+                let stopped = self
+                    .finished_jobs
+                    .iter()
+                    .find(|info| info.id == *job)
+                    .is_some_and(|info| {
+                        matches!(info.status, JobStatus::Failed | JobStatus::Cancelled)
+                    });
+                let command = command.clone();
+                self.queue_running = None;
+                if stopped {
+                    self.stop_queue(&command);
+                    return;
+                }
+            }
+
+            let Some(command) = self.action_queue.pop_front() else {
+                return;
+            };
+            let errors = self.error_count;
+            let job = self.next_job_id;
+            self.run_action(&command.path, &command.key, command.params.clone());
+
+            if self.running_jobs.contains_key(&job) {
+                self.queue_running = Some((command, job));
+            } else if self.error_count > errors {
+                self.stop_queue(&command);
+                return;
+            }
+        }
+    }
+
+    /// Drop the rest of the queue after a command failed
+    fn stop_queue(&mut self, failed: &CommandRef) {
+        let skipped = self.action_queue.len();
+        self.action_queue.clear();
+        self.add_output(format!(
+            "Queue stopped after '{}' failed, skipped {} commands",
+            failed.label(),
+            skipped
+        ));
+    }
+
+    /// Get the actions that can be undone and redone
+    pub fn undo_stack(&self) -> &UndoStack {
+        &self.undo_stack
+    }
+
+    /// Get how often and how long each action ran
+    pub fn action_stats(&self) -> &ExecutionStats {
+        &self.action_stats
+    }
+
+    /// Handle the `stats` command, showing the stats of every action or the given one
+    fn stats_command(&mut self, param: Option<&str>) {
+        let now = Instant::now();
+        match param {
+            None if self.action_stats.is_empty() => {
+                self.add_output("No actions ran yet".to_string());
+            }
+            None => {
+                let table = self.action_stats.table(now);
+                self.shown_output_mut().push_formatted(
+                    None,
+                    OutputLevel::Info,
+                    table.format(),
+                    table.to_plain_text(),
+                );
+            }
+            Some("--reset") => {
+                self.action_stats.clear();
+                self.add_output("Cleared action stats".to_string());
+            }
+            Some(path) => {
+                let Some(stats) = self.action_stats.get(path) else {
+                    self.add_output(format!("No stats for '{}'", path));
+                    return;
+                };
+                let pairs = vec![
+                    ("Command".to_string(), path.to_string()),
+                    ("Runs".to_string(), stats.count.to_string()),
+                    (
+                        "Average".to_string(),
+                        jobs::format_elapsed(stats.average_duration()),
+                    ),
+                    (
+                        "Last run".to_string(),
+                        format!(
+                            "{} ago",
+                            jobs::format_elapsed(now.saturating_duration_since(stats.last_run))
+                        ),
+                    ),
+                ];
+                self.add_output(ActionOutput::KeyValue(pairs).to_plain_text());
+            }
+        }
+    }
+
+    /// Revert the most recent undoable action with its inverse
+    pub fn undo(&mut self) {
+        let Some(entry) = self.undo_stack.pop_undo() else {
+            self.add_output("Nothing to undo".to_string());
+            return;
+        };
+        let inverse = self
+            .menu_manager
+            .resolve_action(&entry.command.path, &entry.command.key)
+            .and_then(|(menu, _)| {
+                let menu = menu.lock().unwrap();
+                menu.find_item(&entry.command.key)
+                    .and_then(|item| item.undo.clone())
+            });
+
+        // The menus may have changed since the action ran
+        let Some(inverse) = inverse else {
+            self.add_output(format!("Can't undo '{}' anymore", entry.command.label()));
+            return;
+        };
+        inverse(&mut self.state, &entry.params);
+        self.add_output(format!("Undid '{}'", entry.command.label()));
+        self.undo_stack.push_undone(entry);
+    }
+
+    /// Run the most recently undone action again
+    pub fn redo(&mut self) {
+        let Some(entry) = self.undo_stack.pop_redo() else {
+            self.add_output("Nothing to redo".to_string());
+            return;
+        };
+        let resolved = self
+            .menu_manager
+            .resolve_action(&entry.command.path, &entry.command.key)
+            .and_then(|(menu, _)| {
+                let policy = menu
+                    .lock()
+                    .unwrap()
+                    .find_item(&entry.command.key)
+                    .map(|item| item.output_policy)?;
+                Some((menu, policy))
+            });
+        let Some((menu, policy)) = resolved else {
+            self.add_output(format!("Can't redo '{}' anymore", entry.command.label()));
+            return;
+        };
+
+        self.add_output(format!("Redoing '{}'", entry.command.label()));
+        if self.start_action(&menu, &entry.command, policy, &entry.params) {
+            self.undo_stack.push_done(entry);
+        }
+    }
+
+    /// Get the action waiting for the user to confirm it, if any
+    pub fn pending_confirmation(&self) -> Option<&CommandRef> {
+        self.pending_confirmation.as_ref()
+    }
+
+    /// Get the commands entered during this session
+    pub fn session_log(&self) -> &SessionLog {
+        &self.session_log
+    }
+
+    /// Write the session's commands to a file, e.g. `export-history run.json --json --results`
+    fn export_history(&mut self, params: Option<&str>) {
+        let params = Params::parse(params.unwrap_or(""));
+        let Some(path) = params.get(0) else {
+            self.add_output(
+                "Usage: export-history <path> [--json] [--timestamps] [--results]".to_string(),
+            );
+            return;
+        };
+
+        let options = ExportOptions {
+            json: params.has_flag("json"),
+            timestamps: params.has_flag("timestamps"),
+            results: params.has_flag("results"),
+        };
+        match self.session_log.export(Path::new(path), options) {
+            Ok(()) => self.add_output(format!(
+                "Exported {} commands to {}",
+                self.session_log.entries().len(),
+                path
+            )),
+            Err(e) => self.add_output(format!("Failed to export history: {}", e)),
+        }
+    }
+
+    /// Get the parameter the user is being prompted for, if any
+    pub fn pending_param(&self) -> Option<&ParamSpec> {
+        self.pending_param.as_ref().map(|(_, spec)| spec)
+    }
+
+    /// Answer the prompt for a missing parameter, running the pending action with it.
+    ///
+    /// Empty input cancels the action.
+    pub fn answer_param(&mut self, value: &str) {
+        let Some((command, _)) = self.pending_param.take() else {
+            return;
+        };
+
+        let value = value.trim();
+        if value.is_empty() {
+            self.add_output(format!("Cancelled '{}'", command.description));
+            return;
+        }
+
+        // Prompts again if the next required parameter is missing as well
+        let mut params = Params::parse(command.params.as_deref().unwrap_or(""));
+        params.push_positional(value);
+        self.run_action_with(&command.path, &command.key, Some(params.to_string()), false);
+    }
+
+    /// Answer the confirmation prompt, running the pending action if confirmed
+    pub fn confirm(&mut self, confirmed: bool) {
+        let Some(command) = self.pending_confirmation.take() else {
+            return;
+        };
+        // Typing the answer closes the dialog asking for it
+        self.dialogs
+            .retain(|dialog| dialog.answer != DialogAnswer::Confirmation);
+
+        if confirmed {
+            self.run_action_with(&command.path, &command.key, command.params, true);
+        } else {
+            self.add_output(format!("Cancelled '{}'", command.description));
+        }
+    }
+
+    /// Run an action, spawning async actions onto the runtime so the UI stays responsive.
+    ///
+    /// The results of async actions are shown on the tick after they finish.
+    /// Returns false if a sync action failed.
+    fn start_action(
+        &mut self,
+        menu: &SharedMenu<T>,
+        command: &CommandRef,
+        policy: OutputPolicy,
+        params: &Params,
+    ) -> bool {
+        let job = self.next_job_id;
+        self.next_job_id += 1;
+        let cancel = CancelToken::new();
+        let mut output = OutputHandle::new(self.output_sender.clone(), Some(command.item_path()));
+        if let Some(tab) = self.item_output_tab(command) {
+            output = output.with_tab(tab);
+        }
+        let handles = Job::new(
+            job,
+            Progress::new(job, self.output_sender.clone()),
+            cancel.clone(),
+            OutputSink::new(output.clone()),
+            self.runtime.handle().clone(),
+        );
+
+        #[cfg(unix)]
+        let capture = if self.capture_stdio {
+            crate::capture::StdioCapture::start()
+                .map_err(|e| self.add_output(format!("Failed to capture output: {}", e)))
+                .ok()
+        } else {
+            None
+        };
+        let started = Instant::now();
+        let execution =
+            MenuManager::start_in(menu, &command.key, &mut self.state, params, &handles);
+        #[cfg(unix)]
+        if let Some(capture) = capture {
+            let captured = capture.finish();
+            for (text, level) in [
+                (captured.stdout, OutputLevel::Info),
+                (captured.stderr, OutputLevel::Warn),
+            ] {
+                if !text.trim().is_empty() {
+                    output.push_level(level, text.trim_end());
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = output;
+
+        match execution {
+            Execution::Finished(result) => {
+                self.action_stats
+                    .record(&command.item_path(), started, Instant::now());
+                // Output the action wrote while running goes above its result
+                self.drain_output_handles();
+                let succeeded = result.is_ok();
+                self.handle_action_result(command, policy, result);
+                succeeded
+            }
+            Execution::Running(future) => {
+                self.running_jobs.insert(
+                    job,
+                    RunningJob {
+                        progress: JobProgress {
+                            id: job,
+                            command: command.item_path(),
+                            percent: None,
+                            message: String::new(),
+                            started,
+                        },
+                        cancel,
+                    },
+                );
+                let sender = self.action_sender.clone();
+                let command = command.clone();
+                self.runtime.spawn(async move {
+                    let result = future.await;
+                    // The application is gone, so there's nobody left to show the result to
+                    let _ = sender.send(FinishedAction {
+                        job,
+                        command,
+                        policy,
+                        result,
+                    });
+                });
+                true
+            }
+        }
+    }
+
+    /// Show the results of async actions that finished since the last tick
+    fn collect_finished_actions(&mut self) {
+        while let Ok(finished) = self.action_receiver.try_recv() {
+            self.finish_action(finished);
+        }
+    }
+
+    /// Show the result of an async action that finished
+    fn finish_action(&mut self, finished: FinishedAction) {
+        // Output the action wrote while running goes above its result
+        self.drain_output_handles();
+        if let Some(running) = self.running_jobs.remove(&finished.job) {
+            let mut info = running.info();
+            info.finished = Some(Instant::now());
+            self.action_stats
+                .record(&info.command, info.started, Instant::now());
+            info.status = match &finished.result {
+                Err(_) => JobStatus::Failed,
+                Ok(_) if running.cancel.is_cancelled() => JobStatus::Cancelled,
+                Ok(_) => JobStatus::Finished,
+            };
+            info.details = match &finished.result {
+                Ok(outcome) => outcome.output.as_ref().map(ActionOutput::to_plain_text),
+                Err(error) => Some(error.clone()),
+            };
+            if self.finished_jobs.len() == FINISHED_JOBS_SIZE {
+                self.finished_jobs.pop_front();
+            }
+            self.finished_jobs.push_back(info);
+        }
+        self.completed_actions += 1;
+        self.handle_action_result(&finished.command, finished.policy, finished.result);
+    }
+
+    /// Get the number of async actions still running in the background
+    pub fn running_actions(&self) -> usize {
+        self.running_jobs.len()
+    }
+
+    /// Get the progress of the async actions running in the background that reported any, oldest first
+    pub fn progress(&self) -> Vec<&JobProgress> {
+        self.running_jobs
+            .values()
+            .map(|running| &running.progress)
+            .filter(|progress| progress.percent.is_some() || !progress.message.is_empty())
+            .collect()
+    }
+
+    /// Get the progress of every async action running in the background, oldest first,
+    /// including those that didn't report any, e.g. to show a spinner for each
+    pub fn activity(&self) -> Vec<&JobProgress> {
+        self.running_jobs
+            .values()
+            .map(|running| &running.progress)
+            .collect()
+    }
+
+    /// Ask the async action running as the given job to stop, returning false if there's no such job.
+    ///
+    /// Actions only stop early if they check their [`CancelToken`]; their result
+    /// is shown once they return.
+    pub fn cancel_job(&mut self, id: u64) -> bool {
+        match self.running_jobs.get(&id) {
+            Some(running) => {
+                running.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the running and recently finished background jobs, oldest first
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.finished_jobs.iter().cloned().collect();
+        jobs.extend(self.running_jobs.values().map(RunningJob::info));
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// Toggle showing the jobs panel in the TUI
+    pub fn toggle_jobs_panel(&mut self) {
+        self.show_jobs = !self.show_jobs;
+    }
+
+    /// Check if the jobs panel should be shown
+    pub fn jobs_panel_visible(&self) -> bool {
+        self.show_jobs
+    }
+
+    /// Handle the `jobs` command, listing the jobs or inspecting the given one
+    fn jobs_command(&mut self, param: Option<&str>) {
+        let now = Instant::now();
+        let Some(param) = param else {
+            let jobs = self.jobs();
+            if jobs.is_empty() {
+                self.add_output("No background jobs".to_string());
+            } else {
+                let table = jobs::jobs_table(&jobs, now);
+                self.shown_output_mut().push_formatted(
+                    None,
+                    OutputLevel::Info,
+                    table.format(),
+                    table.to_plain_text(),
+                );
+            }
+            return;
+        };
+
+        let Ok(id) = param.trim_start_matches('#').parse::<u64>() else {
+            self.add_output("Usage: jobs [job-id]".to_string());
+            return;
+        };
+        let Some(job) = self.jobs().into_iter().find(|job| job.id == id) else {
+            self.add_output(format!("No job {}", id));
+            return;
+        };
+
+        let mut pairs = vec![
+            ("Job".to_string(), job.id.to_string()),
+            ("Command".to_string(), job.command.clone()),
+            ("Status".to_string(), job.status.to_string()),
+            (
+                "Elapsed".to_string(),
+                jobs::format_elapsed(job.elapsed(now)),
+            ),
+        ];
+        if let Some(details) = &job.details {
+            let label = if job.status.is_running() {
+                "Progress"
+            } else {
+                "Result"
+            };
+            pairs.push((label.to_string(), details.clone()));
+        }
+        self.add_output(ActionOutput::KeyValue(pairs).to_plain_text());
+    }
+
+    /// Handle the `cancel` command, cancelling the given job or the latest one
+    fn cancel_command(&mut self, param: Option<&str>) {
+        let id = match param {
+            Some(param) => match param.trim_start_matches('#').parse::<u64>() {
+                Ok(id) => id,
+                Err(_) => {
+                    self.add_output("Usage: cancel [job-id]".to_string());
+                    return;
+                }
+            },
+            None => match self.running_jobs.keys().next_back() {
+                Some(id) => *id,
+                None => {
+                    self.add_output("No running actions to cancel".to_string());
+                    return;
+                }
+            },
+        };
+
+        match self.running_jobs.get(&id) {
+            Some(running) if running.cancel.is_cancelled() => {
+                self.add_output(format!("Job {} is already being cancelled", id))
+            }
+            Some(running) => {
+                let message = format!("Cancelling job {} ({})", id, running.progress.command);
+                self.cancel_job(id);
+                self.add_output(message);
+            }
+            None => self.add_output(format!("No running job {}", id)),
+        }
+    }
+
+    /// Get the number of async actions that finished running in the background
+    pub fn completed_actions(&self) -> usize {
+        self.completed_actions
+    }
+
+    /// Block until every async action running in the background finished, showing their results.
+    ///
+    /// Queued commands keep running one after another while waiting.
+    pub fn wait_for_actions(&mut self) {
+        while !self.running_jobs.is_empty() {
+            // The application holds a sender, so this only fails if it's gone
+            let Ok(finished) = self.action_receiver.recv() else {
+                break;
+            };
+            self.finish_action(finished);
+            self.advance_queue();
+        }
+    }
+
+    /// Tell every plugin about an event
+    fn notify_plugins(&mut self, event: &PluginEvent) {
+        for plugin in &self.plugins {
+            let output = OutputHandle::new(self.output_sender.clone(), Some(plugin.name().into()));
+            plugin.on_event(&mut self.state, event, &output);
+        }
+    }
+
+    /// Show the result of an action, routing its output according to its output policy
+    fn handle_action_result(
+        &mut self,
+        command: &CommandRef,
+        policy: OutputPolicy,
+        result: ActionResult,
+    ) {
+        self.notify_plugins(&PluginEvent::ActionFinished {
+            command,
+            failed: result.is_err(),
+        });
+
+        match result {
+            Ok(outcome) => {
+                if let Some(output) = outcome.output {
+                    self.route_output(command, policy, output);
+                }
+                if let Some(navigation) = outcome.navigation {
+                    self.apply_navigation(navigation);
+                }
+                if let Some(dialog) = outcome.dialog {
+                    self.show_dialog(dialog);
+                }
+            }
+            // Errors always go to the output pane, whatever the item's output policy
+            Err(error) => {
+                self.error_count += 1;
+                let tab = self.item_output_tab(command);
+                self.tab_output_mut(tab.as_deref()).push(
+                    Some(command.item_path()),
+                    OutputLevel::Error,
+                    format!("Error: {}", error),
+                );
+            }
+        }
+    }
+
+    /// Go where an action asked to
+    fn apply_navigation(&mut self, navigation: Navigation) {
+        match navigation {
+            Navigation::Navigate(path) => {
+                let keys: Vec<String> = path
+                    .split('/')
+                    .filter(|key| !key.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if self.menu_manager.navigate_to_path(&keys) {
+                    self.category_filter = None;
+                } else {
+                    self.add_output(format!("No menu at '{}'", path));
+                }
+            }
+            Navigation::Back => {
+                if self.menu_manager.navigate_back() {
+                    self.category_filter = None;
+                }
+            }
+            Navigation::Quit => self.quit_requested = true,
+        }
+    }
+
+    /// Check whether an action, tick handler or plugin asked to quit the application.
+    ///
+    /// Renderers check this after every tick, since async actions finish there.
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    /// Quit the application once the renderer notices, restoring the terminal
+    pub fn request_quit(&mut self) {
+        self.quit_requested = true;
+    }
+
+    /// Notice entering another menu, telling plugins and running the menu's refresh action,
+    /// and re-run the refresh action once its interval elapsed.
+    ///
+    /// Refreshes skip confirmation and aren't recorded as recent commands.
+    fn check_current_menu(&mut self, now: Instant) {
+        let path = self.menu_manager.current_path().to_vec();
+        let menu = self.menu_manager.current_menu();
+        let entered = self.last_menu_path.as_ref() != Some(&path);
+        self.last_menu_path = Some(path.clone());
+        if entered {
+            // Output from now on belongs to the menu entered
+            let menu_path = path.join("/");
+            self.output.set_menu(menu_path.clone());
+            for (_, buffer) in &mut self.output_tabs {
+                buffer.set_menu(menu_path.clone());
+            }
+            self.notify_plugins(&PluginEvent::MenuEntered { path: &path });
+        }
+
+        let Some(refresh) = menu.lock().unwrap().refresh.clone() else {
+            return;
+        };
+        let due = refresh
+            .interval
+            .is_some_and(|interval| now.duration_since(self.last_refresh) >= interval);
+        if !entered && !due {
+            return;
+        }
+        self.last_refresh = now;
+
+        let Some((item_key, description, policy, specs)) =
+            menu.lock().unwrap().find_item(&refresh.key).map(|item| {
+                (
+                    item.key.clone(),
+                    item.description.clone(),
+                    item.output_policy,
+                    item.params.clone(),
+                )
+            })
+        else {
+            return;
+        };
+        let params = match crate::params::validate_params(&item_key, &specs, None) {
+            Ok(params) => params,
+            Err(usage_error) => {
+                self.add_output(usage_error);
+                return;
+            }
+        };
+        let command = CommandRef {
+            path,
+            key: item_key,
+            params: None,
+            description,
+        };
+
+        self.start_action(&menu, &command, policy, &params);
+    }
+
+    /// Get the output tab of the menu item a command runs, if it has one
+    fn item_output_tab(&self, command: &CommandRef) -> Option<String> {
+        let (menu, _) = self
+            .menu_manager
+            .resolve_action(&command.path, &command.key)?;
+        let menu = menu.lock().unwrap();
+        menu.find_item(&command.key)?.output_tab.clone()
+    }
+
+    /// Get the output the results of a menu item go to, its output tab if it has one
+    /// or else the tab shown
+    fn item_output_mut(&mut self, command: &CommandRef) -> &mut OutputBuffer {
+        match self.item_output_tab(command) {
+            Some(tab) => self.tab_output_mut(Some(&tab)),
+            None => self.shown_output_mut(),
+        }
+    }
+
+    /// Route the output of a menu item according to its output policy and output tab
+    fn route_output(&mut self, command: &CommandRef, policy: OutputPolicy, output: ActionOutput) {
+        let source = command.item_path();
+        let output = output.pretty_printed();
+        let format = output.format();
+        let text = output.to_plain_text();
+
+        match policy {
+            OutputPolicy::Append => self.item_output_mut(command).push_formatted(
+                Some(source),
+                OutputLevel::Info,
+                format,
+                text,
+            ),
+            OutputPolicy::Replace => self
+                .item_output_mut(command)
+                .replace_formatted(source, format, text),
+            OutputPolicy::Popup => {
+                self.popup = Some(OutputPopup::new(command.description.clone(), text));
+            }
+        }
+    }
+
+    /// Handle a tick update
+    /// This is called regularly to update any time-based state
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+
+        // Skip the tick handler while it is throttled, so its delta covers the skipped ticks
+        let throttled = self.tick_throttled_until.is_some_and(|until| now < until);
+        if !throttled {
+            let delta_time = now.duration_since(self.last_tick_time).as_secs_f32();
+            self.last_tick_time = now;
+
+            // Call custom tick handler if one is set
+            if let Some(handler) = &self.tick_handler {
+                let output =
+                    OutputHandle::new(self.output_sender.clone(), Some(TICK_SOURCE.into()));
+                handler(&mut self.state, &output, delta_time);
+
+                let elapsed = now.elapsed();
+                self.tick_stats.record(elapsed);
+                self.check_tick_budget(elapsed);
+            }
+        }
+
+        // Plugins aren't throttled along with the tick handler
+        let plugin_delta = now.duration_since(self.last_plugin_tick).as_secs_f32();
+        self.last_plugin_tick = now;
+        for plugin in &self.plugins {
+            let output = OutputHandle::new(self.output_sender.clone(), Some(plugin.name().into()));
+            plugin.tick(&mut self.state, &output, plugin_delta);
+        }
+
+        for metric in &mut self.metrics {
+            metric.sample(&self.state);
+        }
+
+        self.check_current_menu(now);
+        self.collect_finished_actions();
+        self.advance_queue();
+
+        // Pick up output from the tick handler and background jobs
+        self.drain_output_handles();
+    }
+
+    /// Throttle the tick handler if it went over its budget, warning when it starts
+    fn check_tick_budget(&mut self, elapsed: Duration) {
+        let Some(budget) = self.tick_budget else {
+            return;
+        };
+
+        if elapsed > budget {
+            self.tick_stats.slow_ticks += 1;
+            // Back off for a multiple of the time taken, bounding the share of frames it slows down
+            self.tick_throttled_until = Some(Instant::now() + elapsed * TICK_THROTTLE_FACTOR);
+            if !self.tick_throttled {
+                self.tick_throttled = true;
+                self.shown_output_mut().push(
+                    None,
+                    OutputLevel::Warn,
+                    format!(
+                        "Tick handler took {}ms (budget {}ms), throttling it",
+                        elapsed.as_millis(),
+                        budget.as_millis()
+                    ),
+                );
+            }
+        } else {
+            self.tick_throttled = false;
+            self.tick_throttled_until = None;
+        }
+    }
+
+    /// Get timing statistics of the tick handler
+    pub fn tick_stats(&self) -> &TickStats {
+        &self.tick_stats
+    }
+
+    /// Process a single character key command, potentially with parameters
+    pub fn handle_key_with_params(
+        &mut self,
+        key: impl Into<String>,
+        params: Option<String>,
+    ) -> bool {
+        let should_continue = self.dispatch_key(key.into(), params);
+        // React to entering another menu right away instead of on the next tick
+        self.check_current_menu(Instant::now());
+        self.advance_queue();
+        should_continue && !self.quit_requested
+    }
+
+    /// Run a built-in, enter a submenu or run an action for a command
+    fn dispatch_key(&mut self, key_string: String, params: Option<String>) -> bool {
+        // Any input answers a pending parameter prompt
+        if self.pending_param.is_some() {
+            let value = match params {
+                Some(params) => format!("{} {}", key_string, params),
+                None => key_string,
+            };
+            self.answer_param(&value);
+            return true;
+        }
+
+        // Any input answers a pending confirmation prompt
+        if self.pending_confirmation.is_some() {
+            let answer = key_string.to_lowercase();
+            self.confirm(answer == "y" || answer == "yes");
+            return true;
+        }
+
+        // Check for built-in commands first
+        if let Some(should_continue) = self.handle_builtin(&key_string, params.as_deref()) {
+            return should_continue;
+        }
+
+        // Check if the key corresponds to a menu item with a submenu
+        if self.menu_manager.has_submenu(&key_string) {
+            self.menu_manager.navigate_to_submenu(&key_string);
+            self.category_filter = None;
+            return true;
+        }
+
+        // Check if the key corresponds to a menu item with an action
+        if self.menu_manager.has_action(&key_string) {
+            let path = self.menu_manager.current_path().to_vec();
+            self.run_action(&path, &key_string, params);
+            return true;
+        }
+
+        // Fall back to the actions available from every menu
+        let path = self.menu_manager.current_path().to_vec();
+        if self.is_action_at(&path, &key_string) {
+            self.run_action(&path, &key_string, params);
+            return true;
+        }
+
+        // If we get here, the key wasn't recognized
+        self.add_output(format!("Unknown command: {}", key_string));
+        true
+    }
+
+    /// Handle a built-in command, returning None if the key isn't one
+    fn handle_builtin(&mut self, key: &str, params: Option<&str>) -> Option<bool> {
+        let param = params.map(str::trim).filter(|p| !p.is_empty());
+
+        match key {
+            "q" => {
+                // Only quit from root menu
+                if self.menu_manager.is_at_root() {
+                    return Some(false); // Signal to exit the app
+                }
+                self.add_output(
+                    "Use 'b' to return to previous menu, or navigate to root menu to quit"
+                        .to_string(),
+                );
+            }
+            // Quit from any menu
+            "quit!" => return Some(false),
+            // Browser-style navigation through the menus visited so far
+            "<" => {
+                if self.menu_manager.history_back() {
+                    self.category_filter = None;
+                } else {
+                    self.add_output("No earlier menu in history".to_string());
+                }
+            }
+            ">" => {
+                if self.menu_manager.history_forward() {
+                    self.category_filter = None;
+                } else {
+                    self.add_output("No later menu in history".to_string());
+                }
+            }
+            "b" => {
+                // Back navigation
+                if !self.menu_manager.navigate_back() {
+                    self.add_output("Already at root menu".to_string());
+                }
+                self.category_filter = None;
+            }
+            "pin" => {
+                let path = self.menu_manager.current_path().to_vec();
+                match param {
+                    Some(key) => match self.pin(&path, key) {
+                        Ok(()) => self.add_output(format!("Pinned '{}' to favorites", key)),
+                        Err(e) => self.add_output(e),
+                    },
+                    None => self.add_output("Usage: pin <key>".to_string()),
+                }
+            }
+            "unpin" => match param.and_then(|p| p.parse::<usize>().ok()) {
+                Some(number) => match self.unpin(number) {
+                    Some(command) => self.add_output(format!("Unpinned '{}'", command.description)),
+                    None => self.add_output(format!("No favorite {}", number)),
+                },
+                None => self.add_output("Usage: unpin <number>".to_string()),
+            },
+            "category" => self.filter_by_category(param),
+            "tagged" => match param {
+                Some(category) => self.list_tagged(category),
+                None => self.add_output("Usage: tagged <category>".to_string()),
+            },
+            "export-history" => self.export_history(param),
+            "cancel" => self.cancel_command(param),
+            "kill" => match param {
+                Some(_) => self.cancel_command(param),
+                None => self.add_output("Usage: kill <job-id>".to_string()),
+            },
+            "jobs" => self.jobs_command(param),
+            "queue" => self.queue_command(param),
+            "stats" => self.stats_command(param),
+            "theme" => self.theme_command(param),
+            "pane" => self.pane_command(param),
+            "loglevel" => self.loglevel_command(param),
+            "filter" => self.filter_command(param),
+            "tab" => self.tab_command(param),
+            "undo" => self.undo(),
+            "redo" => self.redo(),
+            "keys" => {
+                for line in keymap::format_key_bindings(&self.key_bindings()) {
+                    self.add_output(line);
+                }
+            }
+            "grep" => match param {
+                Some(pattern) => {
+                    let result = self.grep_output(pattern);
+                    self.add_output(result);
+                }
+                None => self.add_output("Usage: grep <pattern>".to_string()),
+            },
+            _ => {
+                // Re-run a recent command by number, e.g. "!1"
+                if let Some(number) = key.strip_prefix('!') {
+                    let recent = number
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| self.recent_commands.get(n))
+                        .cloned();
+                    match recent {
+                        Some(command) => {
+                            let params = params.map(str::to_string).or(command.params);
+                            self.run_action(&command.path, &command.key, params);
+                        }
+                        None => self.add_output(format!("No recent command {}", key)),
+                    }
+                // Run a favorite by number, e.g. "*1"
+                } else if let Some(number) = key.strip_prefix('*') {
+                    let favorite = number
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| self.favorites.get(n))
+                        .cloned();
+                    match favorite {
+                        Some(command) => {
+                            self.run_action(&command.path, &command.key, params.map(str::to_string))
+                        }
+                        None => self.add_output(format!("No favorite {}", key)),
+                    }
+                } else {
+                    return None;
+                }
+            }
+        }
+
+        Some(true)
+    }
+
+    /// Search every line of the output buffer for a pattern, ignoring case
+    fn grep_output(&self, pattern: &str) -> String {
+        let needle = pattern.to_lowercase();
+        let matches: Vec<String> = self
+            .shown_output()
+            .messages()
+            .iter()
+            .flat_map(|message| message.lines())
+            .enumerate()
+            .filter(|(_, line)| line.to_lowercase().contains(&needle))
+            .map(|(idx, line)| format!("{:>5}: {}", idx + 1, line))
+            .collect();
+
+        if matches.is_empty() {
+            format!("grep '{}': no matches", pattern)
+        } else {
+            format!(
+                "grep '{}': {} match(es)\n{}",
+                pattern,
+                matches.len(),
+                matches.join("\n")
+            )
+        }
+    }
+
+    /// Original handle_key method that delegates to handle_key_with_params
+    pub fn handle_key(&mut self, key: impl Into<String>) -> bool {
+        self.handle_key_with_params(key, None)
+    }
+
+    /// Run the application
+    pub fn run(&mut self) -> std::io::Result<()> {
+        crate::rendering::run(self)
+    }
+
+    /// Get the current mode
+    pub fn mode(&self) -> Mode {
+        self.current_mode
     }
 
     /// Toggle between modes
@@ -338,220 +3369,2527 @@ impl<T: std::fmt::Debug> Istari<T> {
         };
     }
 
-    /// Set a specific mode
-    pub fn set_mode(&mut self, mode: Mode) {
-        self.current_mode = mode;
+    /// Set a specific mode
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.current_mode = mode;
+    }
+
+    /// Get the current command input buffer
+    pub fn input_buffer(&self) -> &str {
+        &self.input_buffer
+    }
+
+    /// Add a character to the input buffer
+    pub fn add_to_input_buffer(&mut self, c: char) {
+        self.input_buffer.push(c);
+        self.completions.clear();
+        self.selected_suggestion = None;
+    }
+
+    /// Clear the input buffer
+    pub fn clear_input_buffer(&mut self) {
+        self.input_buffer.clear();
+        self.completions.clear();
+        self.selected_suggestion = None;
+    }
+
+    /// Remove the last character from the input buffer
+    pub fn backspace_input_buffer(&mut self) {
+        self.input_buffer.pop();
+        self.completions.clear();
+        self.selected_suggestion = None;
+    }
+
+    /// Get the commands fuzzy-matching the input, best first, as their key and
+    /// description.
+    ///
+    /// Keys and descriptions of the current menu and the global items are matched,
+    /// until the input has a space, i.e. parameters are being typed. Nothing is
+    /// suggested while a prompt waits for an answer or history is browsed.
+    pub fn command_suggestions(&self) -> Vec<(String, String)> {
+        let input = self.input_buffer.trim_start();
+        if !self.command_suggestions
+            || input.is_empty()
+            || input.contains(' ')
+            || self.pending_param.is_some()
+            || self.pending_confirmation.is_some()
+            || self.command_history.is_browsing()
+        {
+            return Vec::new();
+        }
+
+        let menu = self.current_menu();
+        let menu = menu.lock().unwrap();
+        let globals = self.menu_manager.globals();
+        let globals = globals.lock().unwrap();
+        let local = menu.items.iter().filter(|item| self.is_item_visible(item));
+        // Global items are shadowed by the current menu's
+        let global = globals
+            .items
+            .iter()
+            .filter(|item| menu.find_item(&item.key).is_none());
+
+        let mut matches: Vec<(i64, String, String)> = local
+            .chain(global)
+            .filter_map(|item| {
+                let description = item.describe(&self.state);
+                // Matching the key counts more than matching the description
+                let score = fuzzy_score(input, &item.key)
+                    .map(|score| score + 1000)
+                    .max(fuzzy_score(input, &description))?;
+                Some((score, item.key.clone(), description))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        matches
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, key, description)| (key, description))
+            .collect()
+    }
+
+    /// Get the index of the suggestion picked with the arrow keys, if any
+    pub fn selected_suggestion(&self) -> Option<usize> {
+        self.selected_suggestion
+    }
+
+    /// Move the picked suggestion by `delta`, wrapping around, returning false if
+    /// there are no suggestions
+    pub fn select_suggestion(&mut self, delta: isize) -> bool {
+        let count = self.command_suggestions().len();
+        if count == 0 {
+            self.selected_suggestion = None;
+            return false;
+        }
+        let selected = match self.selected_suggestion {
+            Some(index) => (index as isize + delta).rem_euclid(count as isize) as usize,
+            None if delta < 0 => count - 1,
+            None => 0,
+        };
+        self.selected_suggestion = Some(selected);
+        true
+    }
+
+    /// Fill the input with the key of the picked suggestion, returning false if
+    /// none is picked
+    pub fn accept_suggestion(&mut self) -> bool {
+        let Some(index) = self.selected_suggestion.take() else {
+            return false;
+        };
+        let Some((key, _)) = self.command_suggestions().into_iter().nth(index) else {
+            return false;
+        };
+        self.input_buffer = key;
+        self.completions.clear();
+        true
+    }
+
+    /// Complete the parameter being typed in the input buffer, returning false if
+    /// the command has no completion to offer.
+    ///
+    /// A single match is filled in; several are extended to their common prefix
+    /// and listed by [`Istari::completions`].
+    pub fn complete_input(&mut self) -> bool {
+        self.completions.clear();
+        let Some((key, params)) = self.input_buffer.split_once(' ') else {
+            return false;
+        };
+        let key = key.to_lowercase();
+        // Only the last word is completed, earlier parameters stay as typed
+        let word_start = self.input_buffer.len() - params.len()
+            + params.rfind(char::is_whitespace).map_or(0, |idx| idx + 1);
+        let word = self.input_buffer[word_start..].to_string();
+
+        let candidates = if key == "theme" {
+            THEME_PRESETS.map(str::to_string).to_vec()
+        } else {
+            let path = self.menu_manager.current_path().to_vec();
+            let Some((menu, _)) = self.menu_manager.resolve_action(&path, &key) else {
+                return false;
+            };
+            let Some(completer) = menu
+                .lock()
+                .unwrap()
+                .find_item(&key)
+                .and_then(|item| item.completer.clone())
+            else {
+                return false;
+            };
+            completer(&self.state, &word)
+        };
+        let mut matches: Vec<String> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(&word))
+            .collect();
+        matches.dedup();
+
+        match matches.as_slice() {
+            [] => {}
+            [only] => {
+                self.input_buffer.truncate(word_start);
+                self.input_buffer.push_str(only);
+                self.input_buffer.push(' ');
+            }
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.as_str(), |common, candidate| {
+                    let len = common
+                        .char_indices()
+                        .zip(candidate.chars())
+                        .find(|((_, a), b)| a != b)
+                        .map_or(common.len().min(candidate.len()), |((idx, _), _)| idx);
+                    &common[..len]
+                });
+                self.input_buffer.truncate(word_start);
+                self.input_buffer.push_str(common);
+                self.completions = matches;
+            }
+        }
+        true
+    }
+
+    /// Get the values offered by the last Tab completion, empty unless several matched
+    pub fn completions(&self) -> &[String] {
+        &self.completions
+    }
+
+    /// Toggle showing the input box
+    pub fn toggle_show_input(&mut self) {
+        self.show_input = !self.show_input;
+    }
+
+    /// Check if input should be shown
+    pub fn show_input(&self) -> bool {
+        self.show_input
+    }
+
+    /// Process the current input buffer as a command
+    pub fn process_input_buffer(&mut self) -> bool {
+        // The whole input answers a pending parameter prompt, keeping its case
+        if self.pending_param.is_some() {
+            let value = std::mem::take(&mut self.input_buffer);
+            self.answer_param(&value);
+            return true;
+        }
+
+        // The whole input answers the dialog shown, e.g. in text mode
+        if let Some(dialog) = self.dialogs.front_mut() {
+            let input = std::mem::take(&mut self.input_buffer);
+            match &mut dialog.kind {
+                DialogKind::Message => self.submit_dialog(),
+                DialogKind::Confirm { .. } => {
+                    let answer = input.trim().to_lowercase();
+                    self.answer_dialog(answer == "y" || answer == "yes");
+                }
+                DialogKind::Prompt { value } => {
+                    *value = input;
+                    self.submit_dialog();
+                }
+            }
+            return true;
+        }
+
+        if self.input_buffer.is_empty() {
+            return true;
+        }
+
+        // Create a binding that lives for the entire function
+        let input_clone = self.input_buffer.clone();
+        let input = input_clone.trim();
+
+        // Add command to history
+        if !input.is_empty() {
+            self.command_history.add(input.to_string());
+            if let Some(path) = &self.history_file
+                && let Err(e) = self.command_history.save(path)
+            {
+                self.add_output(format!("Failed to save history: {}", e));
+            }
+        }
+
+        // Split input into command and parameters
+        let parts: Vec<&str> = input.splitn(2, ' ').collect();
+        let command = parts[0].to_lowercase();
+        let params = parts.get(1).map(|&s| s.to_string());
+
+        // Delegate to handle_key_with_params, logging the output it produced in any tab
+        let capture = OutputCapture::default();
+        self.set_output_capture(Some(capture.clone()));
+        let result = self.handle_key_with_params(command, params);
+        self.set_output_capture(None);
+        let output = std::mem::take(&mut *capture.lock().unwrap());
+        self.session_log.record(input, output);
+
+        self.clear_input_buffer();
+        result
+    }
+
+    /// Compose the parameters of the current command in the user's `$EDITOR`.
+    ///
+    /// The controller must suspend the terminal around this call. The saved text
+    /// replaces the parameter part of the input buffer, flattened to a single line.
+    pub fn compose_params_in_editor(&mut self) {
+        let (command, params) = match self.input_buffer.split_once(' ') {
+            Some((command, params)) => (command.to_string(), params.to_string()),
+            None => (self.input_buffer.clone(), String::new()),
+        };
+
+        match crate::editor::edit_text(&params) {
+            Ok(text) => {
+                let params = crate::editor::to_single_line(&text);
+                self.input_buffer = if params.is_empty() {
+                    command
+                } else {
+                    format!("{} {}", command, params)
+                };
+            }
+            Err(e) => self.add_output(format!("Failed to open editor: {}", e)),
+        }
+    }
+
+    /// Navigate up in command history
+    pub fn history_up(&mut self) {
+        if let Some(cmd) = self.command_history.up() {
+            self.input_buffer = cmd.clone();
+        }
+    }
+
+    /// Navigate down in command history
+    pub fn history_down(&mut self) {
+        if let Some(cmd) = self.command_history.down() {
+            self.input_buffer = cmd.clone();
+        } else {
+            // At the end of history or exited browsing mode
+            self.input_buffer.clear();
+        }
+    }
+
+    /// Exit history browsing mode
+    pub fn exit_history_browsing(&mut self) {
+        self.command_history.exit_browsing();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ActionContext;
+    use crate::menu::{Menu, MenuItem};
+    use crate::params::ParamType;
+    use crate::types::ActionOutcome;
+    use std::time::UNIX_EPOCH;
+
+    #[derive(Debug)]
+    pub struct TestState {
+        pub counter: i32,
+    }
+    #[test]
+    fn test_istari_creation() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+
+        let result = Istari::new(menu, state);
+        assert!(result.is_ok());
+
+        let app = result.unwrap();
+        assert_eq!(app.mode(), Mode::Command);
+        assert!(app.output_messages().is_empty());
+        assert!(!app.show_input());
+    }
+
+    #[test]
+    fn test_mode_toggling() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap();
+
+        assert_eq!(app.mode(), Mode::Command);
+        app.toggle_mode();
+        assert_eq!(app.mode(), Mode::Scroll);
+        app.toggle_mode();
+        assert_eq!(app.mode(), Mode::Command);
+
+        app.set_mode(Mode::Scroll);
+        assert_eq!(app.mode(), Mode::Scroll);
+    }
+
+    #[test]
+    fn test_input_buffer() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap();
+
+        assert!(app.input_buffer().is_empty());
+        app.add_to_input_buffer('t');
+        app.add_to_input_buffer('e');
+        app.add_to_input_buffer('s');
+        app.add_to_input_buffer('t');
+        assert_eq!(app.input_buffer(), "test");
+
+        app.backspace_input_buffer();
+        assert_eq!(app.input_buffer(), "tes");
+
+        app.clear_input_buffer();
+        assert!(app.input_buffer().is_empty());
+    }
+
+    #[test]
+    fn test_output_messages() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap();
+
+        assert!(app.output_messages().is_empty());
+        app.add_output("Test message".to_string());
+        assert_eq!(app.output_messages().len(), 1);
+        assert_eq!(app.output_messages()[0], "Test message");
+
+        assert!(app.has_new_output());
+        assert!(!app.has_new_output());
+    }
+
+    #[test]
+    fn test_tick_handler() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap().with_tick_handler(
+            |state: &mut TestState, output: &OutputHandle, _delta: f32| {
+                state.counter += 1;
+                output.push(format!("Tick: {}", state.counter));
+            },
+        );
+
+        // Simulate a tick
+        app.tick();
+        assert_eq!(app.output_messages().len(), 1);
+        assert_eq!(app.output_messages()[0], "Tick: 1");
+    }
+
+    #[test]
+    fn test_command_history() {
+        let mut history = CommandHistory::new(3);
+
+        // Add commands
+        history.add("cmd1".to_string());
+        history.add("cmd2".to_string());
+        history.add("cmd3".to_string());
+
+        // Test navigation
+        assert_eq!(history.up().unwrap(), "cmd3");
+        assert_eq!(history.up().unwrap(), "cmd2");
+        assert_eq!(history.up().unwrap(), "cmd1");
+        assert_eq!(history.up().unwrap(), "cmd1"); // Can't go past beginning
+
+        assert_eq!(history.down().unwrap(), "cmd2");
+        assert_eq!(history.down().unwrap(), "cmd3");
+        assert_eq!(history.down(), None); // Exit browsing
+
+        // Test max size
+        history.add("cmd4".to_string());
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(history.entries[0], "cmd2"); // cmd1 was removed
+    }
+
+    #[test]
+    fn test_output_buffer() {
+        let mut buffer = OutputBuffer::new();
+
+        assert!(buffer.messages().is_empty());
+        buffer.add("Test".to_string());
+        assert_eq!(buffer.messages().len(), 1);
+
+        assert!(buffer.has_new_output());
+        assert!(!buffer.has_new_output());
+
+        buffer.clear();
+        assert!(buffer.messages().is_empty());
+        assert!(!buffer.has_new_output());
+    }
+
+    #[test]
+    fn test_output_buffer_max_size() {
+        let mut buffer = OutputBuffer::with_max_size(3);
+        for n in 1..=5 {
+            buffer.push(None, OutputLevel::Info, format!("line {}", n));
+        }
+        buffer.push(None, OutputLevel::Error, "line 6".to_string());
+        // The oldest messages were dropped along with their levels
+        assert_eq!(*buffer.messages(), ["line 4", "line 5", "line 6"]);
+        assert_eq!(buffer.levels().last(), Some(&OutputLevel::Error));
+        assert_eq!(buffer.total(), 6);
+        assert_eq!(buffer.dropped(), 3);
+
+        buffer.set_max_size(1);
+        assert_eq!(*buffer.messages(), ["line 6"]);
+        assert_eq!(*buffer.levels(), [OutputLevel::Error]);
+        assert_eq!(buffer.dropped(), 5);
+        buffer.clear();
+        assert_eq!(buffer.dropped(), 6);
+
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap().with_max_output_size(2);
+        app.open_output_tab("Logs");
+        app.select_output_tab(1);
+        for n in 1..=3 {
+            app.add_output(format!("log {}", n));
+        }
+        assert_eq!(*app.output_messages(), ["log 2", "log 3"]);
+        assert_eq!(app.output_total(), 3);
+    }
+
+    #[test]
+    fn test_export_output() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        let before = SystemTime::now();
+        app.add_output("Ready".to_string());
+        app.output.push(
+            Some("deploy".to_string()),
+            OutputLevel::Warn,
+            "Slow host".to_string(),
+        );
+
+        app.output
+            .push(None, OutputLevel::Debug, "Pool stats".to_string());
+        app.set_log_level(OutputLevel::Warn);
+        app.open_output_tab("logs");
+        app.tab_output_mut(Some("logs")).add("Log line".to_string());
+
+        // Messages the log level hides are exported too
+        let records = app.export_output(None);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].source.as_deref(), Some("deploy"));
+        assert_eq!(records[1].level, OutputLevel::Warn);
+        assert_eq!(records[2].text, "Pool stats");
+        assert!(records[0].time >= before);
+
+        // As are the messages of other tabs, whichever is shown
+        let logs = app.export_output(Some("logs"));
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].text, "Log line");
+        assert!(app.export_output(Some("missing")).is_empty());
+
+        let json = serde_json::to_value(&records).unwrap();
+        assert_eq!(json[1]["level"], "warn");
+        assert_eq!(json[1]["text"], "Slow host");
+        assert_eq!(json[0]["source"], serde_json::Value::Null);
+        let since = before.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        assert!(json[0]["timestamp"].as_u64().unwrap() >= since);
+        assert!(json[0].get("format").is_none());
+    }
+
+    #[test]
+    fn test_output_sink() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        let sink = app.output_sink();
+        let workers: Vec<_> = (0..3)
+            .map(|n| {
+                let sink = sink.clone();
+                std::thread::spawn(move || sink.write_line(format!("worker {}", n)))
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        sink.write_level(OutputLevel::Warn, "done");
+
+        assert!(app.output_messages().is_empty());
+        app.drain_output();
+        assert_eq!(app.output_messages().len(), 4);
+        assert_eq!(*app.output_levels().last().unwrap(), OutputLevel::Warn);
+    }
+
+    #[test]
+    fn test_pause_output() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        let output = app.output_handle();
+        output.push("before");
+        app.pause_output();
+        assert!(app.is_output_paused());
+
+        output.push("while paused");
+        output.with_tab("logs").push("in a tab");
+        app.tick();
+        assert_eq!(*app.output_messages(), ["before"]);
+        assert_eq!(app.paused_output_count(), 2);
+
+        // Quitting isn't held back
+        output.request_quit();
+        app.tick();
+        assert!(app.quit_requested());
+
+        app.toggle_output_paused();
+        assert!(!app.is_output_paused());
+        assert_eq!(*app.output_messages(), ["before", "while paused"]);
+        assert_eq!(app.output_tabs().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stdio_capture() {
+        use std::io::Write;
+
+        let mut menu: Menu<TestState> = Menu::new("Root");
+        menu.add_action(
+            "p",
+            "Print",
+            |_state: &mut TestState, _params: Option<&str>| {
+                // `println!` goes to the test harness, so write to the streams directly
+                writeln!(std::io::stdout(), "printed").unwrap();
+                writeln!(std::io::stderr(), "complained").unwrap();
+                Some("done".to_string())
+            },
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_stdio_capture(true);
+        app.handle_key("p");
+
+        let messages = app.output_messages();
+        let printed = messages.iter().position(|m| m.contains("printed")).unwrap();
+        let complained = messages
+            .iter()
+            .position(|m| m.contains("complained"))
+            .unwrap();
+        assert_eq!(app.output_levels()[printed], OutputLevel::Info);
+        assert_eq!(app.output_levels()[complained], OutputLevel::Warn);
+        // What the action printed goes above its result
+        assert_eq!(*messages.last().unwrap(), "done");
+    }
+
+    #[test]
+    fn test_log_file() {
+        let path = std::env::temp_dir().join(format!("istari-output-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_log_file(&path);
+        app.add_output("first".to_string());
+        let output = app.output_handle();
+        output
+            .with_tab("build")
+            .with_source("cargo")
+            .push("compiling");
+        // Replaced messages are logged with their new text
+        let build = output.with_tab("build");
+        build.with_source("cargo").replace_last("compiled");
+        build.replace_last("done");
+        app.drain_output();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].ends_with("] info first"));
+        assert!(lines[1].ends_with("] info cargo: compiling"));
+        assert!(lines[2].ends_with("] info cargo: compiled"));
+        assert!(lines[3].ends_with("] info cargo: done"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_menu_output() {
+        let mut submenu: Menu<TestState> = Menu::new("Submenu");
+        submenu.add_action(
+            "a",
+            "Add",
+            |state: &mut TestState, _params: Option<&str>| {
+                state.counter += 1;
+                Some(format!("Counter: {}", state.counter))
+            },
+        );
+        let mut menu: Menu<TestState> = Menu::new("Root");
+        menu.add_submenu("s", "Submenu", submenu);
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_menu_output(true);
+        app.add_output("at the root".to_string());
+        app.handle_key("s");
+        app.handle_key("a");
+
+        assert_eq!(app.output_menu().as_deref(), Some("s"));
+        let menus: Vec<&str> = app
+            .output_records()
+            .iter()
+            .map(|entry| entry.menu.as_str())
+            .collect();
+        assert_eq!(menus, ["", "s"]);
+
+        app.toggle_menu_output();
+        assert_eq!(app.output_menu(), None);
+        app.toggle_menu_output();
+        app.handle_key("b");
+        assert_eq!(app.output_menu().as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_command_suggestions() {
+        let mut menu: Menu<TestState> = Menu::new("Root");
+        menu.add_action(
+            "deploy",
+            "Deploy the app",
+            |_state: &mut TestState, _params: Option<&str>| None,
+        );
+        menu.add_action(
+            "l",
+            "Show logs",
+            |_state: &mut TestState, _params: Option<&str>| None,
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_global_action(
+                "status",
+                "Show the status",
+                |_state: &mut TestState, _params: Option<&str>| None,
+            )
+            .unwrap();
+        let keys = |app: &Istari<TestState>| -> Vec<String> {
+            app.command_suggestions()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect()
+        };
+        assert!(app.command_suggestions().is_empty());
+
+        // Keys and descriptions match, keys first
+        app.add_to_input_buffer('s');
+        assert_eq!(keys(&app), ["status", "l"]);
+        app.add_to_input_buffer('l');
+        assert_eq!(keys(&app), ["l"]);
+        assert_eq!(app.command_suggestions()[0].1, "Show logs");
+
+        // Picking wraps around, Enter fills the key in
+        app.clear_input_buffer();
+        app.add_to_input_buffer('d');
+        assert!(app.select_suggestion(-1));
+        assert_eq!(app.selected_suggestion(), Some(keys(&app).len() - 1));
+        assert!(app.select_suggestion(1));
+        assert_eq!(app.selected_suggestion(), Some(0));
+        assert!(app.accept_suggestion());
+        assert_eq!(app.input_buffer(), "deploy");
+        assert!(!app.accept_suggestion());
+
+        // Nothing is suggested once parameters are typed, or when turned off
+        app.add_to_input_buffer(' ');
+        assert!(app.command_suggestions().is_empty());
+        assert!(!app.select_suggestion(1));
+        let mut app = app.with_command_suggestions(false);
+        app.clear_input_buffer();
+        app.add_to_input_buffer('d');
+        assert!(app.command_suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_collapse_duplicates() {
+        let mut buffer = OutputBuffer::new();
+        buffer.set_collapse_duplicates(true);
+        for message in ["retrying", "retrying", "retrying", "connected", "retrying"] {
+            buffer.add(message.to_string());
+        }
+        buffer.push(None, OutputLevel::Warn, "retrying".to_string());
+        assert_eq!(
+            *buffer.messages(),
+            ["retrying", "connected", "retrying", "retrying"]
+        );
+        let repeats: Vec<usize> = buffer.records().iter().map(|entry| entry.repeats).collect();
+        assert_eq!(repeats, [3, 1, 1, 1]);
+        assert_eq!(buffer.total(), 6);
+        assert_eq!(buffer.records()[0].repeats, 3);
+
+        // Off by default
+        let mut buffer = OutputBuffer::new();
+        buffer.add("tick".to_string());
+        buffer.add("tick".to_string());
+        assert_eq!(buffer.messages().len(), 2);
+    }
+
+    #[test]
+    fn test_output_buffer_replace_from() {
+        let mut buffer = OutputBuffer::new();
+
+        buffer.replace_from("status", "Status: 1".to_string());
+        buffer.add("Other".to_string());
+        buffer.replace_from("status", "Status: 2".to_string());
+
+        assert_eq!(*buffer.messages(), ["Status: 2", "Other"]);
+    }
+
+    #[test]
+    fn test_output_policy_routing() {
+        let state = TestState { counter: 0 };
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "s",
+                "Status".to_string(),
+                |state: &mut TestState, _params: Option<&str>| {
+                    state.counter += 1;
+                    Some(format!("Status: {}", state.counter))
+                },
+            )
+            .with_output_policy(OutputPolicy::Replace),
+        );
+        menu.add_item(
+            MenuItem::new_action(
+                "v",
+                "View".to_string(),
+                |_state: &mut TestState, _params: Option<&str>| Some("Details".to_string()),
+            )
+            .with_output_policy(OutputPolicy::Popup),
+        );
+        let mut app = Istari::new(menu, state).unwrap();
+
+        app.handle_key("s");
+        app.handle_key("s");
+        assert_eq!(*app.output_messages(), ["Status: 2"]);
+
+        app.handle_key("v");
+        assert_eq!(app.output_messages().len(), 1);
+        let popup = app.popup().unwrap();
+        assert_eq!(popup.title, "View");
+        assert_eq!(popup.content, "Details");
+
+        app.close_popup();
+        assert!(app.popup().is_none());
+    }
+
+    #[test]
+    fn test_recent_commands() {
+        let state = TestState { counter: 0 };
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("a", "Add", |state: &mut TestState, params: Option<&str>| {
+            state.counter += params.and_then(|p| p.parse::<i32>().ok()).unwrap_or(1);
+            Some(format!("Counter: {}", state.counter))
+        });
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_submenu("s", "Submenu", submenu);
+        let mut app = Istari::new(menu, state).unwrap().with_recent_commands(5);
+
+        app.handle_key("s");
+        app.handle_key_with_params("a", Some("5".to_string()));
+        app.handle_key("b");
+        assert!(app.recent_commands().is_visible());
+        assert_eq!(app.recent_commands().get(1).unwrap().label(), "s/a 5");
+
+        // Re-run it from the root menu by number
+        app.handle_key("!1");
+        assert_eq!(*app.output_messages().last().unwrap(), "Counter: 10");
+        assert_eq!(app.recent_commands().iter().count(), 1);
+
+        app.handle_key("!2");
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "No recent command !2"
+        );
+    }
+
+    #[test]
+    fn test_favorites() {
+        let state = TestState { counter: 0 };
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("a", "Add", |state: &mut TestState, _: Option<&str>| {
+            state.counter += 1;
+            Some(format!("Counter: {}", state.counter))
+        });
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_submenu("s", "Submenu", submenu);
+
+        let history_file =
+            std::env::temp_dir().join(format!("istari-test-{}.history", std::process::id()));
+        let mut app = Istari::new(menu, state)
+            .unwrap()
+            .with_history_file(&history_file);
+
+        app.handle_key("s");
+        app.handle_key_with_params("pin", Some("a".to_string()));
+        app.handle_key_with_params("pin", Some("x".to_string()));
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "No action with key 'x' to pin"
+        );
+        app.handle_key("b");
+
+        // Run the favorite from the root menu
+        app.handle_key("*1");
+        assert_eq!(*app.output_messages().last().unwrap(), "Counter: 1");
+
+        // Favorites are restored from disk
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("a", "Add", |_: &mut TestState, _: Option<&str>| None);
+        menu.add_submenu("s", "Submenu", submenu);
+        let mut restored = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_history_file(&history_file);
+        assert_eq!(restored.favorites().get(1).unwrap().item_path(), "s/a");
+
+        assert!(restored.unpin(1).is_some());
+        assert!(restored.favorites().is_empty());
+
+        let _ = std::fs::remove_file(Istari::<TestState>::favorites_file(&history_file));
+    }
+
+    #[test]
+    fn test_grep_output() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap();
+
+        app.add_output("first line\nError: disk full".to_string());
+        app.add_output("all good".to_string());
+        app.add_output("error again".to_string());
+
+        app.handle_key_with_params("grep", Some("error".to_string()));
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "grep 'error': 2 match(es)\n    2: Error: disk full\n    4: error again"
+        );
+
+        app.handle_key_with_params("grep", Some("missing".to_string()));
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "grep 'missing': no matches"
+        );
+    }
+
+    #[test]
+    fn test_param_spec_validation() {
+        use crate::params::{ParamSpec, ParamType};
+
+        let state = TestState { counter: 0 };
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "add",
+                "Add".to_string(),
+                |state: &mut TestState, params: Option<&str>| {
+                    state.counter += params.unwrap().parse::<i32>().unwrap();
+                    Some(format!("Counter: {}", state.counter))
+                },
+            )
+            .with_params([ParamSpec::optional("amount", ParamType::Integer).with_default("1")]),
+        );
+        let mut app = Istari::new(menu, state).unwrap();
+
+        // Defaults are filled in before the action runs
+        app.handle_key("add");
+        assert_eq!(*app.output_messages().last().unwrap(), "Counter: 1");
+
+        // Invalid values never reach the action
+        app.handle_key_with_params("add", Some("lots".to_string()));
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "Invalid value 'lots' for parameter 'amount': expected int\nUsage: add [amount:int=1]"
+        );
+        assert_eq!(app.state.counter, 1);
+    }
+
+    #[test]
+    fn test_output_handle() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap().with_tick_handler(
+            |state: &mut TestState, output: &OutputHandle, _delta: f32| {
+                state.counter += 1;
+                output.replace_last(format!("Progress: {}", state.counter));
+            },
+        );
+        app.add_output("Started".to_string());
+
+        // Handles can write from other threads
+        let output = app.output_handle().with_source("job");
+        std::thread::spawn(move || output.push_level(OutputLevel::Warn, "Slow job"))
+            .join()
+            .unwrap();
+
+        // Repeated ticks update the same progress line
+        app.tick();
+        app.tick();
+        assert_eq!(
+            *app.output_messages(),
+            ["Started", "Slow job", "Progress: 2"]
+        );
+        assert_eq!(
+            *app.output_levels(),
+            [OutputLevel::Info, OutputLevel::Warn, OutputLevel::Info]
+        );
+
+        app.output_handle().clear();
+        app.tick();
+        assert_eq!(*app.output_messages(), ["Progress: 3"]);
+    }
+
+    #[test]
+    fn test_global_actions() {
+        let state = TestState { counter: 0 };
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("x", "Local", |_: &mut TestState, _: Option<&str>| None);
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_submenu("s", "Submenu", submenu);
+
+        let mut app = Istari::new(menu, state)
+            .unwrap()
+            .with_global_action(
+                "status",
+                "Show status",
+                |state: &mut TestState, _: Option<&str>| {
+                    Some(format!("Counter: {}", state.counter))
+                },
+            )
+            .unwrap();
+
+        // Global actions run from a submenu without navigating away
+        app.handle_key("s");
+        app.handle_key("status");
+        assert_eq!(*app.output_messages().last().unwrap(), "Counter: 0");
+        assert!(!app.is_at_root());
+
+        // They are recorded independently of the menu they were run from
+        let recent = app.recent_commands().get(1).unwrap();
+        assert!(recent.path.is_empty());
+        assert_eq!(recent.key, "status");
+    }
+
+    #[test]
+    fn test_category_filter() {
+        let state = TestState { counter: 0 };
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "p",
+                "Ping".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_categories(["network"]),
+        );
+        menu.add_action("s", "Status", |_: &mut TestState, _: Option<&str>| None);
+        let mut app = Istari::new(menu, state).unwrap();
+
+        app.handle_key("category");
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "Categories: network"
+        );
+
+        app.handle_key_with_params("category", Some("Network".to_string()));
+        assert_eq!(app.category_filter(), Some("network"));
+        let visible: Vec<String> = {
+            let menu = app.current_menu();
+            let menu = menu.lock().unwrap();
+            menu.items
+                .iter()
+                .filter(|item| app.is_item_visible(item))
+                .map(|item| item.key.clone())
+                .collect()
+        };
+        assert_eq!(visible, ["p"]);
+
+        // Unknown categories leave the filter alone, "category" alone clears it
+        app.handle_key_with_params("category", Some("disk".to_string()));
+        assert_eq!(app.category_filter(), Some("network"));
+        app.handle_key("category");
+        assert_eq!(app.category_filter(), None);
+    }
+
+    #[test]
+    fn test_tick_and_render_rate() {
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        assert_eq!(app.tick_rate(), Duration::from_millis(100));
+        assert_eq!(app.render_rate(), app.tick_rate());
+
+        let app = app
+            .with_tick_rate(Duration::from_secs(1))
+            .with_render_rate(Duration::from_millis(33));
+        assert_eq!(app.tick_rate(), Duration::from_secs(1));
+        assert_eq!(app.render_rate(), Duration::from_millis(33));
+    }
+
+    #[test]
+    fn test_tick_budget() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state)
+            .unwrap()
+            .with_tick_budget(Duration::from_millis(1))
+            .with_tick_handler(|state: &mut TestState, _: &OutputHandle, _delta: f32| {
+                state.counter += 1;
+                std::thread::sleep(Duration::from_millis(20));
+            });
+
+        // A slow tick warns and throttles the handler
+        app.tick();
+        app.tick();
+        assert_eq!(app.state.counter, 1);
+        assert_eq!(app.tick_stats().runs, 1);
+        assert_eq!(app.tick_stats().slow_ticks, 1);
+        assert!(app.tick_stats().max >= Duration::from_millis(20));
+        assert!(
+            app.output_messages()[0].starts_with("Tick handler took"),
+            "{:?}",
+            app.output_messages()
+        );
+        assert_eq!(*app.output_levels(), [OutputLevel::Warn]);
+    }
+
+    #[test]
+    fn test_force_quit() {
+        let state = TestState { counter: 0 };
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_submenu("s", "Submenu", submenu);
+        let mut app = Istari::new(menu, state).unwrap();
+
+        app.handle_key("s");
+        assert!(app.handle_key("q"));
+        assert!(!app.handle_key("quit!"));
+        assert!(!app.handle_quit());
+
+        // The quit key can follow the root menu rule instead
+        let mut app = app.with_quit_behavior(QuitBehavior::Quit);
+        assert!(app.handle_quit());
+        app.handle_key("b");
+        assert!(!app.handle_quit());
+    }
+
+    #[test]
+    fn test_banner() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let app = Istari::new(menu, state)
+            .unwrap()
+            .with_title_banner("Test App", "1.2.3");
+
+        let messages = app.output_messages();
+        assert_eq!(messages.len(), 4);
+        assert!(messages[1].contains("Test App v1.2.3"));
+        // Every line of the frame has the same width
+        assert!(
+            messages
+                .iter()
+                .all(|line| line.width() == messages[0].width())
+        );
+    }
+
+    #[test]
+    fn test_splash() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let app = Istari::new(menu, state).unwrap();
+        assert_eq!(app.splash(), None);
+
+        let mut app = app
+            .with_splash("  ___\n |_ _|")
+            .with_splash_timeout(Duration::from_secs(2));
+        assert_eq!(app.splash(), Some("  ___\n |_ _|"));
+        assert_eq!(app.splash_timeout(), Some(Duration::from_secs(2)));
+        // The splash doesn't end up in the output like a banner
+        assert!(app.output_messages().is_empty());
+
+        app.dismiss_splash();
+        assert_eq!(app.splash(), None);
+    }
+
+    #[test]
+    fn test_keys_command() {
+        let state = TestState { counter: 0 };
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, state).unwrap().with_ui_mode(UIMode::Text);
+
+        app.handle_key("keys");
+        assert_eq!(app.output_messages()[0], "Command mode:");
+        assert_eq!(app.output_messages().len(), app.key_bindings().len() + 1);
+    }
+
+    #[test]
+    fn test_keymap_file() {
+        let path = std::env::temp_dir().join(format!("istari-test-{}.keys", std::process::id()));
+        std::fs::write(&path, "command.edit-params = Ctrl+P\n").unwrap();
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_ui_mode(UIMode::Text)
+            .with_keymap_file(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The keys command lists the remapped key
+        app.handle_key("keys");
+        assert!(
+            app.output_messages()
+                .iter()
+                .any(|line| line.starts_with("  Ctrl+P "))
+        );
+        assert!(
+            !app.output_messages()
+                .iter()
+                .any(|line| line.starts_with("  Ctrl+E "))
+        );
+
+        std::fs::write(&path, "command.edit-params = Nope\n").unwrap();
+        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        let result = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_keymap_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(IstariError::InvalidKeyMap(_))));
+    }
+
+    #[test]
+    fn test_tagged_items() {
+        let state = TestState { counter: 0 };
+        let mut disk: Menu<TestState> = Menu::new("Disk".to_string());
+        disk.add_item(
+            MenuItem::new_action(
+                "w",
+                "Wipe".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_categories(["disk", "Danger"]),
+        );
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "r",
+                "Reboot".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_categories(["danger"]),
+        );
+        menu.add_submenu("d", "Disk", disk);
+
+        let mut app = Istari::new(menu, state)
+            .unwrap()
+            .with_tag_style("danger", OutputLevel::Warn)
+            .with_tag_style("disk", OutputLevel::Debug);
+
+        app.handle_key_with_params("tagged", Some("danger".to_string()));
+        assert_eq!(*app.output_messages(), ["r    Reboot", "d/w  Wipe"]);
+
+        let root = app.current_menu();
+        let root = root.lock().unwrap();
+        assert_eq!(app.item_style(&root.items[0]), OutputLevel::Warn);
+        assert_eq!(app.item_style(&root.items[1]), OutputLevel::Info);
+    }
+
+    #[test]
+    fn test_navigation_history_commands() {
+        let state = TestState { counter: 0 };
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_submenu("s", "Submenu", submenu);
+        let mut app = Istari::new(menu, state).unwrap();
+
+        assert!(app.go_to_menu(&["s".to_string()]));
+        app.handle_key("<");
+        assert!(app.is_at_root());
+        app.handle_key(">");
+        assert!(!app.is_at_root());
+        app.handle_key(">");
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "No later menu in history"
+        );
+    }
+
+    #[test]
+    fn test_confirmation() {
+        let state = TestState { counter: 5 };
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "r",
+                "Reset".to_string(),
+                |state: &mut TestState, _: Option<&str>| {
+                    state.counter = 0;
+                    Some("Reset".to_string())
+                },
+            )
+            .requires_confirmation(true),
+        );
+        let mut app = Istari::new(menu, state).unwrap();
+
+        app.handle_key("r");
+        assert_eq!(app.state.counter, 5);
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "Are you sure you want to run 'Reset'? (y/n)"
+        );
+
+        // Anything but yes cancels
+        app.handle_key("r");
+        assert!(app.pending_confirmation().is_none());
+        assert_eq!(*app.output_messages().last().unwrap(), "Cancelled 'Reset'");
+        assert_eq!(app.state.counter, 5);
+
+        app.handle_key("r");
+        app.handle_key("Y");
+        assert_eq!(app.state.counter, 0);
+        assert_eq!(*app.output_messages().last().unwrap(), "Reset");
+    }
+
+    #[test]
+    fn test_structured_params() {
+        let mut menu = Menu::new("Root");
+        menu.add_action("d", "Deploy", |_: &mut TestState, params: &Params| {
+            Some(format!(
+                "{} {} {:?}",
+                params.get(0)?,
+                params.flag("env").unwrap_or("staging"),
+                params.flag_values("tag")
+            ))
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key_with_params(
+            "d".to_string(),
+            Some(r#""my app" --env prod --tag a --tag b"#.to_string()),
+        );
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            r#"my app prod ["a", "b"]"#
+        );
+        assert_eq!(
+            app.recent_commands().get(1).unwrap().label(),
+            r#"d "my app" --env prod --tag a --tag b"#
+        );
+    }
+
+    #[test]
+    fn test_missing_param_prompt() {
+        let mut menu = Menu::new("Root");
+        menu.add_item(
+            MenuItem::new_action(
+                "d",
+                "Deploy".to_string(),
+                |_: &mut TestState, params: &Params| {
+                    Some(format!("{} to {}", params.get(0)?, params.get(1)?))
+                },
+            )
+            .with_params([
+                ParamSpec::required("app", ParamType::String),
+                ParamSpec::required("env", ParamType::String),
+            ]),
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        // Each missing parameter is prompted for in turn, keeping the answer's case
+        app.handle_key("d");
+        assert_eq!(app.pending_param().unwrap().name, "app");
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "Enter <app:text> for 'Deploy' (empty input cancels)"
+        );
+        for c in "My App".chars() {
+            app.add_to_input_buffer(c);
+        }
+        app.process_input_buffer();
+        assert_eq!(app.pending_param().unwrap().name, "env");
+        app.handle_key_with_params("prod", None);
+        assert!(app.pending_param().is_none());
+        assert_eq!(*app.output_messages().last().unwrap(), "My App to prod");
+
+        // Empty input cancels
+        app.handle_key("d");
+        app.process_input_buffer();
+        assert!(app.pending_param().is_none());
+        assert_eq!(*app.output_messages().last().unwrap(), "Cancelled 'Deploy'");
+
+        // Without prompts the usage is shown instead
+        let mut app = app.with_missing_param_prompts(false);
+        app.handle_key_with_params("d", Some("web".to_string()));
+        assert!(app.pending_param().is_none());
+        assert!(
+            app.output_messages()
+                .last()
+                .unwrap()
+                .starts_with("Missing parameter 'env'")
+        );
+    }
+
+    #[test]
+    fn test_export_history() {
+        let mut menu = Menu::new("Root");
+        menu.add_action("a", "Add", |state: &mut TestState, _: Option<&str>| {
+            state.counter += 1;
+            Some(format!("Counter: {}", state.counter))
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        let path = std::env::temp_dir().join(format!("istari-test-{}.json", std::process::id()));
+
+        for input in ["a", "a"] {
+            for c in input.chars() {
+                app.add_to_input_buffer(c);
+            }
+            app.process_input_buffer();
+        }
+        let command = format!("export-history {} --json --results", path.display());
+        for c in command.chars() {
+            app.add_to_input_buffer(c);
+        }
+        app.process_input_buffer();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        // The export itself isn't part of the exported session yet
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[1]["output"][0], "Counter: 2");
+        assert_eq!(app.session_log().entries().len(), 3);
+    }
+
+    #[test]
+    fn test_session_log_output() {
+        let mut menu = Menu::new("Root");
+        menu.add_item(
+            MenuItem::new_action(
+                "s",
+                "Status".to_string(),
+                |state: &mut TestState, _: Option<&str>| {
+                    state.counter += 1;
+                    Some(format!("Status: {}", state.counter))
+                },
+            )
+            .with_output_policy(OutputPolicy::Replace),
+        );
+        menu.add_item(
+            MenuItem::new_action(
+                "build",
+                "Build".to_string(),
+                |_: &mut TestState, _: Option<&str>| Some("Built".to_string()),
+            )
+            .with_output_tab("build"),
+        );
+        menu.add_action("p", "Ping", |_: &mut TestState, _: Option<&str>| {
+            Some("pong".to_string())
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_collapsed_duplicates(true);
+
+        for input in ["s", "s", "build", "p", "p"] {
+            for c in input.chars() {
+                app.add_to_input_buffer(c);
+            }
+            app.process_input_buffer();
+        }
+        let outputs: Vec<&[String]> = app
+            .session_log()
+            .entries()
+            .iter()
+            .map(|entry| entry.output.as_slice())
+            .collect();
+        // Replaced, collapsed and other tabs' messages are all logged
+        assert_eq!(
+            outputs,
+            [
+                &["Status: 1".to_string()][..],
+                &["Status: 2".to_string()],
+                &["Built".to_string()],
+                &["pong".to_string()],
+                &["pong".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fallible_actions() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "p",
+            "Parse",
+            |state: &mut TestState, params: Option<&str>| -> anyhow::Result<Option<String>> {
+                let value: i32 = params.unwrap_or("").parse()?;
+                state.counter = value;
+                Ok(Some(format!("Counter: {}", value)))
+            },
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key_with_params("p", Some("3".to_string()));
+        assert_eq!(*app.output_messages().last().unwrap(), "Counter: 3");
+        assert_eq!(app.error_count(), 0);
+
+        app.handle_key_with_params("p", Some("three".to_string()));
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "Error: invalid digit found in string"
+        );
+        assert_eq!(*app.output_levels().last().unwrap(), OutputLevel::Error);
+        assert_eq!(app.error_count(), 1);
+        assert_eq!(app.state().counter, 3);
+    }
+
+    #[test]
+    fn test_menu_refresh() {
+        let mut dashboard = Menu::new("Dashboard");
+        dashboard.add_item(
+            MenuItem::new_action(
+                "r",
+                "Refresh".to_string(),
+                |state: &mut TestState, _: Option<&str>| {
+                    state.counter += 1;
+                    Some(format!("Refreshed {} times", state.counter))
+                },
+            )
+            .with_output_policy(OutputPolicy::Replace),
+        );
+        dashboard.set_refresh("r", Some(Duration::from_millis(20)));
+        let mut menu = Menu::new("Root");
+        menu.add_submenu("d", "Dashboard", dashboard);
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        // Nothing runs outside the dashboard
+        app.tick();
+        assert_eq!(app.state().counter, 0);
+
+        // Entering the menu refreshes it right away, then only once the interval elapsed
+        app.handle_key("d");
+        app.tick();
+        app.tick();
+        assert_eq!(app.state().counter, 1);
+        std::thread::sleep(Duration::from_millis(25));
+        app.tick();
+        assert_eq!(app.state().counter, 2);
+        assert_eq!(*app.output_messages(), ["Refreshed 2 times"]);
+        assert!(app.recent_commands().get(1).is_none());
+
+        // Leaving and re-entering refreshes again
+        app.handle_key("b");
+        app.tick();
+        app.handle_key("d");
+        app.tick();
+        assert_eq!(app.state().counter, 3);
+    }
+
+    #[test]
+    fn test_structured_output() {
+        let mut menu = Menu::new("Root");
+        menu.add_action("h", "Hosts", |_: &mut TestState, _: Option<&str>| {
+            ActionOutput::table(["Host", "Status"], [["web-1", "up"]])
+        });
+        menu.add_action("n", "Notes", |_: &mut TestState, _: Option<&str>| {
+            ActionOutput::Markdown("# Notes".to_string())
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key("h");
+        app.handle_key("n");
+        assert_eq!(
+            app.output_messages()[0],
+            "Host   Status\n-----  ------\nweb-1  up"
+        );
+        assert!(matches!(
+            app.output_records()[0].format,
+            OutputFormat::Table(_)
+        ));
+        assert_eq!(app.output_records()[1].format, OutputFormat::Markdown);
+    }
+
+    #[test]
+    fn test_async_actions_run_in_background() {
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+        let mut menu = Menu::new("Root");
+        menu.add_action("f", "Fetch", move |_: &mut TestState, _: Option<&str>| {
+            let wait = wait.clone();
+            async move {
+                // Finish only once the test lets it
+                tokio::task::spawn_blocking(move || wait.lock().unwrap().recv().unwrap())
+                    .await
+                    .unwrap();
+                Some("Fetched".to_string())
+            }
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        // The action doesn't block handling input
+        app.handle_key("f");
+        assert_eq!(app.running_actions(), 1);
+        app.tick();
+        assert!(app.output_messages().is_empty());
+
+        release.send(()).unwrap();
+        app.wait_for_actions();
+        assert_eq!(app.running_actions(), 0);
+        assert_eq!(app.completed_actions(), 1);
+        assert_eq!(*app.output_messages(), ["Fetched"]);
+    }
+
+    #[test]
+    fn test_action_progress() {
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+        let (reported, progress_reported) = std::sync::mpsc::channel::<()>();
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "u",
+            "Upload",
+            move |_: &mut TestState, _: &Params, progress: Progress| {
+                let wait = wait.clone();
+                let reported = reported.clone();
+                async move {
+                    progress.set(150, "Uploading");
+                    reported.send(()).unwrap();
+                    tokio::task::spawn_blocking(move || wait.lock().unwrap().recv().unwrap())
+                        .await
+                        .unwrap();
+                    Some("Uploaded".to_string())
+                }
+            },
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key("u");
+        progress_reported.recv().unwrap();
+        app.tick();
+        let progress = app.progress();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].command, "u");
+        assert_eq!(progress[0].percent, Some(100));
+        assert_eq!(progress[0].message, "Uploading");
+        assert!(app.output_messages().is_empty());
+
+        // Progress goes away once the action finished
+        release.send(()).unwrap();
+        app.wait_for_actions();
+        assert!(app.progress().is_empty());
+        assert_eq!(*app.output_messages(), ["Uploaded"]);
+    }
+
+    #[test]
+    fn test_activity() {
+        let (release, wait) = mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+        let mut menu = Menu::new("Root");
+        menu.add_action("w", "Wait", move |_: &mut TestState, _: Option<&str>| {
+            let wait = wait.clone();
+            async move {
+                tokio::task::spawn_blocking(move || wait.lock().unwrap().recv().unwrap())
+                    .await
+                    .unwrap();
+                Some("Done".to_string())
+            }
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        // Actions show up as soon as they run, even without reporting progress
+        app.handle_key("w");
+        let activity = app.activity();
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].line(activity[0].started), "⠋ #1 w");
+        assert!(app.progress().is_empty());
+
+        release.send(()).unwrap();
+        app.wait_for_actions();
+        assert!(app.activity().is_empty());
     }
 
-    /// Get the current command input buffer
-    pub fn input_buffer(&self) -> &str {
-        &self.input_buffer
+    #[test]
+    fn test_cancel_async_action() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "w",
+            "Watch",
+            |_: &mut TestState, _: &Params, _: Progress, cancel: CancelToken| async move {
+                cancel.cancelled().await;
+                Some("Stopped watching".to_string())
+            },
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key("cancel");
+        assert_eq!(*app.output_messages(), ["No running actions to cancel"]);
+        app.clear_output_messages();
+
+        app.handle_key("w");
+        app.handle_key("w");
+        assert_eq!(app.running_actions(), 2);
+        app.handle_key_with_params("cancel", Some("9".to_string()));
+        app.handle_key_with_params("cancel", Some("#1".to_string()));
+        app.handle_key("cancel");
+        app.wait_for_actions();
+        assert_eq!(
+            *app.output_messages(),
+            [
+                "No running job 9",
+                "Cancelling job 1 (w)",
+                "Cancelling job 2 (w)",
+                "Stopped watching",
+                "Stopped watching"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jobs_list() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "w",
+            "Watch",
+            |_: &mut TestState, _: &Params, progress: Progress, cancel: CancelToken| async move {
+                progress.message("Watching");
+                cancel.cancelled().await;
+                Some("Stopped watching".to_string())
+            },
+        );
+        menu.add_action("f", "Fail", |_: &mut TestState, _: Option<&str>| async {
+            Err::<Option<String>, _>("no connection")
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key("jobs");
+        assert_eq!(*app.output_messages(), ["No background jobs"]);
+        app.clear_output_messages();
+
+        app.handle_key("f");
+        app.handle_key("w");
+        app.handle_key("kill");
+        app.handle_key_with_params("kill", Some("2".to_string()));
+        app.wait_for_actions();
+        app.clear_output_messages();
+
+        let jobs = app.jobs();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!((jobs[0].id, jobs[0].status), (1, JobStatus::Failed));
+        assert_eq!((jobs[1].id, jobs[1].status), (2, JobStatus::Cancelled));
+
+        app.handle_key_with_params("jobs", Some("2".to_string()));
+        let details = &app.output_messages()[0];
+        assert!(details.starts_with("Job:     2\nCommand: w\nStatus:  cancelled\n"));
+        assert!(details.ends_with("Result:  Stopped watching"));
+
+        app.handle_key("jobs");
+        assert!(matches!(
+            app.output_records()[1].format,
+            OutputFormat::Table(_)
+        ));
+        assert!(app.output_messages()[1].contains("1   f        "));
+
+        assert!(!app.jobs_panel_visible());
+        app.toggle_jobs_panel();
+        assert!(app.jobs_panel_visible());
     }
 
-    /// Add a character to the input buffer
-    pub fn add_to_input_buffer(&mut self, c: char) {
-        self.input_buffer.push(c);
+    #[test]
+    fn test_streaming_output() {
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+        let (written, lines_written) = std::sync::mpsc::channel::<()>();
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "t",
+            "Tail",
+            move |_: &mut TestState, _: &Params, output: OutputSink| {
+                let wait = wait.clone();
+                let written = written.clone();
+                async move {
+                    output.write_line("line 1");
+                    output.write_level(OutputLevel::Warn, "line 2");
+                    written.send(()).unwrap();
+                    tokio::task::spawn_blocking(move || wait.lock().unwrap().recv().unwrap())
+                        .await
+                        .unwrap();
+                    Some("Done tailing".to_string())
+                }
+            },
+        );
+        menu.add_action(
+            "s",
+            "Sync",
+            |_: &mut TestState, _: &Params, output: OutputSink| {
+                output.write_line("working");
+                Some("Done".to_string())
+            },
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        // Lines show up while the action is still running
+        app.handle_key("t");
+        lines_written.recv().unwrap();
+        app.tick();
+        assert_eq!(*app.output_messages(), ["line 1", "line 2"]);
+        assert_eq!(app.output_levels()[1], OutputLevel::Warn);
+
+        release.send(()).unwrap();
+        app.wait_for_actions();
+        assert_eq!(app.output_messages()[2], "Done tailing");
+
+        // Output written by sync actions goes above their result
+        app.clear_output_messages();
+        app.handle_key("s");
+        assert_eq!(*app.output_messages(), ["working", "Done"]);
     }
 
-    /// Clear the input buffer
-    pub fn clear_input_buffer(&mut self) {
-        self.input_buffer.clear();
+    #[test]
+    fn test_action_navigation() {
+        let mut network = Menu::new("Network");
+        network.add_action("d", "Done", |_: &mut TestState, _: Option<&str>| {
+            ActionOutcome::new()
+                .with_output("Saved")
+                .with_navigation(Navigation::Back)
+        });
+        let mut settings = Menu::new("Settings");
+        settings.add_submenu("n", "Network", network);
+        let mut menu = Menu::new("Root");
+        menu.add_submenu("s", "Settings", settings);
+        menu.add_action(
+            "g",
+            "Go to network",
+            |_: &mut TestState, _: Option<&str>| Navigation::Navigate("s/n".to_string()),
+        );
+        menu.add_action("x", "Nowhere", |_: &mut TestState, _: Option<&str>| {
+            Navigation::Navigate("s/missing".to_string())
+        });
+        menu.add_action("e", "Exit", |_: &mut TestState, _: Option<&str>| async {
+            Navigation::Quit
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        assert!(app.handle_key("g"));
+        assert_eq!(app.menu_manager.current_path(), ["s", "n"]);
+        app.handle_key("d");
+        assert_eq!(*app.output_messages(), ["Saved"]);
+        assert_eq!(app.menu_manager.current_path(), ["s"]);
+
+        app.handle_key("b");
+        app.handle_key("x");
+        assert_eq!(app.output_messages()[1], "No menu at 's/missing'");
+
+        // Async actions quit once they finished
+        assert!(app.handle_key("e"));
+        app.wait_for_actions();
+        assert!(app.quit_requested());
     }
 
-    /// Remove the last character from the input buffer
-    pub fn backspace_input_buffer(&mut self) {
-        self.input_buffer.pop();
+    #[test]
+    fn test_programmatic_quit() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "s",
+            "Shutdown",
+            |_: &mut TestState, _: &Params, job: Job| {
+                job.request_quit();
+                Some("Shutting down".to_string())
+            },
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        assert!(!app.quit_requested());
+        assert!(!app.handle_key("s"));
+        assert!(app.quit_requested());
+        assert_eq!(*app.output_messages(), ["Shutting down"]);
+
+        // Tick handlers can quit through their output handle
+        let mut app = Istari::new(Menu::new("Root"), TestState { counter: 0 })
+            .unwrap()
+            .with_tick_handler(|_: &mut TestState, output: &OutputHandle, _: f32| {
+                output.request_quit()
+            });
+        app.tick();
+        assert!(app.quit_requested());
     }
 
-    /// Toggle showing the input box
-    pub fn toggle_show_input(&mut self) {
-        self.show_input = !self.show_input;
+    #[test]
+    fn test_action_context() {
+        let mut menu = Menu::new("Root");
+        menu.add_action("i", "Increment", |ctx: &mut ActionContext<TestState>| {
+            let by: i32 = ctx.params().get(0).unwrap_or("1").parse().unwrap_or(1);
+            ctx.state_mut().counter += by;
+            ctx.output()
+                .write_line(format!("In menu {}", ctx.menu().title));
+            Some(format!("Counter: {}", ctx.state().counter))
+        });
+        menu.add_action("s", "Spawn", |ctx: &mut ActionContext<TestState>| {
+            let task = ctx.runtime().spawn(async { 40 + 2 });
+            async move { Some(format!("Answer: {}", task.await.unwrap())) }
+        });
+        menu.add_action("q!", "Quit", |ctx: &mut ActionContext<TestState>| {
+            ctx.request_quit();
+            None
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key_with_params("i", Some("2".to_string()));
+        assert_eq!(app.state().counter, 2);
+        assert_eq!(*app.output_messages(), ["In menu Root", "Counter: 2"]);
+
+        app.handle_key("s");
+        app.wait_for_actions();
+        assert_eq!(app.output_messages()[2], "Answer: 42");
+
+        assert!(!app.handle_key("q!"));
     }
 
-    /// Check if input should be shown
-    pub fn show_input(&self) -> bool {
-        self.show_input
+    #[test]
+    fn test_undo_redo() {
+        let mut menu = Menu::new("Root");
+        menu.add_item(
+            MenuItem::new_action(
+                "inc",
+                "Increment".to_string(),
+                |state: &mut TestState, params: &Params| {
+                    state.counter += params.get(0).and_then(|n| n.parse().ok()).unwrap_or(1);
+                    Some(format!("Counter: {}", state.counter))
+                },
+            )
+            .with_undo(|state: &mut TestState, params: &Params| {
+                state.counter -= params
+                    .get(0)
+                    .and_then(|n| n.parse::<i32>().ok())
+                    .unwrap_or(1);
+            }),
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_undo_limit(2);
+
+        app.handle_key("undo");
+        assert_eq!(*app.output_messages(), ["Nothing to undo"]);
+
+        app.handle_key_with_params("inc", Some("5".to_string()));
+        app.handle_key("inc");
+        app.handle_key("inc");
+        assert_eq!(app.state().counter, 7);
+
+        // Only the last two actions can be undone
+        app.handle_key("undo");
+        app.handle_key("undo");
+        app.handle_key("undo");
+        assert_eq!(app.state().counter, 5);
+        assert!(app.output_messages().into_iter().rev().take(3).eq([
+            "Nothing to undo",
+            "Undid 'inc'",
+            "Undid 'inc'"
+        ]));
+
+        app.handle_key("redo");
+        assert_eq!(app.state().counter, 6);
+        assert!(app.undo_stack().can_undo());
+        assert!(app.undo_stack().can_redo());
+
+        // Running an action drops what could be redone
+        app.handle_key_with_params("inc", Some("10".to_string()));
+        assert!(!app.undo_stack().can_redo());
+        app.handle_key("undo");
+        assert_eq!(app.state().counter, 6);
+        assert_eq!(*app.output_messages().last().unwrap(), "Undid 'inc 10'");
     }
 
-    /// Process the current input buffer as a command
-    pub fn process_input_buffer(&mut self) -> bool {
-        if self.input_buffer.is_empty() {
-            return true;
-        }
+    #[test]
+    fn test_action_queue() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "inc",
+            "Increment",
+            |state: &mut TestState, params: &Params| {
+                state.counter += params.get(0).and_then(|n| n.parse().ok()).unwrap_or(1);
+                Some(format!("Counter: {}", state.counter))
+            },
+        );
+        menu.add_action("s", "Sleep", |_: &mut TestState, _: Option<&str>| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Some("Woke up".to_string())
+        });
+        menu.add_action("f", "Fail", |_: &mut TestState, _: Option<&str>| async {
+            Err::<Option<String>, _>("no connection")
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
 
-        // Create a binding that lives for the entire function
-        let input_clone = self.input_buffer.clone();
-        let input = input_clone.trim();
+        app.handle_key_with_params("queue", Some("inc; nope; sub/inc".to_string()));
+        assert_eq!(*app.output_messages(), ["Unknown command: nope"]);
+        assert!(app.queued_commands().next().is_none());
+        app.clear_output_messages();
 
-        // Add command to history
-        if !input.is_empty() {
-            self.command_history.add(input.to_string());
-        }
+        // The command after an async one waits for it to finish
+        app.handle_key_with_params("queue", Some("s; inc 5; s".to_string()));
+        assert_eq!(app.queue_running().unwrap().key, "s");
+        assert_eq!(app.queued_commands().count(), 2);
+        assert_eq!(app.state().counter, 0);
+        app.wait_for_actions();
+        assert_eq!(app.state().counter, 5);
+        assert_eq!(
+            *app.output_messages(),
+            ["Queued 3 commands", "Woke up", "Counter: 5", "Woke up"]
+        );
+        assert!(app.queue_running().is_none());
+        app.clear_output_messages();
 
-        // Split input into command and parameters
-        let parts: Vec<&str> = input.splitn(2, ' ').collect();
-        let command = parts[0].to_lowercase();
-        let params = parts.get(1).map(|&s| s.to_string());
+        // A failing command drops the rest of the queue
+        app.handle_key_with_params("queue", Some("f; inc".to_string()));
+        app.wait_for_actions();
+        assert_eq!(app.state().counter, 5);
+        assert_eq!(
+            *app.output_messages(),
+            [
+                "Queued 2 commands",
+                "Error: no connection",
+                "Queue stopped after 'f' failed, skipped 1 commands"
+            ]
+        );
+    }
 
-        // Delegate to handle_key_with_params
-        let result = self.handle_key_with_params(command, params);
+    #[test]
+    fn test_action_stats() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "inc",
+            "Increment",
+            |state: &mut TestState, _: Option<&str>| {
+                state.counter += 1;
+                None
+            },
+        );
+        menu.add_action("s", "Sleep", |_: &mut TestState, _: Option<&str>| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            None
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
 
-        self.clear_input_buffer();
-        result
+        app.handle_key("stats");
+        assert_eq!(*app.output_messages(), ["No actions ran yet"]);
+
+        app.handle_key("inc");
+        app.handle_key("inc");
+        app.handle_key("s");
+        assert!(app.action_stats().get("s").is_none());
+        app.wait_for_actions();
+
+        assert_eq!(app.action_stats().get("inc").unwrap().count, 2);
+        let sleep = app.action_stats().get("s").unwrap();
+        assert_eq!(sleep.count, 1);
+        assert!(sleep.average_duration() >= Duration::from_millis(20));
+        let most_run: Vec<_> = app
+            .action_stats()
+            .most_run()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(most_run, ["inc", "s"]);
+
+        app.handle_key_with_params("stats", Some("nope".to_string()));
+        assert_eq!(
+            *app.output_messages().last().unwrap(),
+            "No stats for 'nope'"
+        );
+        app.handle_key_with_params("stats", Some("--reset".to_string()));
+        assert!(app.action_stats().is_empty());
     }
 
-    /// Navigate up in command history
-    pub fn history_up(&mut self) {
-        if let Some(cmd) = self.command_history.up() {
-            self.input_buffer = cmd.clone();
+    #[test]
+    fn test_param_completion() {
+        let mut menu = Menu::new("Root");
+        menu.add_item(
+            MenuItem::new_action(
+                "palette",
+                "Set Theme".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_completion(|_: &TestState, _: &str| {
+                vec![
+                    "dark".to_string(),
+                    "solarized-dark".to_string(),
+                    "solarized-light".to_string(),
+                ]
+            }),
+        );
+        menu.add_action("inc", "Increment", |_: &mut TestState, _: Option<&str>| {
+            None
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        for c in "inc 1".chars() {
+            app.add_to_input_buffer(c);
         }
-    }
+        assert!(!app.complete_input());
+        app.clear_input_buffer();
 
-    /// Navigate down in command history
-    pub fn history_down(&mut self) {
-        if let Some(cmd) = self.command_history.down() {
-            self.input_buffer = cmd.clone();
-        } else {
-            // At the end of history or exited browsing mode
-            self.input_buffer.clear();
+        // Several matches are extended to what they have in common
+        for c in "palette s".chars() {
+            app.add_to_input_buffer(c);
         }
-    }
+        assert!(app.complete_input());
+        assert_eq!(app.input_buffer(), "palette solarized-");
+        assert_eq!(app.completions(), ["solarized-dark", "solarized-light"]);
 
-    /// Exit history browsing mode
-    pub fn exit_history_browsing(&mut self) {
-        self.command_history.exit_browsing();
+        app.add_to_input_buffer('l');
+        assert!(app.completions().is_empty());
+        assert!(app.complete_input());
+        assert_eq!(app.input_buffer(), "palette solarized-light ");
+        assert!(app.completions().is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::menu::Menu;
+    #[test]
+    fn test_fn_mut_action() {
+        let mut menu = Menu::new("Root");
+        let mut calls = 0;
+        menu.add_action_mut("c", "Count", move |_: &mut TestState, _: Option<&str>| {
+            calls += 1;
+            Some(format!("Called {} times", calls))
+        });
+        let mut runs = Vec::new();
+        menu.add_action_mut("l", "Log", move |_: &mut TestState, params: &Params| {
+            runs.push(params.raw().unwrap_or("-").to_string());
+            Some(runs.join(","))
+        });
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
 
-    #[derive(Debug)]
-    pub struct TestState {
-        pub counter: i32,
+        app.handle_key("c");
+        app.handle_key("c");
+        app.handle_key_with_params("l", Some("a".to_string()));
+        app.handle_key("l");
+        assert_eq!(
+            *app.output_messages(),
+            ["Called 1 times", "Called 2 times", "a", "a,-"]
+        );
     }
+
+    #[cfg(feature = "single-threaded")]
     #[test]
-    fn test_istari_creation() {
-        let state = TestState { counter: 0 };
-        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+    fn test_single_threaded_closures() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
 
-        let result = Istari::new(menu, state);
-        assert!(result.is_ok());
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut menu = Menu::new("Root");
+        let action_log = log.clone();
+        menu.add_action(
+            "a",
+            "Log",
+            move |_: &mut TestState, params: Option<&str>| {
+                action_log
+                    .borrow_mut()
+                    .push(params.unwrap_or("-").to_string());
+                None
+            },
+        );
+        let tick_log = log.clone();
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_tick_handler(move |_: &mut TestState, _: &OutputHandle, _: f32| {
+                tick_log.borrow_mut().push("tick".to_string());
+            });
 
-        let app = result.unwrap();
-        assert_eq!(app.mode(), Mode::Command);
-        assert!(app.output_messages().is_empty());
-        assert!(!app.show_input());
+        app.handle_key_with_params("a", Some("x".to_string()));
+        app.tick();
+        assert_eq!(*log.borrow(), ["x", "tick"]);
     }
 
+    #[cfg(feature = "clap")]
     #[test]
-    fn test_mode_toggling() {
-        let state = TestState { counter: 0 };
-        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
-        let mut app = Istari::new(menu, state).unwrap();
+    fn test_clap_params() {
+        let mut menu = Menu::new("Root");
+        menu.add_item(
+            MenuItem::new_action(
+                "inc",
+                "Increment".to_string(),
+                |state: &mut TestState, params: &Params| {
+                    let amount = params.matches()?.get_one::<i32>("amount")?;
+                    state.counter += amount;
+                    Some(format!("Counter: {}", state.counter))
+                },
+            )
+            .with_clap(
+                clap::Command::new("inc").arg(
+                    clap::Arg::new("amount")
+                        .value_parser(clap::value_parser!(i32))
+                        .default_value("1"),
+                ),
+            ),
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
 
-        assert_eq!(app.mode(), Mode::Command);
-        app.toggle_mode();
-        assert_eq!(app.mode(), Mode::Scroll);
-        app.toggle_mode();
-        assert_eq!(app.mode(), Mode::Command);
+        app.handle_key_with_params("inc", Some("4".to_string()));
+        app.handle_key("inc");
+        app.handle_key_with_params("inc", Some("--help".to_string()));
+        app.handle_key_with_params("inc", Some("x".to_string()));
+        assert_eq!(app.state().counter, 5);
+        assert_eq!(app.output_messages()[..2], ["Counter: 4", "Counter: 5"]);
+        assert!(app.output_messages()[2].starts_with("Usage: inc [amount]"));
+        assert!(app.output_messages()[3].starts_with("error: invalid value 'x'"));
+    }
 
-        app.set_mode(Mode::Scroll);
-        assert_eq!(app.mode(), Mode::Scroll);
+    #[test]
+    fn test_theme_command() {
+        let mut menu = Menu::new("Root");
+        menu.add_action("a", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+
+        app.handle_key("theme");
+        app.handle_key_with_params("theme", Some("light".to_string()));
+        app.handle_key_with_params("theme", Some("neon".to_string()));
+        assert_eq!(*app.theme(), Theme::light());
+        assert_eq!(
+            *app.output_messages(),
+            [
+                "Theme: dark (available: dark, light, high-contrast, monochrome)",
+                "Switched to the light theme",
+                "Unknown theme 'neon', available: dark, light, high-contrast, monochrome"
+            ]
+        );
+
+        // The built-in theme names can be completed
+        for c in "theme mo".chars() {
+            app.add_to_input_buffer(c);
+        }
+        assert!(app.complete_input());
+        assert_eq!(app.input_buffer(), "theme monochrome ");
     }
 
     #[test]
-    fn test_input_buffer() {
-        let state = TestState { counter: 0 };
-        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
-        let mut app = Istari::new(menu, state).unwrap();
+    fn test_toggle_panes() {
+        let mut sub = Menu::new("Sub");
+        sub.add_action("a", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut menu = Menu::new("Root");
+        menu.add_submenu("sub", "Submenu", sub);
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
 
-        assert!(app.input_buffer().is_empty());
-        app.add_to_input_buffer('t');
-        app.add_to_input_buffer('e');
-        app.add_to_input_buffer('s');
-        app.add_to_input_buffer('t');
-        assert_eq!(app.input_buffer(), "test");
+        app.toggle_full_output();
+        assert_eq!(app.panes(), Panes::OutputOnly);
+        app.toggle_output_pane();
+        assert_eq!(app.panes(), Panes::MenuOnly);
+        app.toggle_output_pane();
+        assert_eq!(app.panes(), Panes::Both);
 
-        app.backspace_input_buffer();
-        assert_eq!(app.input_buffer(), "tes");
+        // The choice outlives navigating and switching modes
+        app.handle_key_with_params("pane", Some("output".to_string()));
+        app.handle_key("sub");
+        app.toggle_mode();
+        assert_eq!(app.panes(), Panes::OutputOnly);
 
-        app.clear_input_buffer();
-        assert!(app.input_buffer().is_empty());
+        app.handle_key_with_params("pane", Some("left".to_string()));
+        app.handle_key("pane");
+        assert_eq!(
+            *app.output_messages(),
+            [
+                "Panes: output",
+                "Unknown pane 'left', use one of: both, output, menu",
+                "Panes: output"
+            ]
+        );
     }
 
     #[test]
-    fn test_output_messages() {
-        let state = TestState { counter: 0 };
-        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
-        let mut app = Istari::new(menu, state).unwrap();
+    fn test_loglevel_command() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        assert_eq!(app.log_level(), OutputLevel::Debug);
 
-        assert!(app.output_messages().is_empty());
-        app.add_output("Test message".to_string());
-        assert_eq!(app.output_messages().len(), 1);
-        assert_eq!(app.output_messages()[0], "Test message");
+        app.handle_key_with_params("loglevel", Some("WARN".to_string()));
+        assert_eq!(app.log_level(), OutputLevel::Warn);
+        app.handle_key_with_params("loglevel", Some("loud".to_string()));
+        assert_eq!(app.log_level(), OutputLevel::Warn);
+        app.handle_key("loglevel");
+        // Hidden messages are kept
+        assert_eq!(
+            *app.output_messages(),
+            [
+                "Log level: warn",
+                "Unknown log level 'loud', use one of: debug, info, warn, error",
+                "Log level: warn (available: debug, info, warn, error)"
+            ]
+        );
+        assert_eq!(OutputLevel::from_name("error"), Some(OutputLevel::Error));
+    }
 
-        assert!(app.has_new_output());
-        assert!(!app.has_new_output());
+    #[test]
+    fn test_filter_command() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        assert!(app.is_line_shown("anything"));
+
+        app.handle_key_with_params("filter", Some("^ERR|Filter".to_string()));
+        assert!(app.is_line_shown("ERR disk full"));
+        assert!(!app.is_line_shown("all good"));
+        app.handle_key_with_params("filter", Some("(".to_string()));
+        assert_eq!(app.output_filter().map(Regex::as_str), Some("^ERR|Filter"));
+        app.handle_key("filter");
+        app.handle_key_with_params("filter", Some("off".to_string()));
+        assert!(app.output_filter().is_none());
+
+        // Hidden lines are kept
+        let messages = app.output_messages();
+        assert_eq!(messages.len(), 4);
+        assert!(messages[1].starts_with("Invalid filter pattern '('"));
+        assert_eq!(messages[3], "Filter off");
     }
 
     #[test]
-    fn test_tick_handler() {
-        let state = TestState { counter: 0 };
-        let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
-        let mut app = Istari::new(menu, state).unwrap().with_tick_handler(
-            |state: &mut TestState, messages: &mut Vec<String>, _delta: f32| {
+    fn test_status_line() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "inc",
+            "Increment",
+            |state: &mut TestState, _: Option<&str>| {
                 state.counter += 1;
-                messages.push(format!("Tick: {}", state.counter));
+                None
             },
         );
+        let app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        assert_eq!(app.status_line(), None);
 
-        // Simulate a tick
+        let mut app = app
+            .with_status_segment(|_: &TestState| "Connected".to_string())
+            .with_status_segment(|_: &TestState| String::new())
+            .with_status_segment(|state: &TestState| format!("Counter: {}", state.counter));
+        app.handle_key("inc");
+        assert_eq!(app.status_line().as_deref(), Some("Connected | Counter: 1"));
+    }
+
+    #[test]
+    fn test_watches() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        assert!(app.watches().is_empty());
+
+        let mut app = app
+            .with_watch("counter", |state: &TestState| state.counter.to_string())
+            .with_watch("doubled", |state: &TestState| {
+                (state.counter * 2).to_string()
+            });
+        app.state.counter = 21;
+        assert_eq!(
+            app.watches(),
+            [("counter", "21".to_string()), ("doubled", "42".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        let menu: Menu<TestState> = Menu::new("Root");
+        let mut app = Istari::new(menu, TestState { counter: 3 })
+            .unwrap()
+            .with_sparkline("counter", |state: &TestState| state.counter as f64)
+            .with_gauge("half", |state: &TestState| state.counter as f64 / 6.0);
+
+        // Sparklines are sampled every tick, gauges read when drawn
         app.tick();
-        assert_eq!(app.output_messages().len(), 1);
-        assert_eq!(app.output_messages()[0], "Tick: 1");
+        app.state.counter = 6;
+        app.tick();
+        assert_eq!(
+            app.metrics(),
+            [
+                MetricValue::Sparkline {
+                    label: "counter",
+                    samples: vec![3, 6]
+                },
+                MetricValue::Gauge {
+                    label: "half",
+                    ratio: 1.0
+                },
+            ]
+        );
     }
 
     #[test]
-    fn test_command_history() {
-        let mut history = CommandHistory::new(3);
+    fn test_dialogs() {
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "add",
+            "Add",
+            |state: &mut TestState, param: Option<&str>| {
+                state.counter += param.and_then(|amount| amount.parse::<i32>().ok())?;
+                None
+            },
+        );
+        menu.add_action("ask", "Ask", |_: &mut TestState, _: Option<&str>| {
+            ActionOutcome::new().with_dialog(Dialog::prompt("Add", "Amount", "add"))
+        });
+        menu.add_item(
+            MenuItem::new_action(
+                "reset",
+                "Reset".to_string(),
+                |state: &mut TestState, _: Option<&str>| {
+                    state.counter = 0;
+                    None
+                },
+            )
+            .requires_confirmation(true),
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
 
-        // Add commands
-        history.add("cmd1".to_string());
-        history.add("cmd2".to_string());
-        history.add("cmd3".to_string());
+        // Actions open prompts, whose value runs the command
+        app.handle_key("ask");
+        for c in "5".chars() {
+            app.dialog_mut().unwrap().type_char(c);
+        }
+        app.submit_dialog();
+        assert_eq!(app.state().counter, 5);
+        assert!(app.dialog().is_none());
 
-        // Test navigation
-        assert_eq!(history.up().unwrap(), "cmd3");
-        assert_eq!(history.up().unwrap(), "cmd2");
-        assert_eq!(history.up().unwrap(), "cmd1");
-        assert_eq!(history.up().unwrap(), "cmd1"); // Can't go past beginning
+        // Questions run their command only if answered with yes
+        app.show_dialog(Dialog::confirm("Add", "Add 2?", "add 2"));
+        app.show_dialog(Dialog::message("Note", "Shown next"));
+        app.cancel_dialog();
+        assert_eq!(app.dialog().unwrap().title, "Note");
+        app.submit_dialog();
+        app.show_dialog(Dialog::confirm("Add", "Add 2?", "add 2"));
+        app.answer_dialog(true);
+        assert_eq!(app.state().counter, 7);
 
-        assert_eq!(history.down().unwrap(), "cmd2");
-        assert_eq!(history.down().unwrap(), "cmd3");
-        assert_eq!(history.down(), None); // Exit browsing
+        // Confirmations of actions are dialogs, answered either way
+        app.handle_key("reset");
+        assert_eq!(
+            app.dialog().unwrap().kind,
+            DialogKind::Confirm { yes: false }
+        );
+        app.cancel_dialog();
+        assert_eq!(app.state().counter, 7);
+        app.handle_key("reset");
+        app.handle_key("y");
+        assert!(app.dialog().is_none());
+        assert_eq!(app.state().counter, 0);
 
-        // Test max size
-        history.add("cmd4".to_string());
-        assert_eq!(history.entries.len(), 3);
-        assert_eq!(history.entries[0], "cmd2"); // cmd1 was removed
+        // Text mode answers with the next line
+        app.show_dialog(Dialog::prompt("Add", "Amount", "add"));
+        for c in "3".chars() {
+            app.add_to_input_buffer(c);
+        }
+        app.process_input_buffer();
+        assert_eq!(app.state().counter, 3);
     }
 
     #[test]
-    fn test_output_buffer() {
-        let mut buffer = OutputBuffer::new();
+    fn test_output_tabs() {
+        let mut menu = Menu::new("Root");
+        menu.add_item(
+            MenuItem::new_action(
+                "build",
+                "Build".to_string(),
+                |_: &mut TestState, _: Option<&str>| Some("Built".to_string()),
+            )
+            .with_output_tab("build"),
+        );
+        menu.add_action("hi", "Hi", |_: &mut TestState, _: Option<&str>| {
+            Some("Hello".to_string())
+        });
+        menu.add_item(
+            MenuItem::new_action(
+                "peek",
+                "Peek".to_string(),
+                |_: &mut TestState, _: Option<&str>| Some("Peeked".to_string()),
+            )
+            .with_output_tab("peek")
+            .with_output_policy(OutputPolicy::Popup),
+        );
+        let mut app = Istari::new(menu, TestState { counter: 0 }).unwrap();
+        app.clear_output_messages();
 
-        assert!(buffer.messages().is_empty());
-        buffer.add("Test".to_string());
-        assert_eq!(buffer.messages().len(), 1);
+        // Tabs open on their first output, which stays out of the main tab
+        app.handle_key("hi");
+        app.handle_key("build");
+        assert_eq!(*app.output_messages(), ["Hello"]);
+        assert_eq!(app.output_tabs(), [("Main", false), ("build", true)]);
 
-        assert!(buffer.has_new_output());
-        assert!(!buffer.has_new_output());
+        // Handles write to tabs too
+        app.output_handle().with_tab("build").push("Linked");
+        app.tick();
+        app.handle_key_with_params("tab", Some("build".to_string()));
+        assert_eq!(app.active_output_tab(), 1);
+        assert_eq!(*app.output_messages(), ["Built", "Linked"]);
+        // Nothing drew the main tab since "Hello"
+        assert_eq!(app.output_tabs(), [("Main", true), ("build", false)]);
 
-        buffer.clear();
-        assert!(buffer.messages().is_empty());
-        assert!(!buffer.has_new_output());
+        // Switching by number, and messages of Istari go to the tab shown
+        app.handle_key_with_params("tab", Some("1".to_string()));
+        assert_eq!(*app.output_messages(), ["Hello"]);
+        app.handle_key_with_params("tab", Some("3".to_string()));
+        assert_eq!(*app.output_messages(), ["Hello", "No output tab '3'"]);
+        assert!(!app.select_output_tab(2));
+
+        // Results of items without a tab, built-ins and grep use the tab shown too
+        app.select_output_tab(1);
+        app.handle_key("hi");
+        app.handle_key("stats");
+        assert_eq!(app.output_messages()[2], "Hello");
+        assert!(app.output_messages()[3].starts_with("Command"));
+        app.handle_key_with_params("grep", Some("linked".to_string()));
+        assert!(app.output_messages()[4].starts_with("grep 'linked': 1 match(es)"));
+
+        // A popup shows the result without opening the item's tab
+        app.handle_key("peek");
+        assert!(app.popup().is_some());
+        assert_eq!(app.output_tabs().len(), 2);
+    }
+
+    #[test]
+    fn test_title_and_help_lines() {
+        let app = Istari::new(Menu::new("Root"), TestState { counter: 0 }).unwrap();
+        assert_eq!(app.title(), "Istari");
+        assert_eq!(app.app_info(), None);
+        assert_eq!(app.help_line(Mode::Command), None);
+
+        let app = app
+            .with_title("Deploy")
+            .with_app_info("Deploy Tool", "1.2.0")
+            .with_help_line(Mode::Scroll, "j/k Scroll")
+            .with_help_line(Mode::Scroll, "j/k Scroll | Tab Back");
+        assert_eq!(app.title(), "Deploy");
+        assert_eq!(app.app_info().as_deref(), Some("Deploy Tool v1.2.0"));
+        assert_eq!(app.help_line(Mode::Scroll), Some("j/k Scroll | Tab Back"));
+        assert_eq!(app.help_line(Mode::Command), None);
+    }
+
+    #[test]
+    fn test_custom_renderer() {
+        /// Runs a scripted key and records the steps it went through
+        struct ScriptedRenderer(Arc<Mutex<Vec<String>>>);
+
+        impl UIController<TestState> for ScriptedRenderer {
+            fn init(&mut self) -> std::io::Result<()> {
+                self.0.lock().unwrap().push("init".to_string());
+                Ok(())
+            }
+
+            fn cleanup(&mut self) -> std::io::Result<()> {
+                self.0.lock().unwrap().push("cleanup".to_string());
+                Ok(())
+            }
+
+            fn render_frame(&mut self, app: &mut Istari<TestState>) -> std::io::Result<()> {
+                let frame = format!("counter {}", app.state().counter);
+                self.0.lock().unwrap().push(frame);
+                Ok(())
+            }
+
+            fn run_event_loop(&mut self, app: &mut Istari<TestState>) -> std::io::Result<()> {
+                app.handle_key("inc");
+                self.render_frame(app)
+            }
+        }
+
+        let mut menu = Menu::new("Root");
+        menu.add_action(
+            "inc",
+            "Increment",
+            |state: &mut TestState, _: Option<&str>| {
+                state.counter += 1;
+                None
+            },
+        );
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let mut app = Istari::new(menu, TestState { counter: 0 })
+            .unwrap()
+            .with_renderer(ScriptedRenderer(steps.clone()));
+        assert_eq!(app.ui_mode(), UIMode::Custom);
+
+        // The renderer runs again on the next run
+        app.run().unwrap();
+        app.run().unwrap();
+        assert_eq!(
+            *steps.lock().unwrap(),
+            [
+                "init",
+                "counter 1",
+                "cleanup",
+                "init",
+                "counter 2",
+                "cleanup"
+            ]
+        );
     }
 }
@@ -0,0 +1,211 @@
+use crate::cancel::CancelToken;
+use crate::output::{ActionOutput, OutputHandle, OutputSink};
+use crate::process::{self, ProcessExit};
+use crate::progress::Progress;
+use std::fmt;
+use std::io;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::runtime::Handle;
+
+/// Handles an action gets while it runs: its progress, cancel token and output sink
+#[derive(Debug, Clone)]
+pub struct Job {
+    id: u64,
+    progress: Progress,
+    cancel: CancelToken,
+    output: OutputSink,
+    runtime: Handle,
+}
+
+impl Job {
+    /// Bundle the handles of the job with the given id
+    pub(crate) fn new(
+        id: u64,
+        progress: Progress,
+        cancel: CancelToken,
+        output: OutputSink,
+        runtime: Handle,
+    ) -> Self {
+        Self {
+            id,
+            progress,
+            cancel,
+            output,
+            runtime,
+        }
+    }
+
+    /// Create a job whose progress and output aren't shown anywhere and that's never cancelled
+    pub(crate) fn detached(runtime: Handle) -> Self {
+        let (sender, _) = mpsc::channel();
+        Self::new(
+            0,
+            Progress::new(0, sender.clone()),
+            CancelToken::new(),
+            OutputSink::new(OutputHandle::new(sender, None)),
+            runtime,
+        )
+    }
+
+    /// Get the id of the job, as shown by `jobs`
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the handle to report the action's progress through
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Get the token telling whether the action was cancelled
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Get the sink to write output to while the action runs
+    pub fn output(&self) -> &OutputSink {
+        &self.output
+    }
+
+    /// Get the runtime async actions run on
+    pub fn runtime(&self) -> &Handle {
+        &self.runtime
+    }
+
+    /// Run an external command, streaming its output and killing it when the job is
+    /// cancelled, see [`run_process`](crate::process::run_process)
+    pub async fn run_process(&self, command: &mut Command) -> io::Result<ProcessExit> {
+        process::run_process(command, &self.output, &self.cancel).await
+    }
+
+    /// Quit the application, right after a sync action returns or on the next tick otherwise
+    pub fn request_quit(&self) {
+        self.output.handle().request_quit();
+    }
+}
+
+/// State of an async action running as a background job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// The action is still running
+    Running,
+    /// The action was asked to stop but hasn't returned yet
+    Cancelling,
+    /// The action returned
+    Finished,
+    /// The action returned an error
+    Failed,
+    /// The action returned after being cancelled
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Check whether the job is still running
+    pub fn is_running(self) -> bool {
+        matches!(self, Self::Running | Self::Cancelling)
+    }
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Running => "running",
+            Self::Cancelling => "cancelling",
+            Self::Finished => "finished",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A running or recently finished background job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobInfo {
+    /// Id of the job, e.g. for `kill <id>`
+    pub id: u64,
+    /// Path of the action's menu item, e.g. `deploy/start`
+    pub command: String,
+    /// What state the job is in
+    pub status: JobStatus,
+    /// When the job started
+    pub started: Instant,
+    /// When the job finished, if it did
+    pub finished: Option<Instant>,
+    /// The last progress message of a running job, or the output or error of a finished one
+    pub details: Option<String>,
+}
+
+impl JobInfo {
+    /// Get how long the job ran, or has been running so far
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        self.finished
+            .unwrap_or(now)
+            .saturating_duration_since(self.started)
+    }
+}
+
+/// Format a duration for the jobs list, e.g. `4.2s` or `3m 07s`
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    if seconds < 60 {
+        format!("{:.1}s", elapsed.as_secs_f32())
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Lay out jobs as a table of their id, command, elapsed time and status
+pub fn jobs_table(jobs: &[JobInfo], now: Instant) -> ActionOutput {
+    ActionOutput::table(
+        ["ID", "Command", "Elapsed", "Status"],
+        jobs.iter().map(|job| {
+            [
+                job.id.to_string(),
+                job.command.clone(),
+                format_elapsed(job.elapsed(now)),
+                job.status.to_string(),
+            ]
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs_table() {
+        let started = Instant::now();
+        let jobs = [
+            JobInfo {
+                id: 1,
+                command: "deploy/start".to_string(),
+                status: JobStatus::Finished,
+                started,
+                finished: Some(started + Duration::from_millis(4200)),
+                details: None,
+            },
+            JobInfo {
+                id: 2,
+                command: "logs".to_string(),
+                status: JobStatus::Running,
+                started,
+                finished: None,
+                details: None,
+            },
+        ];
+
+        let table = jobs_table(&jobs, started + Duration::from_secs(187));
+        assert_eq!(
+            table.to_plain_text(),
+            "ID  Command       Elapsed  Status\n\
+             --  ------------  -------  --------\n\
+             1   deploy/start  4.2s     finished\n\
+             2   logs          3m 07s   running"
+        );
+        assert!(jobs[1].status.is_running());
+    }
+}
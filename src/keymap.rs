@@ -0,0 +1,824 @@
+use crate::error::IstariError;
+use crate::istari::UIMode;
+use crate::types::Mode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A key binding of the interactive renderers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    /// Mode the binding is active in
+    pub mode: Mode,
+    /// Keys triggering the binding, e.g. `Ctrl+Q`
+    pub keys: String,
+    /// What the binding does
+    pub description: String,
+}
+
+impl KeyBinding {
+    /// Create a key binding
+    pub fn new(mode: Mode, keys: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            mode,
+            keys: keys.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// A key press a [`KeyMap`] binds, e.g. `Ctrl+Q` or `j`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// The key pressed
+    pub code: KeyCode,
+    /// Modifiers held with it, Shift is ignored for characters
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Create a key pressed without modifiers
+    pub const fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    /// Create a character key pressed with Ctrl
+    pub const fn ctrl(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::CONTROL,
+        }
+    }
+
+    /// Create a key pressed with Alt
+    pub const fn alt(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::ALT,
+        }
+    }
+
+    /// Check if a key event is this key
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        // The case of a character already tells whether Shift was held
+        let ignored = match event.code {
+            KeyCode::Char(_) => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+        event.code == self.code && event.modifiers - ignored == self.modifiers - ignored
+    }
+}
+
+impl From<char> for Key {
+    fn from(c: char) -> Self {
+        Self::new(KeyCode::Char(c))
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(code: KeyCode) -> Self {
+        Self::new(code)
+    }
+}
+
+/// Names of the keys that aren't characters, as they're written in key maps
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl FromStr for Key {
+    type Err = IstariError;
+
+    /// Parse a key written like in the key bindings, e.g. `Ctrl+Q`, `Alt+Left`, `F2` or `G`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |reason: &str| IstariError::InvalidKeyMap(format!("key '{}' {}", text, reason));
+        let text = text.trim();
+        // The last part is the key, a `+` on its own included
+        let (modifier_names, name) = match text.rsplit_once('+') {
+            Some((modifiers, "")) if modifiers.ends_with('+') => {
+                (&modifiers[..modifiers.len() - 1], "+")
+            }
+            Some(("", _)) | None => ("", text),
+            Some((modifiers, name)) => (modifiers, name),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in modifier_names.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(invalid("has an unknown modifier")),
+            };
+        }
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let function_key = name
+                    .strip_prefix(['F', 'f'])
+                    .and_then(|number| number.parse().ok())
+                    .filter(|number| (1..=12).contains(number));
+                match function_key {
+                    Some(number) => KeyCode::F(number),
+                    None => KEY_NAMES
+                        .iter()
+                        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| invalid("is not a key"))?,
+                }
+            }
+        };
+        // Terminals report Ctrl and Alt with the lowercase letter
+        let code = match code {
+            KeyCode::Char(c) if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(c)
+                if self
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                write!(f, "{}", c.to_ascii_uppercase())
+            }
+            KeyCode::F(number) => write!(f, "F{}", number),
+            code => match KEY_NAMES.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => f.write_str(name),
+                None => match code {
+                    KeyCode::Char(c) => write!(f, "{}", c),
+                    code => write!(f, "{:?}", code),
+                },
+            },
+        }
+    }
+}
+
+/// What a key does in the interactive renderers, remappable with a [`KeyMap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    /// Quit, asking first if actions are still running
+    Quit,
+    /// Switch between command and scroll mode
+    ToggleMode,
+    /// Complete the parameter being typed
+    Complete,
+    /// Cancel the latest background action
+    Cancel,
+    /// Edit the parameters of the command in `$EDITOR`
+    EditParams,
+    /// Go to the previously visited menu
+    MenuBack,
+    /// Go forward in menu history
+    MenuForward,
+    /// Pick the previous suggestion, or the previous command of the history
+    HistoryUp,
+    /// Pick the next suggestion, or the next command of the history
+    HistoryDown,
+    /// Hide the menu for a full-screen output
+    FullOutput,
+    /// Collapse the output pane
+    CollapseOutput,
+    /// Toggle the jobs panel
+    JobsPanel,
+    /// Show or hide the input box
+    ToggleInput,
+    /// Scroll the output down a line
+    ScrollDown,
+    /// Scroll the output up a line
+    ScrollUp,
+    /// Scroll the output down a page
+    PageDown,
+    /// Scroll the output up a page
+    PageUp,
+    /// Scroll to the top of the output, or to the line of a count
+    Top,
+    /// Scroll to the bottom of the output, or to the line of a count
+    Bottom,
+    /// Scroll unwrapped lines left
+    ScrollLeft,
+    /// Scroll unwrapped lines right
+    ScrollRight,
+    /// Toggle line wrapping
+    Wrap,
+    /// Toggle line numbers
+    LineNumbers,
+    /// Toggle message times
+    Timestamps,
+    /// Search the output
+    Search,
+    /// Jump to the next match of the search
+    NextMatch,
+    /// Jump to the previous match of the search
+    PreviousMatch,
+    /// Clear the search
+    ClearSearch,
+    /// Show the first cut line in view in full
+    ShowCutLine,
+    /// Pause or resume new output
+    Pause,
+    /// Show the output of every menu or of the current one
+    MenuOutput,
+    /// Follow new output or stop following it
+    Follow,
+    /// Close the popup viewer, which scrolls with the scroll mode keys
+    ClosePopup,
+}
+
+impl KeyAction {
+    /// Every action, in the order key maps list them
+    pub const ALL: [KeyAction; 33] = [
+        KeyAction::Quit,
+        KeyAction::ToggleMode,
+        KeyAction::Complete,
+        KeyAction::Cancel,
+        KeyAction::EditParams,
+        KeyAction::MenuBack,
+        KeyAction::MenuForward,
+        KeyAction::HistoryUp,
+        KeyAction::HistoryDown,
+        KeyAction::FullOutput,
+        KeyAction::CollapseOutput,
+        KeyAction::JobsPanel,
+        KeyAction::ToggleInput,
+        KeyAction::ScrollDown,
+        KeyAction::ScrollUp,
+        KeyAction::PageDown,
+        KeyAction::PageUp,
+        KeyAction::Top,
+        KeyAction::Bottom,
+        KeyAction::ScrollLeft,
+        KeyAction::ScrollRight,
+        KeyAction::Wrap,
+        KeyAction::LineNumbers,
+        KeyAction::Timestamps,
+        KeyAction::Search,
+        KeyAction::NextMatch,
+        KeyAction::PreviousMatch,
+        KeyAction::ClearSearch,
+        KeyAction::ShowCutLine,
+        KeyAction::Pause,
+        KeyAction::MenuOutput,
+        KeyAction::Follow,
+        KeyAction::ClosePopup,
+    ];
+
+    /// Get the name of the action in key map files, e.g. `toggle-mode`
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "quit",
+            KeyAction::ToggleMode => "toggle-mode",
+            KeyAction::Complete => "complete",
+            KeyAction::Cancel => "cancel",
+            KeyAction::EditParams => "edit-params",
+            KeyAction::MenuBack => "menu-back",
+            KeyAction::MenuForward => "menu-forward",
+            KeyAction::HistoryUp => "history-up",
+            KeyAction::HistoryDown => "history-down",
+            KeyAction::FullOutput => "full-output",
+            KeyAction::CollapseOutput => "collapse-output",
+            KeyAction::JobsPanel => "jobs-panel",
+            KeyAction::ToggleInput => "toggle-input",
+            KeyAction::ScrollDown => "scroll-down",
+            KeyAction::ScrollUp => "scroll-up",
+            KeyAction::PageDown => "page-down",
+            KeyAction::PageUp => "page-up",
+            KeyAction::Top => "top",
+            KeyAction::Bottom => "bottom",
+            KeyAction::ScrollLeft => "scroll-left",
+            KeyAction::ScrollRight => "scroll-right",
+            KeyAction::Wrap => "wrap",
+            KeyAction::LineNumbers => "line-numbers",
+            KeyAction::Timestamps => "timestamps",
+            KeyAction::Search => "search",
+            KeyAction::NextMatch => "next-match",
+            KeyAction::PreviousMatch => "previous-match",
+            KeyAction::ClearSearch => "clear-search",
+            KeyAction::ShowCutLine => "show-cut-line",
+            KeyAction::Pause => "pause",
+            KeyAction::MenuOutput => "menu-output",
+            KeyAction::Follow => "follow",
+            KeyAction::ClosePopup => "close-popup",
+        }
+    }
+
+    /// Describe what the action does, for the `keys` command
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "Quit",
+            KeyAction::ToggleMode => "Switch between command and scroll mode",
+            KeyAction::Complete => "Complete the parameter being typed",
+            KeyAction::Cancel => "Cancel the latest background action",
+            KeyAction::EditParams => "Edit parameters in $EDITOR",
+            KeyAction::MenuBack => "Go to the previously visited menu",
+            KeyAction::MenuForward => "Go forward in menu history",
+            KeyAction::HistoryUp => "Pick the previous suggestion, or browse history back",
+            KeyAction::HistoryDown => "Pick the next suggestion, or browse history forward",
+            KeyAction::FullOutput => "Hide the menu for a full-screen output",
+            KeyAction::CollapseOutput => "Collapse the output pane",
+            KeyAction::JobsPanel => "Toggle the jobs panel",
+            KeyAction::ToggleInput => "Show or hide the input box",
+            KeyAction::ScrollDown => "Scroll down",
+            KeyAction::ScrollUp => "Scroll up",
+            KeyAction::PageDown => "Scroll down a page",
+            KeyAction::PageUp => "Scroll up a page",
+            KeyAction::Top => "Scroll to the top",
+            KeyAction::Bottom => "Scroll to the bottom",
+            KeyAction::ScrollLeft => "Scroll unwrapped lines left",
+            KeyAction::ScrollRight => "Scroll unwrapped lines right",
+            KeyAction::Wrap => "Toggle line wrapping",
+            KeyAction::LineNumbers => "Toggle line numbers",
+            KeyAction::Timestamps => "Toggle message times",
+            KeyAction::Search => "Search the output",
+            KeyAction::NextMatch => "Jump to the next match",
+            KeyAction::PreviousMatch => "Jump to the previous match",
+            KeyAction::ClearSearch => "Clear the search",
+            KeyAction::ShowCutLine => "Show the first cut line in view in full",
+            KeyAction::Pause => "Pause or resume new output",
+            KeyAction::MenuOutput => "Show the output of every menu or of the current one",
+            KeyAction::Follow => "Follow new output or stop following it",
+            KeyAction::ClosePopup => "Close the popup viewer",
+        }
+    }
+
+    /// Check if the text renderer handles the action, it only has command mode
+    fn in_text_mode(self) -> bool {
+        matches!(
+            self,
+            KeyAction::Quit
+                | KeyAction::Complete
+                | KeyAction::Cancel
+                | KeyAction::EditParams
+                | KeyAction::MenuBack
+                | KeyAction::MenuForward
+                | KeyAction::HistoryUp
+                | KeyAction::HistoryDown
+        )
+    }
+}
+
+impl FromStr for KeyAction {
+    type Err = IstariError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        KeyAction::ALL
+            .into_iter()
+            .find(|action| action.name() == name)
+            .ok_or_else(|| IstariError::InvalidKeyMap(format!("unknown action '{}'", name)))
+    }
+}
+
+/// Keys of the interactive renderers, by mode and action.
+///
+/// The default binds the keys the renderers always had; applications remap
+/// conflicts with [`KeyMap::with_keys`] or a key map file:
+///
+/// ```
+/// # use istari::{KeyAction, KeyMap, Mode};
+/// // Tab only completes, F2 switches to scroll mode
+/// let keymap = KeyMap::default()
+///     .with_keys(Mode::Command, KeyAction::ToggleMode, ["F2".parse()?])
+///     .with_keys(Mode::Scroll, KeyAction::ToggleMode, ["F2".parse()?]);
+/// # Ok::<(), istari::IstariError>(())
+/// ```
+///
+/// The popup viewer scrolls with the scroll mode bindings of
+/// [`KeyAction::ScrollDown`] and [`KeyAction::ScrollUp`], and closes with
+/// [`KeyAction::ClosePopup`]. Typing and editing the command (Enter, Backspace),
+/// counts and `gg` in scroll mode, the output tab digits, the search prompt and
+/// dialogs keep their keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    bindings: Vec<(Mode, KeyAction, Vec<Key>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        use KeyAction::*;
+        let char_keys = |keys: &[char]| keys.iter().map(|&c| Key::from(c)).collect::<Vec<_>>();
+        let bindings = vec![
+            (Mode::Command, Quit, vec![Key::ctrl('q')]),
+            (Mode::Command, Complete, vec![Key::new(KeyCode::Tab)]),
+            (Mode::Command, ToggleMode, vec![Key::new(KeyCode::Tab)]),
+            (Mode::Command, Cancel, vec![Key::new(KeyCode::Esc)]),
+            (Mode::Command, EditParams, vec![Key::ctrl('e')]),
+            (Mode::Command, MenuBack, vec![Key::alt(KeyCode::Left)]),
+            (Mode::Command, MenuForward, vec![Key::alt(KeyCode::Right)]),
+            (Mode::Command, HistoryUp, vec![Key::new(KeyCode::Up)]),
+            (Mode::Command, HistoryDown, vec![Key::new(KeyCode::Down)]),
+            (Mode::Command, FullOutput, vec![Key::ctrl('f')]),
+            (Mode::Command, CollapseOutput, vec![Key::ctrl('o')]),
+            (Mode::Command, JobsPanel, vec![Key::ctrl('t')]),
+            (Mode::Command, ToggleInput, vec![Key::ctrl('i')]),
+            (Mode::Scroll, Quit, vec![Key::ctrl('q')]),
+            (Mode::Scroll, ToggleMode, vec![Key::new(KeyCode::Tab)]),
+            (
+                Mode::Scroll,
+                ScrollDown,
+                vec![Key::from('j'), Key::new(KeyCode::Down)],
+            ),
+            (
+                Mode::Scroll,
+                ScrollUp,
+                vec![Key::from('k'), Key::new(KeyCode::Up)],
+            ),
+            (
+                Mode::Scroll,
+                PageDown,
+                vec![Key::from('d'), Key::new(KeyCode::PageDown)],
+            ),
+            (
+                Mode::Scroll,
+                PageUp,
+                vec![Key::from('u'), Key::new(KeyCode::PageUp)],
+            ),
+            (Mode::Scroll, Top, vec![Key::new(KeyCode::Home)]),
+            (
+                Mode::Scroll,
+                Bottom,
+                vec![Key::from('G'), Key::new(KeyCode::End)],
+            ),
+            (
+                Mode::Scroll,
+                ScrollLeft,
+                vec![Key::from('h'), Key::new(KeyCode::Left)],
+            ),
+            (
+                Mode::Scroll,
+                ScrollRight,
+                vec![Key::from('l'), Key::new(KeyCode::Right)],
+            ),
+            (Mode::Scroll, Wrap, char_keys(&['w'])),
+            (Mode::Scroll, LineNumbers, char_keys(&['#'])),
+            (Mode::Scroll, Timestamps, char_keys(&['t'])),
+            (Mode::Scroll, Search, char_keys(&['/'])),
+            (Mode::Scroll, NextMatch, char_keys(&['n'])),
+            (Mode::Scroll, PreviousMatch, char_keys(&['N'])),
+            (Mode::Scroll, ClearSearch, vec![Key::new(KeyCode::Esc)]),
+            (Mode::Scroll, FullOutput, char_keys(&['f'])),
+            (Mode::Scroll, ShowCutLine, char_keys(&['v'])),
+            (Mode::Scroll, Pause, char_keys(&['p'])),
+            (Mode::Scroll, MenuOutput, char_keys(&['m'])),
+            (Mode::Scroll, Follow, vec![Key::ctrl('a')]),
+            (
+                Mode::Scroll,
+                ClosePopup,
+                vec![
+                    Key::new(KeyCode::Esc),
+                    Key::new(KeyCode::Enter),
+                    Key::from('q'),
+                ],
+            ),
+        ];
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    /// Bind an action of a mode to the given keys instead of its current ones,
+    /// no keys unbinding it
+    pub fn with_keys(
+        mut self,
+        mode: Mode,
+        action: KeyAction,
+        keys: impl IntoIterator<Item = Key>,
+    ) -> Self {
+        let keys: Vec<Key> = keys.into_iter().collect();
+        match self
+            .bindings
+            .iter_mut()
+            .find(|(bound_mode, bound, _)| *bound_mode == mode && *bound == action)
+        {
+            Some((_, _, bound_keys)) => *bound_keys = keys,
+            None => self.bindings.push((mode, action, keys)),
+        }
+        self
+    }
+
+    /// Parse a key map file on top of the default bindings.
+    ///
+    /// Each line binds an action of a mode to keys separated by commas, nothing
+    /// after `=` unbinding it. Empty lines and lines starting with `#` are skipped:
+    ///
+    /// ```text
+    /// command.toggle-mode = F2
+    /// scroll.toggle-mode = F2, Esc
+    /// scroll.scroll-down = j, Down, Ctrl+N
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, IstariError> {
+        let mut keymap = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let at_line = |e: IstariError| match e {
+                IstariError::InvalidKeyMap(reason) => {
+                    IstariError::InvalidKeyMap(format!("line {}: {}", index + 1, reason))
+                }
+                e => e,
+            };
+            let missing = || {
+                IstariError::InvalidKeyMap(format!(
+                    "line {}: expected `mode.action = keys`",
+                    index + 1
+                ))
+            };
+
+            let (binding, keys) = line.split_once('=').ok_or_else(missing)?;
+            let (mode, action) = binding.trim().split_once('.').ok_or_else(missing)?;
+            let mode = match mode {
+                "command" => Mode::Command,
+                "scroll" => Mode::Scroll,
+                _ => {
+                    return Err(at_line(IstariError::InvalidKeyMap(format!(
+                        "unknown mode '{}'",
+                        mode
+                    ))));
+                }
+            };
+            let action: KeyAction = action.parse().map_err(at_line)?;
+            let keys = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<Key>, _>>()
+                .map_err(at_line)?;
+            keymap = keymap.with_keys(mode, action, keys);
+        }
+        Ok(keymap)
+    }
+
+    /// Read a key map file, see [`KeyMap::parse`]
+    pub fn load(path: &Path) -> Result<Self, IstariError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            IstariError::InvalidKeyMap(format!("can't read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&text)
+    }
+
+    /// Get the action a key press triggers in a mode, the first bound if several are
+    pub fn action(&self, mode: Mode, key: &KeyEvent) -> Option<KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(bound_mode, _, keys)| {
+                *bound_mode == mode && keys.iter().any(|k| k.matches(key))
+            })
+            .map(|(_, action, _)| *action)
+    }
+
+    /// Check if a key press triggers an action in a mode, also when another
+    /// action bound to it comes first
+    pub fn triggers(&self, mode: Mode, action: KeyAction, key: &KeyEvent) -> bool {
+        self.keys(mode, action).iter().any(|k| k.matches(key))
+    }
+
+    /// Get the keys bound to an action in a mode
+    pub fn keys(&self, mode: Mode, action: KeyAction) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(bound_mode, bound, _)| *bound_mode == mode && *bound == action)
+            .map_or(&[], |(_, _, keys)| keys.as_slice())
+    }
+
+    /// Get the keys bound to an action joined for a help text, e.g. `j/Down`,
+    /// empty if it's unbound
+    pub fn label(&self, mode: Mode, action: KeyAction) -> String {
+        self.keys(mode, action)
+            .iter()
+            .map(Key::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Get the key bindings of the given renderer, command mode first
+    pub fn bindings(&self, ui_mode: UIMode) -> Vec<KeyBinding> {
+        let mut bindings = vec![
+            KeyBinding::new(
+                Mode::Command,
+                "Enter",
+                "Accept the picked suggestion, or run the typed command",
+            ),
+            KeyBinding::new(Mode::Command, "Backspace", "Delete the last character"),
+        ];
+        for (mode, action, keys) in &self.bindings {
+            let shown = ui_mode == UIMode::TUI || (*mode == Mode::Command && action.in_text_mode());
+            if shown && !keys.is_empty() {
+                bindings.push(KeyBinding::new(
+                    *mode,
+                    self.label(*mode, *action),
+                    action.description(),
+                ));
+            }
+        }
+
+        if ui_mode == UIMode::TUI {
+            bindings.extend([
+                KeyBinding::new(Mode::Scroll, "gg", "Scroll to the top"),
+                KeyBinding::new(
+                    Mode::Scroll,
+                    "<count>",
+                    "Repeat the next key, e.g. 5j, or go to a line with 12G",
+                ),
+                KeyBinding::new(Mode::Scroll, "1-9", "Show an output tab, after a moment"),
+            ]);
+        }
+
+        bindings
+    }
+}
+
+/// Get the default key bindings of the given renderer, command mode first
+pub fn key_bindings(ui_mode: UIMode) -> Vec<KeyBinding> {
+    KeyMap::default().bindings(ui_mode)
+}
+
+/// Format key bindings as one line per binding, grouped by mode
+pub fn format_key_bindings(bindings: &[KeyBinding]) -> Vec<String> {
+    let width = bindings.iter().map(|b| b.keys.len()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+
+    for (mode, title) in [
+        (Mode::Command, "Command mode:"),
+        (Mode::Scroll, "Scroll mode:"),
+    ] {
+        let mut in_mode: Vec<&KeyBinding> = bindings.iter().filter(|b| b.mode == mode).collect();
+        if in_mode.is_empty() {
+            continue;
+        }
+        in_mode.sort_by(|a, b| a.keys.cmp(&b.keys));
+
+        lines.push(title.to_string());
+        for binding in in_mode {
+            lines.push(format!(
+                "  {:<width$}  {}",
+                binding.keys, binding.description
+            ));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_key_bindings() {
+        let lines = format_key_bindings(&key_bindings(UIMode::Text));
+        assert_eq!(lines[0], "Command mode:");
+        // Bindings are sorted and aligned
+        assert_eq!(lines[1], "  Alt+Left   Go to the previously visited menu");
+        assert_eq!(lines[3], "  Backspace  Delete the last character");
+        assert!(!lines.iter().any(|line| line == "Scroll mode:"));
+
+        let lines = format_key_bindings(&key_bindings(UIMode::TUI));
+        assert!(lines.iter().any(|line| line == "Scroll mode:"));
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let key = |text: &str| text.parse::<Key>().unwrap();
+        assert_eq!(key("Ctrl+Q"), Key::ctrl('q'));
+        assert_eq!(key("ctrl+q"), Key::ctrl('q'));
+        assert_eq!(key("Alt+Left"), Key::alt(KeyCode::Left));
+        assert_eq!(key("G"), Key::from('G'));
+        assert_eq!(key("pagedown"), Key::new(KeyCode::PageDown));
+        assert_eq!(key("F2"), Key::new(KeyCode::F(2)));
+        assert_eq!(key("+"), Key::from('+'));
+        assert_eq!(key("Ctrl++"), Key::ctrl('+'));
+        assert!("Hyper+Q".parse::<Key>().is_err());
+        assert!("Nope".parse::<Key>().is_err());
+
+        // Keys print the way they parse
+        for text in ["Ctrl+Q", "Alt+Left", "G", "#", "PageDown", "F12", "Space"] {
+            assert_eq!(key(text).to_string(), text);
+        }
+
+        // Shift is implied by the case of a character
+        let shifted = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert!(key("G").matches(&shifted));
+        assert!(!key("g").matches(&shifted));
+        assert!(!key("Tab").matches(&KeyEvent::new(KeyCode::Tab, KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn test_keymap() {
+        let tab = KeyEvent::from(KeyCode::Tab);
+        let keymap = KeyMap::default();
+        // Tab completes first, and switches mode if there's nothing to complete
+        assert_eq!(
+            keymap.action(Mode::Command, &tab),
+            Some(KeyAction::Complete)
+        );
+        assert!(keymap.triggers(Mode::Command, KeyAction::ToggleMode, &tab));
+        assert_eq!(keymap.label(Mode::Scroll, KeyAction::ScrollDown), "j/Down");
+
+        let keymap = keymap
+            .with_keys(
+                Mode::Command,
+                KeyAction::ToggleMode,
+                ["F2".parse().unwrap()],
+            )
+            .with_keys(Mode::Scroll, KeyAction::Follow, []);
+        assert!(!keymap.triggers(Mode::Command, KeyAction::ToggleMode, &tab));
+        assert_eq!(
+            keymap.action(Mode::Command, &KeyEvent::from(KeyCode::F(2))),
+            Some(KeyAction::ToggleMode)
+        );
+        assert_eq!(
+            keymap.action(
+                Mode::Scroll,
+                &KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL)
+            ),
+            None
+        );
+        // Unbound actions aren't listed
+        let bindings = keymap.bindings(UIMode::TUI);
+        assert!(bindings.iter().any(|binding| binding.keys == "F2"));
+        assert!(
+            !bindings
+                .iter()
+                .any(|binding| binding.description == KeyAction::Follow.description())
+        );
+    }
+
+    #[test]
+    fn test_parse_keymap() {
+        let keymap = KeyMap::parse(
+            "# Free Tab for completion\n\
+             command.toggle-mode = F2\n\
+             \n\
+             scroll.scroll-down = j, Down, Ctrl+N\n\
+             scroll.follow =\n",
+        )
+        .unwrap();
+        assert_eq!(keymap.label(Mode::Command, KeyAction::ToggleMode), "F2");
+        assert_eq!(
+            keymap.label(Mode::Scroll, KeyAction::ScrollDown),
+            "j/Down/Ctrl+N"
+        );
+        assert!(keymap.keys(Mode::Scroll, KeyAction::Follow).is_empty());
+        // Other bindings keep their default keys
+        assert_eq!(keymap.label(Mode::Command, KeyAction::Quit), "Ctrl+Q");
+
+        let error = |text: &str| KeyMap::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("\nscroll.fly = x"),
+            "Invalid key map: line 2: unknown action 'fly'"
+        );
+        assert_eq!(
+            error("insert.quit = x"),
+            "Invalid key map: line 1: unknown mode 'insert'"
+        );
+        assert_eq!(
+            error("scroll.quit = Ctrl+Nope"),
+            "Invalid key map: line 1: key 'Ctrl+Nope' is not a key"
+        );
+        assert_eq!(
+            error("quit"),
+            "Invalid key map: line 1: expected `mode.action = keys`"
+        );
+        assert!(KeyMap::load(Path::new("/nonexistent/istari.keys")).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use ratatui::layout::Rect;
+
+/// Height of the command input box, with its borders
+const INPUT_HEIGHT: u16 = 3;
+
+/// Where the TUI places the menu and output panels, set with [`Istari::with_layout`](crate::Istari::with_layout)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutConfig {
+    /// Share of the main area the menu takes, in percent, the output gets the rest
+    pub menu_percent: u16,
+    /// Put the menu right of the output instead of left of it
+    pub menu_on_right: bool,
+    /// Stack the output below the menu instead of beside it, `menu_on_right` is ignored then
+    pub output_on_bottom: bool,
+    /// Columns (or rows when stacked) the menu gets at least
+    pub min_menu_size: u16,
+    /// Columns (or rows when stacked) the output gets at least
+    pub min_output_size: u16,
+    /// Show the help line below the command input
+    pub help_line: bool,
+    /// Number the output lines, toggled with `#` in scroll mode
+    pub line_numbers: bool,
+    /// Show when each output message was added, toggled with `t` in scroll mode
+    pub timestamps: bool,
+    /// Wrap long output lines instead of cutting them at the pane's edge, toggled with `w` in scroll mode
+    pub wrap: bool,
+    /// Cut output lines wider than this many columns with an ellipsis, even when
+    /// wrapping, `v` in scroll mode shows them in full
+    pub max_line_width: Option<usize>,
+    /// Draw the TUI in this many rows below the shell prompt instead of the alternate
+    /// screen, keeping the scrollback above it in view
+    pub inline_height: Option<u16>,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            menu_percent: 50,
+            menu_on_right: false,
+            output_on_bottom: false,
+            min_menu_size: 0,
+            min_output_size: 0,
+            help_line: true,
+            line_numbers: false,
+            timestamps: false,
+            wrap: true,
+            max_line_width: None,
+            inline_height: None,
+        }
+    }
+}
+
+/// Which of the menu and output panes the TUI shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Panes {
+    /// Both panes, placed by the [`LayoutConfig`]
+    #[default]
+    Both,
+    /// Only the output, across the whole main area
+    OutputOnly,
+    /// Only the menu, with the output collapsed
+    MenuOnly,
+}
+
+impl Panes {
+    /// Get the name used by the `pane` command
+    pub fn name(self) -> &'static str {
+        match self {
+            Panes::Both => "both",
+            Panes::OutputOnly => "output",
+            Panes::MenuOnly => "menu",
+        }
+    }
+
+    /// Get the panes the `pane` command names
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "both" => Some(Panes::Both),
+            "output" => Some(Panes::OutputOnly),
+            "menu" => Some(Panes::MenuOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Areas of the screen the TUI draws into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutAreas {
+    /// The menu title, items and jobs panel, empty if the menu is hidden
+    pub menu: Rect,
+    /// The output pane, empty if it's collapsed
+    pub output: Rect,
+    /// The command input box
+    pub input: Rect,
+    /// The help line, if it's shown
+    pub help: Option<Rect>,
+    /// The status bar at the bottom, if there is one
+    pub status: Option<Rect>,
+}
+
+impl LayoutConfig {
+    /// Create the default layout: menu left, output right, split in half
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the share of the main area the menu takes, in percent
+    pub fn with_menu_percent(mut self, percent: u16) -> Self {
+        self.menu_percent = percent.min(100);
+        self
+    }
+
+    /// Put the menu right of the output
+    pub fn with_menu_on_right(mut self, on_right: bool) -> Self {
+        self.menu_on_right = on_right;
+        self
+    }
+
+    /// Stack the output below the menu
+    pub fn with_output_on_bottom(mut self, on_bottom: bool) -> Self {
+        self.output_on_bottom = on_bottom;
+        self
+    }
+
+    /// Set the minimum sizes of the menu and output panels
+    pub fn with_min_sizes(mut self, menu: u16, output: u16) -> Self {
+        self.min_menu_size = menu;
+        self.min_output_size = output;
+        self
+    }
+
+    /// Show or hide the help line below the command input
+    pub fn with_help_line(mut self, shown: bool) -> Self {
+        self.help_line = shown;
+        self
+    }
+
+    /// Number the output lines
+    pub fn with_line_numbers(mut self, shown: bool) -> Self {
+        self.line_numbers = shown;
+        self
+    }
+
+    /// Show the time of each output message, in UTC, before its first line
+    pub fn with_timestamps(mut self, shown: bool) -> Self {
+        self.timestamps = shown;
+        self
+    }
+
+    /// Wrap long output lines, or cut them and scroll the output sideways with h/l
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Cut output lines wider than `width` columns with an ellipsis, so a huge
+    /// line doesn't take over the output pane when wrapping
+    pub fn with_max_line_width(mut self, width: usize) -> Self {
+        self.max_line_width = Some(width);
+        self
+    }
+
+    /// Draw the TUI inline, in this many rows below the shell prompt, e.g. for
+    /// short-lived menus whose output should stay in the scrollback
+    pub fn with_inline_viewport(mut self, height: u16) -> Self {
+        self.inline_height = Some(height);
+        self
+    }
+
+    /// Split the screen into the areas the TUI draws into, showing the given panes
+    /// and a status bar below everything else if `status_bar` is set
+    pub fn split(&self, area: Rect, panes: Panes, status_bar: bool) -> LayoutAreas {
+        let status_height = u16::from(status_bar).min(area.height);
+        let status = status_bar.then(|| {
+            Rect::new(
+                area.x,
+                area.y + area.height - status_height,
+                area.width,
+                status_height,
+            )
+        });
+        let area = Rect {
+            height: area.height - status_height,
+            ..area
+        };
+
+        let footer_height = (INPUT_HEIGHT + u16::from(self.help_line)).min(area.height);
+        let main_height = area.height - footer_height;
+        let input = Rect::new(
+            area.x,
+            area.y + main_height,
+            area.width,
+            INPUT_HEIGHT.min(footer_height),
+        );
+        let help = self.help_line.then(|| {
+            Rect::new(
+                area.x,
+                input.y + input.height,
+                area.width,
+                footer_height - input.height,
+            )
+        });
+
+        let total = if self.output_on_bottom {
+            main_height
+        } else {
+            area.width
+        };
+        let menu_size = match panes {
+            Panes::Both => self.menu_size(total),
+            Panes::OutputOnly => 0,
+            Panes::MenuOnly => total,
+        };
+        let output_size = total - menu_size;
+
+        let (menu, output) = if self.output_on_bottom {
+            (
+                Rect::new(area.x, area.y, area.width, menu_size),
+                Rect::new(area.x, area.y + menu_size, area.width, output_size),
+            )
+        } else if self.menu_on_right {
+            (
+                Rect::new(area.x + output_size, area.y, menu_size, main_height),
+                Rect::new(area.x, area.y, output_size, main_height),
+            )
+        } else {
+            (
+                Rect::new(area.x, area.y, menu_size, main_height),
+                Rect::new(area.x + menu_size, area.y, output_size, main_height),
+            )
+        };
+
+        LayoutAreas {
+            menu,
+            output,
+            input,
+            help,
+            status,
+        }
+    }
+
+    /// Size of the menu out of `total`, keeping both minimums while they fit
+    fn menu_size(&self, total: u16) -> u16 {
+        let wanted = (u32::from(total) * u32::from(self.menu_percent.min(100)) / 100) as u16;
+        // The output minimum loses when both don't fit, the menu is needed to do anything
+        wanted
+            .min(total.saturating_sub(self.min_output_size))
+            .max(self.min_menu_size.min(total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_split() {
+        let area = Rect::new(0, 0, 100, 40);
+
+        let areas = LayoutConfig::default().split(area, Panes::Both, false);
+        assert_eq!(areas.menu, Rect::new(0, 0, 50, 36));
+        assert_eq!(areas.output, Rect::new(50, 0, 50, 36));
+        assert_eq!(areas.input, Rect::new(0, 36, 100, 3));
+        assert_eq!(areas.help, Some(Rect::new(0, 39, 100, 1)));
+
+        let areas = LayoutConfig::new()
+            .with_menu_percent(30)
+            .with_menu_on_right(true)
+            .with_help_line(false)
+            .split(area, Panes::Both, false);
+        assert_eq!(areas.menu, Rect::new(70, 0, 30, 37));
+        assert_eq!(areas.output, Rect::new(0, 0, 70, 37));
+        assert_eq!(areas.help, None);
+
+        let areas = LayoutConfig::new()
+            .with_output_on_bottom(true)
+            .with_menu_percent(25)
+            .with_min_sizes(12, 0)
+            .split(area, Panes::Both, false);
+        assert_eq!(areas.menu, Rect::new(0, 0, 100, 12));
+        assert_eq!(areas.output, Rect::new(0, 12, 100, 24));
+
+        // The output gets its minimum unless the menu's doesn't fit anymore
+        let layout = LayoutConfig::new()
+            .with_menu_percent(80)
+            .with_min_sizes(30, 40);
+        assert_eq!(layout.split(area, Panes::Both, false).menu.width, 60);
+        assert_eq!(
+            layout
+                .split(Rect::new(0, 0, 50, 40), Panes::Both, false)
+                .menu
+                .width,
+            30
+        );
+
+        // Hiding a pane gives the other one the whole main area, wherever it's placed
+        let areas = layout.split(area, Panes::OutputOnly, false);
+        assert_eq!(areas.output, Rect::new(0, 0, 100, 36));
+        assert!(areas.menu.is_empty());
+        let areas = layout
+            .with_menu_on_right(true)
+            .split(area, Panes::MenuOnly, false);
+        assert_eq!(areas.menu, Rect::new(0, 0, 100, 36));
+        assert!(areas.output.is_empty());
+
+        // The status bar takes the bottom row
+        let areas = LayoutConfig::default().split(area, Panes::Both, true);
+        assert_eq!(areas.status, Some(Rect::new(0, 39, 100, 1)));
+        assert_eq!(areas.help, Some(Rect::new(0, 38, 100, 1)));
+        assert_eq!(areas.menu.height, 35);
+    }
+}
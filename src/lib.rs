@@ -1,12 +1,92 @@
+pub mod cancel;
+#[cfg(unix)]
+mod capture;
+#[cfg(feature = "clap")]
+mod cli;
+mod clock;
+pub mod context;
+pub mod dialog;
+pub mod diff;
+pub mod editor;
 pub mod error;
+mod fuzzy;
+#[cfg(feature = "highlight")]
+mod highlight;
+pub mod highlight_rules;
 pub mod istari;
+pub mod jobs;
+pub mod keymap;
+pub mod layout;
+#[cfg(feature = "log")]
+pub mod logger;
 pub mod menu;
 pub mod menu_manager;
+pub mod metrics;
+pub mod output;
+pub mod params;
+pub mod plugin;
+pub mod process;
+pub mod progress;
 pub mod rendering;
+pub mod session;
+pub mod stats;
+#[cfg(feature = "tracing")]
+pub mod subscriber;
+pub mod theme;
+pub mod tree;
 pub mod types;
+pub mod undo;
 
+pub use cancel::CancelToken;
+#[cfg(feature = "clap")]
+pub use clap;
+pub use context::ActionContext;
+pub use crossterm;
+pub use dialog::{Dialog, DialogKind};
+pub use diff::unified_diff;
 pub use error::IstariError;
-pub use istari::{CommandHistory, Istari, OutputBuffer, UIMode};
-pub use menu::{Menu, MenuItem};
+pub use highlight_rules::HighlightRule;
+pub use istari::{
+    CommandHistory, CommandRef, Favorites, Istari, MAIN_OUTPUT_TAB, OUTPUT_BUFFER_SIZE,
+    OutputBuffer, OutputPopup, RecentCommands, TickStats, UIMode, title_banner,
+};
+pub use jobs::{Job, JobInfo, JobStatus};
+pub use keymap::{Key, KeyAction, KeyBinding, KeyMap};
+pub use layout::{LayoutAreas, LayoutConfig, Panes};
+#[cfg(feature = "log")]
+pub use logger::OutputLogger;
+pub use menu::{Menu, MenuItem, MenuRefresh, SharedMenu};
 pub use menu_manager::MenuManager;
-pub use types::{ActionType, AsyncFnMarker, IntoActionFn, IntoTickFn, Mode, SyncFnMarker};
+pub use metrics::MetricValue;
+pub use output::{
+    ActionOutput, OutputFormat, OutputHandle, OutputLevel, OutputRecord, OutputSink, OutputTable,
+    format_clock_time,
+};
+pub use params::{ParamSpec, ParamType, Params};
+pub use plugin::{MenuPlugin, PluginEvent};
+pub use process::{ProcessExit, run_process};
+pub use progress::{JobProgress, Progress};
+#[cfg(feature = "termion")]
+pub use rendering::TermionTerminal;
+pub use rendering::{
+    CrosstermTerminal, DefaultTerminal, HeadlessRenderer, TerminalBackend, TuiController,
+    UIController,
+};
+pub use session::{ExportOptions, SessionEntry, SessionLog};
+pub use stats::{ActionStats, ExecutionStats};
+#[cfg(feature = "tracing")]
+pub use subscriber::OutputLayer;
+pub use theme::{ColorSupport, THEME_PRESETS, Theme};
+pub use tree::{MenuItemInfo, MenuTree};
+#[allow(deprecated)]
+pub use types::CtrlQBehavior;
+pub use types::{
+    ActionOutcome, ActionResult, ActionType, AsyncCancelFnMarker, AsyncContextFnMarker,
+    AsyncFnMarker, AsyncJobFnMarker, AsyncMutContextFnMarker, AsyncMutFnMarker,
+    AsyncMutParamsFnMarker, AsyncOutputFnMarker, AsyncParamsFnMarker, AsyncProgressFnMarker,
+    IntoActionFn, IntoActionResult, IntoTickFn, MaybeSend, MaybeSendSync, Mode, MutAction,
+    Navigation, OutputPolicy, QuitBehavior, SyncCancelFnMarker, SyncContextFnMarker, SyncFnMarker,
+    SyncJobFnMarker, SyncMutContextFnMarker, SyncMutFnMarker, SyncMutParamsFnMarker,
+    SyncOutputFnMarker, SyncParamsFnMarker, SyncProgressFnMarker,
+};
+pub use undo::{UndoEntry, UndoStack};
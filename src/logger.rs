@@ -0,0 +1,103 @@
+use crate::output::{OutputHandle, OutputLevel};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// A [`log`] logger writing records to the output pane, tagged with their target
+/// as the source and styled by their level.
+///
+/// Install it once the application is built, so `log::warn!` and the logs of
+/// dependencies show up in the TUI instead of corrupting the screen:
+///
+/// ```no_run
+/// # use istari::{Istari, Menu, OutputLogger};
+/// # let mut app = Istari::new(Menu::new("Root"), ()).unwrap();
+/// OutputLogger::new(app.output_handle())
+///     .with_max_level(log::LevelFilter::Info)
+///     .init()?;
+/// log::info!("connected");
+/// app.run()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct OutputLogger {
+    handle: OutputHandle,
+    max_level: LevelFilter,
+}
+
+impl OutputLogger {
+    /// Create a logger writing through the given handle, keeping every record up to `Debug`
+    pub fn new(handle: OutputHandle) -> Self {
+        Self {
+            handle,
+            max_level: LevelFilter::Debug,
+        }
+    }
+
+    /// Drop records more verbose than `level`
+    pub fn with_max_level(mut self, level: LevelFilter) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    /// Install the logger for the whole process, failing if one is already set
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.max_level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+/// Get the output level a log level shows with, trace counting as debug
+fn output_level(level: log::Level) -> OutputLevel {
+    match level {
+        log::Level::Error => OutputLevel::Error,
+        log::Level::Warn => OutputLevel::Warn,
+        log::Level::Info => OutputLevel::Info,
+        log::Level::Debug | log::Level::Trace => OutputLevel::Debug,
+    }
+}
+
+impl Log for OutputLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.max_level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.handle
+            .with_source(record.target())
+            .push_level(output_level(record.level()), record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Istari, Menu};
+
+    #[test]
+    fn test_output_logger() {
+        let mut app = Istari::new(Menu::new("Root"), ()).unwrap();
+        let logger = OutputLogger::new(app.output_handle()).with_max_level(LevelFilter::Info);
+        let log = |level, message: &str| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("db")
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+        log(log::Level::Warn, "slow query");
+        log(log::Level::Info, "connected");
+        log(log::Level::Debug, "pool stats");
+        app.drain_output();
+
+        assert_eq!(*app.output_messages(), ["slow query", "connected"]);
+        assert_eq!(*app.output_levels(), [OutputLevel::Warn, OutputLevel::Info]);
+        let records = app.export_output(None);
+        assert_eq!(records[0].source.as_deref(), Some("db"));
+    }
+}
@@ -1,7 +1,32 @@
 use crate::error::{IstariError, RESERVED_KEYS};
-use crate::types::{ActionType, IntoActionFn};
+use crate::params::{self, ParamSpec, Params};
+use crate::theme::Style;
+use crate::types::{
+    ActionType, CompleteFn, DescriptionFn, IntoActionFn, MaybeSendSync, MutAction, OutputPolicy,
+    UndoFn,
+};
+use std::collections::HashSet;
 use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A menu that can be mounted under several parents
+pub type SharedMenu<T> = Arc<Mutex<Menu<T>>>;
+
+/// Keys handed out to auto-numbered items, in order
+const AUTO_KEYS: &str = "123456789abcdefghijklmnopqrstuvwxyz";
+
+/// State carried through a validation pass over a menu tree
+struct TreeAnalysis<T> {
+    /// Menus on the way to the one being checked, root first, with their titles
+    ancestors: Vec<(Option<*const Mutex<Menu<T>>>, String)>,
+    /// Keys of the submenus entered to reach the one being checked
+    path: Vec<String>,
+    /// Submenus already checked, so shared submenus are only checked once
+    visited: HashSet<*const Mutex<Menu<T>>>,
+    /// Problems found so far
+    problems: Vec<IstariError>,
+}
 
 /// A menu item that can be selected
 pub struct MenuItem<T> {
@@ -13,6 +38,30 @@ pub struct MenuItem<T> {
     pub action: Option<ActionType<T>>,
     /// A submenu that this item leads to, if any
     pub submenu: Option<Arc<Mutex<Menu<T>>>>,
+    /// How the output of this item's action is routed
+    pub output_policy: OutputPolicy,
+    /// Output tab the output of this item's action goes to, the main output if `None`
+    pub output_tab: Option<String>,
+    /// Parameters accepted by this item's action, validated before it runs
+    pub params: Vec<ParamSpec>,
+    /// Categories the item is tagged with, e.g. "network" or "danger"
+    pub categories: Vec<String>,
+    /// Style of the description in the TUI menu on top of the theme, e.g. red for
+    /// dangerous actions. The text renderer ignores it.
+    pub style: Option<Style>,
+    /// Whether the key is assigned by the menu, which renumbers it as items come and go
+    pub auto_key: bool,
+    /// Computes the description shown in the menu from the state, overriding `description`
+    pub dynamic_description: Option<DescriptionFn<T>>,
+    /// Whether the user must confirm before the action runs
+    pub confirm: bool,
+    /// Reverts what the action did, for the `undo` command
+    pub undo: Option<UndoFn<T>>,
+    /// Suggests parameter values for Tab completion
+    pub completer: Option<CompleteFn<T>>,
+    /// Parses the parameters instead of the specs, see [`MenuItem::with_clap`]
+    #[cfg(feature = "clap")]
+    pub clap_command: Option<clap::Command>,
 }
 
 impl<T> Clone for MenuItem<T> {
@@ -22,6 +71,18 @@ impl<T> Clone for MenuItem<T> {
             description: self.description.clone(),
             action: None, // We can't clone the action function, so we set it to None
             submenu: self.submenu.clone(),
+            output_policy: self.output_policy,
+            output_tab: self.output_tab.clone(),
+            params: self.params.clone(),
+            categories: self.categories.clone(),
+            style: self.style,
+            auto_key: self.auto_key,
+            dynamic_description: self.dynamic_description.clone(),
+            confirm: self.confirm,
+            undo: self.undo.clone(),
+            completer: self.completer.clone(),
+            #[cfg(feature = "clap")]
+            clap_command: self.clap_command.clone(),
         }
     }
 }
@@ -40,6 +101,37 @@ impl<T: std::fmt::Debug> fmt::Debug for MenuItem<T> {
                 },
             )
             .field("submenu", &self.submenu)
+            .field("output_policy", &self.output_policy)
+            .field("output_tab", &self.output_tab)
+            .field("params", &self.params)
+            .field("categories", &self.categories)
+            .field("style", &self.style)
+            .field("auto_key", &self.auto_key)
+            .field("confirm", &self.confirm)
+            .field(
+                "undo",
+                &if self.undo.is_some() {
+                    "Some(Fn)"
+                } else {
+                    "None"
+                },
+            )
+            .field(
+                "completer",
+                &if self.completer.is_some() {
+                    "Some(Fn)"
+                } else {
+                    "None"
+                },
+            )
+            .field(
+                "dynamic_description",
+                &if self.dynamic_description.is_some() {
+                    "Some(Fn)"
+                } else {
+                    "None"
+                },
+            )
             .finish()
     }
 }
@@ -55,6 +147,30 @@ impl<T> MenuItem<T> {
             description,
             action: Some(action.into_action_fn()),
             submenu: None,
+            output_policy: OutputPolicy::default(),
+            output_tab: None,
+            params: Vec::new(),
+            categories: Vec::new(),
+            style: None,
+            auto_key: false,
+            dynamic_description: None,
+            confirm: false,
+            undo: None,
+            completer: None,
+            #[cfg(feature = "clap")]
+            clap_command: None,
+        }
+    }
+
+    /// Create a new menu item leading to a submenu that can also be mounted elsewhere
+    pub fn new_shared_submenu(
+        key: impl Into<String>,
+        description: String,
+        submenu: SharedMenu<T>,
+    ) -> Self {
+        MenuItem {
+            submenu: Some(submenu),
+            ..Self::new_submenu(key, description, Menu::default())
         }
     }
 
@@ -65,8 +181,131 @@ impl<T> MenuItem<T> {
             description,
             action: None,
             submenu: Some(Arc::new(Mutex::new(submenu))),
+            output_policy: OutputPolicy::default(),
+            output_tab: None,
+            params: Vec::new(),
+            categories: Vec::new(),
+            style: None,
+            auto_key: false,
+            dynamic_description: None,
+            confirm: false,
+            undo: None,
+            completer: None,
+            #[cfg(feature = "clap")]
+            clap_command: None,
+        }
+    }
+
+    /// Let the menu assign this item's key, see [`Menu::add_auto_action`]
+    pub fn with_auto_key(mut self) -> Self {
+        self.auto_key = true;
+        self
+    }
+
+    /// Compute the description shown in the menu from the state, e.g. "Counter: 42"
+    pub fn with_dynamic_description<F>(mut self, describe: F) -> Self
+    where
+        F: Fn(&T) -> String + MaybeSendSync + 'static,
+    {
+        self.dynamic_description = Some(Arc::new(describe));
+        self
+    }
+
+    /// Get the description to show in the menu for the given state
+    pub fn describe(&self, state: &T) -> String {
+        match &self.dynamic_description {
+            Some(describe) => describe(state),
+            None => self.description.clone(),
         }
     }
+
+    /// Ask "Are you sure? (y/n)" before running the action, e.g. for destructive operations
+    pub fn requires_confirmation(mut self, confirm: bool) -> Self {
+        self.confirm = confirm;
+        self
+    }
+
+    /// Register the inverse of the action, run with the same parameters by `undo`.
+    ///
+    /// `redo` runs the action again. Only actions that ran successfully can be undone.
+    pub fn with_undo<F>(mut self, undo: F) -> Self
+    where
+        F: Fn(&mut T, &Params) + MaybeSendSync + 'static,
+    {
+        self.undo = Some(Arc::new(undo));
+        self
+    }
+
+    /// Suggest values for the action's parameters when Tab is pressed while typing them.
+    ///
+    /// The function gets the word being typed and returns candidates; only those
+    /// starting with the word are offered.
+    pub fn with_completion<F>(mut self, completer: F) -> Self
+    where
+        F: Fn(&T, &str) -> Vec<String> + MaybeSendSync + 'static,
+    {
+        self.completer = Some(Arc::new(completer));
+        self
+    }
+
+    /// Set how the output of this item's action is routed
+    pub fn with_output_policy(mut self, policy: OutputPolicy) -> Self {
+        self.output_policy = policy;
+        self
+    }
+
+    /// Send the output of this item's action to a named output tab, e.g. "build".
+    ///
+    /// The tab opens the first time the action writes to it.
+    pub fn with_output_tab(mut self, tab: impl Into<String>) -> Self {
+        self.output_tab = Some(tab.into());
+        self
+    }
+
+    /// Style the description in the TUI menu, e.g. red and bold for a dangerous
+    /// action or highlighted for a recommended one, patched onto the theme
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// Parse the parameters with a clap command instead of parameter specs.
+    ///
+    /// `<key> --help` shows the generated help in the output pane, and invalid
+    /// parameters show clap's error instead of running the action. Actions read
+    /// the typed values from [`Params::matches`].
+    #[cfg(feature = "clap")]
+    pub fn with_clap(mut self, command: clap::Command) -> Self {
+        self.clap_command = Some(command);
+        self
+    }
+
+    /// Declare the parameters this item's action expects, in order
+    pub fn with_params(mut self, params: impl IntoIterator<Item = ParamSpec>) -> Self {
+        self.params = params.into_iter().collect();
+        self
+    }
+
+    /// Tag this item with categories, used to filter large menus
+    pub fn with_categories<S: Into<String>>(
+        mut self,
+        categories: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.categories = categories.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Check if this item is tagged with a category, ignoring case
+    pub fn has_category(&self, category: &str) -> bool {
+        self.categories
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(category))
+    }
+
+    /// Get the usage line for this item, e.g. `Usage: inc <amount:int>`
+    pub fn usage(&self) -> String {
+        params::usage(&self.key, &self.params)
+    }
 }
 
 /// A menu containing items that can be selected
@@ -76,8 +315,17 @@ pub struct Menu<T> {
     pub title: String,
     /// Items in this menu
     pub items: Vec<MenuItem<T>>,
-    /// Parent menu, if any
-    pub parent: Option<Arc<Mutex<Menu<T>>>>,
+    /// Action re-run to keep the menu's output current, if any
+    pub refresh: Option<MenuRefresh>,
+}
+
+/// An action a menu runs when it's entered and, optionally, on an interval while it's open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuRefresh {
+    /// Key of the action in the menu
+    pub key: String,
+    /// How often to run the action while the menu is open
+    pub interval: Option<Duration>,
 }
 
 impl<T> Default for Menu<T> {
@@ -85,7 +333,7 @@ impl<T> Default for Menu<T> {
         Self {
             title: "Menu".to_string(),
             items: Vec::new(),
-            parent: None,
+            refresh: None,
         }
     }
 }
@@ -96,16 +344,83 @@ impl<T> Menu<T> {
         Self {
             title: title.into(),
             items: Vec::new(),
-            parent: None,
+            refresh: None,
         }
     }
 
+    /// Run the action with the given key whenever the menu is entered, and every
+    /// `interval` while it's open, e.g. to keep a dashboard up to date.
+    ///
+    /// Give the action [`OutputPolicy::Replace`] so refreshes update its output in place.
+    pub fn set_refresh(&mut self, key: impl Into<String>, interval: Option<Duration>) -> &mut Self {
+        self.refresh = Some(MenuRefresh {
+            key: key.into(),
+            interval,
+        });
+        self
+    }
+
     /// Add an item to this menu
     pub fn add_item(&mut self, item: MenuItem<T>) -> &mut Self {
         self.items.push(item);
+        self.renumber();
         self
     }
 
+    /// Insert an item at the given position, renumbering auto-numbered items
+    pub fn insert_item(&mut self, index: usize, item: MenuItem<T>) -> &mut Self {
+        self.items.insert(index, item);
+        self.renumber();
+        self
+    }
+
+    /// Remove the item with the given key, renumbering auto-numbered items
+    pub fn remove_item(&mut self, key: &str) -> Option<MenuItem<T>> {
+        let idx = self
+            .items
+            .iter()
+            .position(|item| item.key.to_lowercase() == key.to_lowercase())?;
+        let item = self.items.remove(idx);
+        self.renumber();
+        Some(item)
+    }
+
+    /// Add an action whose key is assigned automatically: `1`-`9`, then `a`-`z`.
+    ///
+    /// Keys used by other items and reserved keys are skipped, and keys are
+    /// reassigned in order whenever items are inserted or removed.
+    pub fn add_auto_action<F, Marker>(
+        &mut self,
+        description: impl Into<String>,
+        action: F,
+    ) -> &mut Self
+    where
+        F: IntoActionFn<T, Marker>,
+    {
+        self.add_item(MenuItem::new_action("", description.into(), action).with_auto_key())
+    }
+
+    /// Reassign the keys of auto-numbered items in the order they appear
+    pub fn renumber(&mut self) {
+        let taken: HashSet<String> = self
+            .items
+            .iter()
+            .filter(|item| !item.auto_key)
+            .map(|item| item.key.to_lowercase())
+            .collect();
+
+        // Past `z`, keep counting so every item stays reachable
+        let mut keys = AUTO_KEYS
+            .chars()
+            .map(String::from)
+            .chain((10..).map(|n: usize| n.to_string()))
+            .filter(|key| !taken.contains(key) && !RESERVED_KEYS.contains(&key.as_str()));
+
+        for item in self.items.iter_mut().filter(|item| item.auto_key) {
+            item.key = keys.next().expect("auto key sequence is infinite");
+        }
+    }
+
     /// Add a synchronous action item to this menu
     pub fn add_action<F, Marker>(
         &mut self,
@@ -119,52 +434,212 @@ impl<T> Menu<T> {
         self.add_item(MenuItem::new_action(key, description.into(), action))
     }
 
+    /// Add an action whose closure mutates what it captured, wrapping it in a [`MutAction`]
+    pub fn add_action_mut<F, Marker>(
+        &mut self,
+        key: impl Into<String>,
+        description: impl Into<String>,
+        action: F,
+    ) -> &mut Self
+    where
+        MutAction<F>: IntoActionFn<T, Marker>,
+    {
+        self.add_action(key, description, MutAction::new(action))
+    }
+
     /// Add a submenu to this menu
     pub fn add_submenu(
         &mut self,
         key: impl Into<String>,
         description: impl Into<String>,
-        mut submenu: Menu<T>,
+        submenu: Menu<T>,
     ) -> &mut Self {
-        // We'll set the parent when navigating to the submenu
-        submenu.parent = None;
         self.add_item(MenuItem::new_submenu(key, description.into(), submenu))
     }
 
+    /// Mount a submenu that can also be mounted under other parents, e.g. a shared settings menu
+    pub fn add_shared_submenu(
+        &mut self,
+        key: impl Into<String>,
+        description: impl Into<String>,
+        submenu: SharedMenu<T>,
+    ) -> &mut Self {
+        self.add_item(MenuItem::new_shared_submenu(
+            key,
+            description.into(),
+            submenu,
+        ))
+    }
+
+    /// Wrap this menu so it can be mounted under several parents
+    pub fn into_shared(self) -> SharedMenu<T> {
+        Arc::new(Mutex::new(self))
+    }
+
     /// Get the item for a given key
     pub fn get_item(&self, key: &str) -> Option<&MenuItem<T>> {
         self.items.iter().find(|item| item.key == key)
     }
 
-    /// Validate menu structure to ensure no duplicate or reserved keys
+    /// Find the item for a given key, ignoring case like command input does
+    pub fn find_item(&self, key: &str) -> Option<&MenuItem<T>> {
+        self.items
+            .iter()
+            .find(|item| item.key.to_lowercase() == key.to_lowercase())
+    }
+
+    /// Get the categories used by the items of this menu, sorted and without duplicates
+    pub fn categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self
+            .items
+            .iter()
+            .flat_map(|item| item.categories.iter().map(|c| c.to_lowercase()))
+            .collect();
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+
+    /// Validate menu structure to ensure no duplicate, reserved or unreachable keys.
+    ///
+    /// The shape of the tree is checked too: submenus must not be empty, must not
+    /// lead back to an ancestor. Submenus shared by several parents are checked once.
+    ///
+    /// Every problem in the tree is collected: a single problem is returned as is,
+    /// several are returned together in an [`IstariError::ValidationReport`].
     pub fn validate_menu(menu: &Menu<T>) -> Result<(), IstariError> {
-        let mut seen_keys = std::collections::HashSet::new();
+        Self::analyze(menu, None)
+    }
+
+    /// Validate a menu tree, treating `root` as the menu's own location so that
+    /// submenus pointing back to it are reported as cycles
+    pub(crate) fn analyze(
+        menu: &Menu<T>,
+        root: Option<&Arc<Mutex<Menu<T>>>>,
+    ) -> Result<(), IstariError> {
+        let mut analysis = TreeAnalysis {
+            ancestors: vec![(root.map(Arc::as_ptr), menu.title.clone())],
+            path: Vec::new(),
+            visited: HashSet::new(),
+            problems: Vec::new(),
+        };
+        Self::collect_problems(menu, &mut analysis);
 
-        // Check for duplicate and reserved keys in this menu
+        let mut problems = analysis.problems;
+        match problems.len() {
+            0 => Ok(()),
+            1 => Err(problems.remove(0)),
+            _ => Err(IstariError::ValidationReport(problems)),
+        }
+    }
+
+    /// Collect the validation problems of a menu and its submenus
+    fn collect_problems(menu: &Menu<T>, analysis: &mut TreeAnalysis<T>) {
+        let problems = &mut analysis.problems;
+        let mut seen_keys = HashSet::new();
+
+        // Check for duplicate, reserved and unreachable keys in this menu
         for item in &menu.items {
-            // Check if key is reserved
-            if RESERVED_KEYS.contains(&item.key.as_str()) {
-                return Err(IstariError::ReservedCommand(
+            // Commands are matched ignoring case, so compare keys the same way
+            let normalized_key = item.key.to_lowercase();
+
+            if RESERVED_KEYS.contains(&normalized_key.as_str()) {
+                // Check if key is reserved
+                problems.push(IstariError::ReservedCommand(
+                    item.key.clone(),
+                    menu.title.clone(),
+                ));
+            } else if !seen_keys.insert(normalized_key) {
+                // Check if key is a duplicate, including keys differing only by case
+                problems.push(IstariError::DuplicateCommand(
+                    item.key.clone(),
+                    menu.title.clone(),
+                ));
+            } else if let Some(reason) = Self::unreachable_reason(&item.key) {
+                // Check if key can't be typed as a command
+                problems.push(IstariError::UnreachableCommand(
                     item.key.clone(),
                     menu.title.clone(),
+                    reason.to_string(),
                 ));
             }
 
-            // Check if key is a duplicate
-            if !seen_keys.insert(item.key.clone()) {
-                return Err(IstariError::DuplicateCommand(
+            // Check that the parameter specs make sense
+            for reason in params::spec_problems(&item.params) {
+                problems.push(IstariError::InvalidParamSpec(
                     item.key.clone(),
                     menu.title.clone(),
+                    reason,
                 ));
             }
+        }
+
+        // The refresh action must be an action of this menu
+        if let Some(refresh) = &menu.refresh
+            && menu
+                .find_item(&refresh.key)
+                .is_none_or(|item| item.action.is_none())
+        {
+            problems.push(IstariError::InvalidRefresh(
+                refresh.key.clone(),
+                menu.title.clone(),
+            ));
+        }
 
-            // Recursively validate submenu if it exists
+        // Recursively validate submenus, checking the shape of the tree on the way
+        for item in &menu.items {
             if let Some(submenu) = &item.submenu {
-                Self::validate_menu(&submenu.lock().unwrap())?;
+                Self::collect_submenu_problems(&item.key, submenu, analysis);
             }
         }
+    }
+
+    /// Check where a submenu sits in the tree, then validate its contents
+    fn collect_submenu_problems(
+        key: &str,
+        submenu: &Arc<Mutex<Menu<T>>>,
+        analysis: &mut TreeAnalysis<T>,
+    ) {
+        analysis.path.push(key.to_string());
+        let path = analysis.path.join("/");
+        let ptr = Arc::as_ptr(submenu);
+
+        // Ancestors are locked further up the stack, so check pointers before locking
+        if let Some((_, title)) = analysis
+            .ancestors
+            .iter()
+            .find(|(ancestor, _)| *ancestor == Some(ptr))
+        {
+            analysis
+                .problems
+                .push(IstariError::CyclicMenu(path, title.clone()));
+        } else if analysis.visited.insert(ptr) {
+            let submenu = submenu.lock().unwrap();
+            if submenu.items.is_empty() {
+                analysis
+                    .problems
+                    .push(IstariError::EmptyMenu(submenu.title.clone(), path));
+            }
+
+            analysis.ancestors.push((Some(ptr), submenu.title.clone()));
+            Self::collect_problems(&submenu, analysis);
+            analysis.ancestors.pop();
+        }
 
-        Ok(())
+        analysis.path.pop();
+    }
+
+    /// Explain why a key can never be matched by command input, if that is the case
+    fn unreachable_reason(key: &str) -> Option<&'static str> {
+        if key.is_empty() {
+            Some("key is empty")
+        } else if key.chars().any(char::is_whitespace) {
+            Some("key contains whitespace, which separates commands from parameters")
+        } else if key.starts_with('!') || key.starts_with('*') {
+            Some("keys starting with '!' or '*' run recent commands and favorites")
+        } else {
+            None
+        }
     }
 }
 
@@ -181,7 +656,6 @@ mod tests {
         let menu: Menu<TestState> = Menu::new("Test Menu".to_string());
         assert_eq!(menu.title, "Test Menu");
         assert!(menu.items.is_empty());
-        assert!(menu.parent.is_none());
     }
 
     #[test]
@@ -350,6 +824,20 @@ mod tests {
         assert!(cloned.submenu.is_none());
     }
 
+    #[test]
+    fn test_menu_item_output_policy() {
+        let item = MenuItem::new_action(
+            "1".to_string(),
+            "Status".to_string(),
+            |_state: &mut TestState, _params: Option<&str>| Some("OK".to_string()),
+        );
+        assert_eq!(item.output_policy, OutputPolicy::Append);
+
+        let item = item.with_output_policy(OutputPolicy::Replace);
+        assert_eq!(item.output_policy, OutputPolicy::Replace);
+        assert_eq!(item.clone().output_policy, OutputPolicy::Replace);
+    }
+
     #[test]
     fn test_menu_debug() {
         let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
@@ -363,4 +851,222 @@ mod tests {
         assert!(debug_string.contains("Test Menu"));
         assert!(debug_string.contains("Test Action"));
     }
+
+    #[test]
+    fn test_menu_validation_report() {
+        let mut root_menu: Menu<TestState> = Menu::new("Root Menu".to_string());
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+
+        root_menu.add_action("a", "Lowercase", |_: &mut TestState, _: Option<&str>| None);
+        root_menu.add_action("A", "Uppercase", |_: &mut TestState, _: Option<&str>| None);
+        submenu.add_action("Q", "Reserved", |_: &mut TestState, _: Option<&str>| None);
+        submenu.add_action("my cmd", "Spaces", |_: &mut TestState, _: Option<&str>| {
+            None
+        });
+        root_menu.add_submenu("s", "Go to Submenu", submenu);
+
+        // All problems are reported at once
+        match Menu::validate_menu(&root_menu) {
+            Err(IstariError::ValidationReport(problems)) => {
+                assert_eq!(problems.len(), 3);
+                assert!(matches!(
+                    &problems[0],
+                    IstariError::DuplicateCommand(key, menu) if key == "A" && menu == "Root Menu"
+                ));
+                assert!(matches!(
+                    &problems[1],
+                    IstariError::ReservedCommand(key, menu) if key == "Q" && menu == "Submenu"
+                ));
+                assert!(matches!(
+                    &problems[2],
+                    IstariError::UnreachableCommand(key, _, _) if key == "my cmd"
+                ));
+            }
+            other => panic!("Expected ValidationReport, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_menu_validation_param_specs() {
+        use crate::params::ParamType;
+
+        let mut menu: Menu<TestState> = Menu::new("Test Menu".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "1",
+                "Add".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_params([ParamSpec::optional("amount", ParamType::Integer).with_default("one")]),
+        );
+
+        let result = Menu::validate_menu(&menu);
+        if let Err(IstariError::InvalidParamSpec(key, menu_title, reason)) = result {
+            assert_eq!(key, "1");
+            assert_eq!(menu_title, "Test Menu");
+            assert_eq!(
+                reason,
+                "default 'one' of parameter 'amount' is not a valid int"
+            );
+        } else {
+            panic!("Expected InvalidParamSpec error");
+        }
+    }
+
+    #[test]
+    fn test_menu_validation_tree_shape() {
+        let mut shared: Menu<TestState> = Menu::new("Shared".to_string());
+        shared.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let shared = shared.into_shared();
+
+        let mut root_menu: Menu<TestState> = Menu::new("Root Menu".to_string());
+        root_menu.add_submenu("e", "Empty", Menu::new("Empty".to_string()));
+        root_menu.add_shared_submenu("a", "Shared", shared.clone());
+        root_menu.add_shared_submenu("b2", "Shared", shared.clone());
+
+        // Shared submenus are fine, empty ones are not
+        match Menu::validate_menu(&root_menu) {
+            Err(IstariError::EmptyMenu(title, path)) => {
+                assert_eq!(title, "Empty");
+                assert_eq!(path, "e");
+            }
+            other => panic!("Expected EmptyMenu error, got {:?}", other),
+        }
+
+        // Mounting a menu inside itself creates a cycle
+        shared
+            .lock()
+            .unwrap()
+            .add_item(MenuItem::new_shared_submenu(
+                "s",
+                "Again".to_string(),
+                shared.clone(),
+            ));
+        root_menu.items.remove(0);
+        let result = Menu::validate_menu(&root_menu);
+        shared.lock().unwrap().items.pop();
+        match result {
+            Err(IstariError::CyclicMenu(path, ancestor)) => {
+                assert_eq!(path, "a/s");
+                assert_eq!(ancestor, "Shared");
+            }
+            other => panic!("Expected CyclicMenu error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_menu_refresh_validation() {
+        let mut menu: Menu<TestState> = Menu::new("Dashboard");
+        menu.add_action("s", "Status", |_: &mut TestState, _: Option<&str>| None);
+        menu.add_submenu("x", "Details", Menu::new("Details"));
+        menu.items[1]
+            .submenu
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .add_action("d", "Detail", |_: &mut TestState, _: Option<&str>| None);
+
+        menu.set_refresh("S", None);
+        assert!(Menu::validate_menu(&menu).is_ok());
+
+        // Submenus aren't actions
+        menu.set_refresh("x", None);
+        match Menu::validate_menu(&menu) {
+            Err(IstariError::InvalidRefresh(key, title)) => {
+                assert_eq!(key, "x");
+                assert_eq!(title, "Dashboard");
+            }
+            other => panic!("Expected InvalidRefresh error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_menu_categories() {
+        let mut menu: Menu<TestState> = Menu::new("Tools".to_string());
+        menu.add_item(
+            MenuItem::new_action(
+                "p",
+                "Ping".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_categories(["Network"]),
+        );
+        menu.add_item(
+            MenuItem::new_action(
+                "w",
+                "Wipe".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_categories(["disk", "danger"]),
+        );
+
+        assert_eq!(menu.categories(), ["danger", "disk", "network"]);
+        assert!(menu.items[0].has_category("network"));
+        assert!(!menu.items[0].has_category("disk"));
+    }
+
+    #[test]
+    fn test_auto_numbered_keys() {
+        let mut menu: Menu<TestState> = Menu::new("Hosts".to_string());
+        menu.add_action("1", "Manual", |_: &mut TestState, _: Option<&str>| None);
+        for host in ["alpha", "beta", "gamma"] {
+            menu.add_auto_action(host, |_: &mut TestState, _: Option<&str>| None);
+        }
+        let keys = |menu: &Menu<TestState>| -> Vec<String> {
+            menu.items.iter().map(|item| item.key.clone()).collect()
+        };
+
+        // Manual keys are skipped
+        assert_eq!(keys(&menu), ["1", "2", "3", "4"]);
+
+        menu.insert_item(
+            1,
+            MenuItem::new_action(
+                "",
+                "first".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_auto_key(),
+        );
+        assert_eq!(keys(&menu), ["1", "2", "3", "4", "5"]);
+        assert_eq!(menu.get_item("2").unwrap().description, "first");
+
+        menu.remove_item("3");
+        assert_eq!(keys(&menu), ["1", "2", "3", "4"]);
+        assert_eq!(menu.get_item("3").unwrap().description, "beta");
+
+        // Reserved keys are never handed out
+        for n in 0..20 {
+            menu.add_auto_action(
+                format!("host {}", n),
+                |_: &mut TestState, _: Option<&str>| None,
+            );
+        }
+        assert!(menu.get_item("b").is_none());
+        assert!(Menu::validate_menu(&menu).is_ok());
+    }
+
+    #[test]
+    fn test_dynamic_description() {
+        let item = MenuItem::new_action(
+            "c",
+            "Counter".to_string(),
+            |state: &mut TestState, _: Option<&str>| {
+                state.counter += 1;
+                None
+            },
+        )
+        .with_dynamic_description(|state: &TestState| format!("Counter: {}", state.counter));
+
+        assert_eq!(item.describe(&TestState { counter: 42 }), "Counter: 42");
+        // Clones keep the description function
+        assert_eq!(
+            item.clone().describe(&TestState { counter: 1 }),
+            "Counter: 1"
+        );
+
+        let plain = MenuItem::new_submenu("s", "Settings".to_string(), Menu::new("Settings"));
+        assert_eq!(plain.describe(&TestState { counter: 0 }), "Settings");
+    }
 }
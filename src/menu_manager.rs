@@ -1,12 +1,45 @@
+use crate::context::ActionContext;
 use crate::error::IstariError;
-use crate::menu::Menu;
-use crate::types::ActionType;
+use crate::jobs::Job;
+use crate::menu::{Menu, MenuItem, SharedMenu};
+use crate::params::Params;
+use crate::types::{ActionOutcome, ActionResult, ActionType};
+use futures::future::BoxFuture;
 use std::sync::{Arc, Mutex};
 
+/// An action that was started
+pub(crate) enum Execution {
+    /// The action ran to completion
+    Finished(ActionResult),
+    /// The action is async and still has to be awaited
+    Running(BoxFuture<'static, ActionResult>),
+}
+
+/// Title of the menu holding the global actions
+const GLOBALS_TITLE: &str = "Global Commands";
+
+/// Number of visited menus remembered for [`MenuManager::history_back`]
+const NAVIGATION_HISTORY_SIZE: usize = 100;
+
 /// Manages menu navigation and action execution
 pub struct MenuManager<T> {
+    /// The root of the menu tree
+    root_menu: Arc<Mutex<Menu<T>>>,
     /// The current menu being displayed
     current_menu: Arc<Mutex<Menu<T>>>,
+    /// Menus entered on the way to the current menu, root first.
+    ///
+    /// Menus only hold their children, so back navigation lives here instead of
+    /// in parent pointers that would create reference cycles.
+    parents: Vec<Arc<Mutex<Menu<T>>>>,
+    /// Keys of the submenus entered to reach the current menu
+    path: Vec<String>,
+    /// App-level actions available from every menu
+    globals: Arc<Mutex<Menu<T>>>,
+    /// Paths of the menus visited before the current one, most recent last
+    history_back: Vec<Vec<String>>,
+    /// Paths of the menus left with [`MenuManager::history_back`], most recent last
+    history_forward: Vec<Vec<String>>,
 }
 
 impl<T: std::fmt::Debug> MenuManager<T> {
@@ -15,11 +48,75 @@ impl<T: std::fmt::Debug> MenuManager<T> {
         // Validate the menu structure
         Menu::validate_menu(&root_menu)?;
 
+        let root_menu = Arc::new(Mutex::new(root_menu));
         Ok(Self {
-            current_menu: Arc::new(Mutex::new(root_menu)),
+            root_menu: root_menu.clone(),
+            current_menu: root_menu,
+            parents: Vec::new(),
+            path: Vec::new(),
+            globals: Arc::new(Mutex::new(Menu::new(GLOBALS_TITLE))),
+            history_back: Vec::new(),
+            history_forward: Vec::new(),
         })
     }
 
+    /// Register an action available from every menu.
+    ///
+    /// Global actions are only matched when the current menu has no item with the same key.
+    pub fn add_global(&mut self, item: MenuItem<T>) -> Result<(), IstariError> {
+        if item.submenu.is_some() {
+            return Err(IstariError::UnreachableCommand(
+                item.key,
+                GLOBALS_TITLE.to_string(),
+                "global commands can't open submenus".to_string(),
+            ));
+        }
+
+        let mut globals = self.globals.lock().unwrap();
+        globals.add_item(item);
+        if let Err(e) = Menu::validate_menu(&globals) {
+            globals.items.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Get the menu holding the global actions
+    pub fn globals(&self) -> Arc<Mutex<Menu<T>>> {
+        self.globals.clone()
+    }
+
+    /// Find the menu an action runs in when invoked with `key` from the menu at `path`.
+    ///
+    /// Falls back to the global actions when the menu has no item with that key.
+    /// Returns the menu with the path to record for the action, which is empty for global actions.
+    pub fn resolve_action(
+        &self,
+        path: &[String],
+        key: &str,
+    ) -> Option<(SharedMenu<T>, Vec<String>)> {
+        let menu = self.menu_at(path)?;
+        let local_action = menu
+            .lock()
+            .unwrap()
+            .find_item(key)
+            .map(|item| item.action.is_some());
+
+        match local_action {
+            Some(true) => Some((menu, path.to_vec())),
+            Some(false) => None,
+            None => {
+                let has_global = self
+                    .globals
+                    .lock()
+                    .unwrap()
+                    .find_item(key)
+                    .is_some_and(|item| item.action.is_some());
+                has_global.then(|| (self.globals.clone(), Vec::new()))
+            }
+        }
+    }
+
     /// Get a reference to the current menu
     pub fn current_menu(&self) -> Arc<Mutex<Menu<T>>> {
         self.current_menu.clone()
@@ -27,6 +124,16 @@ impl<T: std::fmt::Debug> MenuManager<T> {
 
     /// Navigate to a submenu by key
     pub fn navigate_to_submenu(&mut self, key: &str) -> bool {
+        let from = self.path.clone();
+        let navigated = self.enter_submenu(key);
+        if navigated {
+            self.record_visit(from);
+        }
+        navigated
+    }
+
+    /// Enter a submenu of the current menu without recording it in the navigation history
+    fn enter_submenu(&mut self, key: &str) -> bool {
         // First find the menu item with the given key
         let (has_submenu, idx) = {
             let menu = self.current_menu.lock().unwrap();
@@ -44,25 +151,21 @@ impl<T: std::fmt::Debug> MenuManager<T> {
             (has_submenu, found_idx)
         };
 
-        if let Some(idx) = idx {
-            if has_submenu {
-                // Get the submenu
-                let submenu = {
-                    let menu = self.current_menu.lock().unwrap();
-                    let item = &menu.items[idx];
-                    item.submenu.as_ref().unwrap().clone()
-                };
-
-                // Set the parent of the submenu to the current menu
-                {
-                    let mut submenu_guard = submenu.lock().unwrap();
-                    submenu_guard.parent = Some(self.current_menu.clone());
-                }
-
-                // Update the current menu
-                self.current_menu = submenu;
-                return true;
-            }
+        if let Some(idx) = idx
+            && has_submenu
+        {
+            // Get the submenu
+            let (submenu, item_key) = {
+                let menu = self.current_menu.lock().unwrap();
+                let item = &menu.items[idx];
+                (item.submenu.as_ref().unwrap().clone(), item.key.clone())
+            };
+
+            // Remember where we came from and update the current menu
+            let parent = std::mem::replace(&mut self.current_menu, submenu);
+            self.parents.push(parent);
+            self.path.push(item_key);
+            return true;
         }
 
         false
@@ -70,36 +173,124 @@ impl<T: std::fmt::Debug> MenuManager<T> {
 
     /// Navigate back to the parent menu
     pub fn navigate_back(&mut self) -> bool {
-        let parent = {
-            let menu = self.current_menu.lock().unwrap();
-            menu.parent.clone()
-        };
-
-        if let Some(parent_menu) = parent {
+        if let Some(parent_menu) = self.parents.pop() {
+            let from = self.path.clone();
             self.current_menu = parent_menu;
+            self.path.pop();
+            self.record_visit(from);
             true
         } else {
             false
         }
     }
 
+    /// Jump straight to the menu at the given path of submenu keys
+    pub fn navigate_to_path(&mut self, path: &[String]) -> bool {
+        let from = self.path.clone();
+        let navigated = self.set_path(path);
+        if navigated && from != self.path {
+            self.record_visit(from);
+        }
+        navigated
+    }
+
+    /// Return to the menu visited before the current one, like a browser's back button
+    pub fn history_back(&mut self) -> bool {
+        while let Some(previous) = self.history_back.pop() {
+            let current = self.path.clone();
+            // Menus may have changed since, so skip entries that no longer resolve
+            if self.set_path(&previous) {
+                self.history_forward.push(current);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Undo a [`MenuManager::history_back`], like a browser's forward button
+    pub fn history_forward(&mut self) -> bool {
+        while let Some(next) = self.history_forward.pop() {
+            let current = self.path.clone();
+            if self.set_path(&next) {
+                self.history_back.push(current);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Remember a menu that was just left
+    fn record_visit(&mut self, from: Vec<String>) {
+        self.history_back.push(from);
+        if self.history_back.len() > NAVIGATION_HISTORY_SIZE {
+            self.history_back.remove(0);
+        }
+        self.history_forward.clear();
+    }
+
+    /// Rebuild the navigation state for the menu at the given path, leaving it untouched on failure
+    fn set_path(&mut self, path: &[String]) -> bool {
+        let mut parents = Vec::new();
+        let mut current = self.root_menu.clone();
+        let mut keys = Vec::new();
+
+        for key in path {
+            let entered = {
+                let menu = current.lock().unwrap();
+                menu.find_item(key)
+                    .and_then(|item| Some((item.submenu.clone()?, item.key.clone())))
+            };
+            let Some((submenu, item_key)) = entered else {
+                return false;
+            };
+            parents.push(std::mem::replace(&mut current, submenu));
+            keys.push(item_key);
+        }
+
+        self.current_menu = current;
+        self.parents = parents;
+        self.path = keys;
+        true
+    }
+
     /// Check if the current menu is the root menu
     pub fn is_at_root(&self) -> bool {
-        let menu = self.current_menu.lock().unwrap();
-        menu.parent.is_none()
+        self.parents.is_empty()
     }
 
-    /// Find a menu item by key
-    fn find_item_idx(&self, key: &str) -> Option<usize> {
-        let menu = self.current_menu.lock().unwrap();
+    /// Get the keys of the submenus entered to reach the current menu
+    pub fn current_path(&self) -> &[String] {
+        &self.path
+    }
 
-        for (idx, item) in menu.items.iter().enumerate() {
-            if item.key.to_lowercase() == key.to_lowercase() {
-                return Some(idx);
-            }
+    /// Get the root menu
+    pub fn root_menu(&self) -> Arc<Mutex<Menu<T>>> {
+        self.root_menu.clone()
+    }
+
+    /// Validate the live menu tree, e.g. after menus were changed at runtime.
+    ///
+    /// Unlike [`Menu::validate_menu`], this also catches submenus pointing back to the root.
+    pub fn validate(&self) -> Result<(), IstariError> {
+        Menu::analyze(&self.root_menu.lock().unwrap(), Some(&self.root_menu))
+    }
+
+    /// Resolve a menu by the keys of the submenus leading to it from the root
+    pub fn menu_at(&self, path: &[String]) -> Option<Arc<Mutex<Menu<T>>>> {
+        let mut menu = self.root_menu.clone();
+        for key in path {
+            let submenu = menu.lock().unwrap().find_item(key)?.submenu.clone()?;
+            menu = submenu;
         }
+        Some(menu)
+    }
 
-        None
+    /// Find a menu item by key
+    fn find_item_idx(&self, key: &str) -> Option<usize> {
+        let menu = self.current_menu.lock().unwrap();
+        menu.items
+            .iter()
+            .position(|item| item.key.to_lowercase() == key.to_lowercase())
     }
 
     /// Check if a menu item has an action
@@ -129,25 +320,71 @@ impl<T: std::fmt::Debug> MenuManager<T> {
         &mut self,
         key: &str,
         state: &mut T,
-        params: Option<&str>,
+        params: &Params,
         runtime: &tokio::runtime::Runtime,
-    ) -> Option<String> {
-        let idx = self.find_item_idx(key)?;
+    ) -> ActionResult {
+        let menu = self.current_menu.clone();
+        Self::execute_in(&menu, key, state, params, runtime)
+    }
 
-        // Execute the action
-        let menu = self.current_menu.lock().unwrap();
-        let item = &menu.items[idx];
+    /// Execute an action for a menu item by key in the menu at the given path
+    pub fn execute_action_at(
+        &mut self,
+        path: &[String],
+        key: &str,
+        state: &mut T,
+        params: &Params,
+        runtime: &tokio::runtime::Runtime,
+    ) -> ActionResult {
+        let Some(menu) = self.menu_at(path) else {
+            return Ok(ActionOutcome::new());
+        };
+        Self::execute_in(&menu, key, state, params, runtime)
+    }
 
-        // If there's no action, return None
-        let action = item.action.as_ref()?;
+    /// Execute an action for a menu item by key in the given menu, waiting for async actions
+    pub(crate) fn execute_in(
+        menu: &Arc<Mutex<Menu<T>>>,
+        key: &str,
+        state: &mut T,
+        params: &Params,
+        runtime: &tokio::runtime::Runtime,
+    ) -> ActionResult {
+        match Self::start_in(
+            menu,
+            key,
+            state,
+            params,
+            &Job::detached(runtime.handle().clone()),
+        ) {
+            Execution::Finished(result) => result,
+            Execution::Running(future) => runtime.block_on(future),
+        }
+    }
+
+    /// Start an action for a menu item by key in the given menu.
+    ///
+    /// Sync actions run to completion; async actions hand back their future,
+    /// which doesn't borrow the state, so it can run in the background.
+    pub(crate) fn start_in(
+        menu: &Arc<Mutex<Menu<T>>>,
+        key: &str,
+        state: &mut T,
+        params: &Params,
+        job: &Job,
+    ) -> Execution {
+        let menu = menu.lock().unwrap();
+
+        // If there's no such item or it has no action, there's no output
+        let Some(action) = menu.find_item(key).and_then(|item| item.action.as_ref()) else {
+            return Execution::Finished(Ok(ActionOutcome::new()));
+        };
 
         // Call the action
+        let mut ctx = ActionContext::new(state, params, &menu, job);
         match action {
-            ActionType::Sync(sync_fn) => sync_fn(state, params),
-            ActionType::Async(async_fn) => runtime.block_on(async {
-                let future = async_fn(state, params);
-                future.await
-            }),
+            ActionType::Sync(sync_fn) => Execution::Finished(sync_fn(&mut ctx)),
+            ActionType::Async(async_fn) => Execution::Running(async_fn(&mut ctx)),
         }
     }
 }
@@ -155,7 +392,7 @@ impl<T: std::fmt::Debug> MenuManager<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::menu::MenuItem;
+    use crate::output::ActionOutput;
 
     #[derive(Debug)]
     struct TestState {
@@ -165,12 +402,10 @@ mod tests {
     #[test]
     fn test_menu_navigation() {
         // Create a submenu
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
         let mut root_menu: Menu<TestState> = Menu::new("Root".to_string());
-        root_menu.add_item(MenuItem::new_submenu(
-            "s",
-            "Submenu".to_string(),
-            Menu::<TestState>::new("Submenu".to_string()),
-        ));
+        root_menu.add_item(MenuItem::new_submenu("s", "Submenu".to_string(), submenu));
 
         // Create the menu manager
         let mut manager = MenuManager::new(root_menu).unwrap();
@@ -181,6 +416,7 @@ mod tests {
         // Navigate to submenu
         assert!(manager.navigate_to_submenu("s"));
         assert!(!manager.is_at_root());
+        assert_eq!(manager.current_path(), ["s".to_string()]);
 
         // Navigate back
         assert!(manager.navigate_back());
@@ -188,6 +424,7 @@ mod tests {
 
         // Try to navigate back from root
         assert!(!manager.navigate_back());
+        assert!(manager.current_path().is_empty());
 
         // Navigate to a non-existent submenu
         assert!(!manager.navigate_to_submenu("x"));
@@ -217,17 +454,200 @@ mod tests {
 
         // Execute the action
         let runtime = tokio::runtime::Runtime::new().unwrap();
-        let result = manager.execute_action("a", &mut state, None, &runtime);
-        assert_eq!(result, Some("Counter: 1".to_string()));
+        let result = manager.execute_action("a", &mut state, &Params::default(), &runtime);
+        assert_eq!(result, Ok(ActionOutput::from("Counter: 1").into()));
         assert_eq!(state.counter, 1);
 
         // Execute with parameters
-        let result = manager.execute_action("a", &mut state, Some("param"), &runtime);
-        assert_eq!(result, Some("Counter: 2".to_string()));
+        let result = manager.execute_action("a", &mut state, &Params::parse("param"), &runtime);
+        assert_eq!(result, Ok(ActionOutput::from("Counter: 2").into()));
         assert_eq!(state.counter, 2);
 
         // Execute non-existent action
-        let result = manager.execute_action("x", &mut state, None, &runtime);
-        assert_eq!(result, None);
+        let result = manager.execute_action("x", &mut state, &Params::default(), &runtime);
+        assert_eq!(result, Ok(ActionOutcome::new()));
+    }
+
+    #[test]
+    fn test_action_execution_at_path() {
+        let mut state = TestState { counter: 0 };
+
+        let mut submenu = Menu::new("Submenu".to_string());
+        submenu.add_action(
+            "a",
+            "Increment",
+            |state: &mut TestState, _: Option<&str>| {
+                state.counter += 1;
+                Some(format!("Counter: {}", state.counter))
+            },
+        );
+        let mut root_menu = Menu::new("Root".to_string());
+        root_menu.add_submenu("s", "Submenu", submenu);
+
+        let mut manager = MenuManager::new(root_menu).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // The action is reachable from the root without navigating
+        let path = ["S".to_string()];
+        assert!(manager.menu_at(&path).is_some());
+        let result =
+            manager.execute_action_at(&path, "a", &mut state, &Params::default(), &runtime);
+        assert_eq!(result, Ok(ActionOutput::from("Counter: 1").into()));
+        assert!(manager.is_at_root());
+
+        assert!(manager.menu_at(&["x".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_menus_are_freed() {
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        let mut deeper: Menu<TestState> = Menu::new("Deeper".to_string());
+        deeper.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        submenu.add_submenu("d", "Deeper", deeper);
+        let mut root_menu: Menu<TestState> = Menu::new("Root".to_string());
+        root_menu.add_submenu("s", "Submenu", submenu);
+
+        let mut manager = MenuManager::new(root_menu).unwrap();
+        assert!(manager.navigate_to_submenu("s"));
+        assert!(manager.navigate_to_submenu("d"));
+
+        // Navigation must not leave cycles behind that keep the tree alive
+        let root = Arc::downgrade(&manager.root_menu());
+        let deeper = Arc::downgrade(&manager.current_menu());
+        drop(manager);
+        assert!(root.upgrade().is_none());
+        assert!(deeper.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_runtime_cycle_detection() {
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut root_menu: Menu<TestState> = Menu::new("Root".to_string());
+        root_menu.add_submenu("s", "Submenu", submenu);
+
+        let manager = MenuManager::new(root_menu).unwrap();
+        assert!(manager.validate().is_ok());
+
+        // Point the submenu back at the root after construction
+        let root = manager.root_menu();
+        let submenu = manager.menu_at(&["s".to_string()]).unwrap();
+        submenu
+            .lock()
+            .unwrap()
+            .add_shared_submenu("r", "Back to Root", root.clone());
+
+        match manager.validate() {
+            Err(IstariError::CyclicMenu(path, ancestor)) => {
+                assert_eq!(path, "s/r");
+                assert_eq!(ancestor, "Root");
+            }
+            other => panic!("Expected CyclicMenu error, got {:?}", other),
+        }
+
+        // Break the cycle so the tree can be freed
+        submenu.lock().unwrap().items.pop();
+    }
+
+    #[test]
+    fn test_global_actions() {
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_action("x", "Local", |_: &mut TestState, _: Option<&str>| None);
+        let mut root_menu: Menu<TestState> = Menu::new("Root".to_string());
+        root_menu.add_submenu("s", "Submenu", submenu);
+
+        let mut manager = MenuManager::new(root_menu).unwrap();
+        manager
+            .add_global(MenuItem::new_action(
+                "status",
+                "Status".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            ))
+            .unwrap();
+
+        // Reserved and duplicate keys are rejected
+        let reserved = MenuItem::new_action(
+            "q",
+            "Quit".to_string(),
+            |_: &mut TestState, _: Option<&str>| None,
+        );
+        assert!(matches!(
+            manager.add_global(reserved),
+            Err(IstariError::ReservedCommand(_, _))
+        ));
+        assert_eq!(manager.globals().lock().unwrap().items.len(), 1);
+
+        // Global actions resolve from any menu, local items win
+        let path = vec!["s".to_string()];
+        let (menu, resolved_path) = manager.resolve_action(&path, "status").unwrap();
+        assert!(Arc::ptr_eq(&menu, &manager.globals()));
+        assert!(resolved_path.is_empty());
+        let (_, resolved_path) = manager.resolve_action(&path, "x").unwrap();
+        assert_eq!(resolved_path, path);
+        assert!(manager.resolve_action(&path, "missing").is_none());
+    }
+
+    #[test]
+    fn test_shared_submenu_navigation() {
+        let mut settings: Menu<TestState> = Menu::new("Settings".to_string());
+        settings.add_action("t", "Theme", |_: &mut TestState, _: Option<&str>| None);
+        let settings = settings.into_shared();
+
+        let mut user_menu: Menu<TestState> = Menu::new("User".to_string());
+        user_menu.add_shared_submenu("s", "Settings", settings.clone());
+        let mut root_menu: Menu<TestState> = Menu::new("Root".to_string());
+        root_menu.add_shared_submenu("s", "Settings", settings.clone());
+        root_menu.add_submenu("u", "User", user_menu);
+
+        let mut manager = MenuManager::new(root_menu).unwrap();
+
+        // Back navigation follows the path the shared menu was entered through
+        assert!(manager.navigate_to_submenu("u"));
+        assert!(manager.navigate_to_submenu("s"));
+        assert!(Arc::ptr_eq(&manager.current_menu(), &settings));
+        assert_eq!(manager.current_path(), ["u", "s"]);
+        assert!(manager.navigate_back());
+        assert_eq!(manager.current_menu().lock().unwrap().title, "User");
+
+        assert!(Arc::ptr_eq(
+            &manager.menu_at(&["s".to_string()]).unwrap(),
+            &settings
+        ));
+    }
+
+    #[test]
+    fn test_navigation_history() {
+        let mut deeper: Menu<TestState> = Menu::new("Deeper".to_string());
+        deeper.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut submenu: Menu<TestState> = Menu::new("Submenu".to_string());
+        submenu.add_submenu("d", "Deeper", deeper);
+        let mut other: Menu<TestState> = Menu::new("Other".to_string());
+        other.add_action("x", "Action", |_: &mut TestState, _: Option<&str>| None);
+        let mut root_menu: Menu<TestState> = Menu::new("Root".to_string());
+        root_menu.add_submenu("s", "Submenu", submenu);
+        root_menu.add_submenu("o", "Other", other);
+
+        let mut manager = MenuManager::new(root_menu).unwrap();
+        assert!(!manager.history_back());
+
+        // Jump around without following the tree structure
+        assert!(manager.navigate_to_path(&["s".to_string(), "d".to_string()]));
+        assert!(manager.navigate_to_path(&["o".to_string()]));
+        assert!(!manager.navigate_to_path(&["missing".to_string()]));
+        assert_eq!(manager.current_path(), ["o"]);
+
+        assert!(manager.history_back());
+        assert_eq!(manager.current_path(), ["s", "d"]);
+        // Structural back still goes to the parent
+        assert!(manager.navigate_back());
+        assert_eq!(manager.current_path(), ["s"]);
+        assert!(!manager.history_forward());
+
+        assert!(manager.history_back());
+        assert_eq!(manager.current_path(), ["s", "d"]);
+        assert!(manager.history_back());
+        assert!(manager.is_at_root());
+        assert!(manager.history_forward());
+        assert_eq!(manager.current_path(), ["s", "d"]);
     }
 }
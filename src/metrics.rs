@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+/// Samples a sparkline keeps, the oldest are dropped first
+pub const SPARKLINE_SAMPLES: usize = 60;
+
+/// Bars of the text rendering of a sparkline, lowest first
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Reads a number from the application state, for a sparkline or a gauge
+#[cfg(not(feature = "single-threaded"))]
+pub type MetricFn<T> = Box<dyn Fn(&T) -> f64 + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type MetricFn<T> = Box<dyn Fn(&T) -> f64>;
+
+/// A number of the state shown in the stats pane, added with
+/// [`Istari::with_sparkline`](crate::Istari::with_sparkline) and
+/// [`Istari::with_gauge`](crate::Istari::with_gauge)
+pub(crate) struct Metric<T> {
+    label: String,
+    read: MetricFn<T>,
+    /// Samples taken every tick for a sparkline, `None` for a gauge
+    history: Option<VecDeque<u64>>,
+}
+
+impl<T> Metric<T> {
+    pub(crate) fn sparkline(label: String, read: MetricFn<T>) -> Self {
+        Self {
+            label,
+            read,
+            history: Some(VecDeque::with_capacity(SPARKLINE_SAMPLES)),
+        }
+    }
+
+    pub(crate) fn gauge(label: String, read: MetricFn<T>) -> Self {
+        Self {
+            label,
+            read,
+            history: None,
+        }
+    }
+
+    /// Add a sample of the state to a sparkline
+    pub(crate) fn sample(&mut self, state: &T) {
+        let value = (self.read)(state);
+        if let Some(history) = &mut self.history {
+            if history.len() == SPARKLINE_SAMPLES {
+                history.pop_front();
+            }
+            // Negative and NaN values draw as empty bars
+            history.push_back(value.max(0.0) as u64);
+        }
+    }
+
+    /// Get what the stats pane shows of the metric
+    pub(crate) fn value(&self, state: &T) -> MetricValue<'_> {
+        match &self.history {
+            Some(history) => MetricValue::Sparkline {
+                label: &self.label,
+                samples: history.iter().copied().collect(),
+            },
+            None => MetricValue::Gauge {
+                label: &self.label,
+                ratio: (self.read)(state).clamp(0.0, 1.0),
+            },
+        }
+    }
+}
+
+/// A metric of the stats pane as the renderers draw it
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue<'a> {
+    /// The samples of the last ticks, oldest first
+    Sparkline { label: &'a str, samples: Vec<u64> },
+    /// A ratio between 0 and 1
+    Gauge { label: &'a str, ratio: f64 },
+}
+
+impl MetricValue<'_> {
+    /// Get the label of the metric
+    pub fn label(&self) -> &str {
+        match self {
+            MetricValue::Sparkline { label, .. } | MetricValue::Gauge { label, .. } => label,
+        }
+    }
+
+    /// Describe the metric in text, e.g. `▁▃▅█ 42` or `75%`
+    pub fn to_text(&self) -> String {
+        match self {
+            MetricValue::Sparkline { samples, .. } => {
+                let max = samples.iter().copied().max().unwrap_or(0).max(1);
+                let bars: String = samples
+                    .iter()
+                    .map(|&sample| {
+                        let level = sample * (SPARK_BARS.len() as u64 - 1) / max;
+                        SPARK_BARS[level as usize]
+                    })
+                    .collect();
+                match samples.last() {
+                    Some(last) => format!("{} {}", bars, last),
+                    None => "-".to_string(),
+                }
+            }
+            MetricValue::Gauge { ratio, .. } => format!("{:.0}%", ratio * 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let mut sparkline = Metric::sparkline("load".to_string(), Box::new(|n: &f64| *n));
+        assert_eq!(sparkline.value(&0.0).to_text(), "-");
+        for n in 0..=SPARKLINE_SAMPLES {
+            sparkline.sample(&(n as f64));
+        }
+        let MetricValue::Sparkline { samples, .. } = sparkline.value(&0.0) else {
+            panic!("expected a sparkline");
+        };
+        // The oldest sample was dropped
+        assert_eq!(samples.len(), SPARKLINE_SAMPLES);
+        assert_eq!(samples[0], 1);
+
+        let mut short = Metric::sparkline("load".to_string(), Box::new(|n: &f64| *n));
+        for n in [0.0, 4.0, -1.0, 7.0] {
+            short.sample(&n);
+        }
+        assert_eq!(short.value(&0.0).to_text(), "▁▅▁█ 7");
+
+        let gauge = Metric::gauge("disk".to_string(), Box::new(|n: &f64| *n));
+        assert_eq!(
+            gauge.value(&0.75),
+            MetricValue::Gauge {
+                label: "disk",
+                ratio: 0.75
+            }
+        );
+        assert_eq!(gauge.value(&1.5).to_text(), "100%");
+        assert_eq!(gauge.value(&0.5).label(), "disk");
+    }
+}
@@ -0,0 +1,534 @@
+use crate::clock::UtcTime;
+use serde::Serialize;
+use std::sync::mpsc::Sender;
+use std::time::{SystemTime, UNIX_EPOCH};
+use unicode_width::UnicodeWidthStr;
+
+/// Severity of an output message, used to style it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLevel {
+    /// Diagnostic details
+    Debug,
+    /// Regular output
+    #[default]
+    Info,
+    /// Something the user should look at
+    Warn,
+    /// Something went wrong
+    Error,
+}
+
+impl OutputLevel {
+    /// Every level, lowest first
+    pub const ALL: [OutputLevel; 4] = [
+        OutputLevel::Debug,
+        OutputLevel::Info,
+        OutputLevel::Warn,
+        OutputLevel::Error,
+    ];
+
+    /// Get the name used by the `loglevel` command
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputLevel::Debug => "debug",
+            OutputLevel::Info => "info",
+            OutputLevel::Warn => "warn",
+            OutputLevel::Error => "error",
+        }
+    }
+
+    /// Get the level the `loglevel` command names, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Format the time of day of an output message as `HH:MM:SS`.
+///
+/// The time is in UTC, not the local time zone, like the times of session logs,
+/// and renderers label it as such.
+pub fn format_clock_time(time: SystemTime) -> String {
+    let time = UtcTime::from_system_time(time);
+    format!("{:02}:{:02}:{:02}", time.hour, time.minute, time.second)
+}
+
+/// An output message kept by an [`OutputBuffer`](crate::OutputBuffer), with where it
+/// came from and how to show it.
+///
+/// Records serialize with serde, e.g. to log a session, see
+/// [`Istari::export_output`](crate::Istari::export_output). Their time serializes as
+/// `timestamp`, in milliseconds since the Unix epoch, and their format is left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputRecord {
+    /// When the message was added or last replaced
+    #[serde(rename = "timestamp", serialize_with = "serialize_millis")]
+    pub time: SystemTime,
+    /// Level of the message
+    pub level: OutputLevel,
+    /// What produced the message, e.g. a background job, if known
+    pub source: Option<String>,
+    /// The message
+    pub text: String,
+    /// How the message is formatted
+    #[serde(skip)]
+    pub format: OutputFormat,
+    /// Times the message was added in a row, more than 1 once duplicates collapse
+    pub repeats: usize,
+    /// Path of the menu the message was added in, its keys joined by `/`
+    pub menu: String,
+}
+
+/// Serialize a time as milliseconds since the Unix epoch
+fn serialize_millis<S: serde::Serializer>(
+    time: &SystemTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or(0);
+    serializer.serialize_u64(millis)
+}
+
+/// How an output message is formatted, so renderers can style it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Plain text
+    #[default]
+    Plain,
+    /// An aligned table whose first line holds the headers, with its cells
+    /// so the TUI can draw it as a bordered table
+    Table(OutputTable),
+    /// Markdown, styled by the TUI and shown as is by the Text renderer
+    Markdown,
+    /// Code in the given language, highlighted by the TUI with the `highlight` feature
+    Code(String),
+}
+
+/// Structured output of an action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionOutput {
+    /// A single message
+    PlainText(String),
+    /// Several lines shown together
+    Lines(Vec<String>),
+    /// Rows shown in aligned columns under a header
+    Table(OutputTable),
+    /// Key-value pairs shown with aligned values
+    KeyValue(Vec<(String, String)>),
+    /// Markdown text
+    Markdown(String),
+    /// Code in a language like `json`, `yaml` or `rust`
+    Code { language: String, code: String },
+}
+
+impl ActionOutput {
+    /// Create a table from its headers and rows
+    pub fn table<H, R, C>(headers: H, rows: impl IntoIterator<Item = R>) -> Self
+    where
+        H: IntoIterator,
+        H::Item: Into<String>,
+        R: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        let mut table = OutputTable::new(headers);
+        for row in rows {
+            table.push_row(row);
+        }
+        Self::Table(table)
+    }
+
+    /// Create code output in the given language
+    pub fn code(language: impl Into<String>, code: impl Into<String>) -> Self {
+        Self::Code {
+            language: language.into(),
+            code: code.into(),
+        }
+    }
+
+    /// Create JSON code output of a serializable value, pretty-printed
+    pub fn json<V: Serialize + ?Sized>(value: &V) -> Self {
+        // Values with non-string map keys can't be serialized, show what they look like instead
+        let code = serde_json::to_string_pretty(value).unwrap_or_else(|e| e.to_string());
+        Self::code("json", code)
+    }
+
+    /// Pretty-print JSON output: plain text holding a JSON object or array, and
+    /// code tagged as `json`, are indented and tagged as `json` so the TUI colors
+    /// their keys. Other output is returned as is.
+    pub fn pretty_printed(self) -> Self {
+        match self {
+            Self::PlainText(text) if text.trim_start().starts_with(['{', '[']) => {
+                match serde_json::from_str::<serde_json::Value>(&text) {
+                    Ok(value) => Self::json(&value),
+                    Err(_) => Self::PlainText(text),
+                }
+            }
+            Self::Code { language, code } if language.eq_ignore_ascii_case("json") => {
+                match serde_json::from_str::<serde_json::Value>(&code) {
+                    Ok(value) => Self::json(&value),
+                    Err(_) => Self::Code { language, code },
+                }
+            }
+            output => output,
+        }
+    }
+
+    /// Get the format renderers should style the output with
+    pub fn format(&self) -> OutputFormat {
+        match self {
+            Self::Table(table) => OutputFormat::Table(table.clone()),
+            Self::Markdown(_) => OutputFormat::Markdown,
+            Self::Code { language, .. } => OutputFormat::Code(language.clone()),
+            _ => OutputFormat::Plain,
+        }
+    }
+
+    /// Format the output as plain text, aligning tables and key-value pairs
+    pub fn to_plain_text(&self) -> String {
+        match self {
+            Self::PlainText(text) | Self::Markdown(text) | Self::Code { code: text, .. } => {
+                text.clone()
+            }
+            Self::Lines(lines) => lines.join("\n"),
+            Self::Table(table) => table.to_plain_text(),
+            Self::KeyValue(pairs) => {
+                let width = pairs.iter().map(|(key, _)| key.width()).max().unwrap_or(0);
+                pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        format!("{}:{} {}", key, " ".repeat(width - key.width()), value)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+}
+
+/// Rows of cells under a header, drawn as a bordered table by the TUI and
+/// as aligned columns by the Text renderer
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OutputTable {
+    /// Header of each column
+    pub headers: Vec<String>,
+    /// Cells of each row, missing cells are left empty
+    pub rows: Vec<Vec<String>>,
+}
+
+impl OutputTable {
+    /// Create a table without rows
+    pub fn new<H>(headers: H) -> Self
+    where
+        H: IntoIterator,
+        H::Item: Into<String>,
+    {
+        Self {
+            headers: headers.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row
+    pub fn with_row<C: Into<String>>(mut self, row: impl IntoIterator<Item = C>) -> Self {
+        self.push_row(row);
+        self
+    }
+
+    /// Add a row
+    pub fn push_row<C: Into<String>>(&mut self, row: impl IntoIterator<Item = C>) {
+        self.rows.push(row.into_iter().map(Into::into).collect());
+    }
+
+    /// Get the number of columns, the most cells of the header and any row
+    pub fn columns(&self) -> usize {
+        self.rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.headers.len()])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Get the width of each column, that of its widest cell
+    pub fn column_widths(&self) -> Vec<usize> {
+        let mut widths = vec![0; self.columns()];
+        for row in self.rows.iter().chain([&self.headers]) {
+            for (idx, cell) in row.iter().enumerate() {
+                widths[idx] = widths[idx].max(cell.width());
+            }
+        }
+        widths
+    }
+
+    /// Lay out the table in columns as wide as their widest cell, with a rule under the headers
+    pub fn to_plain_text(&self) -> String {
+        let widths = self.column_widths();
+        let format_row = |row: &[String]| {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(idx, width)| {
+                    let cell = row.get(idx).map(String::as_str).unwrap_or("");
+                    format!("{}{}", cell, " ".repeat(width - cell.width()))
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+
+        let mut lines = vec![format_row(&self.headers)];
+        lines.push(
+            widths
+                .iter()
+                .map(|width| "-".repeat(*width))
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+        lines.extend(self.rows.iter().map(|row| format_row(row)));
+        lines.join("\n")
+    }
+}
+
+impl From<OutputTable> for ActionOutput {
+    fn from(table: OutputTable) -> Self {
+        Self::Table(table)
+    }
+}
+
+impl From<String> for ActionOutput {
+    fn from(text: String) -> Self {
+        Self::PlainText(text)
+    }
+}
+
+impl From<&str> for ActionOutput {
+    fn from(text: &str) -> Self {
+        Self::PlainText(text.to_string())
+    }
+}
+
+/// A change to the output buffer sent through an [`OutputHandle`]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum OutputCommand {
+    /// Add a message
+    Push {
+        source: Option<String>,
+        level: OutputLevel,
+        message: String,
+    },
+    /// Replace the most recent message of a source, or add it
+    ReplaceLast {
+        source: Option<String>,
+        message: String,
+    },
+    /// Remove all messages
+    Clear,
+    /// Apply a change to a named output tab instead of the main output
+    ToTab {
+        tab: String,
+        command: Box<OutputCommand>,
+    },
+    /// Quit the application
+    Quit,
+    /// Update the progress of a running action, see [`Progress`](crate::Progress)
+    Progress {
+        job: u64,
+        percent: Option<u8>,
+        message: String,
+    },
+}
+
+/// Write access to the output buffer, handed to tick handlers and background jobs.
+///
+/// Handles are cheap to clone and can be moved to other threads. Writes are
+/// applied on the next tick of the application.
+#[derive(Debug, Clone)]
+pub struct OutputHandle {
+    /// Channel drained by the application
+    sender: Sender<OutputCommand>,
+    /// Source tagged on every message written through this handle
+    source: Option<String>,
+    /// Output tab written to, the main output if `None`
+    tab: Option<String>,
+}
+
+impl OutputHandle {
+    /// Create a handle writing to the given channel
+    pub(crate) fn new(sender: Sender<OutputCommand>, source: Option<String>) -> Self {
+        Self {
+            sender,
+            source,
+            tab: None,
+        }
+    }
+
+    /// Get a handle tagging its messages with another source
+    pub fn with_source(&self, source: impl Into<String>) -> Self {
+        Self {
+            source: Some(source.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Get a handle writing to the named output tab, opening it on the first write
+    pub fn with_tab(&self, tab: impl Into<String>) -> Self {
+        Self {
+            tab: Some(tab.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Add a message
+    pub fn push(&self, message: impl Into<String>) {
+        self.push_level(OutputLevel::Info, message);
+    }
+
+    /// Add a message with the given level
+    pub fn push_level(&self, level: OutputLevel, message: impl Into<String>) {
+        self.send_output(OutputCommand::Push {
+            source: self.source.clone(),
+            level,
+            message: message.into(),
+        });
+    }
+
+    /// Add a unified diff of two texts, see [`unified_diff`](crate::diff::unified_diff)
+    pub fn push_diff(&self, old_label: &str, old: &str, new_label: &str, new: &str) {
+        self.push(crate::diff::unified_diff(old_label, old, new_label, new));
+    }
+
+    /// Replace the last message written by this handle's source, e.g. to update a progress line.
+    ///
+    /// Adds the message if the source hasn't written one yet. Handles without
+    /// a source replace the last message in the buffer.
+    pub fn replace_last(&self, message: impl Into<String>) {
+        self.send_output(OutputCommand::ReplaceLast {
+            source: self.source.clone(),
+            message: message.into(),
+        });
+    }
+
+    /// Remove all messages from the output buffer, or from the handle's tab
+    pub fn clear(&self) {
+        self.send_output(OutputCommand::Clear);
+    }
+
+    /// Quit the application on its next tick, restoring the terminal
+    pub fn request_quit(&self) {
+        self.send(OutputCommand::Quit);
+    }
+
+    /// Send a change to the output of this handle's tab
+    fn send_output(&self, command: OutputCommand) {
+        match &self.tab {
+            Some(tab) => self.send(OutputCommand::ToTab {
+                tab: tab.clone(),
+                command: Box::new(command),
+            }),
+            None => self.send(command),
+        }
+    }
+
+    fn send(&self, command: OutputCommand) {
+        // The application is gone, so there's nobody left to show the output to
+        let _ = self.sender.send(command);
+    }
+}
+
+/// Output an action writes while it runs, e.g. the lines of a process it tails.
+///
+/// Lines show up in the output pane on the next tick, above the action's
+/// result, and are tagged with the action's item path as their source. Sinks
+/// from [`Istari::output_sink`](crate::Istari::output_sink) let threads and
+/// spawned tasks write lines the same way.
+#[derive(Debug, Clone)]
+pub struct OutputSink {
+    handle: OutputHandle,
+}
+
+impl OutputSink {
+    /// Create a sink writing through the given handle
+    pub(crate) fn new(handle: OutputHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Add a line of output
+    pub fn write_line(&self, line: impl Into<String>) {
+        self.handle.push(line);
+    }
+
+    /// Add a line of output with the given level
+    pub fn write_level(&self, level: OutputLevel, line: impl Into<String>) {
+        self.handle.push_level(level, line);
+    }
+
+    /// Get the handle the sink writes through
+    pub(crate) fn handle(&self) -> &OutputHandle {
+        &self.handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_clock_time() {
+        assert_eq!(format_clock_time(UNIX_EPOCH), "00:00:00");
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(3 * 86_400 + 13 * 3600 + 5 * 60 + 9);
+        assert_eq!(format_clock_time(time), "13:05:09");
+    }
+
+    #[test]
+    fn test_action_output_plain_text() {
+        let table = ActionOutput::table(["Host", "Status"], [["web-1", "up"], ["db", "down"]]);
+        assert!(matches!(table.format(), OutputFormat::Table(_)));
+        assert_eq!(
+            table.to_plain_text(),
+            "Host   Status\n-----  ------\nweb-1  up\ndb     down"
+        );
+
+        let pairs = ActionOutput::KeyValue(vec![
+            ("Version".to_string(), "1.2".to_string()),
+            ("Env".to_string(), "prod".to_string()),
+        ]);
+        assert_eq!(pairs.to_plain_text(), "Version: 1.2\nEnv:     prod");
+
+        let table = OutputTable::new(["Name"]).with_row(["a", "extra"]);
+        assert_eq!(table.columns(), 2);
+        assert_eq!(table.to_plain_text(), "Name\n----  -----\na     extra");
+
+        let lines = ActionOutput::Lines(vec!["one".to_string(), "two".to_string()]);
+        assert_eq!(lines.to_plain_text(), "one\ntwo");
+
+        let code = ActionOutput::code("json", "{}");
+        assert_eq!(code.format(), OutputFormat::Code("json".to_string()));
+        assert_eq!(code.to_plain_text(), "{}");
+    }
+
+    #[test]
+    fn test_pretty_printed() {
+        let detected = ActionOutput::PlainText(r#"{"name":"web","ports":[80,443]}"#.to_string())
+            .pretty_printed();
+        assert_eq!(detected.format(), OutputFormat::Code("json".to_string()));
+        assert_eq!(
+            detected.to_plain_text(),
+            "{\n  \"name\": \"web\",\n  \"ports\": [\n    80,\n    443\n  ]\n}"
+        );
+
+        let marked = ActionOutput::code("JSON", "[1,2]").pretty_printed();
+        assert_eq!(marked.to_plain_text(), "[\n  1,\n  2\n]");
+
+        // Text that only looks like JSON stays as is
+        let text = ActionOutput::PlainText("[ok] done".to_string()).pretty_printed();
+        assert_eq!(text, ActionOutput::PlainText("[ok] done".to_string()));
+        let yaml = ActionOutput::code("yaml", "name: web");
+        assert_eq!(yaml.clone().pretty_printed(), yaml);
+
+        let value = ActionOutput::json(&serde_json::json!({ "up": true }));
+        assert_eq!(value.to_plain_text(), "{\n  \"up\": true\n}");
+    }
+}
@@ -0,0 +1,547 @@
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Type of a parameter value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ParamType {
+    /// Any text
+    String,
+    /// A whole number, e.g. `-3`
+    Integer,
+    /// A decimal number, e.g. `2.5`
+    Float,
+    /// `true`/`false`, `yes`/`no`, `on`/`off` or `1`/`0`
+    Bool,
+}
+
+impl ParamType {
+    /// Check if a value can be parsed as this type
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            ParamType::String => true,
+            ParamType::Integer => value.parse::<i64>().is_ok(),
+            ParamType::Float => value.parse::<f64>().is_ok(),
+            ParamType::Bool => parse_bool(value).is_some(),
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ParamType::String => "text",
+            ParamType::Integer => "int",
+            ParamType::Float => "number",
+            ParamType::Bool => "bool",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parse a boolean parameter value
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Specification of a positional parameter accepted by a menu item
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParamSpec {
+    /// Name of the parameter, shown in usage messages
+    pub name: String,
+    /// Expected type of the parameter
+    pub param_type: ParamType,
+    /// Whether the parameter must be provided
+    pub required: bool,
+    /// Value used when the parameter is omitted
+    pub default: Option<String>,
+}
+
+impl ParamSpec {
+    /// Create a required parameter
+    pub fn required(name: impl Into<String>, param_type: ParamType) -> Self {
+        Self {
+            name: name.into(),
+            param_type,
+            required: true,
+            default: None,
+        }
+    }
+
+    /// Create an optional parameter
+    pub fn optional(name: impl Into<String>, param_type: ParamType) -> Self {
+        Self {
+            name: name.into(),
+            param_type,
+            required: false,
+            default: None,
+        }
+    }
+
+    /// Set the value used when the parameter is omitted, e.g. `"staging"` or `1`
+    pub fn with_default(mut self, default: impl fmt::Display) -> Self {
+        self.default = Some(default.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ParamSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.default, self.required) {
+            (_, true) => write!(f, "<{}:{}>", self.name, self.param_type),
+            (Some(default), false) => {
+                write!(f, "[{}:{}={}]", self.name, self.param_type, default)
+            }
+            (None, false) => write!(f, "[{}:{}]", self.name, self.param_type),
+        }
+    }
+}
+
+/// Parameters handed to an action, parsed from what the user typed after the command.
+///
+/// Arguments are separated by whitespace; single or double quotes group an
+/// argument containing spaces. Arguments starting with `--` are flags, taking
+/// the next argument as their value (`--env prod`) or an inline one
+/// (`--env=prod`). Flags can be repeated, and a bare `--` ends flag parsing.
+///
+/// For items with parameter specs, values are validated against their types
+/// and defaults are filled in before the action runs, so they can be read by
+/// name with [`Params::int`], [`Params::value_as`] and friends.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    /// The parameter text, for actions taking a plain string
+    raw: Option<String>,
+    /// Arguments that aren't flags, in order
+    positional: Vec<String>,
+    /// Names of the specs the positional arguments were validated against, in order
+    names: Vec<String>,
+    /// What the item's clap command parsed, see [`MenuItem::with_clap`](crate::MenuItem::with_clap)
+    #[cfg(feature = "clap")]
+    pub(crate) matches: Option<clap::ArgMatches>,
+    /// Flags in the order they were given, with their values
+    flags: Vec<(String, Option<String>)>,
+}
+
+impl Params {
+    /// Parse parameter text
+    pub fn parse(input: &str) -> Self {
+        let input = input.trim();
+        let mut params = Self {
+            raw: (!input.is_empty()).then(|| input.to_string()),
+            ..Self::default()
+        };
+
+        let mut args = tokenize(input).into_iter().peekable();
+        let mut flags_done = false;
+        while let Some(arg) = args.next() {
+            let flag = arg.strip_prefix("--").filter(|name| !name.is_empty());
+            match flag {
+                Some(flag) if !flags_done => {
+                    let (name, value) = match flag.split_once('=') {
+                        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                        None => {
+                            let value = args.next_if(|next| !next.starts_with("--"));
+                            (flag.to_string(), value)
+                        }
+                    };
+                    params.flags.push((name, value));
+                }
+                _ if arg == "--" && !flags_done => flags_done = true,
+                _ => params.positional.push(arg),
+            }
+        }
+
+        params
+    }
+
+    /// Get the parameter text, e.g. for actions parsing it themselves.
+    ///
+    /// For items with parameter specs this holds the positional values with
+    /// defaults filled in, separated by spaces.
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    /// Check if no parameters were given
+    pub fn is_empty(&self) -> bool {
+        self.positional.is_empty() && self.flags.is_empty()
+    }
+
+    /// Get the arguments that aren't flags
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    /// Get a positional argument by index
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.positional.get(idx).map(String::as_str)
+    }
+
+    /// Get the value of a declared parameter by name, with its default filled in
+    pub fn value(&self, name: &str) -> Option<&str> {
+        let idx = self.names.iter().position(|n| n == name)?;
+        self.get(idx)
+    }
+
+    /// Parse the value of a declared parameter, e.g. `params.value_as::<u16>("port")`.
+    ///
+    /// Returns `None` if the parameter has no value or it doesn't fit the type.
+    pub fn value_as<V: FromStr>(&self, name: &str) -> Option<V> {
+        self.value(name)?.parse().ok()
+    }
+
+    /// Get the value of a declared `Integer` parameter
+    pub fn int(&self, name: &str) -> Option<i64> {
+        self.value_as(name)
+    }
+
+    /// Get the value of a declared `Float` parameter
+    pub fn float(&self, name: &str) -> Option<f64> {
+        self.value_as(name)
+    }
+
+    /// Get the value of a declared `Bool` parameter, accepting `yes`/`no` and the like
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        parse_bool(self.value(name)?)
+    }
+
+    /// Get what the item's clap command parsed, for typed extraction with `get_one` and friends
+    #[cfg(feature = "clap")]
+    pub fn matches(&self) -> Option<&clap::ArgMatches> {
+        self.matches.as_ref()
+    }
+
+    /// Check if a flag was given, with or without a value
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|(flag, _)| flag == name)
+    }
+
+    /// Get the value of a flag, the last one if it was repeated
+    pub fn flag(&self, name: &str) -> Option<&str> {
+        self.flag_values(name).pop()
+    }
+
+    /// Get every value of a repeated flag, e.g. `--tag a --tag b`
+    pub fn flag_values(&self, name: &str) -> Vec<&str> {
+        self.flags
+            .iter()
+            .filter(|(flag, _)| flag == name)
+            .filter_map(|(_, value)| value.as_deref())
+            .collect()
+    }
+
+    /// Add a positional argument after the existing ones
+    pub fn push_positional(&mut self, value: impl Into<String>) {
+        self.positional.push(value.into());
+        self.raw = Some(self.to_string());
+    }
+}
+
+/// Formats the parameters as text parsing back to the same arguments,
+/// with flags first in `--name=value` form
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut args: Vec<String> = self
+            .flags
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("--{}={}", name, quote(value)),
+                None => format!("--{}", name),
+            })
+            .collect();
+        if self.positional.iter().any(|arg| arg.starts_with("--")) {
+            args.push("--".to_string());
+        }
+        args.extend(self.positional.iter().map(|arg| quote(arg)));
+        write!(f, "{}", args.join(" "))
+    }
+}
+
+/// Quote an argument if it wouldn't parse back as a single argument
+fn quote(arg: &str) -> String {
+    let needs_quotes = arg.is_empty()
+        || arg
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'');
+    match (needs_quotes, arg.contains('"')) {
+        (false, _) => arg.to_string(),
+        (true, true) => format!("'{}'", arg),
+        (true, false) => format!("\"{}\"", arg),
+    }
+}
+
+/// Split parameter text into arguments, honoring quotes.
+///
+/// An unterminated quote runs to the end of the input.
+pub(crate) fn tokenize(input: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+
+    for c in input.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// Build a usage line for a command, e.g. `Usage: inc <amount:int> [step:int=1]`
+pub fn usage(key: &str, specs: &[ParamSpec]) -> String {
+    let mut usage = format!("Usage: {}", key);
+    for spec in specs {
+        usage.push(' ');
+        usage.push_str(&spec.to_string());
+    }
+    usage
+}
+
+/// Split positional arguments into one value per spec.
+///
+/// A trailing text parameter takes the remaining arguments, joined by spaces,
+/// so it can contain spaces without quoting.
+fn split_values(specs: &[ParamSpec], positional: &[String]) -> Vec<String> {
+    let takes_rest = specs
+        .last()
+        .is_some_and(|spec| spec.param_type == ParamType::String);
+    if takes_rest && positional.len() > specs.len() {
+        let (values, rest) = positional.split_at(specs.len() - 1);
+        let mut values = values.to_vec();
+        values.push(rest.join(" "));
+        values
+    } else {
+        positional.to_vec()
+    }
+}
+
+/// Validate parameters against their specs, filling in defaults.
+///
+/// Returns the parameters to hand to the action, or a usage error to show the user.
+/// Flags are passed through as given.
+pub fn validate_params(
+    key: &str,
+    specs: &[ParamSpec],
+    params: Option<&str>,
+) -> Result<Params, String> {
+    let mut params = Params::parse(params.unwrap_or(""));
+    let values = split_values(specs, &params.positional);
+
+    if values.len() > specs.len() {
+        return Err(format!(
+            "Too many parameters for '{}'\n{}",
+            key,
+            usage(key, specs)
+        ));
+    }
+
+    let mut resolved = Vec::new();
+    for (idx, spec) in specs.iter().enumerate() {
+        let value = match values.get(idx) {
+            Some(value) => value.clone(),
+            None => match &spec.default {
+                Some(default) => default.clone(),
+                None if spec.required => {
+                    return Err(format!(
+                        "Missing parameter '{}'\n{}",
+                        spec.name,
+                        usage(key, specs)
+                    ));
+                }
+                // Later optional parameters can't be positioned without this one
+                None => break,
+            },
+        };
+
+        if !spec.param_type.accepts(&value) {
+            return Err(format!(
+                "Invalid value '{}' for parameter '{}': expected {}\n{}",
+                value,
+                spec.name,
+                spec.param_type,
+                usage(key, specs)
+            ));
+        }
+        resolved.push(value);
+    }
+
+    params.raw = (!resolved.is_empty()).then(|| resolved.join(" "));
+    params.names = specs
+        .iter()
+        .take(resolved.len())
+        .map(|spec| spec.name.clone())
+        .collect();
+    params.positional = resolved;
+    Ok(params)
+}
+
+/// Find the first required parameter that wasn't given, e.g. to prompt for it
+pub fn missing_param<'a>(specs: &'a [ParamSpec], params: Option<&str>) -> Option<&'a ParamSpec> {
+    let given = split_values(specs, &Params::parse(params.unwrap_or("")).positional).len();
+    specs.iter().skip(given).find(|spec| spec.required)
+}
+
+/// Find inconsistencies in a list of parameter specs
+pub fn spec_problems(specs: &[ParamSpec]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen_names = std::collections::HashSet::new();
+    let mut seen_optional = false;
+
+    for spec in specs {
+        if !seen_names.insert(spec.name.as_str()) {
+            problems.push(format!("parameter '{}' is declared twice", spec.name));
+        }
+        if spec.required && seen_optional {
+            problems.push(format!(
+                "required parameter '{}' follows an optional one",
+                spec.name
+            ));
+        }
+        if spec.required && spec.default.is_some() {
+            problems.push(format!(
+                "required parameter '{}' has a default that is never used",
+                spec.name
+            ));
+        }
+        if let Some(default) = &spec.default
+            && !spec.param_type.accepts(default)
+        {
+            problems.push(format!(
+                "default '{}' of parameter '{}' is not a valid {}",
+                default, spec.name, spec.param_type
+            ));
+        }
+        seen_optional |= !spec.required;
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs() -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::required("amount", ParamType::Integer),
+            ParamSpec::optional("step", ParamType::Integer).with_default("1"),
+            ParamSpec::optional("note", ParamType::String),
+        ]
+    }
+
+    #[test]
+    fn test_validate_params() {
+        let specs = specs();
+        let params = validate_params("inc", &specs, Some("5")).unwrap();
+        assert_eq!(params.raw(), Some("5 1"));
+        assert_eq!(params.positional(), ["5", "1"]);
+        let params = validate_params("inc", &specs, Some("5 2 a long note")).unwrap();
+        assert_eq!(params.raw(), Some("5 2 a long note"));
+        assert_eq!(params.get(2), Some("a long note"));
+        let params = validate_params("inc", &specs, Some("5 2 'a  note' --dry-run")).unwrap();
+        assert_eq!(params.get(2), Some("a  note"));
+        assert!(params.has_flag("dry-run"));
+
+        let missing = validate_params("inc", &specs, None).unwrap_err();
+        assert!(missing.starts_with("Missing parameter 'amount'"));
+        assert!(missing.ends_with("Usage: inc <amount:int> [step:int=1] [note:text]"));
+
+        assert_eq!(missing_param(&specs, None).unwrap().name, "amount");
+        assert!(missing_param(&specs, Some("5")).is_none());
+
+        let invalid = validate_params("inc", &specs, Some("five")).unwrap_err();
+        assert!(invalid.starts_with("Invalid value 'five' for parameter 'amount': expected int"));
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let specs = [
+            ParamSpec::required("amount", ParamType::Integer),
+            ParamSpec::optional("ratio", ParamType::Float).with_default(0.5),
+            ParamSpec::optional("dry-run", ParamType::Bool).with_default("no"),
+            ParamSpec::optional("note", ParamType::String),
+        ];
+        let params = validate_params("inc", &specs, Some("-3")).unwrap();
+        assert_eq!(params.int("amount"), Some(-3));
+        assert_eq!(params.value_as::<i8>("amount"), Some(-3));
+        assert_eq!(params.value_as::<u8>("amount"), None);
+        assert_eq!(params.float("ratio"), Some(0.5));
+        assert_eq!(params.bool("dry-run"), Some(false));
+        assert_eq!(params.value("note"), None);
+        assert_eq!(params.value("missing"), None);
+
+        let params = validate_params("inc", &specs, Some("1 2 on hello there")).unwrap();
+        assert_eq!(params.bool("dry-run"), Some(true));
+        assert_eq!(params.value("note"), Some("hello there"));
+    }
+
+    #[test]
+    fn test_spec_problems() {
+        assert!(spec_problems(&specs()).is_empty());
+
+        let problems = spec_problems(&[
+            ParamSpec::optional("flag", ParamType::Bool).with_default("maybe"),
+            ParamSpec::required("name", ParamType::String),
+        ]);
+        assert_eq!(
+            problems,
+            [
+                "default 'maybe' of parameter 'flag' is not a valid bool",
+                "required parameter 'name' follows an optional one",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let params =
+            Params::parse(r#"deploy "my app" --env prod --tag a --tag=b --force -- --literal"#);
+        assert_eq!(params.positional(), ["deploy", "my app", "--literal"]);
+        assert_eq!(params.flag("env"), Some("prod"));
+        assert_eq!(params.flag_values("tag"), ["a", "b"]);
+        assert_eq!(params.flag("tag"), Some("b"));
+        assert!(params.has_flag("force"));
+        assert_eq!(params.flag("force"), None);
+        assert!(!params.has_flag("literal"));
+
+        assert!(Params::parse("  ").is_empty());
+        assert_eq!(Params::parse("  ").raw(), None);
+        assert_eq!(
+            Params::parse("say 'unterminated quote").get(1),
+            Some("unterminated quote")
+        );
+        assert_eq!(Params::parse("-3 \"\"").positional(), ["-3", ""]);
+
+        // Formatting round-trips
+        let mut params = Params::parse(r#"--env "prod eu" -- --literal"#);
+        params.push_positional("my app");
+        assert_eq!(
+            params.to_string(),
+            r#"--env="prod eu" -- --literal "my app""#
+        );
+        assert_eq!(Params::parse(&params.to_string()), params);
+    }
+}
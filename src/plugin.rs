@@ -0,0 +1,104 @@
+use crate::istari::CommandRef;
+use crate::menu::Menu;
+use crate::output::OutputHandle;
+use crate::types::MaybeSendSync;
+
+/// Something that happened in the application, passed to [`MenuPlugin::on_event`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginEvent<'a> {
+    /// The user entered the menu at this path of submenu keys
+    MenuEntered { path: &'a [String] },
+    /// An action finished running
+    ActionFinished {
+        command: &'a CommandRef,
+        failed: bool,
+    },
+}
+
+/// A separately compiled part of an application contributing its own menus,
+/// actions and background behavior, registered with [`Istari::with_plugin`](crate::Istari::with_plugin)
+pub trait MenuPlugin<T>: MaybeSendSync {
+    /// Name of the plugin, used as the source of its output
+    fn name(&self) -> &str;
+
+    /// Add the plugin's submenus and actions to the root menu
+    fn contribute(&self, root: &mut Menu<T>);
+
+    /// Called on every tick of the application
+    fn tick(&self, _state: &mut T, _output: &OutputHandle, _delta_time: f32) {}
+
+    /// Called when something happens in the application
+    fn on_event(&self, _state: &mut T, _event: &PluginEvent, _output: &OutputHandle) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Istari, IstariError};
+
+    #[derive(Debug, Default)]
+    struct TestState {
+        ticks: u32,
+        events: Vec<String>,
+    }
+
+    struct DeployPlugin;
+
+    impl MenuPlugin<TestState> for DeployPlugin {
+        fn name(&self) -> &str {
+            "deploy"
+        }
+
+        fn contribute(&self, root: &mut Menu<TestState>) {
+            let mut deploy = Menu::new("Deploy");
+            deploy.add_action("s", "Status", |_: &mut TestState, _: Option<&str>| {
+                Some("All good".to_string())
+            });
+            root.add_submenu("d", "Deploy", deploy);
+        }
+
+        fn tick(&self, state: &mut TestState, _output: &OutputHandle, _delta_time: f32) {
+            state.ticks += 1;
+        }
+
+        fn on_event(&self, state: &mut TestState, event: &PluginEvent, _output: &OutputHandle) {
+            match event {
+                PluginEvent::MenuEntered { path } => {
+                    state.events.push(format!("entered /{}", path.join("/")))
+                }
+                PluginEvent::ActionFinished { command, failed } => {
+                    state
+                        .events
+                        .push(format!("ran {} failed={}", command.item_path(), failed))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_plugin_contributions() {
+        let mut app = Istari::new(Menu::new("Root"), TestState::default())
+            .unwrap()
+            .with_plugin(DeployPlugin)
+            .unwrap();
+
+        app.tick();
+        app.handle_key("d");
+        app.handle_key("s");
+        app.tick();
+        assert_eq!(*app.output_messages(), ["All good"]);
+        assert_eq!(app.state().ticks, 2);
+        assert_eq!(
+            app.state().events,
+            ["entered /", "entered /d", "ran d/s failed=false"]
+        );
+
+        // Contributions are validated like the rest of the tree
+        let result = Istari::new(Menu::new("Root"), TestState::default())
+            .unwrap()
+            .with_plugin(DeployPlugin)
+            .unwrap()
+            .with_plugin(DeployPlugin);
+        assert!(matches!(result, Err(IstariError::DuplicateCommand(..))));
+    }
+}
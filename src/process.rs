@@ -0,0 +1,165 @@
+use crate::cancel::CancelToken;
+use crate::output::{ActionOutput, OutputLevel, OutputSink};
+use crate::types::{ActionOutcome, ActionResult, IntoActionResult};
+use std::fmt;
+use std::io;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+/// How an external process run by [`run_process`] ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessExit {
+    /// Exit code of the process, `None` if it was killed by a signal or cancelled
+    pub code: Option<i32>,
+    /// Whether the process was killed because the action was cancelled
+    pub cancelled: bool,
+}
+
+impl ProcessExit {
+    /// Check if the process exited with code 0
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl fmt::Display for ProcessExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            _ if self.cancelled => write!(f, "Process cancelled"),
+            Some(code) => write!(f, "Process exited with code {}", code),
+            None => write!(f, "Process was terminated by a signal"),
+        }
+    }
+}
+
+/// Actions returning how their process ended fail unless it exited with code 0 or was cancelled
+impl IntoActionResult for ProcessExit {
+    fn into_action_result(self) -> ActionResult {
+        if self.success() || self.cancelled {
+            Ok(ActionOutcome::new().with_output(ActionOutput::PlainText(self.to_string())))
+        } else {
+            Err(self.to_string())
+        }
+    }
+}
+
+/// Run an external command, streaming its stdout and stderr to the output line by line.
+///
+/// Stderr lines are written as warnings. The process is killed when the action
+/// is cancelled. Stdin is closed, so the command can't wait for input.
+pub async fn run_process(
+    command: &mut Command,
+    output: &OutputSink,
+    cancel: &CancelToken,
+) -> io::Result<ProcessExit> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    // Both are piped above, so they're always there
+    let mut stdout = child.stdout.take().map(|out| BufReader::new(out).lines());
+    let mut stderr = child.stderr.take().map(|err| BufReader::new(err).lines());
+
+    while stdout.is_some() || stderr.is_some() {
+        tokio::select! {
+            line = next_line(&mut stdout), if stdout.is_some() => match line? {
+                Some(line) => output.write_line(line),
+                None => stdout = None,
+            },
+            line = next_line(&mut stderr), if stderr.is_some() => match line? {
+                Some(line) => output.write_level(OutputLevel::Warn, line),
+                None => stderr = None,
+            },
+            _ = cancel.cancelled() => break,
+        }
+    }
+
+    tokio::select! {
+        status = child.wait() => Ok(ProcessExit {
+            code: status?.code(),
+            cancelled: false,
+        }),
+        _ = cancel.cancelled() => {
+            child.kill().await?;
+            Ok(ProcessExit {
+                code: None,
+                cancelled: true,
+            })
+        }
+    }
+}
+
+/// Read the next line of a pipe that's still open
+async fn next_line<R>(
+    lines: &mut Option<tokio::io::Lines<BufReader<R>>>,
+) -> io::Result<Option<String>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    match lines {
+        Some(lines) => lines.next_line().await,
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{OutputCommand, OutputHandle};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_run_process() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let output = OutputSink::new(OutputHandle::new(sender, None));
+        let cancel = CancelToken::new();
+
+        let exit = runtime
+            .block_on(run_process(
+                Command::new("sh").args(["-c", "echo one; echo two >&2; echo three; exit 3"]),
+                &output,
+                &cancel,
+            ))
+            .unwrap();
+        assert_eq!(exit.code, Some(3));
+        assert_eq!(
+            exit.into_action_result(),
+            Err("Process exited with code 3".to_string())
+        );
+
+        let mut lines: Vec<_> = receiver
+            .try_iter()
+            .filter_map(|command| match command {
+                OutputCommand::Push { level, message, .. } => Some((level, message)),
+                _ => None,
+            })
+            .collect();
+        lines.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(
+            lines,
+            [
+                (OutputLevel::Info, "one".to_string()),
+                (OutputLevel::Info, "three".to_string()),
+                (OutputLevel::Warn, "two".to_string()),
+            ]
+        );
+
+        // Cancelling kills the process instead of waiting for it
+        let started = Instant::now();
+        cancel.cancel();
+        let exit = runtime
+            .block_on(run_process(
+                Command::new("sleep").arg("5"),
+                &output,
+                &cancel,
+            ))
+            .unwrap();
+        assert!(exit.cancelled);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::output::OutputCommand;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Frames of the spinner shown for actions that don't report a percentage
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long each spinner frame is shown
+const SPINNER_FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/// Number of cells of a progress bar
+const BAR_WIDTH: usize = 20;
+
+/// Handle a long-running action reports its progress through.
+///
+/// Handles are cheap to clone and can be moved into the future of an async
+/// action. Updates are shown on the next tick of the application while the
+/// action runs in the background.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// The running action the updates belong to
+    job: u64,
+    /// Channel drained by the application
+    sender: Sender<OutputCommand>,
+}
+
+impl Progress {
+    /// Create a handle reporting the progress of the given job
+    pub(crate) fn new(job: u64, sender: Sender<OutputCommand>) -> Self {
+        Self { job, sender }
+    }
+
+    /// Report how far along the action is, in percent, and what it's doing
+    pub fn set(&self, percent: u8, message: impl Into<String>) {
+        self.send(Some(percent.min(100)), message.into());
+    }
+
+    /// Report what the action is doing without a percentage, shown with a spinner
+    pub fn message(&self, message: impl Into<String>) {
+        self.send(None, message.into());
+    }
+
+    fn send(&self, percent: Option<u8>, message: String) {
+        // The application is gone, so there's nobody left to show the progress to
+        let _ = self.sender.send(OutputCommand::Progress {
+            job: self.job,
+            percent,
+            message,
+        });
+    }
+}
+
+/// Last progress reported by an action running in the background
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobProgress {
+    /// Id of the job the action runs as, e.g. for `cancel <id>`
+    pub id: u64,
+    /// Path of the action's menu item, e.g. `deploy/start`
+    pub command: String,
+    /// How far along the action is, `None` if it only reported a message
+    pub percent: Option<u8>,
+    /// What the action is doing
+    pub message: String,
+    /// When the action started
+    pub started: Instant,
+}
+
+impl JobProgress {
+    /// Render the progress as a single line, a bar if there's a percentage and a spinner otherwise
+    pub fn line(&self, now: Instant) -> String {
+        let indicator = match self.percent {
+            Some(percent) => {
+                let filled = BAR_WIDTH * usize::from(percent) / 100;
+                format!(
+                    "[{}{}] {:>3}%",
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    percent
+                )
+            }
+            None => {
+                let elapsed = now.saturating_duration_since(self.started);
+                let frame = elapsed.as_millis() / SPINNER_FRAME_DURATION.as_millis();
+                SPINNER_FRAMES[frame as usize % SPINNER_FRAMES.len()].to_string()
+            }
+        };
+
+        if self.message.is_empty() {
+            format!("{} #{} {}", indicator, self.id, self.command)
+        } else {
+            format!(
+                "{} #{} {}: {}",
+                indicator, self.id, self.command, self.message
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line() {
+        let started = Instant::now();
+        let mut progress = JobProgress {
+            id: 3,
+            command: "deploy/start".to_string(),
+            percent: Some(50),
+            message: "Uploading".to_string(),
+            started,
+        };
+        assert_eq!(
+            progress.line(started),
+            "[##########----------]  50% #3 deploy/start: Uploading"
+        );
+
+        progress.percent = None;
+        progress.message.clear();
+        assert_eq!(progress.line(started), "⠋ #3 deploy/start");
+        assert_eq!(
+            progress.line(started + Duration::from_millis(250)),
+            "⠹ #3 deploy/start"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crossterm::event::Event;
+use ratatui::backend::{Backend, CrosstermBackend, TestBackend};
+use std::io;
+use std::time::Duration;
+
+/// A terminal library the TUI draws with and reads input from.
+///
+/// Istari ships [`CrosstermTerminal`], and `TermionTerminal` with the `termion`
+/// feature. Other libraries plug in by implementing this trait and running
+/// [`TuiController::with_terminal`](crate::TuiController::with_terminal) as a
+/// custom renderer. Whatever the library, input is handed to the TUI as
+/// [`crossterm`](crate::crossterm) events.
+pub trait TerminalBackend {
+    /// The ratatui backend drawing to the terminal
+    type Backend: Backend;
+
+    /// Create the ratatui backend
+    fn backend(&mut self) -> io::Result<Self::Backend>;
+
+    /// Take over the terminal: raw mode, mouse capture and the alternate screen
+    /// unless the TUI is drawn inline
+    fn enter(&mut self, backend: &mut Self::Backend, alternate_screen: bool) -> io::Result<()>;
+
+    /// Hand the terminal back to the shell, undoing `enter`
+    fn leave(&mut self, backend: &mut Self::Backend, alternate_screen: bool) -> io::Result<()>;
+
+    /// Wait up to `timeout` for the next input event
+    fn poll_event(&mut self, timeout: Duration) -> io::Result<Option<Event>>;
+}
+
+/// The terminal of the process, driven with crossterm
+#[derive(Debug, Default)]
+pub struct CrosstermTerminal;
+
+impl TerminalBackend for CrosstermTerminal {
+    type Backend = CrosstermBackend<io::Stdout>;
+
+    fn backend(&mut self) -> io::Result<Self::Backend> {
+        Ok(CrosstermBackend::new(io::stdout()))
+    }
+
+    fn enter(&mut self, backend: &mut Self::Backend, alternate_screen: bool) -> io::Result<()> {
+        crossterm::terminal::enable_raw_mode()?;
+        if alternate_screen {
+            crossterm::execute!(backend, crossterm::terminal::EnterAlternateScreen)?;
+        }
+        crossterm::execute!(backend, crossterm::event::EnableMouseCapture)
+    }
+
+    fn leave(&mut self, backend: &mut Self::Backend, alternate_screen: bool) -> io::Result<()> {
+        crossterm::terminal::disable_raw_mode()?;
+        if alternate_screen {
+            crossterm::execute!(backend, crossterm::terminal::LeaveAlternateScreen)?;
+        }
+        crossterm::execute!(backend, crossterm::event::DisableMouseCapture)
+    }
+
+    fn poll_event(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if crossterm::event::poll(timeout)? {
+            crossterm::event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// An in-memory screen without input, behind [`HeadlessRenderer`](crate::HeadlessRenderer)
+#[derive(Debug)]
+pub(crate) struct HeadlessTerminal {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+}
+
+impl TerminalBackend for HeadlessTerminal {
+    type Backend = TestBackend;
+
+    fn backend(&mut self) -> io::Result<Self::Backend> {
+        Ok(TestBackend::new(self.width, self.height))
+    }
+
+    fn enter(&mut self, _backend: &mut Self::Backend, _alternate_screen: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn leave(&mut self, _backend: &mut Self::Backend, _alternate_screen: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn poll_event(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        // Events are injected by the renderer instead
+        std::thread::sleep(timeout);
+        Ok(None)
+    }
+}
+
+/// The terminal the TUI uses unless told otherwise
+#[cfg(not(feature = "termion"))]
+pub type DefaultTerminal = CrosstermTerminal;
+
+/// The terminal the TUI uses unless told otherwise
+#[cfg(feature = "termion")]
+pub type DefaultTerminal = super::termion::TermionTerminal;
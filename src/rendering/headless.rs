@@ -0,0 +1,159 @@
+use crate::Istari;
+use crate::rendering::UIController;
+use crate::rendering::backend::HeadlessTerminal;
+use crate::rendering::tui::TuiController;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::buffer::Buffer;
+use std::io;
+use std::time::Instant;
+
+/// Draws the TUI into an in-memory buffer instead of the terminal, for testing
+/// applications built on Istari.
+///
+/// Keys are injected with [`press`](Self::press) and friends, which handle them
+/// like the TUI does and draw the next frame, so the screen can be checked after
+/// every step:
+///
+/// ```
+/// use istari::{HeadlessRenderer, Istari, Menu};
+/// use istari::crossterm::event::KeyCode;
+///
+/// let mut menu = Menu::new("Root");
+/// menu.add_action("hi", "Say hi", |_: &mut (), _: Option<&str>| Some("Hello".to_string()));
+/// let mut app = Istari::new(menu, ()).unwrap();
+///
+/// let mut screen = HeadlessRenderer::new(80, 24).unwrap();
+/// screen.type_text(&mut app, "hi").unwrap();
+/// screen.press(&mut app, KeyCode::Enter).unwrap();
+/// assert!(screen.contains("Hello"));
+/// ```
+pub struct HeadlessRenderer {
+    controller: TuiController<HeadlessTerminal>,
+}
+
+impl HeadlessRenderer {
+    /// Create a renderer drawing into a buffer of the given size
+    pub fn new(width: u16, height: u16) -> io::Result<Self> {
+        Ok(Self {
+            controller: TuiController::with_terminal(HeadlessTerminal { width, height })?,
+        })
+    }
+
+    /// Draw a frame of the application
+    pub fn render<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()> {
+        self.controller.render_frame(app)
+    }
+
+    /// Handle an event like the TUI does and draw the next frame.
+    ///
+    /// Returns false once the application would quit.
+    pub fn send_event<T: std::fmt::Debug>(
+        &mut self,
+        app: &mut Istari<T>,
+        event: Event,
+    ) -> io::Result<bool> {
+        // Events are handled against what's on screen, e.g. clicks against the menu rows
+        self.render(app)?;
+        let keep_running = self.controller.handle_event(app, event)?;
+        self.render(app)?;
+        Ok(keep_running && !app.quit_requested())
+    }
+
+    /// Press a key, returning false once the application would quit
+    pub fn press<T: std::fmt::Debug>(
+        &mut self,
+        app: &mut Istari<T>,
+        code: KeyCode,
+    ) -> io::Result<bool> {
+        self.send_key(app, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    /// Press a key with modifiers, e.g. Ctrl+Q
+    pub fn send_key<T: std::fmt::Debug>(
+        &mut self,
+        app: &mut Istari<T>,
+        key: KeyEvent,
+    ) -> io::Result<bool> {
+        self.send_event(app, Event::Key(key))
+    }
+
+    /// Type text one key at a time, without pressing Enter
+    pub fn type_text<T: std::fmt::Debug>(
+        &mut self,
+        app: &mut Istari<T>,
+        text: &str,
+    ) -> io::Result<bool> {
+        for c in text.chars() {
+            if !self.press(app, KeyCode::Char(c))? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Run a tick of the application, finishing async actions, and draw the next frame
+    pub fn tick<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()> {
+        self.controller.expire_keys(app, Instant::now());
+        app.tick();
+        self.render(app)
+    }
+
+    /// Get the last frame drawn, with its styles
+    pub fn buffer(&self) -> &Buffer {
+        self.controller.buffer()
+    }
+
+    /// Get the rows of the last frame drawn as text
+    pub fn lines(&self) -> Vec<String> {
+        let buffer = self.buffer();
+        let area = buffer.area;
+        (area.top()..area.bottom())
+            .map(|y| {
+                let mut line = String::new();
+                for x in area.left()..area.right() {
+                    line.push_str(buffer[(x, y)].symbol());
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Check whether a row of the last frame drawn holds the text
+    pub fn contains(&self, text: &str) -> bool {
+        self.lines().iter().any(|line| line.contains(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Menu, Mode};
+
+    #[test]
+    fn test_headless_renderer() {
+        let mut menu = Menu::new("Root");
+        menu.add_action("inc", "Increment", |count: &mut i32, _: Option<&str>| {
+            *count += 1;
+            Some(format!("Count: {}", count))
+        });
+        let mut app = Istari::new(menu, 0).unwrap();
+        let mut screen = HeadlessRenderer::new(100, 20).unwrap();
+
+        screen.render(&mut app).unwrap();
+        assert!(screen.contains("[inc] Increment"));
+        assert!(screen.contains("COMMAND MODE"));
+        assert_eq!(screen.lines().len(), 20);
+
+        screen.type_text(&mut app, "inc").unwrap();
+        assert!(screen.contains("inc"));
+        assert!(screen.press(&mut app, KeyCode::Enter).unwrap());
+        assert!(screen.contains("Count: 1"));
+
+        screen.press(&mut app, KeyCode::Tab).unwrap();
+        assert_eq!(app.mode(), Mode::Scroll);
+        assert!(screen.contains("SCROLL MODE"));
+
+        let quit = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert!(!screen.send_key(&mut app, quit).unwrap());
+    }
+}
@@ -0,0 +1,105 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::time::{Duration, Instant};
+
+/// How long a count or `g` waits for the key completing it
+pub(crate) const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// Keys of scroll mode made of several presses, vim style: `gg` for the top and
+/// counts like `5j` repeating the key after them
+#[derive(Debug, Default)]
+pub(crate) struct KeySequence {
+    count: Option<usize>,      // Digits typed so far
+    g: bool,                   // Whether a `g` waits for the second one
+    last_key: Option<Instant>, // When the sequence was last added to
+}
+
+impl KeySequence {
+    /// Add a key press, getting the key to handle with its count once the
+    /// sequence is complete. `gg` comes out as Home.
+    pub(crate) fn push(&mut self, key: KeyEvent, now: Instant) -> Option<(KeyCode, Option<usize>)> {
+        // Shortcuts like Ctrl+A don't take part in sequences
+        if key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            *self = Self::default();
+            return Some((key.code, None));
+        }
+
+        match key.code {
+            KeyCode::Char(digit @ '0'..='9')
+                if !self.g && (digit != '0' || self.count.is_some()) =>
+            {
+                let count = self.count.unwrap_or(0);
+                let digit = digit.to_digit(10).unwrap_or(0) as usize;
+                self.count = Some(count.saturating_mul(10).saturating_add(digit));
+                self.last_key = Some(now);
+                None
+            }
+            KeyCode::Char('g') if !self.g => {
+                self.g = true;
+                self.last_key = Some(now);
+                None
+            }
+            KeyCode::Char('g') => Some((KeyCode::Home, self.take())),
+            code => Some((code, self.take())),
+        }
+    }
+
+    /// End a sequence left unfinished for too long, getting its count if it had one
+    pub(crate) fn expire(&mut self, now: Instant) -> Option<usize> {
+        let last_key = self.last_key?;
+        if now.duration_since(last_key) < SEQUENCE_TIMEOUT {
+            return None;
+        }
+        self.take()
+    }
+
+    /// Whether keys wait for the rest of their sequence
+    pub(crate) fn is_pending(&self) -> bool {
+        self.last_key.is_some()
+    }
+
+    fn take(&mut self) -> Option<usize> {
+        let count = self.count;
+        *self = Self::default();
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_sequence() {
+        let now = Instant::now();
+        let mut keys = KeySequence::default();
+        let push = |keys: &mut KeySequence, c| keys.push(KeyEvent::from(KeyCode::Char(c)), now);
+
+        assert_eq!(push(&mut keys, 'j'), Some((KeyCode::Char('j'), None)));
+        assert_eq!(push(&mut keys, '1'), None);
+        assert_eq!(push(&mut keys, '2'), None);
+        assert_eq!(push(&mut keys, 'j'), Some((KeyCode::Char('j'), Some(12))));
+        assert_eq!(push(&mut keys, 'g'), None);
+        assert_eq!(push(&mut keys, 'g'), Some((KeyCode::Home, None)));
+        assert_eq!(push(&mut keys, '5'), None);
+        assert_eq!(push(&mut keys, 'g'), None);
+        assert_eq!(push(&mut keys, 'g'), Some((KeyCode::Home, Some(5))));
+        // A lone zero isn't a count, and another key drops a pending `g`
+        assert_eq!(push(&mut keys, '0'), Some((KeyCode::Char('0'), None)));
+        assert_eq!(push(&mut keys, 'g'), None);
+        assert_eq!(push(&mut keys, 'k'), Some((KeyCode::Char('k'), None)));
+
+        let ctrl_a = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert_eq!(push(&mut keys, '3'), None);
+        assert_eq!(keys.push(ctrl_a, now), Some((KeyCode::Char('a'), None)));
+
+        // Unfinished sequences time out with their count
+        push(&mut keys, '2');
+        assert!(keys.is_pending());
+        assert_eq!(keys.expire(now), None);
+        assert_eq!(keys.expire(now + SEQUENCE_TIMEOUT), Some(2));
+        assert!(!keys.is_pending());
+    }
+}
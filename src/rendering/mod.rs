@@ -1,6 +1,17 @@
+mod backend;
+mod headless;
+mod keys;
+#[cfg(feature = "termion")]
+mod termion;
 mod text;
 mod tui;
 
+pub use backend::{CrosstermTerminal, DefaultTerminal, TerminalBackend};
+pub use headless::HeadlessRenderer;
+#[cfg(feature = "termion")]
+pub use termion::TermionTerminal;
+pub use tui::TuiController;
+
 use crate::Istari;
 use std::io;
 
@@ -9,11 +20,38 @@ pub fn run<T: std::fmt::Debug>(app: &mut Istari<T>) -> io::Result<()> {
     match app.ui_mode() {
         crate::UIMode::TUI => tui::run(app),
         crate::UIMode::Text => text::run(app),
+        crate::UIMode::Custom => match app.take_renderer() {
+            Some(mut renderer) => {
+                let result = run_controller(renderer.as_mut(), app);
+                app.set_renderer(renderer);
+                result
+            }
+            // The renderer is only missing while it runs
+            None => tui::run(app),
+        },
     }
 }
 
-/// Common trait that all UI controllers must implement
-pub trait UIController {
+/// Run a controller's event loop between its setup and cleanup
+fn run_controller<T, C>(controller: &mut C, app: &mut Istari<T>) -> io::Result<()>
+where
+    C: UIController<T> + ?Sized,
+{
+    controller.init()?;
+    let result = controller.run_event_loop(app);
+    controller.cleanup()?;
+    result
+}
+
+/// A renderer drawing the application and feeding it input, see
+/// [`Istari::with_renderer`](crate::Istari::with_renderer).
+///
+/// Istari runs `init`, then `run_event_loop` until it returns, then `cleanup`,
+/// even if the loop failed. The loop drives the application through its public
+/// API: [`Istari::handle_key`] or [`Istari::process_input_buffer`] for input,
+/// [`Istari::tick`] regularly for async actions and tick handlers, and
+/// [`Istari::quit_requested`] to know when to return.
+pub trait UIController<T> {
     /// Initialize the UI environment
     fn init(&mut self) -> io::Result<()>;
 
@@ -21,10 +59,10 @@ pub trait UIController {
     fn cleanup(&mut self) -> io::Result<()>;
 
     /// Render a frame of the application
-    fn render_frame<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()>;
+    fn render_frame(&mut self, app: &mut Istari<T>) -> io::Result<()>;
 
     /// Run the main event loop
-    fn run_event_loop<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()>;
+    fn run_event_loop(&mut self, app: &mut Istari<T>) -> io::Result<()>;
 }
 
 /// Direction for scrolling operations
@@ -35,6 +73,8 @@ pub enum ScrollDirection {
     PageDown,
     Top,
     Bottom,
+    /// To a line, counting from 0
+    Line(usize),
 }
 
 /// State for scroll position in output window
@@ -42,12 +82,13 @@ pub enum ScrollDirection {
 pub struct ScrollState {
     /// Current scroll position (0 = top)
     pub position: usize,
-    /// Whether to auto-scroll to bottom on new output
+    /// Whether the view follows new output, sticking to the bottom. Scrolling
+    /// up stops following, and scrolling back to the bottom resumes it.
     pub auto_scroll: bool,
 }
 
 impl ScrollState {
-    /// Create a new scroll state with auto-scroll enabled
+    /// Create a new scroll state following new output
     pub fn new() -> Self {
         Self {
             position: 0,
@@ -55,9 +96,13 @@ impl ScrollState {
         }
     }
 
-    /// Toggle auto-scroll
-    pub fn toggle_auto_scroll(&mut self) {
-        self.auto_scroll = !self.auto_scroll;
+    /// Start or stop following new output, jumping to the bottom when starting
+    pub fn toggle_auto_scroll(&mut self, content_height: usize, view_height: usize) {
+        if self.auto_scroll {
+            self.auto_scroll = false;
+        } else {
+            self.scroll(ScrollDirection::Bottom, content_height, view_height);
+        }
     }
 
     /// Scroll in the specified direction
@@ -89,7 +134,20 @@ impl ScrollState {
             ScrollDirection::Bottom => {
                 self.position = max_scroll;
             }
+            ScrollDirection::Line(line) => {
+                self.position = line.min(max_scroll);
+            }
         }
+        // Follow new output from the bottom only
+        self.auto_scroll = self.position >= max_scroll;
+    }
+
+    /// Keep the position within the content, e.g. once it got shorter by re-wrapping
+    /// at a wider width
+    pub fn clamp(&mut self, content_height: usize, view_height: usize) {
+        self.position = self
+            .position
+            .min(content_height.saturating_sub(view_height));
     }
 
     /// Update scroll position if auto-scroll is enabled and there's new content
@@ -0,0 +1,193 @@
+use super::backend::TerminalBackend;
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::backend::TermionBackend;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+use termion::event as tm;
+use termion::input::TermRead;
+use termion::raw::{IntoRawMode, RawTerminal};
+
+const ENTER_MOUSE_SEQUENCE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const EXIT_MOUSE_SEQUENCE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+/// The terminal of the process, driven with termion.
+///
+/// Termion can't wait for input with a timeout, so a thread reads stdin from
+/// the first poll on. It keeps reading while the terminal is handed back to the
+/// shell, so the external editor of the parameter prompt may lose keys to it.
+#[derive(Default)]
+pub struct TermionTerminal {
+    raw_mode: Option<RawTerminal<io::Stdout>>, // Restores the terminal mode when dropped
+    events: Option<Receiver<io::Result<tm::Event>>>,
+}
+
+impl TerminalBackend for TermionTerminal {
+    type Backend = TermionBackend<io::Stdout>;
+
+    fn backend(&mut self) -> io::Result<Self::Backend> {
+        Ok(TermionBackend::new(io::stdout()))
+    }
+
+    fn enter(&mut self, backend: &mut Self::Backend, alternate_screen: bool) -> io::Result<()> {
+        self.raw_mode = Some(io::stdout().into_raw_mode()?);
+        if alternate_screen {
+            write!(backend, "{}", termion::screen::ToAlternateScreen)?;
+        }
+        write!(backend, "{}", ENTER_MOUSE_SEQUENCE)?;
+        backend.flush()
+    }
+
+    fn leave(&mut self, backend: &mut Self::Backend, alternate_screen: bool) -> io::Result<()> {
+        write!(backend, "{}", EXIT_MOUSE_SEQUENCE)?;
+        if alternate_screen {
+            write!(backend, "{}", termion::screen::ToMainScreen)?;
+        }
+        backend.flush()?;
+        self.raw_mode = None;
+        Ok(())
+    }
+
+    fn poll_event(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        let events = self.events.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                for event in io::stdin().events() {
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+            });
+            receiver
+        });
+
+        match events.recv_timeout(timeout) {
+            Ok(event) => Ok(from_termion(event?)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stdin was closed",
+            )),
+        }
+    }
+}
+
+/// Convert a termion event to the crossterm event the TUI handles, if it has one
+fn from_termion(event: tm::Event) -> Option<Event> {
+    match event {
+        tm::Event::Key(key) => from_termion_key(key).map(Event::Key),
+        tm::Event::Mouse(mouse) => {
+            let (kind, column, row) = match mouse {
+                tm::MouseEvent::Press(button, column, row) => {
+                    let kind = match button {
+                        tm::MouseButton::Left => MouseEventKind::Down(MouseButton::Left),
+                        tm::MouseButton::Right => MouseEventKind::Down(MouseButton::Right),
+                        tm::MouseButton::Middle => MouseEventKind::Down(MouseButton::Middle),
+                        tm::MouseButton::WheelUp => MouseEventKind::ScrollUp,
+                        tm::MouseButton::WheelDown => MouseEventKind::ScrollDown,
+                        tm::MouseButton::WheelLeft => MouseEventKind::ScrollLeft,
+                        tm::MouseButton::WheelRight => MouseEventKind::ScrollRight,
+                    };
+                    (kind, column, row)
+                }
+                tm::MouseEvent::Release(column, row) => {
+                    (MouseEventKind::Up(MouseButton::Left), column, row)
+                }
+                tm::MouseEvent::Hold(column, row) => {
+                    (MouseEventKind::Drag(MouseButton::Left), column, row)
+                }
+            };
+            // Termion counts from one, crossterm from zero
+            Some(Event::Mouse(MouseEvent {
+                kind,
+                column: column.saturating_sub(1),
+                row: row.saturating_sub(1),
+                modifiers: KeyModifiers::NONE,
+            }))
+        }
+        tm::Event::Unsupported(_) => None,
+    }
+}
+
+fn from_termion_key(key: tm::Key) -> Option<KeyEvent> {
+    let (code, modifiers) = match key {
+        tm::Key::Backspace => (KeyCode::Backspace, KeyModifiers::NONE),
+        tm::Key::Left => (KeyCode::Left, KeyModifiers::NONE),
+        tm::Key::ShiftLeft => (KeyCode::Left, KeyModifiers::SHIFT),
+        tm::Key::AltLeft => (KeyCode::Left, KeyModifiers::ALT),
+        tm::Key::CtrlLeft => (KeyCode::Left, KeyModifiers::CONTROL),
+        tm::Key::Right => (KeyCode::Right, KeyModifiers::NONE),
+        tm::Key::ShiftRight => (KeyCode::Right, KeyModifiers::SHIFT),
+        tm::Key::AltRight => (KeyCode::Right, KeyModifiers::ALT),
+        tm::Key::CtrlRight => (KeyCode::Right, KeyModifiers::CONTROL),
+        tm::Key::Up => (KeyCode::Up, KeyModifiers::NONE),
+        tm::Key::ShiftUp => (KeyCode::Up, KeyModifiers::SHIFT),
+        tm::Key::AltUp => (KeyCode::Up, KeyModifiers::ALT),
+        tm::Key::CtrlUp => (KeyCode::Up, KeyModifiers::CONTROL),
+        tm::Key::Down => (KeyCode::Down, KeyModifiers::NONE),
+        tm::Key::ShiftDown => (KeyCode::Down, KeyModifiers::SHIFT),
+        tm::Key::AltDown => (KeyCode::Down, KeyModifiers::ALT),
+        tm::Key::CtrlDown => (KeyCode::Down, KeyModifiers::CONTROL),
+        tm::Key::Home => (KeyCode::Home, KeyModifiers::NONE),
+        tm::Key::CtrlHome => (KeyCode::Home, KeyModifiers::CONTROL),
+        tm::Key::End => (KeyCode::End, KeyModifiers::NONE),
+        tm::Key::CtrlEnd => (KeyCode::End, KeyModifiers::CONTROL),
+        tm::Key::PageUp => (KeyCode::PageUp, KeyModifiers::NONE),
+        tm::Key::PageDown => (KeyCode::PageDown, KeyModifiers::NONE),
+        tm::Key::BackTab => (KeyCode::BackTab, KeyModifiers::SHIFT),
+        tm::Key::Delete => (KeyCode::Delete, KeyModifiers::NONE),
+        tm::Key::Insert => (KeyCode::Insert, KeyModifiers::NONE),
+        tm::Key::F(n) => (KeyCode::F(n), KeyModifiers::NONE),
+        // Termion reports Enter and Tab as characters
+        tm::Key::Char('\n') => (KeyCode::Enter, KeyModifiers::NONE),
+        tm::Key::Char('\t') => (KeyCode::Tab, KeyModifiers::NONE),
+        tm::Key::Char(c) => (KeyCode::Char(c), KeyModifiers::NONE),
+        tm::Key::Alt(c) => (KeyCode::Char(c), KeyModifiers::ALT),
+        tm::Key::Ctrl(c) => (KeyCode::Char(c), KeyModifiers::CONTROL),
+        tm::Key::Null => (KeyCode::Null, KeyModifiers::NONE),
+        tm::Key::Esc => (KeyCode::Esc, KeyModifiers::NONE),
+        _ => return None,
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_termion() {
+        let key = |event| match from_termion(tm::Event::Key(event)) {
+            Some(Event::Key(key)) => Some((key.code, key.modifiers)),
+            _ => None,
+        };
+        assert_eq!(
+            key(tm::Key::Char('\n')),
+            Some((KeyCode::Enter, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            key(tm::Key::Char('a')),
+            Some((KeyCode::Char('a'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            key(tm::Key::Ctrl('q')),
+            Some((KeyCode::Char('q'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            key(tm::Key::ShiftUp),
+            Some((KeyCode::Up, KeyModifiers::SHIFT))
+        );
+
+        let click = tm::Event::Mouse(tm::MouseEvent::Press(tm::MouseButton::Left, 5, 1));
+        match from_termion(click) {
+            Some(Event::Mouse(mouse)) => {
+                assert_eq!(mouse.kind, MouseEventKind::Down(MouseButton::Left));
+                assert_eq!((mouse.column, mouse.row), (4, 0));
+            }
+            other => panic!("expected a click, got {:?}", other),
+        }
+        assert_eq!(from_termion(tm::Event::Unsupported(vec![0x1b])), None);
+    }
+}
@@ -1,19 +1,87 @@
-use crate::Istari;
+use crate::dialog::{DialogAnswer, DialogKind};
+use crate::keymap::KeyAction;
 use crate::rendering::UIController;
+use crate::theme::ColorSupport;
+use crate::{Istari, Mode, OutputLevel, format_clock_time};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent},
+    style::{Attribute, Color as TermColor, ContentStyle, StyledContent},
     terminal::{disable_raw_mode, enable_raw_mode},
 };
+use ratatui::style::{Color, Modifier, Style};
+use std::fmt::Display;
 use std::io::{self, Write, stdout};
 use std::time::{Duration, Instant};
 
+/// How often the progress of background actions is printed
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Apply a theme style to text printed to the terminal, degraded to its colors
+fn paint<D: Display>(text: D, style: Style, colors: ColorSupport) -> StyledContent<D> {
+    let style = colors.style(style);
+    let mut content = ContentStyle::new();
+    content.foreground_color = style.fg.map(term_color);
+    content.background_color = style.bg.map(term_color);
+    for (modifier, attribute) in [
+        (Modifier::BOLD, Attribute::Bold),
+        (Modifier::DIM, Attribute::Dim),
+        (Modifier::ITALIC, Attribute::Italic),
+        (Modifier::UNDERLINED, Attribute::Underlined),
+        (Modifier::REVERSED, Attribute::Reverse),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            content.attributes.set(attribute);
+        }
+    }
+    content.apply(text)
+}
+
+/// Convert a theme color to the terminal's, the way the TUI backend does
+fn term_color(color: Color) -> TermColor {
+    match color {
+        Color::Reset => TermColor::Reset,
+        Color::Black => TermColor::Black,
+        Color::Red => TermColor::DarkRed,
+        Color::Green => TermColor::DarkGreen,
+        Color::Yellow => TermColor::DarkYellow,
+        Color::Blue => TermColor::DarkBlue,
+        Color::Magenta => TermColor::DarkMagenta,
+        Color::Cyan => TermColor::DarkCyan,
+        Color::Gray => TermColor::Grey,
+        Color::DarkGray => TermColor::DarkGrey,
+        Color::LightRed => TermColor::Red,
+        Color::LightGreen => TermColor::Green,
+        Color::LightYellow => TermColor::Yellow,
+        Color::LightBlue => TermColor::Blue,
+        Color::LightMagenta => TermColor::Magenta,
+        Color::LightCyan => TermColor::Cyan,
+        Color::White => TermColor::White,
+        Color::Rgb(r, g, b) => TermColor::Rgb { r, g, b },
+        Color::Indexed(idx) => TermColor::AnsiValue(idx),
+    }
+}
+
 /// Simple text UI controller for Istari application
-pub struct TextController {}
+pub struct TextController {
+    /// Title of the application, greeting the user
+    title: String,
+    /// Name and version of the application, if set
+    app_info: Option<String>,
+    /// Async actions whose results were already printed
+    seen_completed: usize,
+    /// When the progress of background actions was last printed
+    last_progress: Instant,
+}
 
 impl TextController {
-    /// Create a new text UI controller
-    pub fn new() -> io::Result<Self> {
-        Ok(Self {})
+    /// Create a new text UI controller greeting the user with the application's title
+    pub fn new(title: impl Into<String>, app_info: Option<String>) -> io::Result<Self> {
+        Ok(Self {
+            title: title.into(),
+            app_info,
+            seen_completed: 0,
+            last_progress: Instant::now(),
+        })
     }
 
     /// Print the menu items
@@ -21,16 +89,92 @@ impl TextController {
         let menu = app.current_menu();
         let menu = menu.lock().unwrap();
 
-        // Print the title
-        println!("\n== {} ==", menu.title);
+        // Print the title, with the filters applied
+        let mut filters = Vec::new();
+        if let Some(category) = app.category_filter() {
+            filters.push(format!("category: {}", category));
+        }
+        if app.log_level() > OutputLevel::Debug {
+            filters.push(format!("log level: {}+", app.log_level().name()));
+        }
+        if let Some(filter) = app.output_filter() {
+            filters.push(format!("filtered: {}", filter.as_str()));
+        }
+        if filters.is_empty() {
+            println!("\n== {} ==", menu.title);
+        } else {
+            println!("\n== {} ({}) ==", menu.title, filters.join(", "));
+        }
+
+        // Print pinned actions at the top of the root menu
+        if app.is_at_root() && !app.favorites().is_empty() {
+            println!("Favorites:");
+            for (idx, command) in app.favorites().iter().enumerate() {
+                println!(
+                    "[*{}] {} ({})",
+                    idx + 1,
+                    command.description,
+                    command.item_path()
+                );
+            }
+            println!();
+        }
+
+        // Print recently run commands at the top of the root menu
+        if app.is_at_root() && app.recent_commands().is_visible() {
+            println!("Recent:");
+            for (idx, command) in app.recent_commands().iter().enumerate() {
+                println!("[!{}] {}", idx + 1, command.label());
+            }
+            println!();
+        }
+
+        // Print queued commands in every menu while the queue runs
+        if app.queue_running().is_some() || app.queued_commands().next().is_some() {
+            println!("Queue:");
+            if let Some(command) = app.queue_running() {
+                println!("> {} (running)", command.label());
+            }
+            for (idx, command) in app.queued_commands().enumerate() {
+                println!("{}. {}", idx + 1, command.label());
+            }
+            println!();
+        }
 
         // Print menu items
-        for item in &menu.items {
-            println!("[{}] {}", item.key, item.description);
+        for item in menu.items.iter().filter(|item| app.is_item_visible(item)) {
+            let description = item.describe(app.state());
+            let (theme, colors) = (app.theme(), app.color_support());
+            match app.item_style(item) {
+                OutputLevel::Debug => {
+                    println!("[{}] {}", item.key, paint(description, theme.debug, colors))
+                }
+                OutputLevel::Info => println!("[{}] {}", item.key, description),
+                OutputLevel::Warn => {
+                    println!(
+                        "[{}] {}",
+                        item.key,
+                        paint(description, theme.warning, colors)
+                    )
+                }
+                OutputLevel::Error => {
+                    println!("[{}] {}", item.key, paint(description, theme.error, colors))
+                }
+            }
+        }
+
+        // Print the actions available from every menu
+        let globals = app.global_menu();
+        let globals = globals.lock().unwrap();
+        if !globals.items.is_empty() {
+            println!("Global:");
+            for item in &globals.items {
+                println!("[{}] {}", item.key, item.describe(app.state()));
+            }
         }
 
         // Add back/quit option if not at root
-        if menu.parent.is_some() {
+        if !app.is_at_root() {
             println!("[b] Back");
         } else {
             println!("[q] Quit");
@@ -44,25 +188,122 @@ impl TextController {
 
     /// Print the output messages
     fn print_output<T: std::fmt::Debug>(&self, app: &Istari<T>) -> io::Result<()> {
-        let output_messages = app.output_messages();
-        if !output_messages.is_empty() {
+        let last = app.output_records().back();
+        let last_level = last.map(|entry| entry.level).unwrap_or_default();
+        let in_menu = match (app.output_menu(), last) {
+            (Some(shown), Some(entry)) => shown == entry.menu,
+            _ => true,
+        };
+        // Messages below the log level or of other menus are kept but not printed
+        if last_level >= app.log_level() && in_menu {
             // Only print the last message
-            if let Some(last_msg) = output_messages.last() {
-                println!("Output:");
-                println!("  {}", last_msg);
+            if let Some(last) = last {
+                let is_error = last_level == OutputLevel::Error;
+                if is_error {
+                    println!("Output (errors this session: {}):", app.error_count());
+                } else {
+                    println!("Output:");
+                }
+                // Indent every line, so multi-line output like diffs stays aligned,
+                // past the time of the message with the layout's timestamps on
+                let time = if app.layout().timestamps {
+                    format!("[{} UTC] ", format_clock_time(last.time))
+                } else {
+                    String::new()
+                };
+                // Collapsed duplicates count up after the message
+                let repeats = if last.repeats > 1 {
+                    format!(" ×{}", last.repeats)
+                } else {
+                    String::new()
+                };
+                let lines: Vec<&str> = last
+                    .text
+                    .lines()
+                    .filter(|line| app.is_line_shown(line))
+                    .collect();
+                let count = lines.len();
+                for (idx, line) in lines.into_iter().enumerate() {
+                    let line = if idx + 1 == count {
+                        format!("{}{}", line, repeats)
+                    } else {
+                        line.to_string()
+                    };
+                    let line = line.as_str();
+                    let prefix = if idx == 0 {
+                        time.clone()
+                    } else {
+                        " ".repeat(time.len())
+                    };
+                    if is_error {
+                        let line = paint(line, app.theme().error, app.color_support());
+                        println!("  {}{}", prefix, line);
+                    } else {
+                        println!("  {}{}", prefix, line);
+                    }
+                }
                 println!("----------------------------------------");
             }
         }
         Ok(())
     }
+
+    /// Print the popup viewer content, if any, and close it
+    fn print_popup<T: std::fmt::Debug>(&self, app: &mut Istari<T>) -> io::Result<()> {
+        if let Some(popup) = app.popup() {
+            println!("=== {} ===", popup.title);
+            for line in popup.content.lines() {
+                println!("  {}", line);
+            }
+            println!("========================================");
+            app.close_popup();
+        }
+        Ok(())
+    }
+}
+
+impl TextController {
+    /// Print the dialog waiting for an answer, if any; messages need none and are closed
+    fn print_dialog<T: std::fmt::Debug>(&self, app: &mut Istari<T>) -> io::Result<()> {
+        while let Some(dialog) = app.dialog() {
+            // Confirmations already asked in the output
+            if dialog.answer == DialogAnswer::Confirmation {
+                return Ok(());
+            }
+
+            println!("=== {} ===", dialog.title);
+            for line in dialog.text.lines() {
+                println!("  {}", line);
+            }
+            match dialog.kind {
+                DialogKind::Message => {
+                    println!("========================================");
+                    app.submit_dialog();
+                }
+                DialogKind::Confirm { .. } => {
+                    println!("Answer y/n and press Enter");
+                    return Ok(());
+                }
+                DialogKind::Prompt { .. } => {
+                    println!("Type a value and press Enter, empty input cancels");
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
-impl UIController for TextController {
+impl<T: std::fmt::Debug> UIController<T> for TextController {
     fn init(&mut self) -> io::Result<()> {
         // Print welcome message
-        println!("Welcome to Istari (Text Mode)");
+        println!("Welcome to {} (Text Mode)", self.title);
+        if let Some(info) = &self.app_info {
+            println!("{}", info);
+        }
         println!("Type commands and press Enter to execute");
         println!("Use Up/Down arrows for command history");
+        println!("Press Ctrl+E to compose parameters in $EDITOR");
         println!("Type 'b' to go back, 'q' to quit");
         println!("----------------------------------------");
 
@@ -74,15 +315,41 @@ impl UIController for TextController {
         Ok(())
     }
 
-    fn render_frame<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()> {
+    fn render_frame(&mut self, app: &mut Istari<T>) -> io::Result<()> {
         // In text mode, we directly print the menu and output
         disable_raw_mode()?;
         self.print_menu(app)?;
         self.print_output(app)?;
+        self.print_popup(app)?;
+        self.print_dialog(app)?;
         enable_raw_mode()?;
 
-        // Print command prompt
+        // Print the watched expressions, the status bar and the command prompt
         disable_raw_mode()?;
+        for (label, value) in app.watches() {
+            println!(
+                "{}: {}",
+                paint(label, app.theme().key, app.color_support()),
+                value
+            );
+        }
+        for metric in app.metrics() {
+            println!(
+                "{}: {}",
+                paint(metric.label(), app.theme().key, app.color_support()),
+                metric.to_text()
+            );
+        }
+        if let Some(status) = app.status_line() {
+            println!(
+                "{}",
+                paint(
+                    format!(" {} ", status),
+                    app.theme().status,
+                    app.color_support()
+                )
+            );
+        }
         print!("> ");
         stdout().flush()?;
         enable_raw_mode()?;
@@ -90,11 +357,16 @@ impl UIController for TextController {
         Ok(())
     }
 
-    fn run_event_loop<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()> {
-        // Define the tick rate
-        let tick_rate = Duration::from_millis(100);
+    fn run_event_loop(&mut self, app: &mut Istari<T>) -> io::Result<()> {
+        let tick_rate = app.tick_rate();
         let mut last_tick = Instant::now();
 
+        // Print the splash once, before the first menu
+        if let Some(splash) = app.splash() {
+            println!("{}", splash);
+            app.dismiss_splash();
+        }
+
         // Enable raw mode to handle arrow keys
         enable_raw_mode()?;
 
@@ -105,108 +377,219 @@ impl UIController for TextController {
 
             // Command input processing
             let mut input = String::new();
-            let mut cursor_pos = 0;
+            let mut cursor_pos = 0; // Byte offset in the input, on a character boundary
 
             loop {
                 // Check if it's time for a tick update
                 if last_tick.elapsed() >= tick_rate {
                     app.tick();
                     last_tick = Instant::now();
+
+                    // An action that finished in the background asked to quit
+                    if app.quit_requested() {
+                        disable_raw_mode()?;
+                        println!("\nExiting...");
+                        return Ok(());
+                    }
+
+                    // Print results of async actions, unless the user is typing
+                    if app.completed_actions() != self.seen_completed && input.is_empty() {
+                        self.seen_completed = app.completed_actions();
+                        disable_raw_mode()?;
+                        println!();
+                        enable_raw_mode()?;
+                        break;
+                    }
+
+                    // Print the progress of background actions now and then, unless the user is typing
+                    let progress = app.progress();
+                    if !progress.is_empty()
+                        && input.is_empty()
+                        && self.last_progress.elapsed() >= PROGRESS_INTERVAL
+                    {
+                        self.last_progress = Instant::now();
+                        disable_raw_mode()?;
+                        print!("\r");
+                        for job in progress {
+                            println!(
+                                "  {}",
+                                paint(
+                                    job.line(self.last_progress),
+                                    app.theme().progress,
+                                    app.color_support()
+                                )
+                            );
+                        }
+                        print!("> ");
+                        stdout().flush()?;
+                        enable_raw_mode()?;
+                    }
                 }
 
-                // Poll for events with a timeout
-                if event::poll(Duration::from_millis(100))? {
-                    if let Event::Key(KeyEvent {
+                // Poll for events until the next tick
+                let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+                if event::poll(timeout)?
+                    && let Event::Key(KeyEvent {
                         code, modifiers, ..
                     }) = event::read()?
-                    {
-                        match code {
-                            // Exit application with Ctrl+Q
-                            KeyCode::Char('q') if modifiers.contains(KeyModifiers::CONTROL) => {
+                {
+                    let action = app
+                        .keymap()
+                        .action(Mode::Command, &KeyEvent::new(code, modifiers));
+                    match (action, code) {
+                        // Exit the application
+                        (Some(KeyAction::Quit), _) => {
+                            let keep_running = app.handle_quit();
+                            if !keep_running {
                                 disable_raw_mode()?;
                                 println!("\nExiting...");
                                 return Ok(());
                             }
+                        }
 
-                            // Enter key - process command
-                            KeyCode::Enter => {
-                                // Update input buffer from our local input
-                                app.clear_input_buffer();
-                                for c in input.chars() {
-                                    app.add_to_input_buffer(c);
-                                }
+                        // Enter key - process command
+                        (_, KeyCode::Enter) => {
+                            // Update input buffer from our local input
+                            app.clear_input_buffer();
+                            for c in input.chars() {
+                                app.add_to_input_buffer(c);
+                            }
 
-                                // Process the input
-                                disable_raw_mode()?;
-                                println!(); // New line after input
-                                let should_continue = app.process_input_buffer();
-                                if !should_continue {
-                                    println!("Exiting...");
-                                    return Ok(());
-                                }
-                                break;
+                            // Process the input
+                            disable_raw_mode()?;
+                            println!(); // New line after input
+                            let should_continue = app.process_input_buffer();
+                            if !should_continue {
+                                println!("Exiting...");
+                                return Ok(());
                             }
+                            break;
+                        }
 
-                            // Backspace - delete last character
-                            KeyCode::Backspace => {
-                                if cursor_pos > 0 {
-                                    input.remove(cursor_pos - 1);
-                                    cursor_pos -= 1;
-
-                                    // Redraw the input line
-                                    disable_raw_mode()?;
-                                    print!("\r> {}", input);
-                                    print!("{}", " ".repeat(10)); // Clear any trailing characters
-                                    print!("\r> {}", input);
-                                    stdout().flush()?;
-                                    enable_raw_mode()?;
-                                }
+                        // Compose the command parameters in $EDITOR
+                        (Some(KeyAction::EditParams), _) => {
+                            app.clear_input_buffer();
+                            for c in input.chars() {
+                                app.add_to_input_buffer(c);
                             }
 
-                            // Up arrow - previous command in history
-                            KeyCode::Up => {
-                                app.history_up();
-                                input = app.input_buffer().to_string();
-                                cursor_pos = input.len();
+                            disable_raw_mode()?;
+                            println!();
+                            app.compose_params_in_editor();
+                            input = app.input_buffer().to_string();
+                            cursor_pos = input.len();
+                            print!("> {}", input);
+                            stdout().flush()?;
+                            enable_raw_mode()?;
+                        }
 
-                                // Redraw the input line
-                                disable_raw_mode()?;
-                                print!("\r> {}", input);
-                                print!("{}", " ".repeat(10)); // Clear any trailing characters
-                                print!("\r> {}", input);
-                                stdout().flush()?;
-                                enable_raw_mode()?;
+                        // Complete the parameter being typed
+                        (Some(KeyAction::Complete), _) => {
+                            app.clear_input_buffer();
+                            for c in input.chars() {
+                                app.add_to_input_buffer(c);
+                            }
+                            if !app.complete_input() {
+                                continue;
                             }
 
-                            // Down arrow - next command in history
-                            KeyCode::Down => {
-                                app.history_down();
-                                input = app.input_buffer().to_string();
-                                cursor_pos = input.len();
-
-                                // Redraw the input line
-                                disable_raw_mode()?;
-                                print!("\r> {}", input);
-                                print!("{}", " ".repeat(10)); // Clear any trailing characters
-                                print!("\r> {}", input);
-                                stdout().flush()?;
-                                enable_raw_mode()?;
+                            disable_raw_mode()?;
+                            if !app.completions().is_empty() {
+                                println!();
+                                println!(
+                                    "{}",
+                                    paint(
+                                        app.completions().join("  "),
+                                        app.theme().muted,
+                                        app.color_support()
+                                    )
+                                );
                             }
+                            input = app.input_buffer().to_string();
+                            cursor_pos = input.len();
+                            print!("\r> {}", input);
+                            stdout().flush()?;
+                            enable_raw_mode()?;
+                        }
 
-                            // Normal character input
-                            KeyCode::Char(c) => {
-                                input.insert(cursor_pos, c);
-                                cursor_pos += 1;
+                        // Backspace - delete last character
+                        (_, KeyCode::Backspace) if cursor_pos > 0 => {
+                            let removed = input[..cursor_pos].chars().next_back().unwrap_or(' ');
+                            cursor_pos -= removed.len_utf8();
+                            input.remove(cursor_pos);
+
+                            // Redraw the input line
+                            disable_raw_mode()?;
+                            print!("\r> {}", input);
+                            print!("{}", " ".repeat(10)); // Clear any trailing characters
+                            print!("\r> {}", input);
+                            stdout().flush()?;
+                            enable_raw_mode()?;
+                        }
 
-                                // Redraw the input line
-                                disable_raw_mode()?;
-                                print!("\r> {}", input);
-                                stdout().flush()?;
-                                enable_raw_mode()?;
-                            }
+                        // Cancel the latest background action
+                        (Some(KeyAction::Cancel), _) => {
+                            disable_raw_mode()?;
+                            println!();
+                            app.handle_key("cancel");
+                            break;
+                        }
 
-                            _ => {}
+                        // Browse visited menus
+                        (Some(KeyAction::MenuBack | KeyAction::MenuForward), _) => {
+                            disable_raw_mode()?;
+                            println!();
+                            app.handle_key(if action == Some(KeyAction::MenuBack) {
+                                "<"
+                            } else {
+                                ">"
+                            });
+                            break;
                         }
+
+                        // Previous command in history
+                        (Some(KeyAction::HistoryUp), _) => {
+                            app.history_up();
+                            input = app.input_buffer().to_string();
+                            cursor_pos = input.len();
+
+                            // Redraw the input line
+                            disable_raw_mode()?;
+                            print!("\r> {}", input);
+                            print!("{}", " ".repeat(10)); // Clear any trailing characters
+                            print!("\r> {}", input);
+                            stdout().flush()?;
+                            enable_raw_mode()?;
+                        }
+
+                        // Next command in history
+                        (Some(KeyAction::HistoryDown), _) => {
+                            app.history_down();
+                            input = app.input_buffer().to_string();
+                            cursor_pos = input.len();
+
+                            // Redraw the input line
+                            disable_raw_mode()?;
+                            print!("\r> {}", input);
+                            print!("{}", " ".repeat(10)); // Clear any trailing characters
+                            print!("\r> {}", input);
+                            stdout().flush()?;
+                            enable_raw_mode()?;
+                        }
+
+                        // Normal character input
+                        (_, KeyCode::Char(c)) => {
+                            input.insert(cursor_pos, c);
+                            cursor_pos += c.len_utf8();
+
+                            // Redraw the input line
+                            disable_raw_mode()?;
+                            print!("\r> {}", input);
+                            stdout().flush()?;
+                            enable_raw_mode()?;
+                        }
+
+                        _ => {}
                     }
                 }
             }
@@ -216,12 +599,6 @@ impl UIController for TextController {
 
 /// Run the application in Text mode
 pub fn run<T: std::fmt::Debug>(app: &mut crate::Istari<T>) -> io::Result<()> {
-    let mut controller = TextController::new()?;
-    controller.init()?;
-
-    let result = controller.run_event_loop(app);
-
-    controller.cleanup()?;
-
-    result
+    let mut controller = TextController::new(app.title(), app.app_info())?;
+    super::run_controller(&mut controller, app)
 }
@@ -1,64 +1,1607 @@
+use crate::dialog::{Dialog, DialogKind};
+use crate::diff::is_unified_diff;
+use crate::highlight_rules::apply_highlight_rules;
+use crate::jobs::{JobStatus, format_elapsed};
+use crate::keymap::{Key, KeyAction, KeyMap};
+use crate::metrics::MetricValue;
+use crate::output::{OutputFormat, OutputRecord, OutputTable, format_clock_time};
+use crate::rendering::backend::{DefaultTerminal, HeadlessTerminal, TerminalBackend};
+use crate::rendering::keys::KeySequence;
 use crate::rendering::{ScrollDirection, ScrollState, UIController};
-use crate::{Istari, Mode};
+use crate::theme::{ColorSupport, StyledLine, Theme};
+use crate::{Istari, Mode, OutputLevel, OutputPopup};
 use ratatui::{
-    Terminal,
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    Terminal, TerminalOptions, Viewport,
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{
+        Block, Borders, Cell, Clear, Gauge, List, ListItem, Paragraph, Row, Sparkline, Table,
+        Widget,
+    },
 };
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-pub struct TuiController {
-    terminal: Terminal<CrosstermBackend<io::Stdout>>,
+/// Columns between the cells of a table in the output
+const TABLE_COLUMN_SPACING: usize = 2;
+
+/// Marker drawn at the start of rows continuing a wrapped output line
+const WRAP_MARKER: &str = "↪ ";
+/// Columns the time of output messages takes, with the space after it
+const TIMESTAMP_WIDTH: usize = 9;
+
+/// Marker drawn at the end of truncated output lines
+const TRUNCATE_MARKER: char = '…';
+/// How long the output border flashes after an error, when error focus is on
+const ERROR_FLASH_DURATION: Duration = Duration::from_millis(1200);
+/// How long each blink of the flashing border lasts
+const ERROR_FLASH_BLINK: Duration = Duration::from_millis(200);
+/// Tallest the jobs panel gets, including its borders
+const JOBS_PANEL_MAX_HEIGHT: u16 = 8;
+/// Tallest the watch panel gets, including its borders
+const WATCH_PANEL_MAX_HEIGHT: u16 = 8;
+/// Tallest the stats pane gets, including its borders
+const STATS_PANEL_MAX_HEIGHT: u16 = 10;
+/// Columns the output moves sideways per h/l press when lines aren't wrapped
+const HORIZONTAL_SCROLL_STEP: usize = 8;
+/// Rows of output drawn past each edge of the view
+const OUTPUT_RENDER_MARGIN: usize = 20;
+
+/// Scroll position of an output tab, kept while another tab is shown
+#[derive(Debug, Clone, Copy)]
+struct TabScroll {
+    scroll_state: ScrollState,
+    content_height: usize,
+    seen_errors: usize,
+}
+
+impl Default for TabScroll {
+    fn default() -> Self {
+        Self {
+            scroll_state: ScrollState::new(),
+            content_height: 0,
+            // Errors already in a tab shown for the first time don't flash the border
+            seen_errors: usize::MAX,
+        }
+    }
+}
+
+/// Search through the output, started with `/` in scroll mode
+#[derive(Debug, Clone, Default)]
+struct OutputSearch {
+    /// Text searched for, ignoring ASCII case
+    query: String,
+    /// Whether the query is still being typed
+    editing: bool,
+    /// Output rows holding a match, found whenever the output is drawn
+    rows: Vec<usize>,
+    /// Row of the match jumped to last
+    current: Option<usize>,
+}
+
+impl OutputSearch {
+    /// Jump to the next or previous match, from the last one or else from the scroll position.
+    ///
+    /// Wraps around at either end of the output.
+    fn jump(&mut self, position: usize, forward: bool) -> Option<usize> {
+        let row = match (self.current, forward) {
+            (Some(current), true) => self.rows.iter().find(|row| **row > current),
+            (Some(current), false) => self.rows.iter().rev().find(|row| **row < current),
+            (None, true) => self.rows.iter().find(|row| **row >= position),
+            (None, false) => self.rows.iter().rev().find(|row| **row <= position),
+        };
+        let row = match forward {
+            true => row.or(self.rows.first()),
+            false => row.or(self.rows.last()),
+        };
+        self.current = row.copied();
+        self.current
+    }
+
+    /// Describe the search for the output pane's border
+    fn status(&self) -> String {
+        if self.editing {
+            return format!("/{}_", self.query);
+        }
+        let found = match self
+            .current
+            .and_then(|row| self.rows.iter().position(|r| *r == row))
+        {
+            _ if self.rows.is_empty() => "no matches".to_string(),
+            Some(index) => format!("{}/{}", index + 1, self.rows.len()),
+            None => format!("{} matches", self.rows.len()),
+        };
+        format!("'{}' {} | n/N Next/Previous | Esc Clear", self.query, found)
+    }
+}
+
+/// Style the matches of a query in a row of output, ignoring ASCII case.
+///
+/// Returns whether the row holds a match.
+fn highlight_matches(row: &StyledLine, query: &str, style: Style) -> (StyledLine, bool) {
+    let mut chars: Vec<(char, Style)> = row
+        .iter()
+        .flat_map(|(part, style)| part.chars().map(move |c| (c, *style)))
+        .collect();
+    let query: Vec<char> = query.chars().collect();
+    let mut found = false;
+    let mut start = 0;
+    while !query.is_empty() && start + query.len() <= chars.len() {
+        let matches = chars[start..start + query.len()]
+            .iter()
+            .zip(&query)
+            .all(|((c, _), q)| c.eq_ignore_ascii_case(q));
+        if matches {
+            for (_, char_style) in &mut chars[start..start + query.len()] {
+                *char_style = char_style.patch(style);
+            }
+            found = true;
+            start += query.len();
+        } else {
+            start += 1;
+        }
+    }
+
+    let mut parts: StyledLine = Vec::new();
+    for (c, char_style) in chars {
+        match parts.last_mut() {
+            Some((part, last)) if *last == char_style => part.push(c),
+            _ => parts.push((c.to_string(), char_style)),
+        }
+    }
+    (parts, found)
+}
+
+/// The split-view TUI, drawn with any [`TerminalBackend`].
+///
+/// Istari runs it in TUI mode on the [`DefaultTerminal`]. Run it as a custom
+/// renderer to draw with another terminal library:
+///
+/// ```no_run
+/// # use istari::{CrosstermTerminal, Istari, Menu, TuiController};
+/// # let app = Istari::new(Menu::new("Root"), ()).unwrap();
+/// let tui = TuiController::with_terminal(CrosstermTerminal)?;
+/// let mut app = app.with_renderer(tui);
+/// app.run()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct TuiController<E: TerminalBackend = DefaultTerminal> {
+    terminal: Terminal<E::Backend>,
+    terminal_backend: E, // Sets up the terminal and reads input from it
+    inline: bool,        // Drawn in a viewport below the prompt instead of the alternate screen
     scroll_state: ScrollState,
     last_content_height: usize, // Track the last content height to detect changes
+    soft_wrap: bool,            // Wrap long output lines instead of truncating them
+    line_numbers: bool,         // Number the output lines
+    timestamps: bool,           // Show the time of each output message
+    h_scroll: usize,            // Columns the output is scrolled sideways when not wrapping
+    max_h_scroll: usize,        // Columns the widest output line goes past the pane
+    output_height: usize,       // Rows of output the pane shows at once
+    search: Option<OutputSearch>, // Search through the output, if one was started
+    cut_lines: Vec<(usize, usize, String)>, // First row, number and text of lines cut at the maximum width
+    line_rows: Vec<usize>,                  // First row of each output line, as last laid out
+    output_cache: OutputCache,              // Output messages styled by earlier frames
+    wrap_width: Option<usize>, // Columns the output lines were last wrapped at, None when cut
+    seen_errors: usize,        // Error messages already brought into view
+    flash_until: Option<Instant>, // When the output border stops flashing
+    splash_until: Option<Instant>, // When the splash screen times out, if it does
+    keys: KeySequence,         // Scroll mode keys waiting for the rest of their sequence
+    last_frame: u64,           // Fingerprint of the last frame drawn, see frame_key
+    menu_area: Rect,           // Where the menu items were last drawn
+    menu_rows: Vec<(usize, String)>, // Rows of clickable menu entries, with the command they run
+    shown_tab: usize,          // Output tab the scroll state belongs to
+    tab_scrolls: HashMap<usize, TabScroll>, // Scroll state of the other output tabs
 }
 
 impl TuiController {
     /// Create a new TUI controller
     pub fn new() -> io::Result<Self> {
-        let backend = CrosstermBackend::new(io::stdout());
-        let terminal = Terminal::new(backend)?;
+        Self::with_terminal(DefaultTerminal::default())
+    }
+}
+
+impl TuiController<HeadlessTerminal> {
+    /// Get the last frame drawn into the test backend
+    pub(crate) fn buffer(&self) -> &Buffer {
+        self.terminal.backend().buffer()
+    }
+}
+
+impl<E: TerminalBackend> TuiController<E> {
+    /// Temporarily hand the terminal back to the shell, e.g. to run an external editor
+    pub fn suspend(&mut self) -> io::Result<()> {
+        if self.inline {
+            // Leave the cursor at the top of the viewport for the shell to carry on from
+            self.terminal.clear()?;
+        }
+        self.terminal_backend
+            .leave(self.terminal.backend_mut(), !self.inline)?;
+        self.terminal.show_cursor()?;
+        Ok(())
+    }
+
+    /// Take the terminal back after a suspend and redraw from scratch
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.terminal_backend
+            .enter(self.terminal.backend_mut(), !self.inline)?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+
+    /// Create a TUI controller drawing with the given terminal library
+    pub fn with_terminal(terminal_backend: E) -> io::Result<Self> {
+        Self::with_viewport(terminal_backend, Viewport::Fullscreen)
+    }
+
+    /// Create a TUI controller drawing in `height` rows below the shell prompt,
+    /// keeping the scrollback above in view, see
+    /// [`LayoutConfig::with_inline_viewport`](crate::LayoutConfig::with_inline_viewport)
+    pub fn with_inline_viewport(terminal_backend: E, height: u16) -> io::Result<Self> {
+        Self::with_viewport(terminal_backend, Viewport::Inline(height))
+    }
+
+    fn with_viewport(mut terminal_backend: E, viewport: Viewport) -> io::Result<Self> {
+        let inline = matches!(viewport, Viewport::Inline(_));
+        let terminal =
+            Terminal::with_options(terminal_backend.backend()?, TerminalOptions { viewport })?;
         Ok(Self {
             terminal,
+            terminal_backend,
+            inline,
             scroll_state: ScrollState::new(),
             last_content_height: 0,
+            soft_wrap: true,
+            line_numbers: false,
+            timestamps: false,
+            h_scroll: 0,
+            max_h_scroll: 0,
+            output_height: 0,
+            search: None,
+            cut_lines: Vec::new(),
+            line_rows: Vec::new(),
+            output_cache: OutputCache::default(),
+            wrap_width: None,
+            seen_errors: 0,
+            flash_until: None,
+            splash_until: None,
+            keys: KeySequence::default(),
+            last_frame: 0,
+            menu_area: Rect::default(),
+            menu_rows: Vec::new(),
+            shown_tab: 0,
+            tab_scrolls: HashMap::new(),
         })
     }
+
+    /// Scroll to the next or previous match of the output search
+    fn jump_to_match(&mut self, forward: bool) {
+        let position = self.scroll_state.position;
+        let Some(row) = self
+            .search
+            .as_mut()
+            .and_then(|search| search.jump(position, forward))
+        else {
+            return;
+        };
+        self.scroll_state.scroll(
+            ScrollDirection::Line(row),
+            self.last_content_height,
+            self.output_height,
+        );
+        // Stay on the match instead of following new output
+        self.scroll_state.auto_scroll = false;
+    }
+
+    /// Finish scroll mode keys left waiting for too long: a count alone shows
+    /// the output tab of its number. Returns whether a sequence ended.
+    pub(crate) fn expire_keys<T: std::fmt::Debug>(
+        &mut self,
+        app: &mut Istari<T>,
+        now: Instant,
+    ) -> bool {
+        if !self.keys.is_pending() {
+            return false;
+        }
+        if let Some(tab @ 1..) = self.keys.expire(now) {
+            app.select_output_tab(tab - 1);
+        }
+        !self.keys.is_pending()
+    }
+
+    /// Handle a terminal event, returning false once the application should quit
+    pub(crate) fn handle_event<T: std::fmt::Debug>(
+        &mut self,
+        app: &mut Istari<T>,
+        event: crossterm::event::Event,
+    ) -> io::Result<bool> {
+        match event {
+            crossterm::event::Event::Key(_) if app.splash().is_some() => {
+                // The first key only takes the splash screen down
+                app.dismiss_splash();
+            }
+            crossterm::event::Event::Key(key) if app.dialog().is_some() => {
+                // The dialog takes all key events until it's answered
+                let is_prompt = matches!(
+                    app.dialog().map(|dialog| &dialog.kind),
+                    Some(DialogKind::Prompt { .. })
+                );
+                match key.code {
+                    crossterm::event::KeyCode::Enter => app.submit_dialog(),
+                    crossterm::event::KeyCode::Esc => app.cancel_dialog(),
+                    crossterm::event::KeyCode::Backspace => {
+                        if let Some(dialog) = app.dialog_mut() {
+                            dialog.backspace();
+                        }
+                    }
+                    crossterm::event::KeyCode::Char(c) if is_prompt => {
+                        if let Some(dialog) = app.dialog_mut() {
+                            dialog.type_char(c);
+                        }
+                    }
+                    crossterm::event::KeyCode::Char('y' | 'Y') => app.answer_dialog(true),
+                    crossterm::event::KeyCode::Char('n' | 'N') => app.answer_dialog(false),
+                    crossterm::event::KeyCode::Left
+                    | crossterm::event::KeyCode::Right
+                    | crossterm::event::KeyCode::Tab => {
+                        if let Some(dialog) = app.dialog_mut() {
+                            dialog.toggle_choice();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            crossterm::event::Event::Key(key) if app.popup().is_some() => {
+                // The popup viewer takes all key events while it is open, with
+                // the scroll mode keys
+                let keymap = app.keymap();
+                if keymap.triggers(Mode::Scroll, KeyAction::ClosePopup, &key) {
+                    app.close_popup();
+                } else if keymap.triggers(Mode::Scroll, KeyAction::ScrollDown, &key) {
+                    if let Some(popup) = app.popup_mut() {
+                        popup.scroll_by(1);
+                    }
+                } else if keymap.triggers(Mode::Scroll, KeyAction::ScrollUp, &key)
+                    && let Some(popup) = app.popup_mut()
+                {
+                    popup.scroll_by(-1);
+                }
+            }
+            crossterm::event::Event::Key(key) => {
+                // Process key events based on current mode
+                match app.mode() {
+                    crate::Mode::Command => {
+                        // Handle different key events in command mode
+                        let action = app.keymap().action(Mode::Command, &key);
+                        match (action, key.code) {
+                            // Exit the application
+                            (Some(KeyAction::Quit), _) => {
+                                let keep_running = app.handle_quit();
+                                if !keep_running {
+                                    return Ok(false);
+                                }
+                            }
+
+                            // Cancel the latest background action
+                            (Some(KeyAction::Cancel), _) => {
+                                app.handle_key("cancel");
+                            }
+
+                            // Complete the parameter being typed, or toggle mode if it's the same key
+                            (Some(KeyAction::Complete), _) => {
+                                if !app.complete_input()
+                                    && app.keymap().triggers(
+                                        Mode::Command,
+                                        KeyAction::ToggleMode,
+                                        &key,
+                                    )
+                                {
+                                    app.toggle_mode();
+                                }
+                            }
+                            (Some(KeyAction::ToggleMode), _) => {
+                                app.toggle_mode();
+                            }
+
+                            // Compose the command parameters in $EDITOR
+                            (Some(KeyAction::EditParams), _) => {
+                                self.suspend()?;
+                                app.compose_params_in_editor();
+                                self.resume()?;
+                            }
+
+                            // Hide the menu for a full-screen output
+                            (Some(KeyAction::FullOutput), _) => {
+                                app.toggle_full_output();
+                            }
+
+                            // Collapse the output pane
+                            (Some(KeyAction::CollapseOutput), _) => {
+                                app.toggle_output_pane();
+                            }
+
+                            // Toggle the jobs panel
+                            (Some(KeyAction::JobsPanel), _) => {
+                                app.toggle_jobs_panel();
+                            }
+
+                            // Toggle input display
+                            (Some(KeyAction::ToggleInput), _) => {
+                                app.toggle_show_input();
+                            }
+
+                            // Browse visited menus
+                            (Some(KeyAction::MenuBack), _) => {
+                                app.handle_key("<");
+                            }
+                            (Some(KeyAction::MenuForward), _) => {
+                                app.handle_key(">");
+                            }
+
+                            // Pick a suggestion, or navigate history
+                            (Some(KeyAction::HistoryUp), _) => {
+                                if !app.select_suggestion(-1) {
+                                    app.history_up();
+                                }
+                            }
+                            (Some(KeyAction::HistoryDown), _) => {
+                                if !app.select_suggestion(1) {
+                                    app.history_down();
+                                }
+                            }
+
+                            // Accept the picked suggestion, or process the input
+                            (_, crossterm::event::KeyCode::Enter) if app.accept_suggestion() => {}
+                            (_, crossterm::event::KeyCode::Enter) => {
+                                // Empty input still answers a parameter prompt, cancelling it
+                                let has_input =
+                                    !app.input_buffer().is_empty() || app.pending_param().is_some();
+                                if has_input && !app.process_input_buffer() {
+                                    return Ok(false);
+                                }
+                            }
+
+                            // Backspace to delete last character
+                            (_, crossterm::event::KeyCode::Backspace) => {
+                                app.exit_history_browsing();
+                                app.backspace_input_buffer();
+                            }
+
+                            // Any other key press exits history browsing
+                            (_, crossterm::event::KeyCode::Char(c)) => {
+                                app.exit_history_browsing();
+                                app.add_to_input_buffer(c);
+                            }
+
+                            // Handle single-key commands directly
+                            _ => {
+                                // Exit history browsing for any other key
+                                app.exit_history_browsing();
+
+                                // Convert keycode to string representation
+                                if let crossterm::event::KeyCode::Char(c) = key.code
+                                    && app.input_buffer().is_empty()
+                                    && !app.handle_key(c.to_string())
+                                {
+                                    return Ok(false);
+                                }
+                            }
+                        }
+                    }
+
+                    crate::Mode::Scroll
+                        if self.search.as_ref().is_some_and(|search| search.editing) =>
+                    {
+                        // The search prompt takes all keys until it's submitted
+                        match key.code {
+                            crossterm::event::KeyCode::Enter => {
+                                if let Some(search) = self.search.as_mut() {
+                                    search.editing = false;
+                                }
+                                self.jump_to_match(true);
+                            }
+                            crossterm::event::KeyCode::Esc => self.search = None,
+                            crossterm::event::KeyCode::Backspace => {
+                                if let Some(search) = self.search.as_mut() {
+                                    search.query.pop();
+                                }
+                            }
+                            crossterm::event::KeyCode::Char(c) => {
+                                if let Some(search) = self.search.as_mut() {
+                                    search.query.push(c);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    crate::Mode::Scroll => {
+                        // Counts and `gg` collect keys until the one they apply to
+                        let Some((code, count)) = self.keys.push(key, Instant::now()) else {
+                            return Ok(true);
+                        };
+                        let repeat = count.unwrap_or(1);
+                        // `gg` comes out of the sequence as Home, but scrolls to the top whatever Home does
+                        let gg = key.code == crossterm::event::KeyCode::Char('g')
+                            && code == crossterm::event::KeyCode::Home;
+                        let action = if gg {
+                            Some(KeyAction::Top)
+                        } else {
+                            let pressed = crossterm::event::KeyEvent::new(code, key.modifiers);
+                            app.keymap().action(Mode::Scroll, &pressed)
+                        };
+                        let Some(action) = action else {
+                            return Ok(true);
+                        };
+
+                        // Handle different key events in scroll mode
+                        match action {
+                            // Search the output
+                            KeyAction::Search => {
+                                self.search = Some(OutputSearch {
+                                    editing: true,
+                                    ..OutputSearch::default()
+                                });
+                            }
+
+                            // Jump between the matches of the search
+                            KeyAction::NextMatch => {
+                                for _ in 0..repeat {
+                                    self.jump_to_match(true);
+                                }
+                            }
+                            KeyAction::PreviousMatch => {
+                                for _ in 0..repeat {
+                                    self.jump_to_match(false);
+                                }
+                            }
+
+                            // Clear the search
+                            KeyAction::ClearSearch => self.search = None,
+
+                            // Exit the application
+                            KeyAction::Quit => {
+                                let keep_running = app.handle_quit();
+                                if !keep_running {
+                                    return Ok(false);
+                                }
+                            }
+
+                            // Toggle mode
+                            KeyAction::ToggleMode => {
+                                app.toggle_mode();
+                            }
+
+                            // Follow new output from the bottom, or stop following it
+                            KeyAction::Follow => {
+                                self.scroll_state.toggle_auto_scroll(
+                                    self.last_content_height,
+                                    self.output_height,
+                                );
+                            }
+
+                            // Toggle between wrapped and truncated output lines
+                            KeyAction::Wrap => {
+                                self.soft_wrap = !self.soft_wrap;
+                                self.h_scroll = 0;
+                            }
+
+                            // Number the output lines
+                            KeyAction::LineNumbers => {
+                                self.line_numbers = !self.line_numbers;
+                            }
+
+                            // Show the time of each message
+                            KeyAction::Timestamps => {
+                                self.timestamps = !self.timestamps;
+                            }
+
+                            // Scroll lines cut at the edge sideways
+                            KeyAction::ScrollLeft => {
+                                self.h_scroll = self
+                                    .h_scroll
+                                    .saturating_sub(HORIZONTAL_SCROLL_STEP.saturating_mul(repeat));
+                            }
+                            KeyAction::ScrollRight => {
+                                self.h_scroll = self
+                                    .h_scroll
+                                    .saturating_add(HORIZONTAL_SCROLL_STEP.saturating_mul(repeat))
+                                    .min(self.max_h_scroll);
+                            }
+
+                            // Hide the menu for a full-screen output
+                            KeyAction::FullOutput => {
+                                app.toggle_full_output();
+                            }
+
+                            // Show the first line cut at the maximum width in view in full
+                            KeyAction::ShowCutLine => {
+                                let top = self.scroll_state.position;
+                                let bottom = top + self.output_height.max(1);
+                                if let Some((_, number, text)) = self
+                                    .cut_lines
+                                    .iter()
+                                    .find(|(row, _, _)| (top..bottom).contains(row))
+                                {
+                                    app.show_popup(OutputPopup::new(
+                                        format!("Line {}", number),
+                                        text.clone(),
+                                    ));
+                                }
+                            }
+
+                            // Freeze the output to read it, catching up on resume
+                            KeyAction::Pause => {
+                                app.toggle_output_paused();
+                            }
+
+                            // Show the output of every menu or of the current one
+                            KeyAction::MenuOutput => {
+                                app.toggle_menu_output();
+                            }
+
+                            // Scroll down
+                            KeyAction::ScrollDown => {
+                                for _ in 0..repeat {
+                                    self.scroll_state.scroll(
+                                        ScrollDirection::Down,
+                                        self.last_content_height,
+                                        self.output_height,
+                                    );
+                                }
+                            }
+
+                            // Scroll up
+                            KeyAction::ScrollUp => {
+                                for _ in 0..repeat {
+                                    self.scroll_state.scroll(
+                                        ScrollDirection::Up,
+                                        self.last_content_height,
+                                        self.output_height,
+                                    );
+                                }
+                            }
+
+                            // Page down
+                            KeyAction::PageDown => {
+                                for _ in 0..repeat {
+                                    self.scroll_state.scroll(
+                                        ScrollDirection::PageDown,
+                                        self.last_content_height,
+                                        self.output_height,
+                                    );
+                                }
+                            }
+
+                            // Page up
+                            KeyAction::PageUp => {
+                                for _ in 0..repeat {
+                                    self.scroll_state.scroll(
+                                        ScrollDirection::PageUp,
+                                        self.last_content_height,
+                                        self.output_height,
+                                    );
+                                }
+                            }
+
+                            // Go to the top (gg), the bottom (G) or the line of a count
+                            KeyAction::Top | KeyAction::Bottom => {
+                                let direction = match count {
+                                    // Lines wrapped into several rows push the ones below them down
+                                    Some(line) => ScrollDirection::Line(
+                                        self.line_rows
+                                            .get(line.saturating_sub(1))
+                                            .copied()
+                                            .unwrap_or(self.last_content_height),
+                                    ),
+                                    None if action == KeyAction::Top => ScrollDirection::Top,
+                                    None => ScrollDirection::Bottom,
+                                };
+                                self.scroll_state.scroll(
+                                    direction,
+                                    self.last_content_height,
+                                    self.output_height,
+                                );
+                            }
+
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            crossterm::event::Event::Mouse(mouse)
+                if app.dialog().is_none()
+                    && app.popup().is_none()
+                    && app.pending_param().is_none()
+                    && app.pending_confirmation().is_none() =>
+            {
+                // Clicking a menu entry runs it like typing its key
+                if let crossterm::event::MouseEventKind::Down(crossterm::event::MouseButton::Left) =
+                    mouse.kind
+                    && let Some(command) =
+                        menu_command_at(self.menu_area, &self.menu_rows, mouse.column, mouse.row)
+                            .map(str::to_string)
+                {
+                    app.exit_history_browsing();
+                    if !app.handle_key(command) {
+                        return Ok(false);
+                    }
+                }
+            }
+            crossterm::event::Event::Resize(_, _) => {
+                // Resize events are automatically handled by the Terminal
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Keep the scroll state of the output tab shown so far, and restore the one of `tab`
+    fn switch_tab(&mut self, tab: usize) {
+        let previous = TabScroll {
+            scroll_state: self.scroll_state,
+            content_height: self.last_content_height,
+            seen_errors: self.seen_errors,
+        };
+        self.tab_scrolls.insert(self.shown_tab, previous);
+        let next = self.tab_scrolls.remove(&tab).unwrap_or_default();
+        self.scroll_state = next.scroll_state;
+        self.last_content_height = next.content_height;
+        self.seen_errors = next.seen_errors;
+        self.shown_tab = tab;
+    }
+}
+
+/// Get the command of the menu entry drawn at the given screen position in the menu list, if any
+fn menu_command_at(area: Rect, rows: &[(usize, String)], column: u16, row: u16) -> Option<&str> {
+    let inner = area.inner(ratatui::layout::Margin::new(1, 1));
+    if !inner.contains(ratatui::layout::Position::new(column, row)) {
+        return None;
+    }
+    let index = (row - inner.y) as usize;
+    rows.iter()
+        .find(|(menu_row, _)| *menu_row == index)
+        .map(|(_, command)| command.as_str())
+}
+
+/// Line listing the output tabs by number, the shown one highlighted and the ones with new output starred
+fn tab_bar_line<'a>(tabs: &[(&'a str, bool)], active: usize, theme: &Theme) -> Line<'a> {
+    let mut spans = Vec::with_capacity(tabs.len());
+    for (index, (name, unseen)) in tabs.iter().enumerate() {
+        let style = if index == active {
+            theme.key.add_modifier(Modifier::REVERSED)
+        } else if *unseen {
+            theme.warning
+        } else {
+            theme.muted
+        };
+        let marker = if *unseen { "*" } else { "" };
+        spans.push(Span::styled(
+            format!(" {}:{}{} ", index + 1, name, marker),
+            style,
+        ));
+    }
+    Line::from(spans)
+}
+
+/// Compute a rectangle centered in `area` taking the given percentage of its size
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
 
-impl UIController for TuiController {
+/// Help line naming the keys of the key map, shown unless the application sets its own
+fn default_help_line(mode: Mode, keymap: &KeyMap) -> String {
+    use KeyAction::*;
+    // Hints without actions are keys the key map doesn't cover
+    let (intro, hints): (&str, &[(&[KeyAction], &str)]) = match mode {
+        Mode::Command => (
+            "Type commands with optional parameters | ",
+            &[
+                (&[EditParams], "Edit params in $EDITOR"),
+                (&[ToggleMode], "to switch mode"),
+                (&[Quit], "to quit"),
+            ],
+        ),
+        Mode::Scroll => (
+            "SCROLL MODE: ",
+            &[
+                (&[ToggleMode], "to exit"),
+                (&[ScrollDown, ScrollUp], "Scroll"),
+                (&[PageUp, PageDown], "Page"),
+                (&[Top, Bottom], "Top/Bottom"),
+                (&[Wrap], "Toggle wrap"),
+                (&[Search], "Search"),
+                (&[ScrollLeft, ScrollRight], "Sideways"),
+                (&[LineNumbers], "Line numbers"),
+                (&[FullOutput], "Full screen"),
+                (&[], "1-9 Tabs"),
+                (&[Follow], "Follow"),
+            ],
+        ),
+    };
+    let first_key = |action: KeyAction| match action {
+        // `gg` goes to the top whatever else does
+        Top => Some("g".to_string()),
+        _ => keymap.keys(mode, action).first().map(Key::to_string),
+    };
+    let hints: Vec<String> = hints
+        .iter()
+        .filter_map(|(actions, text)| {
+            if actions.is_empty() {
+                return Some(text.to_string());
+            }
+            let keys: Option<Vec<String>> =
+                actions.iter().map(|&action| first_key(action)).collect();
+            keys.map(|keys| format!("{} {}", keys.join("/"), text))
+        })
+        .collect();
+    format!("{}{}", intro, hints.join(" | "))
+}
+
+/// Title of the popup viewer with its keys as mapped, e.g. `Logs - j/k Scroll | Esc to close`
+fn popup_title(title: &str, keymap: &KeyMap) -> String {
+    let first_key = |action| {
+        keymap
+            .keys(Mode::Scroll, action)
+            .first()
+            .map(Key::to_string)
+    };
+    let mut hints = Vec::new();
+    if let (Some(down), Some(up)) = (
+        first_key(KeyAction::ScrollDown),
+        first_key(KeyAction::ScrollUp),
+    ) {
+        hints.push(format!("{}/{} Scroll", down, up));
+    }
+    if let Some(close) = first_key(KeyAction::ClosePopup) {
+        hints.push(format!("{} to close", close));
+    }
+    if hints.is_empty() {
+        title.to_string()
+    } else {
+        format!("{} - {}", title, hints.join(" | "))
+    }
+}
+
+/// Lines of the splash screen: the art, the application name and version and
+/// the main key bindings. The art is padded to a block so centering keeps it aligned.
+fn splash_lines<'a>(
+    splash: &'a str,
+    app_info: Option<String>,
+    keymap: &KeyMap,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
+    let width = splash.lines().map(|line| line.width()).max().unwrap_or(0);
+    let mut lines: Vec<Line> = splash
+        .lines()
+        .map(|line| {
+            let padding = " ".repeat(width - line.width());
+            Line::from(vec![Span::styled(line, theme.title), Span::raw(padding)])
+        })
+        .collect();
+
+    if let Some(info) = app_info {
+        lines.push(Line::default());
+        lines.push(Line::styled(info, theme.section));
+    }
+
+    lines.push(Line::default());
+    let mut hints = Vec::new();
+    let first_key = |action| {
+        keymap
+            .keys(Mode::Command, action)
+            .first()
+            .map(Key::to_string)
+    };
+    for (key, description) in [
+        (first_key(KeyAction::ToggleMode), "Scroll output"),
+        (Some("keys".to_string()), "Key bindings"),
+        (first_key(KeyAction::Quit), "Quit"),
+    ] {
+        let Some(key) = key else {
+            continue;
+        };
+        if !hints.is_empty() {
+            hints.push(Span::styled(" | ", theme.muted));
+        }
+        hints.push(Span::styled(key, theme.key));
+        hints.push(Span::styled(format!(" {}", description), theme.item));
+    }
+    lines.push(Line::from(hints));
+    lines.push(Line::styled("Press any key to start", theme.muted));
+    lines
+}
+
+/// Lay out a dialog centered on the screen, as wide as 60% of it and as high as its content
+fn dialog_area(dialog: &Dialog, area: Rect) -> Rect {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let inner_width = width.saturating_sub(2).max(1) as usize;
+    let text_rows: usize = dialog
+        .text
+        .lines()
+        .map(|line| line.width().div_ceil(inner_width).max(1))
+        .sum();
+    // Borders, plus a blank row and the field or buttons below the text
+    let extra = match dialog.kind {
+        DialogKind::Message => 2,
+        _ => 4,
+    };
+    let height = ((text_rows + extra) as u16).min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Lines of a dialog: its text, then the buttons of a question or the field of a prompt
+fn dialog_lines<'a>(dialog: &'a Dialog, theme: &Theme) -> Vec<Line<'a>> {
+    let mut lines: Vec<Line> = dialog
+        .text
+        .lines()
+        .map(|line| Line::styled(line, theme.item))
+        .collect();
+    match &dialog.kind {
+        DialogKind::Message => {}
+        DialogKind::Confirm { yes } => {
+            let button = |label, selected| {
+                if selected {
+                    Span::styled(label, theme.key.add_modifier(Modifier::REVERSED))
+                } else {
+                    Span::styled(label, theme.muted)
+                }
+            };
+            lines.push(Line::default());
+            lines.push(
+                Line::from(vec![
+                    button("[ Yes ]", *yes),
+                    Span::raw("   "),
+                    button("[ No ]", !*yes),
+                ])
+                .centered(),
+            );
+        }
+        DialogKind::Prompt { value } => {
+            lines.push(Line::default());
+            lines.push(Line::styled(format!("> {}", value), theme.input));
+        }
+    }
+    lines
+}
+
+/// What output messages are styled for, restyling them all once it changes:
+/// the tab shown, pane width, theme, log level, menu shown and filter
+type StyleKey = (
+    usize,
+    usize,
+    Theme,
+    OutputLevel,
+    Option<String>,
+    Option<String>,
+);
+
+/// An output message styled into lines, kept between frames until it changes
+struct StyledMessage {
+    /// Time and repeats of the message when it was styled, which replacing or repeating it changes
+    stamp: (SystemTime, usize),
+    /// Lines shown, none when the log level, menu or filter hide the message
+    lines: Vec<StyledLine>,
+    /// Columns each line takes
+    widths: Vec<usize>,
+    /// Rows each line takes, empty until counted
+    rows: Vec<usize>,
+}
+
+/// Output messages styled and counted into rows by earlier frames, so a frame
+/// only styles the messages that changed and fits the lines near the view
+#[derive(Default)]
+struct OutputCache {
+    /// What the messages were styled for
+    style_key: Option<StyleKey>,
+    /// Output width, wrapping and maximum line width the rows were counted at
+    row_key: Option<(usize, bool, Option<usize>)>,
+    /// Messages dropped from the buffer before the first one cached, see [`Istari::output_dropped`]
+    first: usize,
+    /// Messages of the buffer shown, oldest first
+    messages: VecDeque<StyledMessage>,
+    /// Bumped whenever lines or their rows change
+    generation: u64,
+    /// Query, generation and sideways scroll the search matches were found at, with their rows
+    matches: Option<((String, u64, usize), Vec<usize>)>,
+}
+
+impl OutputCache {
+    /// Catch up with the messages of the buffer shown, styling the new and changed ones
+    fn sync(
+        &mut self,
+        records: &VecDeque<OutputRecord>,
+        dropped: usize,
+        key: StyleKey,
+        style: impl Fn(&OutputRecord) -> Vec<StyledLine>,
+    ) {
+        if self.style_key.as_ref() != Some(&key) || dropped < self.first {
+            self.style_key = Some(key);
+            self.messages.clear();
+            self.first = dropped;
+        }
+        // Forget the messages pushed out of the buffer or cleared
+        let gone = (dropped - self.first).min(self.messages.len());
+        self.messages.drain(..gone);
+        self.messages.truncate(records.len());
+        self.first = dropped;
+
+        let mut changed = gone > 0;
+        for (index, record) in records.iter().enumerate() {
+            let stamp = (record.time, record.repeats);
+            if self
+                .messages
+                .get(index)
+                .is_some_and(|message| message.stamp == stamp)
+            {
+                continue;
+            }
+            let lines = style(record);
+            let message = StyledMessage {
+                stamp,
+                widths: lines
+                    .iter()
+                    .map(|line| line.iter().map(|(part, _)| part.width()).sum())
+                    .collect(),
+                lines,
+                rows: Vec::new(),
+            };
+            match self.messages.get_mut(index) {
+                Some(cached) => *cached = message,
+                None => self.messages.push_back(message),
+            }
+            changed = true;
+        }
+        if changed {
+            self.generation += 1;
+        }
+    }
+
+    /// Count the rows of the lines not counted yet, recounting them all once the layout changed
+    fn count_rows(&mut self, output_width: usize, soft_wrap: bool, max_line_width: Option<usize>) {
+        let key = (output_width, soft_wrap, max_line_width);
+        if self.row_key != Some(key) {
+            self.row_key = Some(key);
+            for message in &mut self.messages {
+                message.rows.clear();
+            }
+            self.generation += 1;
+        }
+        for message in &mut self.messages {
+            if message.rows.len() == message.lines.len() {
+                continue;
+            }
+            message.rows = message
+                .lines
+                .iter()
+                .zip(&message.widths)
+                .map(
+                    |(line, &width)| match cut_line(line, width, max_line_width) {
+                        _ if !soft_wrap => 1,
+                        Some(cut) => wrapped_row_count(&cut, output_width),
+                        None => wrapped_row_count(line, output_width),
+                    },
+                )
+                .collect();
+        }
+    }
+
+    /// Find the rows holding a match of the search query, searching again only
+    /// once the lines, their rows or the sideways scroll changed
+    fn find_matches(&mut self, query: &str, h_scroll: usize, style: Style) -> &[usize] {
+        let key = (query.to_string(), self.generation, h_scroll);
+        if self.matches.as_ref().is_none_or(|(found, _)| *found != key) {
+            let (output_width, soft_wrap, max_line_width) = self.row_key.unwrap_or_default();
+            let mut rows = Vec::new();
+            let mut row = 0;
+            for message in &self.messages {
+                for ((line, &width), &line_rows) in
+                    message.lines.iter().zip(&message.widths).zip(&message.rows)
+                {
+                    let cut = cut_line(line, width, max_line_width);
+                    let line = cut.as_ref().unwrap_or(line);
+                    let skipped;
+                    let line = if soft_wrap {
+                        line
+                    } else {
+                        skipped = skip_columns(line, h_scroll);
+                        &skipped
+                    };
+                    for (offset, (_, text)) in fit_styled_line(line, output_width, soft_wrap)
+                        .iter()
+                        .enumerate()
+                    {
+                        if highlight_matches(text, query, style).1 {
+                            rows.push(row + offset);
+                        }
+                    }
+                    row += line_rows;
+                }
+            }
+            self.matches = Some((key, rows));
+        }
+        self.matches.as_ref().map_or(&[], |(_, rows)| rows)
+    }
+}
+
+/// Cut a line of the given width at the maximum line width, if it's wider
+fn cut_line(line: &StyledLine, width: usize, max_line_width: Option<usize>) -> Option<StyledLine> {
+    max_line_width
+        .filter(|&max| width > max)
+        .map(|max| fit_styled_line(line, max, false).swap_remove(0).1)
+}
+
+/// Count the rows [`fit_output_line`] wraps a styled line into, without building them
+fn wrapped_row_count(line: &StyledLine, width: usize) -> usize {
+    let marker_width = WRAP_MARKER.width();
+    let width = width.max(marker_width + 1);
+
+    let mut rows = 1;
+    let mut row_width = 0;
+    let mut limit = width;
+    for c in line.iter().flat_map(|(part, _)| part.chars()) {
+        let char_width = c.width().unwrap_or(0);
+        if row_width + char_width > limit {
+            rows += 1;
+            row_width = 0;
+            limit = width - marker_width;
+        }
+        row_width += char_width;
+    }
+    rows
+}
+
+/// Fit an output line into rows of at most `width` columns.
+///
+/// Leading whitespace is kept as is. When wrapping, every row after the first
+/// starts with [`WRAP_MARKER`]; otherwise the line is cut and ends with [`TRUNCATE_MARKER`].
+/// Each row is returned with whether it continues the previous one.
+fn fit_output_line(line: &str, width: usize, wrap: bool) -> Vec<(bool, String)> {
+    let marker_width = WRAP_MARKER.width();
+    let width = width.max(marker_width + 1);
+
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut row_width = 0;
+    let mut limit = width;
+
+    for c in line.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if row_width + char_width > limit {
+            if !wrap {
+                // Make room for the marker
+                while row_width + 1 > limit {
+                    let Some(removed) = row.pop() else { break };
+                    row_width -= removed.width().unwrap_or(0);
+                }
+                row.push(TRUNCATE_MARKER);
+                return vec![(false, row)];
+            }
+
+            rows.push((!rows.is_empty(), std::mem::take(&mut row)));
+            row_width = 0;
+            limit = width - marker_width;
+        }
+        row.push(c);
+        row_width += char_width;
+    }
+
+    rows.push((!rows.is_empty(), row));
+    rows
+}
+
+/// Cut a styled line into rows of at most `width` columns, see [`fit_output_line`]
+fn fit_styled_line(line: &StyledLine, width: usize, wrap: bool) -> Vec<(bool, StyledLine)> {
+    let text: String = line.iter().map(|(part, _)| part.as_str()).collect();
+    let mut styles = line
+        .iter()
+        .flat_map(|(part, style)| part.chars().map(move |_| *style));
+
+    fit_output_line(&text, width, wrap)
+        .into_iter()
+        .map(|(continued, row)| {
+            let mut parts: StyledLine = Vec::new();
+            for c in row.chars() {
+                // The truncate marker takes the style of the character it replaces
+                let style = styles.next().unwrap_or_default();
+                match parts.last_mut() {
+                    Some((part, last)) if *last == style => part.push(c),
+                    _ => parts.push((c.to_string(), style)),
+                }
+            }
+            (continued, parts)
+        })
+        .collect()
+}
+
+/// Drop the first `columns` columns of a styled line, to scroll it sideways
+fn skip_columns(line: &StyledLine, columns: usize) -> StyledLine {
+    let mut skipped = 0;
+    let mut parts: StyledLine = Vec::new();
+    for (part, style) in line {
+        let mut kept = String::new();
+        for c in part.chars() {
+            if skipped < columns {
+                skipped += c.width().unwrap_or(0);
+            } else {
+                kept.push(c);
+            }
+        }
+        if !kept.is_empty() {
+            parts.push((kept, *style));
+        }
+    }
+    parts
+}
+
+/// Split an output message into lines styled by its level and format
+fn styled_lines(
+    message: &str,
+    level: OutputLevel,
+    format: &OutputFormat,
+    theme: &Theme,
+) -> Vec<StyledLine> {
+    let style = level_style(level, theme);
+    let mut lines: Vec<StyledLine> = match format {
+        _ if is_unified_diff(message) => message
+            .lines()
+            .map(|line| vec![(line.to_string(), diff_line_style(line, theme))])
+            .collect(),
+        OutputFormat::Plain => message
+            .lines()
+            .map(|line| vec![(line.to_string(), style)])
+            .collect(),
+        OutputFormat::Table(_) => message
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| {
+                let line_style = match idx {
+                    0 => style.add_modifier(Modifier::BOLD),
+                    1 => theme.debug,
+                    _ => style,
+                };
+                vec![(line.to_string(), line_style)]
+            })
+            .collect(),
+        OutputFormat::Markdown => markdown_lines(message, style, theme),
+        OutputFormat::Code(language) => code_lines(message, language, theme),
+    };
+
+    // Keep empty messages as a blank row
+    if lines.is_empty() {
+        lines.push(vec![(String::new(), style)]);
+    }
+    lines
+}
+
+/// Draw a table as a bordered ratatui [`Table`] of at most `width` columns.
+///
+/// The table is turned into styled lines so it scrolls with the rest of the
+/// output. When it doesn't fit, the widest columns are narrowed and their
+/// cells cut with [`TRUNCATE_MARKER`].
+fn table_lines(table: &OutputTable, style: Style, width: usize, theme: &Theme) -> Vec<StyledLine> {
+    let mut widths = table.column_widths();
+    // Borders on both sides and spacing between the columns
+    let chrome = 2 + TABLE_COLUMN_SPACING * widths.len().saturating_sub(1);
+    let available = width.saturating_sub(chrome);
+    while widths.iter().sum::<usize>() > available {
+        match widths.iter_mut().max() {
+            Some(widest) if *widest > 1 => *widest -= 1,
+            _ => break,
+        }
+    }
+
+    let row = |cells: &[String]| {
+        Row::new(widths.iter().enumerate().map(|(idx, width)| {
+            let cell = cells.get(idx).map_or("", String::as_str);
+            let (_, fitted) = fit_output_line(cell, *width, false).remove(0);
+            Cell::from(fitted)
+        }))
+    };
+    let area = Rect::new(
+        0,
+        0,
+        (widths.iter().sum::<usize>() + chrome).min(u16::MAX as usize) as u16,
+        (table.rows.len() + 3).min(u16::MAX as usize) as u16,
+    );
+    let mut buffer = Buffer::empty(area);
+    Table::new(
+        table.rows.iter().map(|cells| row(cells)),
+        widths.iter().map(|width| Constraint::Length(*width as u16)),
+    )
+    .header(row(&table.headers).style(style.add_modifier(Modifier::BOLD)))
+    .column_spacing(TABLE_COLUMN_SPACING as u16)
+    .style(style)
+    .block(Block::bordered().border_style(theme.debug))
+    .render(area, &mut buffer);
+
+    (0..area.height)
+        .map(|y| {
+            let mut line: StyledLine = Vec::new();
+            let mut x = 0;
+            while x < area.width {
+                let cell = &buffer[(x, y)];
+                let style = cell.style();
+                match line.last_mut() {
+                    Some((part, last)) if *last == style => part.push_str(cell.symbol()),
+                    _ => line.push((cell.symbol().to_string(), style)),
+                }
+                // The cells a wide character covers are blank
+                x += cell.symbol().width().max(1) as u16;
+            }
+            line
+        })
+        .collect()
+}
+
+/// Style code in the given language, highlighted if the `highlight` feature knows it,
+/// otherwise with the keys of JSON and YAML in the key style
+fn code_lines(code: &str, language: &str, theme: &Theme) -> Vec<StyledLine> {
+    #[cfg(feature = "highlight")]
+    if let Some(lines) = theme
+        .syntax_theme
+        .and_then(|name| crate::highlight::highlight_code(code, language, name))
+    {
+        return lines;
+    }
+
+    match language.to_ascii_lowercase().as_str() {
+        "json" | "yaml" | "yml" => code.lines().map(|line| data_line(line, theme)).collect(),
+        _ => code
+            .lines()
+            .map(|line| vec![(line.to_string(), theme.code)])
+            .collect(),
+    }
+}
+
+/// Style a line of JSON or YAML in the code style, with the key it starts with,
+/// if any, in the key style
+fn data_line(line: &str, theme: &Theme) -> StyledLine {
+    let content = line.trim_start();
+    let indent = &line[..line.len() - content.len()];
+    // YAML list items can start with a key too
+    let (indent, content) = match content.strip_prefix("- ") {
+        Some(rest) => (&line[..indent.len() + 2], rest),
+        None => (indent, content),
+    };
+
+    let key_end = if let Some(quoted) = content.strip_prefix('"') {
+        // A JSON key, or a quoted YAML one
+        quoted
+            .find('"')
+            .map(|end| end + 2)
+            .filter(|&end| content[end..].trim_start().starts_with(':'))
+    } else if content.starts_with('#') {
+        None
+    } else {
+        // A plain YAML key, followed by a space or the end of the line
+        content
+            .find(':')
+            .filter(|&end| content[end + 1..].is_empty() || content[end + 1..].starts_with(' '))
+    };
+
+    match key_end {
+        Some(end) if end > 0 => {
+            let mut parts = Vec::with_capacity(3);
+            if !indent.is_empty() {
+                parts.push((indent.to_string(), theme.code));
+            }
+            parts.push((content[..end].to_string(), theme.key));
+            if end < content.len() {
+                parts.push((content[end..].to_string(), theme.code));
+            }
+            parts
+        }
+        _ => vec![(line.to_string(), theme.code)],
+    }
+}
+
+/// Style markdown line by line: headings, bullets, quotes and fenced code blocks
+fn markdown_lines(message: &str, style: Style, theme: &Theme) -> Vec<StyledLine> {
+    let mut lines = Vec::new();
+    // Language and lines of the fenced code block being read
+    let mut code: Option<(String, Vec<&str>)> = None;
+
+    for line in message.lines() {
+        let trimmed = line.trim_start();
+        if let Some(fence) = trimmed.strip_prefix("```") {
+            match code.take() {
+                Some((language, block)) => {
+                    lines.extend(indented_code(&block, &language, theme));
+                }
+                None => code = Some((fence.trim().to_string(), Vec::new())),
+            }
+            continue;
+        }
+
+        if let Some((_, block)) = &mut code {
+            block.push(line);
+            continue;
+        }
+
+        let styled = if let Some(heading) = trimmed.strip_prefix('#') {
+            let text = heading.trim_start_matches('#').trim_start();
+            (text.to_string(), style.patch(theme.heading))
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            let indent = &line[..line.len() - trimmed.len()];
+            (format!("{}• {}", indent, strip_inline_markup(item)), style)
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            (format!("│ {}", quote.trim_start()), theme.quote)
+        } else {
+            (strip_inline_markup(line), style)
+        };
+        lines.push(vec![styled]);
+    }
+
+    // Show a code block that's never closed anyway
+    if let Some((language, block)) = code {
+        lines.extend(indented_code(&block, &language, theme));
+    }
+    lines
+}
+
+/// Style the lines of a fenced code block, indented to set them apart
+fn indented_code(block: &[&str], language: &str, theme: &Theme) -> Vec<StyledLine> {
+    code_lines(&block.join("\n"), language, theme)
+        .into_iter()
+        .map(|mut line| {
+            line.insert(0, ("  ".to_string(), theme.code));
+            line
+        })
+        .collect()
+}
+
+/// Remove inline emphasis and code markers, which can't be styled within a row
+fn strip_inline_markup(line: &str) -> String {
+    line.replace("**", "").replace('`', "")
+}
+
+/// Style used for a line of a unified diff
+fn diff_line_style(line: &str, theme: &Theme) -> Style {
+    if line.starts_with("+++ ") || line.starts_with("--- ") {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else if line.starts_with("@@") {
+        theme.heading
+    } else if line.starts_with('+') {
+        theme.success
+    } else if line.starts_with('-') {
+        theme.error
+    } else {
+        theme.output
+    }
+}
+
+/// Style used for output messages of the given level
+/// Get the columns the input is scrolled sideways and the column of the cursor
+/// after it, counting the display width of wide characters like CJK and emoji
+fn input_cursor(input: &str, width: u16) -> (u16, u16) {
+    let typed = u16::try_from(input.width()).unwrap_or(u16::MAX);
+    // Keep a column free for the cursor
+    let visible = width.saturating_sub(1);
+    let scroll = typed.saturating_sub(visible);
+    (scroll, typed - scroll)
+}
+
+/// Fingerprint of what a frame shows that can change without input, e.g. output
+/// of background actions or menu items the tick handler updates
+fn frame_key<T: std::fmt::Debug>(app: &Istari<T>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    app.output_tabs().hash(&mut hasher);
+    app.output_records().len().hash(&mut hasher);
+    app.output_total().hash(&mut hasher);
+    app.log_level().hash(&mut hasher);
+    app.paused_output_count().hash(&mut hasher);
+    app.output_menu().hash(&mut hasher);
+    app.output_filter()
+        .map(|filter| filter.as_str())
+        .hash(&mut hasher);
+    app.mode().hash(&mut hasher);
+    app.panes().hash(&mut hasher);
+    app.input_buffer().hash(&mut hasher);
+    app.selected_suggestion().hash(&mut hasher);
+    app.status_line().hash(&mut hasher);
+    app.watches().hash(&mut hasher);
+    app.splash().hash(&mut hasher);
+    for metric in app.metrics() {
+        match metric {
+            MetricValue::Sparkline { samples, .. } => samples.hash(&mut hasher),
+            MetricValue::Gauge { ratio, .. } => ratio.to_bits().hash(&mut hasher),
+        }
+    }
+    app.queued_commands().count().hash(&mut hasher);
+    (app.dialog().is_some(), app.popup().is_some()).hash(&mut hasher);
+    (
+        app.pending_param().is_some(),
+        app.pending_confirmation().is_some(),
+    )
+        .hash(&mut hasher);
+    for job in app.jobs() {
+        (job.id, job.finished.is_some(), job.details).hash(&mut hasher);
+    }
+
+    let menu = app.current_menu();
+    let menu = menu.lock().unwrap();
+    menu.title.hash(&mut hasher);
+    for item in menu.items.iter().filter(|item| app.is_item_visible(item)) {
+        (&item.key, item.describe(app.state()), app.item_style(item)).hash(&mut hasher);
+    }
+    let globals = app.global_menu();
+    for item in &globals.lock().unwrap().items {
+        item.describe(app.state()).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Degrade the colors of a drawn frame to the ones the terminal shows
+fn degrade_colors(buffer: &mut Buffer, colors: ColorSupport) {
+    if colors == ColorSupport::TrueColor {
+        return;
+    }
+    for cell in &mut buffer.content {
+        let style = colors.style(Style::default().fg(cell.fg).bg(cell.bg));
+        cell.fg = style.fg.unwrap_or(Color::Reset);
+        cell.bg = style.bg.unwrap_or(Color::Reset);
+        cell.modifier |= style.add_modifier;
+    }
+}
+
+fn level_style(level: OutputLevel, theme: &Theme) -> Style {
+    match level {
+        OutputLevel::Debug => theme.debug,
+        OutputLevel::Info => theme.output,
+        OutputLevel::Warn => theme.warning,
+        OutputLevel::Error => theme.error,
+    }
+}
+
+impl<T: std::fmt::Debug, E: TerminalBackend> UIController<T> for TuiController<E> {
     /// Initialize the terminal
     fn init(&mut self) -> io::Result<()> {
-        crossterm::terminal::enable_raw_mode()?;
-        crossterm::execute!(
-            io::stdout(),
-            crossterm::terminal::EnterAlternateScreen,
-            crossterm::event::EnableMouseCapture
-        )?;
-        self.terminal.clear()?;
-        Ok(())
+        self.resume()
     }
 
     /// Restore the terminal
     fn cleanup(&mut self) -> io::Result<()> {
-        crossterm::terminal::disable_raw_mode()?;
-        crossterm::execute!(
-            io::stdout(),
-            crossterm::terminal::LeaveAlternateScreen,
-            crossterm::event::DisableMouseCapture
-        )?;
-        self.terminal.show_cursor()?;
-        Ok(())
+        self.suspend()
     }
 
     /// Render the current menu
-    fn render_frame<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()> {
+    fn render_frame(&mut self, app: &mut Istari<T>) -> io::Result<()> {
         let menu = app.current_menu();
 
+        // Each output tab keeps its own scroll position
+        if app.active_output_tab() != self.shown_tab {
+            self.switch_tab(app.active_output_tab());
+        }
+
         // Check for new output and update auto-scroll before rendering
         let has_new_output = app.has_new_output();
 
@@ -69,57 +1612,63 @@ impl UIController for TuiController {
             self.terminal.hide_cursor()?;
         }
 
+        let theme = *app.theme();
+        let layout = *app.layout();
+        let colors = app.color_support();
+        // Matched before the frame locks the menu
+        let suggestions = if app.mode() == Mode::Command {
+            app.command_suggestions()
+        } else {
+            Vec::new()
+        };
         self.terminal.draw(|f| {
             let area = f.area();
 
-            // First split the screen vertically into main content and footer
-            let vertical_split = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(5),     // Main content area
-                    Constraint::Length(4),  // Footer (help text + command input)
-                ])
-                .split(area);
+            // Split the screen into the menu, output and footer panels
+            let status_line = app.status_line();
+            let areas = layout.split(area, app.panes(), status_line.is_some());
 
-            // Split the footer vertically with command input above help text
-            let footer_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length(3),  // Command input
-                    Constraint::Length(1),  // Help text
-                ])
-                .split(vertical_split[1]);
-
-            // Split the main content horizontally for menu and output
-            let horizontal_split = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Percentage(50),  // Menu side
-                    Constraint::Percentage(50),  // Output side
-                ])
-                .split(vertical_split[0]);
-
-            // Split the menu side vertically
+            // Split the menu side vertically, with the jobs panel at the bottom if it's shown
+            let jobs = app.jobs();
+            let jobs_height = if app.jobs_panel_visible() {
+                (jobs.len().max(1) as u16 + 2).min(JOBS_PANEL_MAX_HEIGHT)
+            } else {
+                0
+            };
+            // Watched expressions go between the menu items and the jobs
+            let watches = app.watches();
+            let watch_height = if watches.is_empty() {
+                0
+            } else {
+                (watches.len() as u16 + 2).min(WATCH_PANEL_MAX_HEIGHT)
+            };
+            let metrics = app.metrics();
+            let stats_height = if metrics.is_empty() {
+                0
+            } else {
+                (metrics.len() as u16 + 2).min(STATS_PANEL_MAX_HEIGHT)
+            };
             let menu_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(3),  // Title
-                    Constraint::Min(0),     // Menu items
+                    Constraint::Length(3),            // Title
+                    Constraint::Min(0),               // Menu items
+                    Constraint::Length(watch_height), // Watch panel
+                    Constraint::Length(stats_height), // Stats pane
+                    Constraint::Length(jobs_height),  // Jobs panel
                 ])
-                .split(horizontal_split[0]);
+                .split(areas.menu);
 
-            // Output takes the entire right side of the main content
-            let output_chunk = horizontal_split[1];
+            let output_chunk = areas.output;
 
             let menu = menu.lock().unwrap();
 
             // Render title
-            let title_text = Text::styled(
-                menu.title.clone(),
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD)
-            );
+            let menu_title = match app.category_filter() {
+                Some(category) => format!("{} (category: {})", menu.title, category),
+                None => menu.title.clone(),
+            };
+            let title_text = Text::styled(menu_title, theme.title);
 
             // Add mode indicator to title
             let mode_name = match app.mode() {
@@ -127,93 +1676,442 @@ impl UIController for TuiController {
                 Mode::Scroll => "SCROLL MODE",
             };
             let mode_style = match app.mode() {
-                Mode::Command => Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
-                Mode::Scroll => Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                Mode::Command => theme.command_mode,
+                Mode::Scroll => theme.scroll_mode,
             };
 
-            let title = Paragraph::new(title_text)
-                .block(Block::default().borders(Borders::ALL).title(format!("Istari - {}", 
-                    Span::styled(mode_name, mode_style))));
+            let mut title_block = Block::default().borders(Borders::ALL).title(format!(
+                "{} - {}",
+                app.title(),
+                Span::styled(mode_name, mode_style)
+            ));
+            if let Some(info) = app.app_info() {
+                title_block = title_block.title(Line::styled(info, theme.muted).right_aligned());
+            }
+            let title = Paragraph::new(title_text).block(title_block);
             f.render_widget(title, menu_chunks[0]);
 
-            // Render menu items
+            // Render menu items, remembering which rows run a command when clicked
             let mut items = Vec::new();
-            for item in &menu.items {
-                let key_style = Style::default().fg(Color::Yellow);
-                let desc_style = Style::default().fg(Color::White);
+            self.menu_rows.clear();
+
+            // Show pinned actions at the top of the root menu
+            if app.is_at_root() && !app.favorites().is_empty() {
+                items.push(ListItem::new(Line::from(Span::styled(
+                    "Favorites",
+                    theme.section,
+                ))));
+                for (idx, command) in app.favorites().iter().enumerate() {
+                    self.menu_rows.push((items.len(), format!("*{}", idx + 1)));
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled(format!("[*{}] ", idx + 1), theme.key),
+                        Span::styled(command.description.clone(), theme.item),
+                        Span::styled(format!(" ({})", command.item_path()), theme.muted),
+                    ])));
+                }
+                items.push(ListItem::new(""));
+            }
+
+            // Show recently run commands at the top of the root menu
+            if app.is_at_root() && app.recent_commands().is_visible() {
+                items.push(ListItem::new(Line::from(Span::styled(
+                    "Recent",
+                    theme.section,
+                ))));
+                for (idx, command) in app.recent_commands().iter().enumerate() {
+                    self.menu_rows.push((items.len(), format!("!{}", idx + 1)));
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled(format!("[!{}] ", idx + 1), theme.key),
+                        Span::styled(command.label(), theme.muted),
+                    ])));
+                }
+                items.push(ListItem::new(""));
+            }
+
+            // Show queued commands in every menu while the queue runs
+            if app.queue_running().is_some() || app.queued_commands().next().is_some() {
+                items.push(ListItem::new(Line::from(Span::styled(
+                    "Queue",
+                    theme.section,
+                ))));
+                if let Some(command) = app.queue_running() {
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled("▶ ", theme.success),
+                        Span::styled(command.label(), theme.item),
+                    ])));
+                }
+                for (idx, command) in app.queued_commands().enumerate() {
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled(format!("{}. ", idx + 1), theme.key),
+                        Span::styled(command.label(), theme.muted),
+                    ])));
+                }
+                items.push(ListItem::new(""));
+            }
+
+            for item in menu.items.iter().filter(|item| app.is_item_visible(item)) {
+                let key_style = theme.key;
+                let desc_style = match (item.style, app.item_style(item)) {
+                    (Some(style), _) => theme.item.patch(style),
+                    (None, OutputLevel::Info) => theme.item,
+                    (None, level) => level_style(level, &theme),
+                };
                 let item_line = Line::from(vec![
                     Span::styled(format!("[{}] ", item.key), key_style),
-                    Span::styled(&item.description, desc_style),
+                    Span::styled(item.describe(app.state()), desc_style),
                 ]);
+                self.menu_rows.push((items.len(), item.key.clone()));
                 items.push(ListItem::new(item_line));
             }
 
+            // Show the actions available from every menu below the menu's own items
+            let globals = app.global_menu();
+            let globals = globals.lock().unwrap();
+            if !globals.items.is_empty() {
+                items.push(ListItem::new(""));
+                items.push(ListItem::new(Line::from(Span::styled(
+                    "Global",
+                    theme.section,
+                ))));
+                for item in &globals.items {
+                    self.menu_rows.push((items.len(), item.key.clone()));
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled(format!("[{}] ", item.key), theme.key),
+                        Span::styled(
+                            item.describe(app.state()),
+                            theme.item.patch(item.style.unwrap_or_default()),
+                        ),
+                    ])));
+                }
+                items.push(ListItem::new(""));
+            }
+
             // Add back/quit option if not at root
-            if menu.parent.is_some() {
+            if !app.is_at_root() {
+                self.menu_rows.push((items.len(), "b".to_string()));
                 items.push(ListItem::new(Line::from(vec![
-                    Span::styled("[b] ", Style::default().fg(Color::Yellow)),
-                    Span::styled("Back", Style::default().fg(Color::White)),
+                    Span::styled("[b] ", theme.key),
+                    Span::styled("Back", theme.item),
                 ])));
             } else {
+                self.menu_rows.push((items.len(), "q".to_string()));
                 items.push(ListItem::new(Line::from(vec![
-                    Span::styled("[q] ", Style::default().fg(Color::Yellow)),
-                    Span::styled("Quit", Style::default().fg(Color::White)),
+                    Span::styled("[q] ", theme.key),
+                    Span::styled("Quit", theme.item),
                 ])));
             }
 
-            let items_list = List::new(items)
-                .block(Block::default().borders(Borders::ALL).title("Menu Items"));
-            f.render_widget(items_list, menu_chunks[1]);
-
+            let items_list =
+                List::new(items).block(Block::default().borders(Borders::ALL).title("Menu Items"));
+            f.render_widget(items_list, menu_chunks[1]);
+            self.menu_area = menu_chunks[1];
+
+            // Render the watch panel, labels aligned
+            if !watches.is_empty() {
+                let label_width = watches
+                    .iter()
+                    .map(|(label, _)| label.width())
+                    .max()
+                    .unwrap_or(0);
+                let watch_items: Vec<ListItem> = watches
+                    .iter()
+                    .map(|(label, value)| {
+                        let padding = " ".repeat(label_width - label.width());
+                        ListItem::new(Line::from(vec![
+                            Span::styled(format!("{}{}  ", label, padding), theme.key),
+                            Span::styled(value.clone(), theme.item),
+                        ]))
+                    })
+                    .collect();
+                let watch_list = List::new(watch_items)
+                    .block(Block::default().borders(Borders::ALL).title("Watch"));
+                f.render_widget(watch_list, menu_chunks[2]);
+            }
+
+            // Render the stats pane, a row per sparkline or gauge
+            if !metrics.is_empty() {
+                let block = Block::default().borders(Borders::ALL).title("Stats");
+                let inner = block.inner(menu_chunks[3]);
+                f.render_widget(block, menu_chunks[3]);
+                let label_width = metrics
+                    .iter()
+                    .map(|metric| metric.label().width())
+                    .max()
+                    .unwrap_or(0) as u16
+                    + 2;
+                for (row, metric) in (inner.top()..inner.bottom()).zip(&metrics) {
+                    let label_area = Rect::new(inner.x, row, label_width.min(inner.width), 1);
+                    let chart_area = Rect::new(
+                        inner.x + label_area.width,
+                        row,
+                        inner.width - label_area.width,
+                        1,
+                    );
+                    f.render_widget(
+                        Paragraph::new(Span::styled(metric.label(), theme.key)),
+                        label_area,
+                    );
+                    match metric {
+                        MetricValue::Sparkline { samples, .. } => {
+                            // The latest samples that fit
+                            let shown =
+                                &samples[samples.len().saturating_sub(chart_area.width as usize)..];
+                            f.render_widget(
+                                Sparkline::default().data(shown).style(theme.progress),
+                                chart_area,
+                            );
+                        }
+                        MetricValue::Gauge { ratio, .. } => {
+                            let gauge = Gauge::default()
+                                .ratio(*ratio)
+                                .label(format!("{:.0}%", ratio * 100.0))
+                                .gauge_style(theme.progress);
+                            f.render_widget(gauge, chart_area);
+                        }
+                    }
+                }
+            }
+
+            // Render the jobs panel, latest jobs last
+            if app.jobs_panel_visible() {
+                let now = Instant::now();
+                let visible = (jobs_height as usize).saturating_sub(2);
+                let mut job_items: Vec<ListItem> = jobs
+                    .iter()
+                    .skip(jobs.len().saturating_sub(visible))
+                    .map(|job| {
+                        let status_style = match job.status {
+                            JobStatus::Running => theme.progress,
+                            JobStatus::Cancelling | JobStatus::Cancelled => theme.warning,
+                            JobStatus::Finished => theme.success,
+                            JobStatus::Failed => theme.error,
+                        };
+                        ListItem::new(Line::from(vec![
+                            Span::styled(format!("#{} ", job.id), theme.key),
+                            Span::styled(job.command.clone(), theme.item),
+                            Span::styled(
+                                format!(" {} ", format_elapsed(job.elapsed(now))),
+                                theme.muted,
+                            ),
+                            Span::styled(job.status.to_string(), status_style),
+                        ]))
+                    })
+                    .collect();
+                if job_items.is_empty() {
+                    job_items.push(ListItem::new(Span::styled(
+                        "No background jobs",
+                        theme.muted,
+                    )));
+                }
+                let jobs_title = match app
+                    .keymap()
+                    .keys(Mode::Command, KeyAction::JobsPanel)
+                    .first()
+                {
+                    Some(key) => format!("Jobs ({} to hide)", key),
+                    None => "Jobs".to_string(),
+                };
+                let jobs_list = List::new(job_items)
+                    .block(Block::default().borders(Borders::ALL).title(jobs_title));
+                f.render_widget(jobs_list, menu_chunks[4]);
+            }
+
             // Render command input box when in Command mode
             if app.mode() == Mode::Command {
                 let input_text = app.input_buffer();
+                let input_title = if let Some(spec) = app.pending_param() {
+                    format!("Enter {} - Press Enter, empty input cancels", spec)
+                } else if app.pending_confirmation().is_some() {
+                    "Confirm - Type y or n and press Enter".to_string()
+                } else if !app.completions().is_empty() {
+                    format!("Completions: {}", app.completions().join("  "))
+                } else {
+                    "Command Input - Command [param] - Press Enter to execute".to_string()
+                };
+                // Long input scrolls sideways to keep its end in view
+                let (input_scroll, cursor_x) =
+                    input_cursor(input_text, areas.input.width.saturating_sub(2));
                 let input_widget = Paragraph::new(input_text)
-                    .style(Style::default().fg(Color::White))
-                    .block(Block::default().borders(Borders::ALL).title("Command Input - Command [param] - Press Enter to execute"));
-                f.render_widget(input_widget, footer_chunks[0]);
+                    .style(theme.input)
+                    .scroll((0, input_scroll))
+                    .block(Block::default().borders(Borders::ALL).title(input_title));
+                f.render_widget(input_widget, areas.input);
 
                 // Show cursor at input position
-                let cursor_x = input_text.len() as u16;
-                f.set_cursor_position(
-                    ratatui::layout::Position::new(
-                        footer_chunks[0].x + cursor_x + 1, // +1 for border
-                        footer_chunks[0].y + 1             // +1 for border
-                    )
-                );
+                f.set_cursor_position(ratatui::layout::Position::new(
+                    areas.input.x + cursor_x + 1, // +1 for border
+                    areas.input.y + 1,            // +1 for border
+                ));
             }
 
-            // Render help text based on current mode
-            let help_text = match app.mode() {
-                Mode::Command => {
-                    Paragraph::new("Type commands with optional parameters | Tab to switch mode | Ctrl+Q to quit")
-                        .style(Style::default().fg(Color::Gray))
-                },
-                Mode::Scroll => {
-                    Paragraph::new("SCROLL MODE: Tab to exit | j/k Scroll | u/d Page | g/G Top/Bottom | Ctrl+A Toggle auto-scroll")
-                        .style(Style::default().fg(Color::Yellow))
-                }
+            // Show a spinner or the progress of background actions in place of the help text
+            let progress = app.activity();
+            let help_text = if !progress.is_empty() {
+                let now = Instant::now();
+                let lines: Vec<String> = progress.iter().map(|job| job.line(now)).collect();
+                Paragraph::new(lines.join(" | ")).style(theme.progress)
+            } else if let Some(line) = app.help_line(app.mode()) {
+                let style = match app.mode() {
+                    Mode::Command => theme.muted,
+                    Mode::Scroll => theme.scroll_help,
+                };
+                Paragraph::new(line).style(style)
+            } else {
+                let style = match app.mode() {
+                    Mode::Command => theme.muted,
+                    Mode::Scroll => theme.scroll_help,
+                };
+                Paragraph::new(default_help_line(app.mode(), app.keymap())).style(style)
             };
-            f.render_widget(help_text, footer_chunks[1]);
+            if let Some(help_area) = areas.help {
+                f.render_widget(help_text, help_area);
+            }
+
+            // Render the status bar segments of the application
+            if let (Some(status), Some(status_area)) = (status_line, areas.status) {
+                f.render_widget(
+                    Paragraph::new(format!(" {}", status)).style(theme.status),
+                    status_area,
+                );
+            }
 
             // Render output area on the right side
-            let output_messages = app.output_messages();
-            let output_text = if output_messages.is_empty() {
-                Text::styled(
-                    "No output yet. Run commands to see their output here.",
-                    Style::default().fg(Color::Gray)
-                )
+            let output_entries = app.output_records();
+            // First row of each message and the number of lines before it,
+            // to scroll to a message and to draw the ones near the view
+            let mut message_rows = Vec::with_capacity(output_entries.len());
+            let mut message_numbers = Vec::with_capacity(output_entries.len());
+            let mut output_layout = None;
+            self.cut_lines.clear();
+            let content_height = if output_entries.is_empty() {
+                self.line_rows.clear();
+                1
             } else {
-                let messages: Vec<Line> = output_messages
+                // Lay out rows ourselves so indentation survives and scrolling counts real rows
+                let pane_width = output_chunk.width.saturating_sub(2) as usize; // Adjusting for borders
+                let output_menu = app.output_menu();
+                let style_key = (
+                    app.active_output_tab(),
+                    pane_width,
+                    theme,
+                    app.log_level(),
+                    output_menu.clone(),
+                    app.output_filter()
+                        .map(|filter| filter.as_str().to_string()),
+                );
+                // Only messages added or changed since the last frame get styled
+                self.output_cache
+                    .sync(output_entries, app.output_dropped(), style_key, |entry| {
+                        let mut lines = match &entry.format {
+                            // Messages below the log level take no rows but are kept
+                            _ if entry.level < app.log_level() => Vec::new(),
+                            // As do messages of other menus
+                            _ if output_menu
+                                .as_ref()
+                                .is_some_and(|shown| *shown != entry.menu) =>
+                            {
+                                Vec::new()
+                            }
+                            OutputFormat::Table(table) if table.columns() > 0 => table_lines(
+                                table,
+                                level_style(entry.level, &theme),
+                                pane_width,
+                                &theme,
+                            ),
+                            format => styled_lines(&entry.text, entry.level, format, &theme),
+                        };
+                        // So do lines the filter hides
+                        if app.output_filter().is_some() {
+                            lines.retain(|line| {
+                                let text: String =
+                                    line.iter().map(|(part, _)| part.as_str()).collect();
+                                app.is_line_shown(&text)
+                            });
+                        }
+                        // Collapsed duplicates count up after the message
+                        if entry.repeats > 1
+                            && let Some(last) = lines.last_mut()
+                        {
+                            last.push((format!(" ×{}", entry.repeats), theme.muted));
+                        }
+                        lines
+                    });
+
+                // Line numbers take a gutter as wide as the last number, plus a space
+                let messages = &self.output_cache.messages;
+                let line_count: usize = messages.iter().map(|message| message.lines.len()).sum();
+                let gutter = if self.line_numbers {
+                    line_count.to_string().len() + 1
+                } else {
+                    0
+                };
+                // Times take a column of their own after the line numbers, `HH:MM:SS `
+                let time_column = if self.timestamps { TIMESTAMP_WIDTH } else { 0 };
+                let output_width = pane_width.saturating_sub(gutter + time_column).max(1);
+
+                // Lines cut at the edge scroll sideways, up to the widest one
+                let max_line_width = layout.max_line_width;
+                let widest = messages
                     .iter()
-                    .map(|msg| Line::from(msg.as_str()))
-                    .collect();
-                Text::from(messages)
+                    .flat_map(|message| &message.widths)
+                    .map(|&width| max_line_width.map_or(width, |max| width.min(max)))
+                    .max()
+                    .unwrap_or(0);
+                self.max_h_scroll = if self.soft_wrap {
+                    0
+                } else {
+                    widest.saturating_sub(output_width)
+                };
+                self.h_scroll = self.h_scroll.min(self.max_h_scroll);
+
+                // Lines only get wrapped again once they change or the width does
+                self.output_cache
+                    .count_rows(output_width, self.soft_wrap, max_line_width);
+                let mut line_rows = Vec::with_capacity(line_count);
+                let mut row = 0;
+                for message in &self.output_cache.messages {
+                    message_rows.push(row);
+                    message_numbers.push(line_rows.len());
+                    for rows in &message.rows {
+                        line_rows.push(row);
+                        row += rows;
+                    }
+                }
+
+                let query = self
+                    .search
+                    .as_ref()
+                    .map(|search| search.query.clone())
+                    .filter(|query| !query.is_empty());
+                if let (Some(query), Some(search)) = (&query, self.search.as_mut()) {
+                    search.rows = self
+                        .output_cache
+                        .find_matches(query, self.h_scroll, theme.search_match)
+                        .to_vec();
+                }
+
+                // Rows move when lines wrap at another width, keep the line at the top in view
+                let top_line = self
+                    .line_rows
+                    .partition_point(|&first_row| first_row <= self.scroll_state.position)
+                    .saturating_sub(1);
+                let wrap_width = self.soft_wrap.then_some(output_width);
+                let rewrapped = wrap_width != self.wrap_width;
+                self.wrap_width = wrap_width;
+                self.line_rows = line_rows;
+                if rewrapped
+                    && !self.scroll_state.auto_scroll
+                    && let Some(&first_row) = self.line_rows.get(top_line)
+                {
+                    self.scroll_state.position = first_row;
+                }
+                output_layout = Some((gutter, time_column, output_width, query));
+                row
             };
 
             // Calculate max scroll position based on content height
-            let output_area_height = output_chunk.height as usize - 2; // Adjusting for borders
-            let content_height = output_messages.len();
+            let output_area_height = output_chunk.height.saturating_sub(2) as usize; // Adjusting for borders
+            self.output_height = output_area_height;
 
             // Check if content height changed
             let content_changed = content_height != self.last_content_height;
@@ -223,244 +2121,1620 @@ impl UIController for TuiController {
             self.scroll_state.update_auto_scroll(
                 content_height,
                 output_area_height,
-                has_new_output || content_changed
+                has_new_output || content_changed,
             );
+            // The content may have got shorter, e.g. wrapping at a wider width
+            self.scroll_state.clamp(content_height, output_area_height);
+
+            // Bring new errors into view and flash the border, so they aren't missed
+            let error_count = output_entries
+                .iter()
+                .filter(|entry| entry.level == OutputLevel::Error)
+                .count();
+            if error_count < self.seen_errors {
+                // The buffer was cleared
+                self.seen_errors = error_count;
+            }
+            if app.error_focus() && error_count > self.seen_errors {
+                if let Some(row) = output_entries
+                    .iter()
+                    .rposition(|entry| entry.level == OutputLevel::Error)
+                    .and_then(|idx| message_rows.get(idx))
+                {
+                    self.scroll_state.scroll(
+                        ScrollDirection::Line(*row),
+                        content_height,
+                        output_area_height,
+                    );
+                }
+                self.flash_until = Some(Instant::now() + ERROR_FLASH_DURATION);
+            }
+            self.seen_errors = error_count;
+            let border_style = match self.flash_until {
+                // Blink between red and the default border
+                Some(until) if Instant::now() < until => {
+                    let remaining = until.duration_since(Instant::now()).as_millis();
+                    if (remaining / ERROR_FLASH_BLINK.as_millis()).is_multiple_of(2) {
+                        theme.error
+                    } else {
+                        Style::default()
+                    }
+                }
+                _ => Style::default(),
+            };
 
-            // Show auto-scroll status in title
+            // Show whether the view follows new output in the title
             let scroll_status = if self.scroll_state.auto_scroll {
-                "Auto-scroll ON"
+                "Follow ON"
             } else {
-                "Auto-scroll OFF"
+                "Follow OFF"
+            };
+
+            // Show wrapping status in title
+            let wrap_status = match (self.soft_wrap, self.h_scroll) {
+                (true, _) => "Wrap".to_string(),
+                (false, 0) => "No wrap".to_string(),
+                (false, columns) => format!("No wrap +{}", columns),
             };
 
             // Calculate max_scroll for display
             let max_scroll = content_height.saturating_sub(output_area_height);
 
-            // Render output content
+            // Count failed actions separately from the rest of the output, and show background work
+            let mut error_status = match app.error_count() {
+                0 => String::new(),
+                count => format!(" [Errors: {}]", count),
+            };
+            if app.running_actions() > 0 {
+                error_status.push_str(&format!(" [Running: {}]", app.running_actions()));
+            }
+            if app.log_level() > OutputLevel::Debug {
+                error_status.push_str(&format!(" [Level: {}+]", app.log_level().name()));
+            }
+            if let Some(filter) = app.output_filter() {
+                error_status.push_str(&format!(" [Filtered: {}]", filter.as_str()));
+            }
+            if app.is_output_paused() {
+                error_status.push_str(&format!(" [Paused: {} new]", app.paused_output_count()));
+            }
+            if let Some(menu) = app.output_menu() {
+                error_status.push_str(&format!(" [Menu: /{}]", menu));
+            }
+            if self.timestamps {
+                error_status.push_str(" [UTC]");
+            }
+
+            // List the output tabs below the output once there's more than one
+            let mut output_block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style)
+                .title(format!(
+                    "Output [{}] [{}] [{}/{}]{}",
+                    scroll_status,
+                    wrap_status,
+                    self.scroll_state.position,
+                    max_scroll,
+                    error_status
+                ));
+            let tabs = app.output_tabs();
+            if tabs.len() > 1 {
+                output_block =
+                    output_block.title_bottom(tab_bar_line(&tabs, app.active_output_tab(), &theme));
+            }
+            if let Some(search) = &self.search {
+                output_block = output_block
+                    .title_bottom(Line::styled(search.status(), theme.scroll_help).right_aligned());
+            }
+
+            // Draw the rows in view, plus a margin, already wrapped or truncated to fit
+            let position = self.scroll_state.position;
+            let window = position.saturating_sub(OUTPUT_RENDER_MARGIN)
+                ..position + output_area_height + OUTPUT_RENDER_MARGIN;
+            let (output_text, window_start) = match &output_layout {
+                None => (
+                    Text::styled(
+                        "No output yet. Run commands to see their output here.",
+                        theme.muted,
+                    ),
+                    0,
+                ),
+                Some((gutter, time_column, output_width, query)) => {
+                    // Start from the last message beginning at or above the window
+                    let first = message_rows
+                        .partition_point(|&row| row <= window.start)
+                        .saturating_sub(1);
+                    let mut row = message_rows[first];
+                    let mut number = message_numbers[first];
+                    let mut window_start = None;
+                    let mut rows: Vec<Line> = Vec::new();
+                    'messages: for (message, cached) in
+                        self.output_cache.messages.iter().enumerate().skip(first)
+                    {
+                        for (index, (line, &width)) in
+                            cached.lines.iter().zip(&cached.widths).enumerate()
+                        {
+                            if row >= window.end {
+                                break 'messages;
+                            }
+                            number += 1;
+                            let first_row = row;
+                            row += cached.rows[index];
+                            if row <= window.start {
+                                continue;
+                            }
+                            window_start.get_or_insert(first_row);
+
+                            // Cut huge lines, keeping their text to show them in full
+                            let line = apply_highlight_rules(line, app.highlight_rules());
+                            let line = match cut_line(&line, width, layout.max_line_width) {
+                                Some(cut) => {
+                                    let text = line.iter().map(|(part, _)| part.as_str()).collect();
+                                    self.cut_lines.push((first_row, number, text));
+                                    cut
+                                }
+                                None => line,
+                            };
+                            let line = if self.soft_wrap {
+                                line
+                            } else {
+                                skip_columns(&line, self.h_scroll)
+                            };
+                            let fitted = fit_styled_line(&line, *output_width, self.soft_wrap);
+                            for (offset, (continued, row)) in fitted.into_iter().enumerate() {
+                                let row = match query {
+                                    Some(query) => {
+                                        highlight_matches(&row, query, theme.search_match).0
+                                    }
+                                    None => row,
+                                };
+                                let mut spans = Vec::with_capacity(row.len() + 2);
+                                if *gutter > 0 {
+                                    let label = if continued {
+                                        String::new()
+                                    } else {
+                                        number.to_string()
+                                    };
+                                    spans.push(Span::styled(
+                                        format!("{:>width$} ", label, width = gutter - 1),
+                                        theme.muted,
+                                    ));
+                                }
+                                if *time_column > 0 {
+                                    // Only the first row of a message shows its time
+                                    let label = if index == 0 && offset == 0 {
+                                        format_clock_time(output_entries[message].time)
+                                    } else {
+                                        String::new()
+                                    };
+                                    spans.push(Span::styled(
+                                        format!("{:<width$} ", label, width = time_column - 1),
+                                        theme.muted,
+                                    ));
+                                }
+                                if continued {
+                                    spans.push(Span::styled(WRAP_MARKER, theme.debug));
+                                }
+                                spans.extend(
+                                    row.into_iter()
+                                        .map(|(part, style)| Span::styled(part, style)),
+                                );
+                                rows.push(Line::from(spans));
+                            }
+                        }
+                    }
+                    (Text::from(rows), window_start.unwrap_or(0))
+                }
+            };
             let output_widget = Paragraph::new(output_text)
-                .block(Block::default().borders(Borders::ALL).title(format!("Output [{}] [{}/{}]", 
-                    scroll_status, self.scroll_state.position, max_scroll)))
-                .scroll((self.scroll_state.position as u16, 0))
-                .wrap(ratatui::widgets::Wrap { trim: true });
+                .block(output_block)
+                .scroll((position.saturating_sub(window_start) as u16, 0));
 
             f.render_widget(output_widget, output_chunk);
+
+            // Drop the commands matching the input down from the input box
+            if !suggestions.is_empty() {
+                let key_width = suggestions
+                    .iter()
+                    .map(|(key, _)| key.width())
+                    .max()
+                    .unwrap_or(0);
+                let items: Vec<ListItem> = suggestions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (key, description))| {
+                        let line = Line::from(vec![
+                            Span::styled(format!("{:<key_width$}  ", key), theme.key),
+                            Span::styled(description.as_str(), theme.item),
+                        ]);
+                        if app.selected_suggestion() == Some(index) {
+                            line.patch_style(Style::default().add_modifier(Modifier::REVERSED))
+                        } else {
+                            line
+                        }
+                    })
+                    .map(ListItem::new)
+                    .collect();
+                let hint = "↑/↓ Pick | Enter Accept";
+                let widest = suggestions
+                    .iter()
+                    .map(|(_, description)| key_width + 2 + description.width())
+                    .max()
+                    .unwrap_or(0)
+                    .max(hint.width());
+                let width = (widest as u16 + 2).min(areas.input.width);
+                let height = (suggestions.len() as u16 + 2).min(area.height);
+                // Below the input if it fits, above it otherwise
+                let y = if areas.input.bottom() + height <= area.bottom() {
+                    areas.input.bottom()
+                } else {
+                    areas.input.y.saturating_sub(height).max(area.y)
+                };
+                let dropdown_area = Rect {
+                    x: areas.input.x,
+                    y,
+                    width,
+                    height,
+                };
+                let dropdown = List::new(items).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(theme.section)
+                        .title_bottom(Line::styled(hint, theme.muted)),
+                );
+                f.render_widget(Clear, dropdown_area);
+                f.render_widget(dropdown, dropdown_area);
+            }
+
+            // Render the popup viewer on top of everything else
+            if let Some(popup) = app.popup() {
+                let popup_area = centered_rect(80, 80, area);
+                let popup_widget = Paragraph::new(popup.content.as_str())
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(popup_title(&popup.title, app.keymap())),
+                    )
+                    .scroll((popup.scroll as u16, 0))
+                    .wrap(ratatui::widgets::Wrap { trim: false });
+                f.render_widget(Clear, popup_area);
+                f.render_widget(popup_widget, popup_area);
+            }
+
+            // Render the dialog waiting for an answer above everything else
+            if let Some(dialog) = app.dialog() {
+                let dialog_area = dialog_area(dialog, area);
+                let dialog_widget = Paragraph::new(dialog_lines(dialog, &theme))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(theme.section)
+                            .title(Span::styled(dialog.title.as_str(), theme.title))
+                            .title_bottom(Line::styled(dialog.key_hint(), theme.muted)),
+                    )
+                    .wrap(ratatui::widgets::Wrap { trim: false });
+                f.render_widget(Clear, dialog_area);
+                f.render_widget(dialog_widget, dialog_area);
+
+                // The cursor goes to the end of the field of a prompt
+                if let DialogKind::Prompt { value } = &dialog.kind {
+                    let row = dialog_area.y + dialog_area.height.saturating_sub(2);
+                    let column = dialog_area.x + 3 + value.width() as u16;
+                    f.set_cursor_position(ratatui::layout::Position::new(
+                        column.min(dialog_area.right().saturating_sub(2)),
+                        row,
+                    ));
+                }
+            }
+
+            // The splash screen covers everything until it's dismissed
+            if let Some(splash) = app.splash() {
+                let lines = splash_lines(splash, app.app_info(), app.keymap(), &theme);
+                let height = (lines.len() as u16).min(area.height);
+                let splash_area = Rect {
+                    y: area.y + (area.height - height) / 2,
+                    height,
+                    ..area
+                };
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).centered(), splash_area);
+            }
+
+            // Degrade everything drawn, highlight rules and syntax colors included
+            degrade_colors(f.buffer_mut(), colors);
         })?;
+        self.last_frame = frame_key(app);
         Ok(())
     }
 
     /// Run the application event loop
-    fn run_event_loop<T: std::fmt::Debug>(&mut self, app: &mut Istari<T>) -> io::Result<()> {
-        // Define the tick rate (how often to redraw)
-        let tick_rate = Duration::from_millis(100);
+    fn run_event_loop(&mut self, app: &mut Istari<T>) -> io::Result<()> {
+        let tick_rate = app.tick_rate();
+        let render_rate = app.render_rate();
         let mut last_tick = Instant::now();
+        let mut last_render = Instant::now();
 
+        // Show the output the way the layout asks, the keys toggle it from there
+        self.soft_wrap = app.layout().wrap;
+        self.line_numbers = app.layout().line_numbers;
+        self.timestamps = app.layout().timestamps;
+        self.splash_until = app.splash_timeout().map(|timeout| Instant::now() + timeout);
+
+        // Draw the first frame, then only when something changed
+        let mut dirty = true;
         loop {
-            // Render the current state
-            self.render_frame(app)?;
+            // Input shows right away, everything else at the render rate. Running
+            // jobs count up their elapsed time and errors flash the border.
+            let frame_due = last_render.elapsed() >= render_rate;
+            if frame_due {
+                // Output from other threads shows every frame, not just every tick
+                app.drain_output();
+            }
+            let animating = app.running_actions() > 0
+                || self.flash_until.is_some_and(|until| Instant::now() < until);
+            if dirty || (frame_due && (animating || frame_key(app) != self.last_frame)) {
+                self.render_frame(app)?;
+                dirty = false;
+                last_render = Instant::now();
+            } else if frame_due {
+                last_render = Instant::now();
+            }
 
-            // Check if we should perform a tick update
+            // Wait for events until the next tick or frame
             let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-
-            // Poll for events with a timeout
-            if crossterm::event::poll(timeout)? {
-                match crossterm::event::read()? {
-                    crossterm::event::Event::Key(key) => {
-                        // Process key events based on current mode
-                        match app.mode() {
-                            crate::Mode::Command => {
-                                // Handle different key events in command mode
-                                match key.code {
-                                    // Exit the application
-                                    crossterm::event::KeyCode::Char('q')
-                                        if key
-                                            .modifiers
-                                            .contains(crossterm::event::KeyModifiers::CONTROL) =>
-                                    {
-                                        return Ok(());
-                                    }
+                .saturating_sub(last_tick.elapsed())
+                .min(render_rate.saturating_sub(last_render.elapsed()));
 
-                                    // Toggle mode
-                                    crossterm::event::KeyCode::Tab => {
-                                        app.toggle_mode();
-                                    }
+            if self.expire_keys(app, Instant::now()) {
+                dirty = true;
+            }
+            if app.splash().is_some()
+                && self
+                    .splash_until
+                    .is_some_and(|until| Instant::now() >= until)
+            {
+                app.dismiss_splash();
+                dirty = true;
+            }
 
-                                    // Toggle input display
-                                    crossterm::event::KeyCode::Char('i')
-                                        if key
-                                            .modifiers
-                                            .contains(crossterm::event::KeyModifiers::CONTROL) =>
-                                    {
-                                        app.toggle_show_input();
-                                    }
+            // Any event may change the screen: input, mode, menu or a resize
+            if let Some(event) = self.terminal_backend.poll_event(timeout)? {
+                if !self.handle_event(app, event)? {
+                    return Ok(());
+                }
+                dirty = true;
+            }
 
-                                    // Process input when Enter is pressed
-                                    crossterm::event::KeyCode::Enter => {
-                                        if !app.input_buffer().is_empty()
-                                            && !app.process_input_buffer()
-                                        {
-                                            return Ok(());
-                                        }
-                                    }
+            // Check if it's time for a tick update
+            if last_tick.elapsed() >= tick_rate {
+                app.tick();
+                last_tick = Instant::now();
 
-                                    // Backspace to delete last character
-                                    crossterm::event::KeyCode::Backspace => {
-                                        app.exit_history_browsing();
-                                        app.backspace_input_buffer();
-                                    }
+                // An action that finished in the background asked to quit
+                if app.quit_requested() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
 
-                                    // Up arrow key for history navigation
-                                    crossterm::event::KeyCode::Up => {
-                                        app.history_up();
-                                    }
+/// Run the application in TUI mode
+pub fn run<T: std::fmt::Debug>(app: &mut crate::Istari<T>) -> io::Result<()> {
+    let mut controller = match app.layout().inline_height {
+        Some(height) => TuiController::with_inline_viewport(DefaultTerminal::default(), height)?,
+        None => TuiController::new()?,
+    };
+    super::run_controller(&mut controller, app)
+}
 
-                                    // Down arrow key for history navigation
-                                    crossterm::event::KeyCode::Down => {
-                                        app.history_down();
-                                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                                    // Any other key press exits history browsing
-                                    crossterm::event::KeyCode::Char(c) => {
-                                        app.exit_history_browsing();
-                                        app.add_to_input_buffer(c);
-                                    }
+    #[test]
+    fn test_styled_lines() {
+        let lines = styled_lines(
+            "# Status\n- **All** good\n```\nlet x = 1;\n```",
+            OutputLevel::Info,
+            &OutputFormat::Markdown,
+            &Theme::default(),
+        );
+        assert_eq!(line_texts(&lines), ["Status", "• All good", "  let x = 1;"]);
+        assert!(lines[0][0].1.add_modifier.contains(Modifier::BOLD));
 
-                                    // Handle single-key commands directly
-                                    _ => {
-                                        // Exit history browsing for any other key
-                                        app.exit_history_browsing();
-
-                                        // Convert keycode to string representation
-                                        if let crossterm::event::KeyCode::Char(c) = key.code {
-                                            if app.input_buffer().is_empty()
-                                                && !app.handle_key(c.to_string())
-                                            {
-                                                return Ok(());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+        let lines = styled_lines(
+            "a  b\n----\n1  2",
+            OutputLevel::Info,
+            &OutputFormat::Table(OutputTable::default()),
+            &Theme::default(),
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0][0].1.add_modifier.contains(Modifier::BOLD));
 
-                            crate::Mode::Scroll => {
-                                // Handle different key events in scroll mode
-                                match key.code {
-                                    // Exit the application
-                                    crossterm::event::KeyCode::Char('q')
-                                        if key
-                                            .modifiers
-                                            .contains(crossterm::event::KeyModifiers::CONTROL) =>
-                                    {
-                                        return Ok(());
-                                    }
+        assert_eq!(
+            styled_lines(
+                "",
+                OutputLevel::Info,
+                &OutputFormat::Plain,
+                &Theme::default()
+            )
+            .len(),
+            1
+        );
+    }
 
-                                    // Toggle mode
-                                    crossterm::event::KeyCode::Tab => {
-                                        app.toggle_mode();
-                                    }
+    #[test]
+    fn test_themed_lines() {
+        let theme = Theme {
+            error: Style::default().fg(ratatui::style::Color::Magenta),
+            ..Theme::default()
+        };
+        let lines = styled_lines("boom", OutputLevel::Error, &OutputFormat::Plain, &theme);
+        assert_eq!(lines[0][0].1, theme.error);
+        let lines = styled_lines(
+            "--- a\n+++ b\n-old",
+            OutputLevel::Info,
+            &OutputFormat::Plain,
+            &theme,
+        );
+        assert_eq!(lines[2][0].1, theme.error);
+    }
 
-                                    // Toggle auto-scroll
-                                    crossterm::event::KeyCode::Char('a')
-                                        if key
-                                            .modifiers
-                                            .contains(crossterm::event::KeyModifiers::CONTROL) =>
-                                    {
-                                        self.scroll_state.toggle_auto_scroll();
-                                    }
+    fn line_texts(lines: &[StyledLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|(part, _)| part.as_str()).collect())
+            .collect()
+    }
 
-                                    // Scroll down
-                                    crossterm::event::KeyCode::Char('j')
-                                    | crossterm::event::KeyCode::Down => {
-                                        self.scroll_state.scroll(
-                                            ScrollDirection::Down,
-                                            app.output_messages().len(),
-                                            10, // Approximate view height
-                                        );
-                                    }
+    #[test]
+    fn test_data_lines() {
+        let theme = Theme {
+            syntax_theme: None,
+            ..Theme::default()
+        };
+        let styles = |code: &str, language: &str| -> Vec<Vec<(String, bool)>> {
+            code_lines(code, language, &theme)
+                .into_iter()
+                .map(|line| {
+                    line.into_iter()
+                        .map(|(part, style)| (part, style == theme.key))
+                        .collect()
+                })
+                .collect()
+        };
+        let part = |text: &str, key| (text.to_string(), key);
 
-                                    // Scroll up
-                                    crossterm::event::KeyCode::Char('k')
-                                    | crossterm::event::KeyCode::Up => {
-                                        self.scroll_state.scroll(
-                                            ScrollDirection::Up,
-                                            app.output_messages().len(),
-                                            10, // Approximate view height
-                                        );
-                                    }
+        assert_eq!(
+            styles(
+                "{\n  \"name\": \"a: b\",\n  \"ports\": [\n    80\n  ]\n}",
+                "json"
+            ),
+            [
+                vec![part("{", false)],
+                vec![
+                    part("  ", false),
+                    part("\"name\"", true),
+                    part(": \"a: b\",", false)
+                ],
+                vec![
+                    part("  ", false),
+                    part("\"ports\"", true),
+                    part(": [", false)
+                ],
+                vec![part("    80", false)],
+                vec![part("  ]", false)],
+                vec![part("}", false)],
+            ]
+        );
+        assert_eq!(
+            styles("# url: ignored\nservers:\n- url: http://web", "yaml"),
+            [
+                vec![part("# url: ignored", false)],
+                vec![part("servers", true), part(":", false)],
+                vec![
+                    part("- ", false),
+                    part("url", true),
+                    part(": http://web", false)
+                ],
+            ]
+        );
+    }
 
-                                    // Page down
-                                    crossterm::event::KeyCode::Char('d')
-                                    | crossterm::event::KeyCode::PageDown => {
-                                        self.scroll_state.scroll(
-                                            ScrollDirection::PageDown,
-                                            app.output_messages().len(),
-                                            10, // Approximate view height
-                                        );
-                                    }
+    #[test]
+    fn test_code_lines() {
+        let theme = Theme::default();
+        let code = OutputFormat::Code("json".to_string());
+        let lines = styled_lines("{\n  \"a\": 1\n}", OutputLevel::Info, &code, &theme);
+        assert_eq!(line_texts(&lines), ["{", "  \"a\": 1", "}"]);
+        let lines = styled_lines(
+            "Config:\n```json\n{\"a\": 1}\n```",
+            OutputLevel::Info,
+            &OutputFormat::Markdown,
+            &theme,
+        );
+        assert_eq!(line_texts(&lines), ["Config:", "  {\"a\": 1}"]);
 
-                                    // Page up
-                                    crossterm::event::KeyCode::Char('u')
-                                    | crossterm::event::KeyCode::PageUp => {
-                                        self.scroll_state.scroll(
-                                            ScrollDirection::PageUp,
-                                            app.output_messages().len(),
-                                            10, // Approximate view height
-                                        );
-                                    }
+        // Without a syntax theme code keeps the code style
+        let plain = Theme {
+            syntax_theme: None,
+            ..theme
+        };
+        let lines = styled_lines("{}", OutputLevel::Info, &code, &plain);
+        assert_eq!(lines, [vec![("{}".to_string(), plain.code)]]);
 
-                                    // Go to top
-                                    crossterm::event::KeyCode::Char('g')
-                                    | crossterm::event::KeyCode::Home => {
-                                        self.scroll_state.scroll(
-                                            ScrollDirection::Top,
-                                            app.output_messages().len(),
-                                            10, // Approximate view height
-                                        );
-                                    }
+        #[cfg(feature = "highlight")]
+        {
+            let lines = styled_lines("{\"a\": 1}", OutputLevel::Info, &code, &theme);
+            assert!(lines[0].len() > 1);
+        }
+    }
 
-                                    // Go to bottom
-                                    crossterm::event::KeyCode::Char('G')
-                                    | crossterm::event::KeyCode::End => {
-                                        self.scroll_state.scroll(
-                                            ScrollDirection::Bottom,
-                                            app.output_messages().len(),
-                                            10, // Approximate view height
-                                        );
-                                    }
+    #[test]
+    fn test_table_lines() {
+        let theme = Theme::default();
+        let table = OutputTable::new(["Host", "Status"])
+            .with_row(["web-1", "up"])
+            .with_row(["db", "down"]);
+        let lines = table_lines(&table, theme.output, 40, &theme);
+        assert_eq!(
+            line_texts(&lines),
+            [
+                "┌─────────────┐",
+                "│Host   Status│",
+                "│web-1  up    │",
+                "│db     down  │",
+                "└─────────────┘",
+            ]
+        );
+        assert_eq!(lines[0][0].1.fg, theme.debug.fg);
+        assert!(lines[1][1].1.add_modifier.contains(Modifier::BOLD));
 
-                                    _ => {}
-                                }
-                            }
-                        }
-                    }
-                    crossterm::event::Event::Mouse(_) => {
-                        // Mouse events could be handled here if needed
-                    }
-                    crossterm::event::Event::Resize(_, _) => {
-                        // Resize events are automatically handled by the Terminal
-                    }
-                    _ => {}
-                }
-            }
+        // Narrow terminals get the widest columns cut
+        let table = OutputTable::new(["Name", "Description"])
+            .with_row(["deploy", "Deploy the app to production"]);
+        let lines = table_lines(&table, theme.output, 20, &theme);
+        assert_eq!(line_texts(&lines)[2], "│deploy  Deploy th…│");
+        assert!(line_texts(&lines).iter().all(|line| line.width() == 20));
+    }
 
-            // Check if it's time for a tick update
-            if last_tick.elapsed() >= tick_rate {
-                app.tick();
-                last_tick = Instant::now();
+    #[test]
+    fn test_tab_bar_line() {
+        let theme = Theme::default();
+        let line = tab_bar_line(&[("Main", false), ("build", true)], 0, &theme);
+        assert_eq!(line.to_string(), " 1:Main  2:build* ");
+        assert!(
+            line.spans[0]
+                .style
+                .add_modifier
+                .contains(Modifier::REVERSED)
+        );
+        assert_eq!(line.spans[1].style, theme.warning);
+    }
+
+    #[test]
+    fn test_menu_command_at() {
+        let area = Rect::new(0, 3, 30, 10);
+        let rows = vec![(0, "a".to_string()), (2, "b".to_string())];
+        assert_eq!(menu_command_at(area, &rows, 5, 4), Some("a"));
+        // Blank rows and borders run nothing
+        assert_eq!(menu_command_at(area, &rows, 5, 5), None);
+        assert_eq!(menu_command_at(area, &rows, 5, 6), Some("b"));
+        assert_eq!(menu_command_at(area, &rows, 5, 3), None);
+        assert_eq!(menu_command_at(area, &rows, 29, 4), None);
+    }
+
+    #[test]
+    fn test_dialog_layout() {
+        let theme = Theme::default();
+        let area = Rect::new(0, 0, 100, 40);
+        let dialog = Dialog::confirm("Clear", "Clear the logs?\nThis can't be undone.", "clear");
+        assert_eq!(dialog_area(&dialog, area), Rect::new(20, 17, 60, 6));
+
+        let lines = dialog_lines(&dialog, &theme);
+        assert_eq!(lines.len(), 4);
+        // "No" is selected until the user picks "Yes"
+        assert_eq!(lines[3].spans[2].content, "[ No ]");
+        assert!(
+            lines[3].spans[2]
+                .style
+                .add_modifier
+                .contains(Modifier::REVERSED)
+        );
+
+        let prompt = Dialog::prompt("Rename", "New name", "rename").with_value("web");
+        assert_eq!(dialog_lines(&prompt, &theme)[2].to_string(), "> web");
+        let message = Dialog::message("Done", "Deployed");
+        assert_eq!(dialog_area(&message, Rect::new(0, 0, 30, 10)).width, 30);
+    }
+
+    #[test]
+    fn test_output_search() {
+        let red = Style::default().fg(ratatui::style::Color::Red);
+        let row = vec![
+            ("ERROR: disk".to_string(), red),
+            (" error".to_string(), Style::default()),
+        ];
+        let (highlighted, found) = highlight_matches(&row, "error", Modifier::REVERSED.into());
+        assert!(found);
+        assert_eq!(highlighted.len(), 4);
+        assert_eq!(
+            highlighted[0],
+            ("ERROR".to_string(), red.add_modifier(Modifier::REVERSED))
+        );
+        assert_eq!(highlighted[2].0, " ");
+        assert!(!highlight_matches(&row, "warn", Style::default()).1);
+
+        // Jumps go from the scroll position, then from match to match, wrapping around
+        let mut search = OutputSearch {
+            rows: vec![2, 5, 9],
+            ..OutputSearch::default()
+        };
+        assert_eq!(search.jump(4, true), Some(5));
+        assert_eq!(search.jump(0, true), Some(9));
+        assert_eq!(search.jump(0, true), Some(2));
+        assert_eq!(search.jump(0, false), Some(9));
+        assert_eq!(search.status(), "'' 3/3 | n/N Next/Previous | Esc Clear");
+    }
+
+    #[test]
+    fn test_skip_columns() {
+        let red = Style::default().fg(ratatui::style::Color::Red);
+        let line = vec![
+            ("abc".to_string(), red),
+            ("日本".to_string(), Style::default()),
+        ];
+        assert_eq!(
+            skip_columns(&line, 2),
+            [
+                ("c".to_string(), red),
+                ("日本".to_string(), Style::default())
+            ]
+        );
+        assert_eq!(
+            skip_columns(&line, 5),
+            [("本".to_string(), Style::default())]
+        );
+        assert!(skip_columns(&line, 10).is_empty());
+    }
+
+    #[test]
+    fn test_fit_styled_line() {
+        let red = Style::default().fg(ratatui::style::Color::Red);
+        let line = vec![
+            ("abc".to_string(), red),
+            ("defg".to_string(), Style::default()),
+        ];
+        assert_eq!(
+            fit_styled_line(&line, 5, true),
+            [
+                (
+                    false,
+                    vec![
+                        ("abc".to_string(), red),
+                        ("de".to_string(), Style::default())
+                    ]
+                ),
+                (true, vec![("fg".to_string(), Style::default())]),
+            ]
+        );
+        assert_eq!(
+            fit_styled_line(&line, 3, false),
+            [(false, vec![("ab…".to_string(), red)])]
+        );
+    }
+
+    #[test]
+    fn test_fit_output_line() {
+        // Short lines keep their indentation untouched
+        assert_eq!(
+            fit_output_line("    indented", 20, true),
+            [(false, "    indented".to_string())]
+        );
+
+        assert_eq!(
+            fit_output_line("  abcdefghij", 6, true),
+            [
+                (false, "  abcd".to_string()),
+                (true, "efgh".to_string()),
+                (true, "ij".to_string()),
+            ]
+        );
+        assert_eq!(
+            fit_output_line("  abcdefghij", 6, false),
+            [(false, "  abc…".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_inline_viewport() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_inline_viewport(terminal, 12).unwrap();
+        controller.render_frame(&mut app).unwrap();
+
+        let buffer = controller.buffer();
+        let row = |y| (0..80).map(|x| buffer[(x, y)].symbol()).collect::<String>();
+        assert!((0..12).any(|y| row(y).contains("COMMAND MODE")));
+        assert!((12..30).all(|y| row(y).trim().is_empty()));
+    }
+
+    #[test]
+    fn test_splash_screen() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_app_info("Deploy Tool", "1.2.0")
+            .with_splash(" _\n| |\n|_|");
+        let terminal = HeadlessTerminal {
+            width: 60,
+            height: 20,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| {
+            let buffer = controller.buffer();
+            (0..20)
+                .map(|y| (0..60).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        controller.render_frame(&mut app).unwrap();
+        let splash = screen(&controller);
+        assert!(splash.contains("Deploy Tool v1.2.0"));
+        assert!(splash.contains("Press any key to start"));
+        assert!(!splash.contains("COMMAND MODE"));
+        // The art stays aligned while centered
+        let art: Vec<usize> = splash
+            .lines()
+            .filter_map(|row| row.find("|_|").or(row.find("| |")))
+            .collect();
+        assert_eq!(art.len(), 2);
+        assert_eq!(art[0], art[1]);
+
+        // The first key only dismisses the splash
+        let key = crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char('x'));
+        assert!(
+            controller
+                .handle_event(&mut app, crossterm::event::Event::Key(key))
+                .unwrap()
+        );
+        assert_eq!(app.splash(), None);
+        assert!(app.input_buffer().is_empty());
+        controller.render_frame(&mut app).unwrap();
+        assert!(screen(&controller).contains("COMMAND MODE"));
+    }
+
+    #[test]
+    fn test_degrade_colors() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 3, 1));
+        buffer[(0, 0)].set_fg(Color::Rgb(255, 135, 0));
+        buffer[(1, 0)].set_fg(Color::White).set_bg(Color::DarkGray);
+
+        let mut degraded = buffer.clone();
+        degrade_colors(&mut degraded, ColorSupport::Ansi16);
+        assert_eq!(degraded[(0, 0)].fg, Color::Yellow);
+        assert_eq!(degraded[(1, 0)].bg, Color::DarkGray);
+
+        degrade_colors(&mut buffer, ColorSupport::Monochrome);
+        assert_eq!(buffer[(0, 0)].fg, Color::Reset);
+        assert_eq!(buffer[(1, 0)].bg, Color::Reset);
+        assert!(buffer[(1, 0)].modifier.contains(Modifier::REVERSED));
+        assert!(!buffer[(2, 0)].modifier.contains(Modifier::REVERSED));
+    }
+
+    #[test]
+    fn test_frame_key() {
+        let mut menu = crate::Menu::new("Root");
+        menu.add_item(
+            crate::MenuItem::new_action(
+                "inc",
+                "Increment".to_string(),
+                |count: &mut i32, _: Option<&str>| {
+                    *count += 1;
+                    None
+                },
+            )
+            .with_dynamic_description(|count: &i32| format!("Count: {}", count)),
+        );
+        let mut app = Istari::new(menu, 0).unwrap();
+        let key = frame_key(&app);
+        app.tick();
+        assert_eq!(frame_key(&app), key);
+
+        app.add_output("Hello".to_string());
+        let key = frame_key(&app);
+        assert_ne!(
+            key,
+            frame_key(&Istari::new(crate::Menu::new("Root"), 0).unwrap())
+        );
+
+        // Menu descriptions depending on the state count too
+        app.handle_key("inc");
+        assert_ne!(frame_key(&app), key);
+    }
+
+    #[test]
+    fn test_scroll_uses_pane_height() {
+        use crossterm::event::{Event, KeyCode, KeyEvent};
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        for i in 0..100 {
+            app.add_output(format!("line {}", i));
+        }
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 40,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+        // The output pane is the screen less the header, footer and borders
+        assert!(controller.output_height > 10);
+
+        for code in [KeyCode::Tab, KeyCode::Home, KeyCode::PageDown] {
+            let event = Event::Key(KeyEvent::from(code));
+            controller.handle_event(&mut app, event).unwrap();
+        }
+        assert_eq!(controller.scroll_state.position, controller.output_height);
+
+        let event = Event::Key(KeyEvent::from(KeyCode::End));
+        controller.handle_event(&mut app, event).unwrap();
+        assert_eq!(
+            controller.scroll_state.position,
+            100 - controller.output_height
+        );
+    }
+
+    #[test]
+    fn test_input_cursor() {
+        assert_eq!(input_cursor("", 20), (0, 0));
+        assert_eq!(input_cursor("run", 20), (0, 3));
+        // Wide characters take two columns, combining marks none
+        assert_eq!(input_cursor("echo 日本", 20), (0, 9));
+        assert_eq!(input_cursor("say 👋", 20), (0, 6));
+        assert_eq!(input_cursor("cafe\u{301}", 20), (0, 4));
+        // Past the edge the input scrolls, leaving the cursor in the last column
+        assert_eq!(input_cursor("abcdefghij", 6), (5, 5));
+    }
+
+    #[test]
+    fn test_scroll_key_sequences() {
+        use crossterm::event::{Event, KeyCode, KeyEvent};
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        for i in 0..100 {
+            app.add_output(format!("line {}", i));
+        }
+        app.open_output_tab("Logs");
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+
+        let mut press = |controller: &mut TuiController<HeadlessTerminal>, keys: &str| {
+            for c in keys.chars() {
+                let code = if c == '\t' {
+                    KeyCode::Tab
+                } else {
+                    KeyCode::Char(c)
+                };
+                let event = Event::Key(KeyEvent::from(code));
+                controller.handle_event(&mut app, event).unwrap();
             }
+        };
+        press(&mut controller, "\tgg");
+        assert_eq!(controller.scroll_state.position, 0);
+        press(&mut controller, "5j");
+        assert_eq!(controller.scroll_state.position, 5);
+        press(&mut controller, "2k");
+        assert_eq!(controller.scroll_state.position, 3);
+        press(&mut controller, "20G");
+        assert_eq!(controller.scroll_state.position, 19);
+        // A lone g waits for the second one
+        press(&mut controller, "g");
+        assert_eq!(controller.scroll_state.position, 19);
+        press(&mut controller, "g");
+        assert_eq!(controller.scroll_state.position, 0);
+
+        // A number without a key after it shows the output tab
+        press(&mut controller, "2");
+        assert!(!controller.expire_keys(&mut app, Instant::now()));
+        let later = Instant::now() + crate::rendering::keys::SEQUENCE_TIMEOUT;
+        assert!(controller.expire_keys(&mut app, later));
+        assert_eq!(app.active_output_tab(), 1);
+    }
+
+    #[test]
+    fn test_item_style() {
+        let danger = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+        let mut menu = crate::Menu::new("Root");
+        menu.add_item(
+            crate::MenuItem::new_action(
+                "wipe",
+                "Wipe the disk".to_string(),
+                |_: &mut (), _: Option<&str>| None,
+            )
+            .with_style(danger),
+        );
+        let mut app = Istari::new(menu, ())
+            .unwrap()
+            .with_color_support(ColorSupport::TrueColor);
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 20,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+
+        let buffer = controller.buffer();
+        let area = buffer.area;
+        let cell = (area.top()..area.bottom())
+            .find_map(|y| {
+                let row: String = (0..area.width).map(|x| buffer[(x, y)].symbol()).collect();
+                let x = row.find("Wipe")?;
+                Some(&buffer[(x as u16, y)])
+            })
+            .unwrap();
+        assert_eq!(cell.fg, Color::Red);
+        assert!(cell.modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn test_stats_pane() {
+        let mut app = Istari::new(crate::Menu::new("Root"), 0.75)
+            .unwrap()
+            .with_sparkline("load", |load: &f64| load * 10.0)
+            .with_gauge("disk", |usage: &f64| *usage);
+        app.tick();
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+
+        let buffer = controller.buffer();
+        let rows: Vec<String> = (0..30)
+            .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        assert!(rows.iter().any(|row| row.contains("Stats")));
+        assert!(rows.iter().any(|row| row.contains("load")));
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("disk") && row.contains("75%"))
+        );
+    }
+
+    #[test]
+    fn test_log_level_filter() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_log_level(OutputLevel::Warn);
+        let output = app.output_handle();
+        output.push_level(OutputLevel::Debug, "cache hit");
+        output.push_level(OutputLevel::Info, "request served");
+        output.push_level(OutputLevel::Warn, "disk almost full");
+        app.tick();
+        let terminal = HeadlessTerminal {
+            width: 120,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| {
+                    (0..120)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        controller.render_frame(&mut app).unwrap();
+        let filtered = screen(&controller);
+        assert!(filtered.contains("disk almost full"));
+        assert!(filtered.contains("[Level: warn+]"));
+        assert!(!filtered.contains("cache hit"));
+        assert!(!filtered.contains("request served"));
+
+        // Lowering the level brings the hidden output back
+        app.set_log_level(OutputLevel::Debug);
+        controller.render_frame(&mut app).unwrap();
+        let all = screen(&controller);
+        assert!(all.contains("cache hit"));
+        assert!(all.contains("request served"));
+    }
+
+    #[test]
+    fn test_collapsed_duplicates() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_collapsed_duplicates(true);
+        for _ in 0..3 {
+            app.add_output("retrying".to_string());
         }
+        app.add_output("connected".to_string());
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+        let buffer = controller.buffer();
+        let rows: Vec<String> = (0..30)
+            .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+
+        assert_eq!(
+            rows.iter().filter(|row| row.contains("retrying")).count(),
+            1
+        );
+        assert!(rows.iter().any(|row| row.contains("retrying ×3")));
+        assert!(!rows.iter().any(|row| row.contains("connected ×")));
     }
-}
 
-/// Run the application in TUI mode
-pub fn run<T: std::fmt::Debug>(app: &mut crate::Istari<T>) -> io::Result<()> {
-    let mut controller = TuiController::new()?;
-    controller.init()?;
+    #[test]
+    fn test_timestamps() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        app.add_output("first line\nsecond line".to_string());
+        let time = format_clock_time(app.output_records()[0].time);
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let rows = |controller: &TuiController<HeadlessTerminal>| -> Vec<String> {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect())
+                .collect()
+        };
+
+        controller.render_frame(&mut app).unwrap();
+        assert!(!rows(&controller).iter().any(|row| row.contains(&time)));
+
+        app.toggle_mode();
+        let key = crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char('t'));
+        controller
+            .handle_event(&mut app, crossterm::event::Event::Key(key))
+            .unwrap();
+        controller.render_frame(&mut app).unwrap();
+        let rows = rows(&controller);
+        // Times are labelled as UTC in the title
+        assert!(rows[0].contains("[UTC]"));
+        // The time goes before the first line of the message only
+        let first = rows.iter().find(|row| row.contains("first line")).unwrap();
+        assert!(first.contains(&format!("{} first line", time)));
+        let second = rows.iter().find(|row| row.contains("second line")).unwrap();
+        assert!(!second.contains(&time));
+    }
+
+    #[test]
+    fn test_output_filter() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        app.add_output("GET /health 200\nGET /login 500".to_string());
+        app.add_output("POST /upload 500".to_string());
+        app.set_output_filter(Some(" 500$")).unwrap();
+        let terminal = HeadlessTerminal {
+            width: 120,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+
+        let buffer = controller.buffer();
+        let screen: String = (0..30)
+            .map(|y| {
+                (0..120)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect();
+        assert!(screen.contains("GET /login 500"));
+        assert!(screen.contains("POST /upload 500"));
+        assert!(!screen.contains("GET /health"));
+        assert!(screen.contains("[Filtered:  500$]"));
+    }
+
+    #[test]
+    fn test_wrapped_row_count() {
+        for (text, width) in [
+            ("", 10),
+            ("short", 10),
+            (&"x".repeat(25) as &str, 10),
+            ("宽字符宽字符宽字符", 7),
+        ] {
+            let line: StyledLine = vec![(text.to_string(), Style::default())];
+            assert_eq!(
+                wrapped_row_count(&line, width),
+                fit_output_line(text, width, true).len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_scroll_by_rows() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        for n in 1..=40 {
+            let padding = if n % 2 == 0 {
+                "x".repeat(150)
+            } else {
+                String::new()
+            };
+            app.add_output(format!("line {} {}", n, padding));
+        }
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let top_line = |controller: &TuiController<HeadlessTerminal>| -> String {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+                .find(|row| row.contains("line "))
+                .unwrap()
+        };
+        app.toggle_mode();
+        let mut press = |controller: &mut TuiController<HeadlessTerminal>, key| {
+            controller
+                .handle_event(&mut app, crossterm::event::Event::Key(key))
+                .unwrap();
+            controller.render_frame(&mut app).unwrap();
+        };
+        let char_key = |c| crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char(c));
+
+        press(
+            &mut controller,
+            crossterm::event::KeyEvent::new(
+                crossterm::event::KeyCode::Char('a'),
+                crossterm::event::KeyModifiers::CONTROL,
+            ),
+        );
+        // 12G goes to line 12 even though the lines above it wrap
+        for c in ['1', '2', 'G'] {
+            press(&mut controller, char_key(c));
+        }
+        assert!(top_line(&controller).contains("line 12 "));
+
+        // Cutting the lines instead keeps the same line at the top
+        press(&mut controller, char_key('w'));
+        assert!(top_line(&controller).contains("line 12 "));
+    }
+
+    #[test]
+    fn test_command_suggestions() {
+        let mut menu = crate::Menu::new("Root");
+        menu.add_action(
+            "deploy",
+            "Deploy the app",
+            |_state: &mut (), _params: Option<&str>| Some("deployed".to_string()),
+        );
+        menu.add_action(
+            "l",
+            "Show logs",
+            |_state: &mut (), _params: Option<&str>| None,
+        );
+        let mut app = Istari::new(menu, ()).unwrap();
+        let terminal = HeadlessTerminal {
+            width: 100,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| -> String {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| {
+                    (0..100)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect::<String>()
+                        + "\n"
+                })
+                .collect()
+        };
+        let press = |controller: &mut TuiController<HeadlessTerminal>,
+                     app: &mut Istari<()>,
+                     code: crossterm::event::KeyCode| {
+            let key = crossterm::event::KeyEvent::from(code);
+            controller
+                .handle_event(app, crossterm::event::Event::Key(key))
+                .unwrap();
+            controller.render_frame(app).unwrap();
+        };
+        controller.render_frame(&mut app).unwrap();
+        assert!(!screen(&controller).contains("Enter Accept"));
+
+        // Typing drops the matching commands down with their descriptions
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('d'),
+        );
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('p'),
+        );
+        let shown = screen(&controller);
+        assert!(shown.contains("deploy  Deploy the app"));
+        assert!(shown.contains("Enter Accept"));
+        assert_eq!(app.command_suggestions().len(), 1);
+
+        // The arrows pick one, Enter fills it in and Enter again runs it
+        press(&mut controller, &mut app, crossterm::event::KeyCode::Down);
+        assert_eq!(app.selected_suggestion(), Some(0));
+        press(&mut controller, &mut app, crossterm::event::KeyCode::Enter);
+        assert_eq!(app.input_buffer(), "deploy");
+        press(&mut controller, &mut app, crossterm::event::KeyCode::Enter);
+        assert!(app.input_buffer().is_empty());
+        assert!(app.output_messages().contains(&"deployed"));
+
+        // Without suggestions the arrows browse history
+        press(&mut controller, &mut app, crossterm::event::KeyCode::Up);
+        assert_eq!(app.input_buffer(), "deploy");
+        assert!(app.command_suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_keymap() {
+        let keymap = KeyMap::default()
+            .with_keys(
+                Mode::Command,
+                KeyAction::ToggleMode,
+                ["F2".parse().unwrap()],
+            )
+            .with_keys(Mode::Scroll, KeyAction::ToggleMode, ["F2".parse().unwrap()])
+            .with_keys(Mode::Scroll, KeyAction::Wrap, ['x'.into()]);
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_keymap(keymap);
+        let terminal = HeadlessTerminal {
+            width: 150,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let press = |controller: &mut TuiController<HeadlessTerminal>,
+                     app: &mut Istari<()>,
+                     code: crossterm::event::KeyCode| {
+            let key = crossterm::event::KeyEvent::from(code);
+            controller
+                .handle_event(app, crossterm::event::Event::Key(key))
+                .unwrap();
+        };
 
-    let result = controller.run_event_loop(app);
+        // Tab no longer switches mode, F2 does
+        press(&mut controller, &mut app, crossterm::event::KeyCode::Tab);
+        assert_eq!(app.mode(), Mode::Command);
+        press(&mut controller, &mut app, crossterm::event::KeyCode::F(2));
+        assert_eq!(app.mode(), Mode::Scroll);
 
-    controller.cleanup()?;
+        // Lines wrap until the remapped key toggles it
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('w'),
+        );
+        assert!(controller.soft_wrap);
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('x'),
+        );
+        assert!(!controller.soft_wrap);
+        // `gg` still goes to the top
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('g'),
+        );
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('g'),
+        );
+        assert_eq!(controller.scroll_state.position, 0);
 
-    result
+        // The help line names the keys of the key map
+        assert!(
+            default_help_line(Mode::Scroll, app.keymap())
+                .starts_with("SCROLL MODE: F2 to exit | j/k Scroll")
+        );
+        assert!(default_help_line(Mode::Scroll, app.keymap()).contains("x Toggle wrap"));
+        assert_eq!(
+            default_help_line(Mode::Command, &KeyMap::default()),
+            "Type commands with optional parameters | Ctrl+E Edit params in $EDITOR | Tab to switch mode | Ctrl+Q to quit"
+        );
+    }
+
+    #[test]
+    fn test_popup_keymap() {
+        let keymap = KeyMap::default()
+            .with_keys(Mode::Scroll, KeyAction::ScrollDown, ['n'.into()])
+            .with_keys(Mode::Scroll, KeyAction::ClosePopup, ['x'.into()]);
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_keymap(keymap);
+        let terminal = HeadlessTerminal {
+            width: 100,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let press = |controller: &mut TuiController<HeadlessTerminal>,
+                     app: &mut Istari<()>,
+                     code: crossterm::event::KeyCode| {
+            let key = crossterm::event::KeyEvent::from(code);
+            controller
+                .handle_event(app, crossterm::event::Event::Key(key))
+                .unwrap();
+        };
+        let lines: Vec<String> = (1..=100).map(|n| format!("line {}", n)).collect();
+        app.show_popup(OutputPopup::new("Logs", lines.join("\n")));
+
+        // The title names the keys of the key map
+        controller.render_frame(&mut app).unwrap();
+        let buffer = controller.buffer();
+        let screen: String = (0..30)
+            .flat_map(|y| (0..100).map(move |x| (x, y)))
+            .map(|(x, y)| buffer[(x, y)].symbol())
+            .collect();
+        assert!(screen.contains("Logs - n/k Scroll | x to close"));
+
+        // The popup scrolls and closes with the remapped keys only
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('j'),
+        );
+        assert_eq!(app.popup().unwrap().scroll, 0);
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('n'),
+        );
+        assert_eq!(app.popup().unwrap().scroll, 1);
+        press(&mut controller, &mut app, crossterm::event::KeyCode::Esc);
+        assert!(app.popup().is_some());
+        press(
+            &mut controller,
+            &mut app,
+            crossterm::event::KeyCode::Char('x'),
+        );
+        assert!(app.popup().is_none());
+    }
+
+    #[test]
+    fn test_follow_mode() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ()).unwrap();
+        for n in 1..=40 {
+            app.add_output(format!("line {}", n));
+        }
+        let terminal = HeadlessTerminal {
+            width: 120,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| -> String {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| {
+                    (0..120)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect::<String>()
+                        + "\n"
+                })
+                .collect()
+        };
+        let press = |controller: &mut TuiController<HeadlessTerminal>, app: &mut Istari<()>, c| {
+            let key = crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char(c));
+            controller
+                .handle_event(app, crossterm::event::Event::Key(key))
+                .unwrap();
+            controller.render_frame(app).unwrap();
+        };
+        controller.render_frame(&mut app).unwrap();
+        assert!(screen(&controller).contains("Follow ON"));
+        app.toggle_mode();
+
+        // Scrolling up stops following, new output doesn't pull the view down
+        press(&mut controller, &mut app, 'k');
+        assert!(screen(&controller).contains("Follow OFF"));
+        app.add_output("line 41".to_string());
+        controller.render_frame(&mut app).unwrap();
+        assert!(!screen(&controller).contains("line 41"));
+
+        // Scrolling back to the bottom follows again
+        press(&mut controller, &mut app, 'j');
+        press(&mut controller, &mut app, 'j');
+        assert!(screen(&controller).contains("Follow ON"));
+        app.add_output("line 42".to_string());
+        controller.render_frame(&mut app).unwrap();
+        assert!(screen(&controller).contains("line 42"));
+    }
+
+    #[test]
+    fn test_virtualized_output() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_max_output_size(20_000);
+        for n in 0..20_000 {
+            app.add_output(format!("message {}", n));
+        }
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| -> String {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect::<String>() + "\n")
+                .collect()
+        };
+
+        // Rows past the 16-bit scroll offset of a paragraph still show
+        controller.render_frame(&mut app).unwrap();
+        assert_eq!(controller.last_content_height, 20_000);
+        assert!(screen(&controller).contains("message 19999"));
+
+        app.toggle_mode();
+        for key in ['g', 'g'] {
+            let key = crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char(key));
+            controller
+                .handle_event(&mut app, crossterm::event::Event::Key(key))
+                .unwrap();
+        }
+        controller.render_frame(&mut app).unwrap();
+        let top = screen(&controller);
+        assert!(top.contains("message 0 "));
+        assert!(!top.contains("message 19999"));
+    }
+
+    #[test]
+    fn test_output_cache() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_max_output_size(3);
+        for n in 1..=3 {
+            app.add_output(format!("message {}", n));
+        }
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| -> String {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect::<String>() + "\n")
+                .collect()
+        };
+        controller.render_frame(&mut app).unwrap();
+        let generation = controller.output_cache.generation;
+
+        // Nothing is styled again while the output stays the same
+        controller.render_frame(&mut app).unwrap();
+        assert_eq!(controller.output_cache.generation, generation);
+
+        // The oldest message goes with the one pushing it out
+        app.add_output("message 4".to_string());
+        controller.render_frame(&mut app).unwrap();
+        assert_eq!(controller.output_cache.first, 1);
+        assert_eq!(controller.output_cache.messages.len(), 3);
+        let shown = screen(&controller);
+        assert!(!shown.contains("message 1"));
+        assert!(shown.contains("message 4"));
+
+        // Replaced messages are styled again in place
+        app.output_handle().replace_last("message 5");
+        app.drain_output();
+        controller.render_frame(&mut app).unwrap();
+        let shown = screen(&controller);
+        assert!(!shown.contains("message 4"));
+        assert!(shown.contains("message 5"));
+
+        // As are all of them once the filter changes
+        app.handle_key_with_params("filter", Some("message [23]".to_string()));
+        controller.render_frame(&mut app).unwrap();
+        let shown = screen(&controller);
+        assert!(shown.contains("message 3"));
+        assert!(!shown.contains("message 5"));
+
+        // Rows are counted again when wrapping stops
+        let generation = controller.output_cache.generation;
+        controller.soft_wrap = false;
+        controller.render_frame(&mut app).unwrap();
+        assert!(controller.output_cache.generation > generation);
+        assert_eq!(
+            controller.output_cache.row_key.map(|key| key.1),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_max_line_width() {
+        let mut app = Istari::new(crate::Menu::new("Root"), ())
+            .unwrap()
+            .with_layout(crate::LayoutConfig::new().with_max_line_width(20));
+        let long = "x".repeat(500);
+        app.add_output("short".to_string());
+        app.add_output(long.clone());
+        let terminal = HeadlessTerminal {
+            width: 80,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        controller.render_frame(&mut app).unwrap();
+        let buffer = controller.buffer();
+        let rows: Vec<String> = (0..30)
+            .map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+
+        // The line takes one row, cut with an ellipsis
+        let cut: Vec<&String> = rows.iter().filter(|row| row.contains("xxx")).collect();
+        assert_eq!(cut.len(), 1);
+        assert!(cut[0].contains(&format!("{}…", "x".repeat(19))));
+
+        // `v` shows it in full
+        app.toggle_mode();
+        let key = crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char('v'));
+        controller
+            .handle_event(&mut app, crossterm::event::Event::Key(key))
+            .unwrap();
+        let popup = app.popup().unwrap();
+        assert_eq!(popup.title, "Line 2");
+        assert_eq!(popup.content, long);
+    }
+
+    #[test]
+    fn test_menu_output() {
+        let mut tools = crate::Menu::new("Tools");
+        tools.add_action("c", "Compile", |_: &mut (), _: Option<&str>| {
+            Some("build finished".to_string())
+        });
+        let mut menu = crate::Menu::new("Root");
+        menu.add_submenu("t", "Tools", tools);
+        let mut app = Istari::new(menu, ()).unwrap().with_menu_output(true);
+        app.add_output("connected".to_string());
+        app.handle_key("t");
+        app.handle_key("c");
+        let terminal = HeadlessTerminal {
+            width: 120,
+            height: 30,
+        };
+        let mut controller = TuiController::with_terminal(terminal).unwrap();
+        let screen = |controller: &TuiController<HeadlessTerminal>| -> String {
+            let buffer = controller.buffer();
+            (0..30)
+                .map(|y| {
+                    (0..120)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect::<String>()
+                        + "\n"
+                })
+                .collect()
+        };
+
+        controller.render_frame(&mut app).unwrap();
+        let scoped = screen(&controller);
+        assert!(scoped.contains("build finished"));
+        assert!(!scoped.contains("connected"));
+        assert!(scoped.contains("[Menu: /t]"));
+
+        // `m` switches to the output of every menu
+        app.toggle_mode();
+        let key = crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char('m'));
+        controller
+            .handle_event(&mut app, crossterm::event::Event::Key(key))
+            .unwrap();
+        controller.render_frame(&mut app).unwrap();
+        let all = screen(&controller);
+        assert!(all.contains("connected"));
+        assert!(all.contains("build finished"));
+        assert!(!all.contains("[Menu:"));
+    }
 }
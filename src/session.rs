@@ -0,0 +1,216 @@
+use crate::clock::UtcTime;
+use crate::output::OutputLevel;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A command entered during the session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionEntry {
+    /// The command as typed
+    pub command: String,
+    /// When the command was entered, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Output messages the command produced
+    pub output: Vec<String>,
+}
+
+/// How to write the session log with [`SessionLog::export`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExportOptions {
+    /// Write a JSON array instead of plain text
+    pub json: bool,
+    /// Include when each command was entered
+    pub timestamps: bool,
+    /// Include the output of each command
+    pub results: bool,
+}
+
+/// Commands entered during the current session, e.g. to turn it into a runbook.
+///
+/// Unlike [`CommandHistory`](crate::CommandHistory) this doesn't include commands
+/// loaded from a history file.
+#[derive(Debug, Clone, Default)]
+pub struct SessionLog {
+    entries: Vec<SessionEntry>,
+}
+
+impl SessionLog {
+    /// Create an empty session log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a command and the output it produced
+    pub fn record(&mut self, command: impl Into<String>, output: Vec<String>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        self.entries.push(SessionEntry {
+            command: command.into(),
+            timestamp,
+            output,
+        });
+    }
+
+    /// Get the recorded commands, oldest first
+    pub fn entries(&self) -> &[SessionEntry] {
+        &self.entries
+    }
+
+    /// Render the log as plain text, one command per line with its output indented below it
+    pub fn to_text(&self, options: ExportOptions) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            if options.timestamps {
+                text.push_str(&format!("[{}] ", format_timestamp(entry.timestamp)));
+            }
+            text.push_str(&entry.command);
+            text.push('\n');
+            if options.results {
+                for line in entry.output.iter().flat_map(|message| message.lines()) {
+                    text.push_str(&format!("    {}\n", line));
+                }
+            }
+        }
+        text
+    }
+
+    /// Render the log as a JSON array of entries
+    pub fn to_json(&self, options: ExportOptions) -> String {
+        let entries: Vec<serde_json::Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut value = serde_json::json!({ "command": entry.command });
+                if options.timestamps {
+                    value["timestamp"] = format_timestamp(entry.timestamp).into();
+                }
+                if options.results {
+                    value["output"] = entry.output.clone().into();
+                }
+                value
+            })
+            .collect();
+        // Serializing plain JSON values can't fail
+        serde_json::to_string_pretty(&entries).unwrap_or_default()
+    }
+
+    /// Write the log to a file
+    pub fn export(&self, path: &Path, options: ExportOptions) -> std::io::Result<()> {
+        let contents = if options.json {
+            self.to_json(options)
+        } else {
+            self.to_text(options)
+        };
+        std::fs::write(path, contents)
+    }
+}
+
+/// File every output message is appended to as it's added, so a session leaves
+/// a trail even if it crashes. Set with [`Istari::with_log_file`](crate::Istari::with_log_file).
+#[derive(Debug, Clone)]
+pub(crate) struct OutputLogFile {
+    file: Arc<Mutex<File>>,
+}
+
+impl OutputLogFile {
+    /// Open the file for appending, creating it if needed
+    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Append a message as `[<time>] <level> <source>: <text>`, with further lines indented
+    pub(crate) fn write(&self, level: OutputLevel, source: Option<&str>, message: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        let mut entry = format!("[{}] {}", format_timestamp(timestamp), level.name());
+        if let Some(source) = source {
+            entry.push(' ');
+            entry.push_str(source);
+            entry.push(':');
+        }
+        let mut lines = message.lines();
+        entry.push(' ');
+        entry.push_str(lines.next().unwrap_or(""));
+        entry.push('\n');
+        for line in lines {
+            entry.push_str(&format!("    {}\n", line));
+        }
+
+        // Unbuffered, so each message reaches the file right away. The output pane
+        // is where errors would show, so failing writes are dropped.
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.write_all(entry.as_bytes());
+        }
+    }
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp
+fn format_timestamp(timestamp: u64) -> String {
+    let time = UtcTime::from_unix(timestamp);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.year, time.month, time.day, time.hour, time.minute, time.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_export() {
+        let mut log = SessionLog::new();
+        log.record("inc 5", vec!["Counter: 5".to_string()]);
+        log.record("s", Vec::new());
+        log.entries[0].timestamp = 1_700_000_000;
+
+        assert_eq!(log.to_text(ExportOptions::default()), "inc 5\ns\n");
+        let text = log.to_text(ExportOptions {
+            timestamps: true,
+            results: true,
+            ..ExportOptions::default()
+        });
+        assert!(text.starts_with("[2023-11-14T22:13:20Z] inc 5\n    Counter: 5\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&log.to_json(ExportOptions {
+            results: true,
+            ..ExportOptions::default()
+        }))
+        .unwrap();
+        assert_eq!(json[0]["command"], "inc 5");
+        assert_eq!(json[0]["output"][0], "Counter: 5");
+        assert!(json[0].get("timestamp").is_none());
+    }
+
+    #[test]
+    fn test_output_log_file() {
+        let path = std::env::temp_dir().join(format!("istari-log-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = OutputLogFile::open(&path).unwrap();
+        log.write(OutputLevel::Warn, Some("db"), "slow query\nrows: 120");
+        // Reopening appends instead of truncating
+        OutputLogFile::open(&path)
+            .unwrap()
+            .write(OutputLevel::Info, None, "done");
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with('['));
+        assert!(lines[0].ends_with("] warn db: slow query"));
+        assert_eq!(lines[1], "    rows: 120");
+        assert!(lines[2].ends_with("] info done"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::jobs::format_elapsed;
+use crate::output::ActionOutput;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often and how long an action ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionStats {
+    /// Number of times the action ran
+    pub count: u64,
+    /// When the action last started
+    pub last_run: Instant,
+    /// Time all runs took together
+    pub total_duration: Duration,
+}
+
+impl ActionStats {
+    /// Get how long a run took on average
+    pub fn average_duration(&self) -> Duration {
+        // Stats only exist once the action ran, but don't trust that when dividing
+        self.total_duration / self.count.max(1) as u32
+    }
+}
+
+/// Execution stats of every action that ran, keyed by its item path, e.g. `deploy/start`
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionStats {
+    actions: HashMap<String, ActionStats>,
+}
+
+impl ExecutionStats {
+    /// Create empty stats
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a run of the action at the given path
+    pub fn record(&mut self, path: &str, started: Instant, finished: Instant) {
+        let duration = finished.saturating_duration_since(started);
+        let stats = self.actions.entry(path.to_string()).or_insert(ActionStats {
+            count: 0,
+            last_run: started,
+            total_duration: Duration::ZERO,
+        });
+        stats.count += 1;
+        stats.last_run = stats.last_run.max(started);
+        stats.total_duration += duration;
+    }
+
+    /// Get the stats of the action at the given path, if it ran
+    pub fn get(&self, path: &str) -> Option<&ActionStats> {
+        self.actions.get(path)
+    }
+
+    /// Get the stats of every action that ran, most run first
+    pub fn most_run(&self) -> Vec<(&str, &ActionStats)> {
+        let mut actions: Vec<_> = self
+            .actions
+            .iter()
+            .map(|(path, stats)| (path.as_str(), stats))
+            .collect();
+        actions.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        actions
+    }
+
+    /// Check if no action ran yet
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Forget the stats of every action
+    pub fn clear(&mut self) {
+        self.actions.clear();
+    }
+
+    /// Lay out the stats as a table of the actions' runs, average duration and last run, most run first
+    pub fn table(&self, now: Instant) -> ActionOutput {
+        ActionOutput::table(
+            ["Command", "Runs", "Average", "Last run"],
+            self.most_run().into_iter().map(|(path, stats)| {
+                [
+                    path.to_string(),
+                    stats.count.to_string(),
+                    format_elapsed(stats.average_duration()),
+                    format!(
+                        "{} ago",
+                        format_elapsed(now.saturating_duration_since(stats.last_run))
+                    ),
+                ]
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execution_stats() {
+        let start = Instant::now();
+        let mut stats = ExecutionStats::new();
+        stats.record("deploy/start", start, start + Duration::from_secs(4));
+        stats.record("logs", start, start + Duration::from_millis(100));
+        stats.record(
+            "deploy/start",
+            start + Duration::from_secs(10),
+            start + Duration::from_secs(12),
+        );
+
+        let deploy = stats.get("deploy/start").unwrap();
+        assert_eq!(deploy.count, 2);
+        assert_eq!(deploy.last_run, start + Duration::from_secs(10));
+        assert_eq!(deploy.average_duration(), Duration::from_secs(3));
+        assert_eq!(
+            stats.table(start + Duration::from_secs(20)).to_plain_text(),
+            "Command       Runs  Average  Last run\n\
+             ------------  ----  -------  ---------\n\
+             deploy/start  2     3.0s     10.0s ago\n\
+             logs          1     0.1s     20.0s ago"
+        );
+
+        stats.clear();
+        assert!(stats.is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::output::{OutputHandle, OutputLevel};
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A [`tracing_subscriber`] layer writing events to the output pane, so
+/// instrumented applications get their logs in the TUI instead of on the screen
+/// it draws.
+///
+/// Events are tagged with their target as the source and styled by their level.
+/// The spans they happened in come first, with their fields, like
+/// `request{id=7}: query: slow rows=120`.
+///
+/// ```no_run
+/// # use istari::{Istari, Menu, OutputLayer};
+/// use tracing_subscriber::prelude::*;
+///
+/// # let mut app = Istari::new(Menu::new("Root"), ()).unwrap();
+/// tracing_subscriber::registry()
+///     .with(OutputLayer::new(app.output_handle()))
+///     .init();
+/// tracing::info!(port = 8080, "listening");
+/// app.run()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct OutputLayer {
+    handle: OutputHandle,
+}
+
+impl OutputLayer {
+    /// Create a layer writing through the given handle
+    pub fn new(handle: OutputHandle) -> Self {
+        Self { handle }
+    }
+}
+
+/// Fields of a span formatted once it's created, kept in its extensions
+struct SpanFields(String);
+
+/// Formats the fields of an event or span, the message first and the rest as `key=value`
+#[derive(Default)]
+struct FieldFormatter {
+    message: String,
+    fields: String,
+}
+
+impl FieldFormatter {
+    fn finish(self) -> String {
+        match (self.message.is_empty(), self.fields.is_empty()) {
+            (_, true) => self.message,
+            (true, false) => self.fields,
+            (false, false) => format!("{} {}", self.message, self.fields),
+        }
+    }
+}
+
+impl Visit for FieldFormatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+            return;
+        }
+        if !self.fields.is_empty() {
+            self.fields.push(' ');
+        }
+        let _ = write!(self.fields, "{}={:?}", field.name(), value);
+    }
+}
+
+/// Get the output level a tracing level shows with, trace counting as debug
+fn output_level(level: &Level) -> OutputLevel {
+    match *level {
+        Level::ERROR => OutputLevel::Error,
+        Level::WARN => OutputLevel::Warn,
+        Level::INFO => OutputLevel::Info,
+        _ => OutputLevel::Debug,
+    }
+}
+
+impl<S> Layer<S> for OutputLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = FieldFormatter::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(SpanFields(fields.finish()));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = FieldFormatter::default();
+        values.record(&mut fields);
+        let added = fields.finish();
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanFields>() {
+            Some(SpanFields(existing)) if !existing.is_empty() => {
+                existing.push(' ');
+                existing.push_str(&added);
+            }
+            Some(SpanFields(existing)) => *existing = added,
+            None => extensions.insert(SpanFields(added)),
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut line = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                line.push_str(span.name());
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>()
+                    && !fields.is_empty()
+                {
+                    let _ = write!(line, "{{{}}}", fields);
+                }
+                line.push_str(": ");
+            }
+        }
+        let mut fields = FieldFormatter::default();
+        event.record(&mut fields);
+        line.push_str(&fields.finish());
+
+        let metadata = event.metadata();
+        self.handle
+            .with_source(metadata.target())
+            .push_level(output_level(metadata.level()), line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Istari, Menu};
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_output_layer() {
+        let mut app = Istari::new(Menu::new("Root"), ()).unwrap();
+        let subscriber = tracing_subscriber::registry().with(OutputLayer::new(app.output_handle()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "db", rows = 120, "slow query");
+            let span = tracing::info_span!("request", id = 7);
+            let _entered = span.enter();
+            tracing::debug!(target: "db", "cache miss");
+        });
+        app.drain_output();
+
+        assert_eq!(
+            *app.output_messages(),
+            ["slow query rows=120", "request{id=7}: cache miss"]
+        );
+        assert_eq!(
+            *app.output_levels(),
+            [OutputLevel::Warn, OutputLevel::Debug]
+        );
+        assert_eq!(app.export_output(None)[0].source.as_deref(), Some("db"));
+    }
+}
@@ -0,0 +1,411 @@
+pub use ratatui::style::{Color, Modifier, Style};
+
+/// A line of output split into differently styled parts
+pub(crate) type StyledLine = Vec<(String, Style)>;
+
+/// Styles of everything the renderers draw, set with [`Istari::with_theme`](crate::Istari::with_theme).
+///
+/// The text renderer only uses the colors and the bold, italic, dim, underlined
+/// and reversed modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// The menu title
+    pub title: Style,
+    /// The mode indicator in command mode
+    pub command_mode: Style,
+    /// The mode indicator in scroll mode
+    pub scroll_mode: Style,
+    /// Headings of the Favorites, Recent, Queue and Global sections
+    pub section: Style,
+    /// Keys of menu items, e.g. `[inc]`
+    pub key: Style,
+    /// Descriptions of menu items
+    pub item: Style,
+    /// Secondary text: paths, hints, elapsed times and placeholders
+    pub muted: Style,
+    /// The command being typed
+    pub input: Style,
+    /// The help line in scroll mode
+    pub scroll_help: Style,
+    /// Progress of background actions and running jobs
+    pub progress: Style,
+    /// Regular output
+    pub output: Style,
+    /// Debug output, table separators and wrap markers
+    pub debug: Style,
+    /// Warnings and jobs being cancelled
+    pub warning: Style,
+    /// Errors, failed jobs and the flashing output border
+    pub error: Style,
+    /// Finished jobs, the running queue entry and added diff lines
+    pub success: Style,
+    /// Markdown headings and diff hunk headers
+    pub heading: Style,
+    /// Markdown code blocks
+    pub code: Style,
+    /// Markdown quotes
+    pub quote: Style,
+    /// The status bar below the command input
+    pub status: Style,
+    /// Matches of the output search
+    pub search_match: Style,
+    /// Name of the syntect theme highlighting code tagged with a language, e.g.
+    /// `base16-ocean.dark`, or `None` to style it as [`code`](Self::code).
+    /// Only used with the `highlight` feature.
+    pub syntax_theme: Option<&'static str>,
+}
+
+/// Names of the built-in themes, for the `theme` command
+pub const THEME_PRESETS: [&str; 4] = ["dark", "light", "high-contrast", "monochrome"];
+
+impl Theme {
+    /// Get a built-in theme by name, see [`THEME_PRESETS`]
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "monochrome" => Some(Self::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the built-in theme this is, if it's one of them
+    pub fn preset_name(&self) -> Option<&'static str> {
+        THEME_PRESETS
+            .into_iter()
+            .find(|name| Self::preset(name).as_ref() == Some(self))
+    }
+
+    /// The default theme, for dark terminals
+    pub fn dark() -> Self {
+        Self::default()
+    }
+
+    /// A theme for terminals with a light background
+    pub fn light() -> Self {
+        let fg = |color| Style::default().fg(color);
+        Self {
+            title: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            command_mode: fg(Color::Green).add_modifier(Modifier::BOLD),
+            scroll_mode: fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            section: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            key: fg(Color::Magenta),
+            item: fg(Color::Black),
+            muted: fg(Color::DarkGray),
+            input: fg(Color::Black),
+            scroll_help: fg(Color::Magenta),
+            progress: fg(Color::Blue),
+            output: Style::default(),
+            debug: fg(Color::DarkGray),
+            warning: fg(Color::Magenta),
+            error: fg(Color::Red),
+            success: fg(Color::Green),
+            heading: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            code: fg(Color::Green),
+            quote: fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+            status: fg(Color::Black).bg(Color::Gray),
+            search_match: fg(Color::Black).bg(Color::Yellow),
+            syntax_theme: Some("InspiredGitHub"),
+        }
+    }
+
+    /// Bright, bold colors that stay readable on any background
+    pub fn high_contrast() -> Self {
+        let fg = |color| Style::default().fg(color);
+        let bold = |color| fg(color).add_modifier(Modifier::BOLD);
+        Self {
+            title: bold(Color::White).add_modifier(Modifier::UNDERLINED),
+            command_mode: bold(Color::LightGreen),
+            scroll_mode: bold(Color::LightYellow),
+            section: bold(Color::LightCyan),
+            key: bold(Color::LightYellow),
+            item: fg(Color::White),
+            muted: fg(Color::White),
+            input: bold(Color::White),
+            scroll_help: bold(Color::LightYellow),
+            progress: bold(Color::LightCyan),
+            output: fg(Color::White),
+            debug: fg(Color::Gray),
+            warning: bold(Color::LightYellow),
+            error: bold(Color::LightRed),
+            success: bold(Color::LightGreen),
+            heading: bold(Color::LightCyan),
+            code: fg(Color::LightGreen),
+            quote: fg(Color::White).add_modifier(Modifier::ITALIC),
+            status: bold(Color::Black).bg(Color::White),
+            search_match: bold(Color::Black).bg(Color::LightYellow),
+            syntax_theme: Some("base16-eighties.dark"),
+        }
+    }
+
+    /// No colors, only bold, dim, italic, underlined and reversed text
+    pub fn monochrome() -> Self {
+        let with = |modifier| Style::default().add_modifier(modifier);
+        Self {
+            title: with(Modifier::BOLD),
+            command_mode: with(Modifier::BOLD | Modifier::REVERSED),
+            scroll_mode: with(Modifier::REVERSED),
+            section: with(Modifier::BOLD | Modifier::UNDERLINED),
+            key: with(Modifier::BOLD),
+            item: Style::default(),
+            muted: with(Modifier::DIM),
+            input: Style::default(),
+            scroll_help: with(Modifier::DIM),
+            progress: with(Modifier::ITALIC),
+            output: Style::default(),
+            debug: with(Modifier::DIM),
+            warning: with(Modifier::BOLD),
+            error: with(Modifier::BOLD | Modifier::UNDERLINED),
+            success: Style::default(),
+            heading: with(Modifier::BOLD),
+            code: with(Modifier::DIM),
+            quote: with(Modifier::ITALIC),
+            status: with(Modifier::REVERSED),
+            search_match: with(Modifier::REVERSED | Modifier::UNDERLINED),
+            syntax_theme: None,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let fg = |color| Style::default().fg(color);
+        Self {
+            title: fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            command_mode: fg(Color::Green).add_modifier(Modifier::BOLD),
+            scroll_mode: fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            section: fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            key: fg(Color::Yellow),
+            item: fg(Color::White),
+            muted: fg(Color::Gray),
+            input: fg(Color::White),
+            scroll_help: fg(Color::Yellow),
+            progress: fg(Color::Cyan),
+            output: Style::default(),
+            debug: fg(Color::DarkGray),
+            warning: fg(Color::Yellow),
+            error: fg(Color::Red),
+            success: fg(Color::Green),
+            heading: fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            code: fg(Color::Green),
+            quote: fg(Color::Gray).add_modifier(Modifier::ITALIC),
+            status: fg(Color::White).bg(Color::DarkGray),
+            search_match: fg(Color::Black).bg(Color::Yellow),
+            syntax_theme: Some("base16-ocean.dark"),
+        }
+    }
+}
+
+/// Colors the terminal can show, set with [`Istari::with_color_support`](crate::Istari::with_color_support).
+///
+/// Both renderers degrade the theme to it, e.g. RGB colors to the closest of the
+/// 16 ANSI colors, or all colors to the modifiers of the monochrome theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// No colors, only bold, dim, italic, underlined and reversed text
+    Monochrome,
+    /// The 16 ANSI colors
+    Ansi16,
+    /// The 256-color palette
+    Ansi256,
+    /// Any RGB color
+    TrueColor,
+}
+
+/// RGB values of the 16 ANSI colors, as xterm shows them
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each component in the 6x6x6 color cube of the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+    /// Detect the colors of the terminal from the environment: `NO_COLOR`,
+    /// `TERM` and `COLORTERM`
+    pub fn detect() -> Self {
+        Self::from_env(
+            std::env::var("NO_COLOR").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+            std::env::var("COLORTERM").ok().as_deref(),
+        )
+    }
+
+    /// Work out the colors of a terminal from its environment variables
+    pub fn from_env(no_color: Option<&str>, term: Option<&str>, colorterm: Option<&str>) -> Self {
+        // See https://no-color.org, an empty NO_COLOR doesn't count
+        if no_color.is_some_and(|value| !value.is_empty()) {
+            return ColorSupport::Monochrome;
+        }
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match term {
+            Some("dumb") => ColorSupport::Monochrome,
+            Some(term) if term.contains("256color") || term.contains("direct") => {
+                ColorSupport::Ansi256
+            }
+            // Without TERM, e.g. on Windows, assume a modern terminal
+            None => ColorSupport::TrueColor,
+            Some(_) => ColorSupport::Ansi16,
+        }
+    }
+
+    /// Degrade a color to one the terminal shows, `None` if it shows none
+    pub fn color(self, color: Color) -> Option<Color> {
+        match (self, color) {
+            (_, Color::Reset) => Some(Color::Reset),
+            (ColorSupport::Monochrome, _) => None,
+            (ColorSupport::TrueColor, color) => Some(color),
+            (ColorSupport::Ansi256, Color::Rgb(r, g, b)) => Some(Color::Indexed(to_256((r, g, b)))),
+            (ColorSupport::Ansi256, color) => Some(color),
+            (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => Some(to_16((r, g, b))),
+            (ColorSupport::Ansi16, Color::Indexed(index)) => Some(match index {
+                0..16 => ANSI_COLORS[usize::from(index)].0,
+                _ => to_16(indexed_rgb(index)),
+            }),
+            (ColorSupport::Ansi16, color) => Some(color),
+        }
+    }
+
+    /// Degrade a style to the colors the terminal shows. Without colors, a
+    /// background turns into reversed text so highlights stay visible.
+    pub fn style(self, style: Style) -> Style {
+        let mut degraded = style;
+        degraded.fg = style.fg.and_then(|color| self.color(color));
+        degraded.bg = style.bg.and_then(|color| self.color(color));
+        if self == ColorSupport::Monochrome && style.bg.is_some_and(|color| color != Color::Reset) {
+            degraded = degraded.add_modifier(Modifier::REVERSED);
+        }
+        degraded
+    }
+}
+
+/// Squared distance between two RGB colors
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// The closest of the 16 ANSI colors
+fn to_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// The closest color of the 256-color palette's color cube and gray ramp
+fn to_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |value: u8| {
+        (0..6u8)
+            .min_by_key(|&i| CUBE_LEVELS[usize::from(i)].abs_diff(value))
+            .unwrap_or(0)
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = (u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    if distance(rgb, indexed_rgb(gray)) < distance(rgb, indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// RGB value of a color of the 256-color palette
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => ANSI_COLORS[usize::from(index)].1,
+        16..232 => {
+            let i = index - 16;
+            let level = |n: u8| CUBE_LEVELS[usize::from(n)];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let value = 8 + 10 * (index - 232);
+            (value, value, value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_presets() {
+        for name in THEME_PRESETS {
+            assert_eq!(Theme::preset(name).unwrap().preset_name(), Some(name));
+        }
+        assert_eq!(Theme::default().preset_name(), Some("dark"));
+        assert!(Theme::preset("solarized").is_none());
+
+        let custom = Theme {
+            key: Style::default().fg(Color::Magenta),
+            ..Theme::default()
+        };
+        assert_eq!(custom.preset_name(), None);
+    }
+
+    #[test]
+    fn test_color_support() {
+        use ColorSupport::*;
+        assert_eq!(
+            ColorSupport::from_env(Some("1"), None, Some("truecolor")),
+            Monochrome
+        );
+        assert_eq!(
+            ColorSupport::from_env(Some(""), Some("xterm"), None),
+            Ansi16
+        );
+        assert_eq!(ColorSupport::from_env(None, Some("dumb"), None), Monochrome);
+        assert_eq!(
+            ColorSupport::from_env(None, Some("xterm-256color"), None),
+            Ansi256
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, Some("xterm"), Some("24bit")),
+            TrueColor
+        );
+
+        let orange = Color::Rgb(255, 135, 0);
+        assert_eq!(TrueColor.color(orange), Some(orange));
+        assert_eq!(Ansi256.color(orange), Some(Color::Indexed(208)));
+        assert_eq!(
+            Ansi256.color(Color::Rgb(128, 128, 128)),
+            Some(Color::Indexed(244))
+        );
+        assert_eq!(Ansi16.color(orange), Some(Color::Yellow));
+        assert_eq!(Ansi16.color(Color::Indexed(9)), Some(Color::LightRed));
+        assert_eq!(Ansi16.color(Color::Indexed(231)), Some(Color::White));
+        assert_eq!(Monochrome.color(Color::Red), None);
+
+        let status = Theme::default().status;
+        assert_eq!(Ansi16.style(status), status);
+        assert_eq!(
+            Monochrome.style(status),
+            Style::default().add_modifier(Modifier::REVERSED)
+        );
+        assert_eq!(
+            Monochrome.style(Theme::default().title),
+            Style::default().add_modifier(Modifier::BOLD)
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::menu::{Menu, MenuItem};
+use crate::params::ParamSpec;
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// Description of a menu and everything below it, e.g. to generate documentation or completions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MenuTree {
+    /// Title of the menu
+    pub title: String,
+    /// Items of the menu, in display order
+    pub items: Vec<MenuItemInfo>,
+}
+
+/// Description of a single menu item
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MenuItemInfo {
+    /// The key that activates the item
+    pub key: String,
+    /// Description of the item
+    pub description: String,
+    /// Whether the item runs an action
+    pub has_action: bool,
+    /// Parameters accepted by the item's action
+    pub params: Vec<ParamSpec>,
+    /// Categories the item is tagged with
+    pub categories: Vec<String>,
+    /// The submenu the item leads to, if any.
+    ///
+    /// Left out for submenus leading back to one of their ancestors.
+    pub submenu: Option<MenuTree>,
+}
+
+impl MenuTree {
+    /// Describe a menu and its submenus
+    pub fn from_menu<T>(menu: &Menu<T>) -> Self {
+        Self::build(menu, &mut Vec::new())
+    }
+
+    fn build<T>(menu: &Menu<T>, ancestors: &mut Vec<*const Mutex<Menu<T>>>) -> Self {
+        Self {
+            title: menu.title.clone(),
+            items: menu
+                .items
+                .iter()
+                .map(|item| MenuItemInfo::build(item, ancestors))
+                .collect(),
+        }
+    }
+
+    /// Find every item tagged with a category, with the key path leading to it
+    pub fn tagged(&self, category: &str) -> Vec<(String, &MenuItemInfo)> {
+        let mut found = Vec::new();
+        self.collect_tagged(category, "", &mut found);
+        found
+    }
+
+    fn collect_tagged<'a>(
+        &'a self,
+        category: &str,
+        prefix: &str,
+        found: &mut Vec<(String, &'a MenuItemInfo)>,
+    ) {
+        for item in &self.items {
+            let path = format!("{}{}", prefix, item.key);
+            if item
+                .categories
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(category))
+            {
+                found.push((path.clone(), item));
+            }
+            if let Some(submenu) = &item.submenu {
+                submenu.collect_tagged(category, &format!("{}/", path), found);
+            }
+        }
+    }
+
+    /// Find the menu at the given path of submenu keys
+    pub fn find(&self, path: &[&str]) -> Option<&MenuTree> {
+        let Some((key, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        self.items
+            .iter()
+            .find(|item| item.key.eq_ignore_ascii_case(key))
+            .and_then(|item| item.submenu.as_ref())
+            .and_then(|submenu| submenu.find(rest))
+    }
+}
+
+impl MenuItemInfo {
+    fn build<T>(item: &MenuItem<T>, ancestors: &mut Vec<*const Mutex<Menu<T>>>) -> Self {
+        // Ancestors are locked further up the stack, so check pointers before locking
+        let submenu = item.submenu.as_ref().and_then(|submenu| {
+            let ptr = std::sync::Arc::as_ptr(submenu);
+            if ancestors.contains(&ptr) {
+                return None;
+            }
+            ancestors.push(ptr);
+            let tree = MenuTree::build(&submenu.lock().unwrap(), ancestors);
+            ancestors.pop();
+            Some(tree)
+        });
+
+        Self {
+            key: item.key.clone(),
+            description: item.description.clone(),
+            has_action: item.action.is_some(),
+            params: item.params.clone(),
+            categories: item.categories.clone(),
+            submenu,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParamType;
+
+    #[derive(Debug)]
+    struct TestState;
+
+    #[test]
+    fn test_menu_tree() {
+        let mut settings: Menu<TestState> = Menu::new("Settings");
+        settings.add_item(
+            MenuItem::new_action(
+                "t",
+                "Theme".to_string(),
+                |_: &mut TestState, _: Option<&str>| None,
+            )
+            .with_params([ParamSpec::required("name", ParamType::String)]),
+        );
+        let mut root_menu: Menu<TestState> = Menu::new("Root");
+        root_menu.add_submenu("s", "Settings", settings);
+
+        let tree = MenuTree::from_menu(&root_menu);
+        let settings = tree.find(&["S"]).unwrap();
+        assert_eq!(settings.title, "Settings");
+        assert!(settings.items[0].has_action);
+        assert!(tree.find(&["x"]).is_none());
+        assert!(tree.tagged("danger").is_empty());
+
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json["items"][0]["key"], "s");
+        assert_eq!(
+            json["items"][0]["submenu"]["items"][0]["params"][0]["param_type"],
+            "String"
+        );
+    }
+}
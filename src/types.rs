@@ -1,8 +1,16 @@
+use crate::cancel::CancelToken;
+use crate::context::ActionContext;
+use crate::dialog::Dialog;
+use crate::jobs::Job;
+use crate::output::{ActionOutput, OutputHandle, OutputSink};
+use crate::params::Params;
+use crate::progress::Progress;
 use futures::future::BoxFuture;
 use std::future::Future;
+use std::sync::{Arc, Mutex};
 
 /// Defines the possible application modes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mode {
     /// Mode for navigating menus and triggering actions
     Command,
@@ -10,16 +18,192 @@ pub enum Mode {
     Scroll,
 }
 
+/// Defines how the output of a menu item is routed to the output area
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputPolicy {
+    /// Append the output to the end of the output buffer
+    #[default]
+    Append,
+    /// Replace the previous output of the same item, appending if there is none
+    Replace,
+    /// Show the output in a popup viewer instead of the output buffer
+    Popup,
+}
+
 /// Marker structs to differentiate between sync and async functions
 pub struct SyncFnMarker;
 pub struct AsyncFnMarker;
 
+/// Marker structs for functions taking structured [`Params`]
+pub struct SyncParamsFnMarker;
+pub struct AsyncParamsFnMarker;
+
+/// Marker structs for functions taking a [`Progress`] handle
+pub struct SyncProgressFnMarker;
+pub struct AsyncProgressFnMarker;
+
+/// Marker structs for functions that can be cancelled through a [`CancelToken`]
+pub struct SyncCancelFnMarker;
+pub struct AsyncCancelFnMarker;
+
+/// Marker structs for functions writing output while they run through an [`OutputSink`]
+pub struct SyncOutputFnMarker;
+pub struct AsyncOutputFnMarker;
+
+/// Marker structs for functions taking all handles of their [`Job`]
+pub struct SyncJobFnMarker;
+pub struct AsyncJobFnMarker;
+
+/// Marker structs for functions taking an [`ActionContext`]
+pub struct SyncContextFnMarker;
+pub struct AsyncContextFnMarker;
+
+/// Marker structs for `FnMut` closures wrapped in a [`MutAction`]
+pub struct SyncMutFnMarker;
+pub struct AsyncMutFnMarker;
+pub struct SyncMutParamsFnMarker;
+pub struct AsyncMutParamsFnMarker;
+pub struct SyncMutContextFnMarker;
+pub struct AsyncMutContextFnMarker;
+
+/// Bounds of the closures stored in menus: `Send + Sync`, or nothing with the
+/// `single-threaded` feature so closures can capture `Rc` and `RefCell`
+#[cfg(not(feature = "single-threaded"))]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(not(feature = "single-threaded"))]
+impl<F: Send + Sync> MaybeSendSync for F {}
+#[cfg(feature = "single-threaded")]
+pub trait MaybeSendSync {}
+#[cfg(feature = "single-threaded")]
+impl<F> MaybeSendSync for F {}
+
+/// Bound of the `FnMut` closures wrapped in a [`MutAction`]: `Send`, or nothing
+/// with the `single-threaded` feature
+#[cfg(not(feature = "single-threaded"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(feature = "single-threaded"))]
+impl<F: Send> MaybeSend for F {}
+#[cfg(feature = "single-threaded")]
+pub trait MaybeSend {}
+#[cfg(feature = "single-threaded")]
+impl<F> MaybeSend for F {}
+
 /// Type for synchronous action functions that can be executed when menu items are selected
-pub type ActionFn<T> = Box<dyn Fn(&mut T, Option<&str>) -> Option<String> + Send + Sync>;
+#[cfg(not(feature = "single-threaded"))]
+pub type ActionFn<T> = Box<dyn Fn(&mut ActionContext<T>) -> ActionResult + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type ActionFn<T> = Box<dyn Fn(&mut ActionContext<T>) -> ActionResult>;
 
 /// Type for asynchronous action functions that can be executed when menu items are selected
+#[cfg(not(feature = "single-threaded"))]
 pub type AsyncActionFn<T> =
-    Box<dyn Fn(&mut T, Option<&str>) -> BoxFuture<'static, Option<String>> + Send + Sync>;
+    Box<dyn Fn(&mut ActionContext<T>) -> BoxFuture<'static, ActionResult> + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type AsyncActionFn<T> = Box<dyn Fn(&mut ActionContext<T>) -> BoxFuture<'static, ActionResult>>;
+
+/// Where to go after an action ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Navigation {
+    /// Go to the menu at a `/`-separated path of submenu keys from the root, e.g. `settings/network`
+    Navigate(String),
+    /// Go back to the parent menu
+    Back,
+    /// Quit the application from any menu, like `quit!`
+    Quit,
+}
+
+/// What an action produced: its output and where to go next
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ActionOutcome {
+    /// Output shown according to the item's output policy
+    pub output: Option<ActionOutput>,
+    /// Navigation applied after the output is shown
+    pub navigation: Option<Navigation>,
+    /// Dialog shown once the action finished
+    pub dialog: Option<Dialog>,
+}
+
+impl ActionOutcome {
+    /// Create an outcome without output or navigation
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show the given output
+    pub fn with_output(mut self, output: impl Into<ActionOutput>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// Navigate once the action finished
+    pub fn with_navigation(mut self, navigation: Navigation) -> Self {
+        self.navigation = Some(navigation);
+        self
+    }
+
+    /// Show a dialog once the action finished, e.g. to ask what to do next
+    pub fn with_dialog(mut self, dialog: Dialog) -> Self {
+        self.dialog = Some(dialog);
+        self
+    }
+}
+
+impl From<ActionOutput> for ActionOutcome {
+    fn from(output: ActionOutput) -> Self {
+        Self::new().with_output(output)
+    }
+}
+
+/// Result of running an action: its outcome, or the message of the error it failed with
+pub type ActionResult = Result<ActionOutcome, String>;
+
+/// Return types actions can have, converted to an [`ActionResult`]
+pub trait IntoActionResult {
+    fn into_action_result(self) -> ActionResult;
+}
+
+/// Actions that can't fail
+impl IntoActionResult for Option<String> {
+    fn into_action_result(self) -> ActionResult {
+        Ok(ActionOutcome {
+            output: self.map(ActionOutput::PlainText),
+            navigation: None,
+            dialog: None,
+        })
+    }
+}
+
+/// Actions with structured output
+impl IntoActionResult for ActionOutput {
+    fn into_action_result(self) -> ActionResult {
+        Ok(self.into())
+    }
+}
+
+/// Actions driving navigation without output
+impl IntoActionResult for Navigation {
+    fn into_action_result(self) -> ActionResult {
+        Ok(ActionOutcome::new().with_navigation(self))
+    }
+}
+
+/// Actions with output and navigation
+impl IntoActionResult for ActionOutcome {
+    fn into_action_result(self) -> ActionResult {
+        Ok(self)
+    }
+}
+
+/// Fallible actions, e.g. returning `anyhow::Result<Option<String>>`
+impl<R: IntoActionResult, E: std::fmt::Display> IntoActionResult for Result<R, E> {
+    fn into_action_result(self) -> ActionResult {
+        match self {
+            Ok(output) => output.into_action_result(),
+            // The alternate form includes the causes of an anyhow::Error
+            Err(e) => Err(format!("{:#}", e)),
+        }
+    }
+}
 
 /// Represents either a synchronous or asynchronous action function
 pub enum ActionType<T> {
@@ -29,48 +213,371 @@ pub enum ActionType<T> {
     Async(AsyncActionFn<T>),
 }
 
-pub type TickFn<T> = Box<dyn Fn(&mut T, &mut Vec<String>, f32) + Send + Sync>;
+/// What the quit key does, Ctrl+Q unless a [`KeyMap`](crate::KeyMap) rebinds it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuitBehavior {
+    /// Exit immediately from any menu, like `quit!`
+    #[default]
+    ForceQuit,
+    /// Behave like `q`, which only quits from the root menu
+    Quit,
+}
+
+/// Former name of [`QuitBehavior`], from before the quit key could be rebound
+#[deprecated(note = "renamed to `QuitBehavior`")]
+pub type CtrlQBehavior = QuitBehavior;
+
+/// A function computing a menu item description from the application state
+#[cfg(not(feature = "single-threaded"))]
+pub type DescriptionFn<T> = Arc<dyn Fn(&T) -> String + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type DescriptionFn<T> = Arc<dyn Fn(&T) -> String>;
+
+/// An inverse operation reverting what an action did, given the parameters it ran with
+#[cfg(not(feature = "single-threaded"))]
+pub type UndoFn<T> = Arc<dyn Fn(&mut T, &Params) + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type UndoFn<T> = Arc<dyn Fn(&mut T, &Params)>;
+
+/// Suggests values for the parameter being typed, given what was typed of it so far
+#[cfg(not(feature = "single-threaded"))]
+pub type CompleteFn<T> = Arc<dyn Fn(&T, &str) -> Vec<String> + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type CompleteFn<T> = Arc<dyn Fn(&T, &str) -> Vec<String>>;
+
+/// Produces text from the application state, for a status bar segment or a watch
+#[cfg(not(feature = "single-threaded"))]
+pub type StatusFn<T> = Box<dyn Fn(&T) -> String + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type StatusFn<T> = Box<dyn Fn(&T) -> String>;
+
+#[cfg(not(feature = "single-threaded"))]
+pub type TickFn<T> = Box<dyn Fn(&mut T, &OutputHandle, f32) + Send + Sync>;
+#[cfg(feature = "single-threaded")]
+pub type TickFn<T> = Box<dyn Fn(&mut T, &OutputHandle, f32)>;
 
 /// A trait for converting closures to ActionFn
-pub trait IntoActionFn<T, Marker>: Send + Sync + 'static {
+pub trait IntoActionFn<T, Marker>: MaybeSendSync + 'static {
     fn into_action_fn(self) -> ActionType<T>;
 }
 
 /// Implementation for synchronous closures that can be converted to ActionFn
-impl<T, F> IntoActionFn<T, SyncFnMarker> for F
+impl<T, F, R> IntoActionFn<T, SyncFnMarker> for F
 where
-    F: Fn(&mut T, Option<&str>) -> Option<String> + Send + Sync + 'static,
+    F: Fn(&mut T, Option<&str>) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
 {
     fn into_action_fn(self) -> ActionType<T> {
-        ActionType::Sync(Box::new(self))
+        ActionType::Sync(Box::new(move |ctx| {
+            self(ctx.state, ctx.params.raw()).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous closures taking structured parameters
+impl<T, F, R> IntoActionFn<T, SyncParamsFnMarker> for F
+where
+    F: Fn(&mut T, &Params) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            self(ctx.state, ctx.params).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous closures reporting their progress
+impl<T, F, R> IntoActionFn<T, SyncProgressFnMarker> for F
+where
+    F: Fn(&mut T, &Params, Progress) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            self(ctx.state, ctx.params, ctx.job.progress().clone()).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous closures taking a cancel token, e.g. to share code with async ones
+impl<T, F, R> IntoActionFn<T, SyncCancelFnMarker> for F
+where
+    F: Fn(&mut T, &Params, Progress, CancelToken) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            self(
+                ctx.state,
+                ctx.params,
+                ctx.job.progress().clone(),
+                ctx.job.cancel_token().clone(),
+            )
+            .into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous closures writing output while they run
+impl<T, F, R> IntoActionFn<T, SyncOutputFnMarker> for F
+where
+    F: Fn(&mut T, &Params, OutputSink) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            self(ctx.state, ctx.params, ctx.job.output().clone()).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous closures taking all handles of their job
+impl<T, F, R> IntoActionFn<T, SyncJobFnMarker> for F
+where
+    F: Fn(&mut T, &Params, Job) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            self(ctx.state, ctx.params, ctx.job.clone()).into_action_result()
+        }))
     }
 }
 
 /// Implementation for asynchronous closures that can be converted to ActionFn
 impl<T, F, Fut> IntoActionFn<T, AsyncFnMarker> for F
 where
-    F: Fn(&mut T, Option<&str>) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = Option<String>> + Send + 'static,
+    F: Fn(&mut T, Option<&str>) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
 {
     fn into_action_fn(self) -> ActionType<T> {
-        ActionType::Async(Box::new(move |state, params| {
+        ActionType::Async(Box::new(move |ctx| {
             // Clone self to ensure the future doesn't reference the original closure
-            let fut = self(state, params);
+            let fut = self(ctx.state, ctx.params.raw());
             // Convert the future to a BoxFuture
-            Box::pin(fut)
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous closures taking structured parameters
+impl<T, F, Fut> IntoActionFn<T, AsyncParamsFnMarker> for F
+where
+    F: Fn(&mut T, &Params) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = self(ctx.state, ctx.params);
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous closures reporting their progress, e.g. from a
+/// multi-second task whose future owns the handle
+impl<T, F, Fut> IntoActionFn<T, AsyncProgressFnMarker> for F
+where
+    F: Fn(&mut T, &Params, Progress) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = self(ctx.state, ctx.params, ctx.job.progress().clone());
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous closures that stop early when cancelled
+impl<T, F, Fut> IntoActionFn<T, AsyncCancelFnMarker> for F
+where
+    F: Fn(&mut T, &Params, Progress, CancelToken) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = self(
+                ctx.state,
+                ctx.params,
+                ctx.job.progress().clone(),
+                ctx.job.cancel_token().clone(),
+            );
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous closures writing output while they run, e.g. tailing a process
+impl<T, F, Fut> IntoActionFn<T, AsyncOutputFnMarker> for F
+where
+    F: Fn(&mut T, &Params, OutputSink) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = self(ctx.state, ctx.params, ctx.job.output().clone());
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous closures taking all handles of their job
+impl<T, F, Fut> IntoActionFn<T, AsyncJobFnMarker> for F
+where
+    F: Fn(&mut T, &Params, Job) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = self(ctx.state, ctx.params, ctx.job.clone());
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for synchronous closures taking an action context
+impl<T, F, R> IntoActionFn<T, SyncContextFnMarker> for F
+where
+    F: Fn(&mut ActionContext<T>) -> R + MaybeSendSync + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| self(ctx).into_action_result()))
+    }
+}
+
+/// Implementation for asynchronous closures taking an action context; their
+/// future can't borrow it, so it clones the handles it needs
+impl<T, F, Fut> IntoActionFn<T, AsyncContextFnMarker> for F
+where
+    F: Fn(&mut ActionContext<T>) -> Fut + MaybeSendSync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = self(ctx);
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// An action closure that mutates what it captured, e.g. counting its own calls.
+///
+/// Actions are `Fn` by default; wrap an `FnMut` closure taking `Option<&str>`,
+/// `&Params` or an [`ActionContext`] in this to use it instead. Calls are
+/// serialized, and the closure only needs to be `Send`.
+pub struct MutAction<F>(Mutex<F>);
+
+impl<F> MutAction<F> {
+    /// Wrap an `FnMut` closure
+    pub fn new(action: F) -> Self {
+        Self(Mutex::new(action))
+    }
+}
+
+/// Implementation for synchronous `FnMut` closures
+impl<T, F, R> IntoActionFn<T, SyncMutFnMarker> for MutAction<F>
+where
+    F: FnMut(&mut T, Option<&str>) -> R + MaybeSend + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            (self.0.lock().unwrap())(ctx.state, ctx.params.raw()).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous `FnMut` closures taking structured parameters
+impl<T, F, R> IntoActionFn<T, SyncMutParamsFnMarker> for MutAction<F>
+where
+    F: FnMut(&mut T, &Params) -> R + MaybeSend + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            (self.0.lock().unwrap())(ctx.state, ctx.params).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for synchronous `FnMut` closures taking an action context
+impl<T, F, R> IntoActionFn<T, SyncMutContextFnMarker> for MutAction<F>
+where
+    F: FnMut(&mut ActionContext<T>) -> R + MaybeSend + 'static,
+    R: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Sync(Box::new(move |ctx| {
+            (self.0.lock().unwrap())(ctx).into_action_result()
+        }))
+    }
+}
+
+/// Implementation for asynchronous `FnMut` closures; only creating the future
+/// is serialized, the futures themselves can run side by side
+impl<T, F, Fut> IntoActionFn<T, AsyncMutFnMarker> for MutAction<F>
+where
+    F: FnMut(&mut T, Option<&str>) -> Fut + MaybeSend + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = (self.0.lock().unwrap())(ctx.state, ctx.params.raw());
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous `FnMut` closures taking structured parameters
+impl<T, F, Fut> IntoActionFn<T, AsyncMutParamsFnMarker> for MutAction<F>
+where
+    F: FnMut(&mut T, &Params) -> Fut + MaybeSend + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = (self.0.lock().unwrap())(ctx.state, ctx.params);
+            Box::pin(async move { fut.await.into_action_result() })
+        }))
+    }
+}
+
+/// Implementation for asynchronous `FnMut` closures taking an action context
+impl<T, F, Fut> IntoActionFn<T, AsyncMutContextFnMarker> for MutAction<F>
+where
+    F: FnMut(&mut ActionContext<T>) -> Fut + MaybeSend + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoActionResult,
+{
+    fn into_action_fn(self) -> ActionType<T> {
+        ActionType::Async(Box::new(move |ctx| {
+            let fut = (self.0.lock().unwrap())(ctx);
+            Box::pin(async move { fut.await.into_action_result() })
         }))
     }
 }
 
 /// A trait for converting closures to TickFn
-pub trait IntoTickFn<T>: Send + Sync + 'static {
+pub trait IntoTickFn<T>: MaybeSendSync + 'static {
     fn into_tick_fn(self) -> TickFn<T>;
 }
 
 /// Implementation for closures that can be converted to TickFn
 impl<T, F> IntoTickFn<T> for F
 where
-    F: Fn(&mut T, &mut Vec<String>, f32) + Send + Sync + 'static,
+    F: Fn(&mut T, &OutputHandle, f32) + MaybeSendSync + 'static,
 {
     fn into_tick_fn(self) -> TickFn<T> {
         Box::new(self)
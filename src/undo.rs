@@ -0,0 +1,123 @@
+use crate::istari::CommandRef;
+use crate::params::Params;
+use std::collections::VecDeque;
+
+/// Default number of actions that can be undone
+pub const DEFAULT_UNDO_LIMIT: usize = 50;
+
+/// An undoable action that ran, with the parameters its inverse gets
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoEntry {
+    /// The action that ran
+    pub command: CommandRef,
+    /// The parameters it ran with
+    pub params: Params,
+}
+
+/// Actions that can be undone and redone, most recent last
+#[derive(Debug, Clone)]
+pub struct UndoStack {
+    /// Actions that can be undone
+    done: VecDeque<UndoEntry>,
+    /// Undone actions that can be redone
+    undone: Vec<UndoEntry>,
+    /// Maximum number of actions that can be undone
+    limit: usize,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNDO_LIMIT)
+    }
+}
+
+impl UndoStack {
+    /// Create an empty stack keeping at most `limit` actions
+    pub fn new(limit: usize) -> Self {
+        Self {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Record an action the user ran, which can't redo what was undone before it anymore
+    pub fn record(&mut self, entry: UndoEntry) {
+        self.undone.clear();
+        self.push_done(entry);
+    }
+
+    /// Take the most recent action to undo it
+    pub fn pop_undo(&mut self) -> Option<UndoEntry> {
+        self.done.pop_back()
+    }
+
+    /// Take the most recently undone action to redo it
+    pub fn pop_redo(&mut self) -> Option<UndoEntry> {
+        self.undone.pop()
+    }
+
+    /// Remember an undone action so it can be redone
+    pub(crate) fn push_undone(&mut self, entry: UndoEntry) {
+        self.undone.push(entry);
+    }
+
+    /// Remember a redone action so it can be undone again
+    pub(crate) fn push_done(&mut self, entry: UndoEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.done.len() == self.limit {
+            self.done.pop_front();
+        }
+        self.done.push_back(entry);
+    }
+
+    /// Check if there's an action to undo
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    /// Check if there's an action to redo
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str) -> UndoEntry {
+        UndoEntry {
+            command: CommandRef {
+                path: Vec::new(),
+                key: key.to_string(),
+                params: None,
+                description: key.to_string(),
+            },
+            params: Params::default(),
+        }
+    }
+
+    #[test]
+    fn test_undo_stack() {
+        let mut stack = UndoStack::new(2);
+        stack.record(entry("a"));
+        stack.record(entry("b"));
+        stack.record(entry("c"));
+
+        // The oldest action falls off once the limit is reached
+        let c = stack.pop_undo().unwrap();
+        assert_eq!(c.command.key, "c");
+        stack.push_undone(c);
+        assert_eq!(stack.pop_undo().unwrap().command.key, "b");
+        assert!(!stack.can_undo());
+        assert!(stack.can_redo());
+
+        // New actions drop what could have been redone
+        stack.record(entry("d"));
+        assert!(!stack.can_redo());
+        assert!(stack.pop_redo().is_none());
+    }
+}